@@ -4,7 +4,8 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use nebula_protocol::cluster_factory::{
-    ClusterExistsResponse, ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg,
+    ClusterExistsResponse, ClusterRegisteredResponse, ConfigResponse, ExecuteMsg, InstantiateMsg,
+    QueryMsg,
 };
 
 fn main() {
@@ -18,4 +19,5 @@ fn main() {
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(ConfigResponse), &out_dir);
     export_schema(&schema_for!(ClusterExistsResponse), &out_dir);
+    export_schema(&schema_for!(ClusterRegisteredResponse), &out_dir);
 }
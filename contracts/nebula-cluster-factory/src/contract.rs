@@ -4,22 +4,29 @@ use astroport::querier::query_pair_info;
 use astroport::token::InstantiateMsg as TokenInstantiateMsg;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    attr, to_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Reply,
-    ReplyOn, Response, StdResult, Storage, SubMsg, Uint128, WasmMsg,
+    attr, to_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo,
+    QueryRequest, Reply, ReplyOn, Response, StdResult, Storage, SubMsg, Uint128, WasmMsg,
+    WasmQuery,
 };
 use cosmwasm_std::{entry_point, StdError};
 use cw2::set_contract_version;
 use cw20::{Cw20ExecuteMsg, MinterResponse};
 use protobuf::Message;
+use std::str::FromStr;
 
 use nebula_protocol::cluster::{
-    ExecuteMsg as ClusterExecuteMsg, InstantiateMsg as ClusterInstantiateMsg,
+    ConfigResponse as ClusterConfigResponse, ExecuteMsg as ClusterExecuteMsg,
+    InstantiateMsg as ClusterInstantiateMsg, QueryMsg as ClusterQueryMsg,
 };
 use nebula_protocol::cluster_factory::{
-    ClusterExistsResponse, ClusterListResponse, ConfigResponse, DistributionInfoResponse,
-    ExecuteMsg, InstantiateMsg, MigrateMsg, Params, QueryMsg,
+    ClusterExistsResponse, ClusterFeeListResponse, ClusterFeesResponse, ClusterListResponse,
+    ClusterRegisteredResponse, ConfigResponse, DistributionInfoResponse, ExecuteMsg,
+    InstantiateMsg, MigrateMsg, Params, QueryMsg,
+};
+use nebula_protocol::penalty::{
+    ConfigResponse as PenaltyConfigResponse, ExecuteMsg as PenaltyExecuteMsg,
+    QueryMsg as PenaltyQueryMsg,
 };
-use nebula_protocol::penalty::ExecuteMsg as PenaltyExecuteMsg;
 use nebula_protocol::staking::{
     Cw20HookMsg as StakingCw20HookMsg, ExecuteMsg as StakingExecuteMsg,
 };
@@ -27,11 +34,12 @@ use nebula_protocol::staking::{
 use crate::error::ContractError;
 use crate::response::MsgInstantiateContractResponse;
 use crate::state::{
-    cluster_exists, deactivate_cluster, decrease_total_weight, get_cluster_data,
-    increase_total_weight, read_all_weight, read_config, read_last_distributed, read_params,
-    read_tmp_asset, read_tmp_cluster, read_total_weight, read_weight, record_cluster,
-    remove_params, remove_weight, store_config, store_last_distributed, store_params,
-    store_tmp_asset, store_tmp_cluster, store_total_weight, store_weight, Config,
+    cluster_exists, cluster_registered, deactivate_cluster, decrease_total_weight,
+    get_cluster_data, increase_total_weight, read_all_weight, read_cluster_fees, read_config,
+    read_last_distributed, read_params, read_tmp_asset, read_tmp_cluster, read_total_weight,
+    read_weight, record_cluster, remove_params, remove_weight, store_cluster_fees, store_config,
+    store_last_distributed, store_params, store_tmp_asset, store_tmp_cluster, store_total_weight,
+    store_weight, ClusterFees, Config,
 };
 
 /// Contract name that is used for migration.
@@ -128,6 +136,18 @@ pub fn instantiate(
 ///             cluster_token,
 ///         }** Decommissions an active cluster.
 ///
+/// - **ExecuteMsg::RecommissionCluster {
+///             cluster_contract,
+///             cluster_token,
+///             weight,
+///         }** Reactivates a decommissioned cluster.
+///
+/// - **ExecuteMsg::UpdateClusterFees {
+///             cluster_contract,
+///             create_fee_rate,
+///             redeem_fee_rate,
+///         }** Overrides the create / redeem fee rates of a cluster.
+///
 /// - **ExecuteMsg::UpdateWeight {
 ///             asset_token,
 ///             weight,
@@ -173,6 +193,22 @@ pub fn execute(
             cluster_contract,
             cluster_token,
         } => decommission_cluster(deps, info, cluster_contract, cluster_token),
+        ExecuteMsg::RecommissionCluster {
+            cluster_contract,
+            cluster_token,
+            weight,
+        } => recommission_cluster(deps, info, cluster_contract, cluster_token, weight),
+        ExecuteMsg::UpdateClusterFees {
+            cluster_contract,
+            create_fee_rate,
+            redeem_fee_rate,
+        } => update_cluster_fees(
+            deps,
+            info,
+            cluster_contract,
+            create_fee_rate,
+            redeem_fee_rate,
+        ),
         ExecuteMsg::UpdateWeight {
             asset_token,
             weight,
@@ -345,6 +381,86 @@ pub fn update_weight(
     ]))
 }
 
+/// ## Description
+/// Checks that an optional fee rate is a valid decimal less than 1.
+///
+/// ## Params
+/// - **fee_rate** is a reference to an object of type [`Option<String>`].
+fn validate_fee_rate(fee_rate: &Option<String>) -> Result<(), ContractError> {
+    if let Some(fee_rate) = fee_rate {
+        let rate = Decimal::from_str(fee_rate)?;
+        if rate >= Decimal::one() {
+            return Err(ContractError::Generic(
+                "Fee rate must be less than 1".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// ## Definition
+/// Overrides the create / redeem fee rates of a cluster. A rate that is not
+/// provided falls back to the factory protocol fee rate.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **cluster_contract** is an object of type [`String`] which is the address
+///     of a cluster contract.
+///
+/// - **create_fee_rate** is an object of type [`Option<String>`] which is
+///     the fee rate charged on cluster token creation.
+///
+/// - **redeem_fee_rate** is an object of type [`Option<String>`] which is
+///     the fee rate charged on cluster token redemption.
+///
+/// ## Executor
+/// Only the owner can execute this.
+pub fn update_cluster_fees(
+    deps: DepsMut,
+    info: MessageInfo,
+    cluster_contract: String,
+    create_fee_rate: Option<String>,
+    redeem_fee_rate: Option<String>,
+) -> Result<Response, ContractError> {
+    // Validate address format
+    let validated_cluster_contract = deps.api.addr_validate(cluster_contract.as_str())?;
+    let config: Config = read_config(deps.storage)?;
+
+    // Permission check
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Only registered clusters, active or decommissioned, can have fees
+    if !cluster_registered(deps.storage, &validated_cluster_contract)? {
+        return Err(ContractError::Generic("Cluster does not exist".to_string()));
+    }
+
+    validate_fee_rate(&create_fee_rate)?;
+    validate_fee_rate(&redeem_fee_rate)?;
+
+    store_cluster_fees(
+        deps.storage,
+        &validated_cluster_contract,
+        &ClusterFees {
+            create_fee_rate,
+            redeem_fee_rate,
+        },
+    )?;
+
+    let fees = query_cluster_fees(deps.as_ref(), cluster_contract)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_cluster_fees"),
+        attr("cluster_contract", fees.cluster_contract),
+        attr("create_fee_rate", fees.create_fee_rate),
+        attr("redeem_fee_rate", fees.redeem_fee_rate),
+    ]))
+}
+
 /// ## Definition
 /// Passes command to other contract e.g. update config.
 ///
@@ -428,6 +544,10 @@ pub fn create_cluster(
         ));
     }
 
+    // Validate the cluster fee rates
+    validate_fee_rate(&params.create_fee_rate)?;
+    validate_fee_rate(&params.redeem_fee_rate)?;
+
     // Store the parameters for cluster creation process
     store_params(deps.storage, &params)?;
 
@@ -537,18 +657,20 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractEr
 /// ## Description
 /// ClusterCreationHook
 /// 1. Record cluster address.
-/// 2. Create token contract with `config.token_code_id`.
+/// 2. Transfer the penalty contract ownership to the cluster, unless the penalty contract
+///    is shared by the clusters of this factory.
+/// 3. Create token contract with `config.token_code_id`.
 ///
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
 ///
-/// - **_env** is an object of type [`Env`].
+/// - **env** is an object of type [`Env`].
 ///
 /// - **cluster_contract** is an object of type [`String`] which is the address
 ///     of the cluster contract.
 pub fn cluster_creation_hook(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     cluster_contract: String,
 ) -> Result<Response, ContractError> {
     // Validate address format
@@ -565,10 +687,26 @@ pub fn cluster_creation_hook(
 
     // Register the new cluster contract as active
     record_cluster(deps.storage, &validated_cluster_contract)?;
+    // Record the fee schedule of the new cluster
+    store_cluster_fees(
+        deps.storage,
+        &validated_cluster_contract,
+        &ClusterFees {
+            create_fee_rate: params.create_fee_rate.clone(),
+            redeem_fee_rate: params.redeem_fee_rate.clone(),
+        },
+    )?;
     // Save cluster contract address for using after creating a cluster token contract
     store_tmp_cluster(deps.storage, &validated_cluster_contract)?;
-    Ok(Response::new()
-        .add_messages(vec![
+
+    // A shared penalty contract keeps its owner and tracks every cluster separately
+    let penalty_config: PenaltyConfigResponse =
+        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: params.penalty.to_string(),
+            msg: to_binary(&PenaltyQueryMsg::Config {})?,
+        }))?;
+    let messages = match penalty_config.factory {
+        None => vec![
             // Tell penalty contract to set owner to cluster
             CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: params.penalty.to_string(),
@@ -578,7 +716,17 @@ pub fn cluster_creation_hook(
                     penalty_params: None,
                 })?,
             }),
-        ])
+        ],
+        Some(factory) if factory == env.contract.address.as_str() => vec![],
+        Some(_) => {
+            return Err(ContractError::Generic(
+                "Penalty contract is shared by another factory".to_string(),
+            ))
+        }
+    };
+
+    Ok(Response::new()
+        .add_messages(messages)
         // Execute `TokenInstantiateMsg` submessage to create a new cluster token contract
         // with submessage ID as 2 for Reply callback
         .add_submessages(vec![SubMsg {
@@ -677,6 +825,17 @@ pub fn cluster_token_creation_hook(
                     target_oracle: None,
                     penalty: None,
                     target: None,
+                    price_freshness: None,
+                    management_fee_rate: None,
+                    caps: None,
+                    reconcile_policy: None,
+                    guardian: None,
+                    swap_fee_rate: None,
+                    target_mode: None,
+                    target_provider: None,
+                    redeem_bounty_rate: None,
+                    native_decimals: None,
+                    referral_fee_rate: None,
                 })?,
             }),
         ])
@@ -924,6 +1083,94 @@ pub fn decommission_cluster(
         ]))
 }
 
+/// ## Definition
+/// Reactivates a decommissioned cluster and restores its LP staking pool weight.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **cluster_contract** is an object of type [`String`] which is an address of
+///     a cluster contract.
+///
+/// - **cluster_token** is an object of type [`String`] which is an address of
+///     a cluster token contract corresponding with the cluster contract.
+///
+/// - **weight** is an object of type [`Option<u32>`] which is the distribution weight
+///     of the cluster LP staking pool.
+///
+/// ## Executor
+/// Only the owner can execute this.
+pub fn recommission_cluster(
+    deps: DepsMut,
+    info: MessageInfo,
+    cluster_contract: String,
+    cluster_token: String,
+    weight: Option<u32>,
+) -> Result<Response, ContractError> {
+    // Validate address format
+    let validated_cluster_contract = deps.api.addr_validate(cluster_contract.as_str())?;
+    let validated_cluster_token = deps.api.addr_validate(cluster_token.as_str())?;
+
+    let config: Config = read_config(deps.storage)?;
+
+    // Permission check
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Only registered and decommissioned clusters can be recommissioned
+    if !cluster_registered(deps.storage, &validated_cluster_contract)? {
+        return Err(ContractError::Generic("Cluster does not exist".to_string()));
+    }
+    if cluster_exists(deps.storage, &validated_cluster_contract)? {
+        return Err(ContractError::Generic(
+            "Cluster is not decommissioned".to_string(),
+        ));
+    }
+
+    // The cluster token must be the one of the recommissioned cluster
+    let cluster_config: ClusterConfigResponse =
+        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: validated_cluster_contract.to_string(),
+            msg: to_binary(&ClusterQueryMsg::Config {})?,
+        }))?;
+    if cluster_config.config.cluster_token.as_ref() != Some(&validated_cluster_token) {
+        return Err(ContractError::Generic(
+            "Cluster token does not match the cluster".to_string(),
+        ));
+    }
+
+    // Do not count the weight of an already weighted LP staking pool twice
+    if read_weight(deps.storage, &validated_cluster_token).is_ok() {
+        return Err(ContractError::Generic(
+            "Cluster token already has a distribution weight".to_string(),
+        ));
+    }
+
+    // Restore the weight of the given cluster token LP staking pool
+    let weight = weight.unwrap_or(NORMAL_TOKEN_WEIGHT);
+    store_weight(deps.storage, &validated_cluster_token, weight)?;
+    increase_total_weight(deps.storage, weight)?;
+
+    // Reactivate the cluster
+    record_cluster(deps.storage, &validated_cluster_contract)?;
+
+    Ok(Response::new()
+        .add_messages(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: validated_cluster_contract.to_string(),
+            funds: vec![],
+            msg: to_binary(&ClusterExecuteMsg::Recommission {})?,
+        })])
+        .add_attributes(vec![
+            attr("action", "recommission_asset"),
+            attr("cluster_token", validated_cluster_token.to_string()),
+            attr("cluster_contract", validated_cluster_contract.to_string()),
+            attr("weight", weight.to_string()),
+        ]))
+}
+
 /// ## Description
 /// Exposes all the queries available in the contract.
 ///
@@ -939,8 +1186,16 @@ pub fn decommission_cluster(
 ///
 /// - **QueryMsg::ClusterExists { contract_addr }** Returns whether a given address is an active cluster.
 ///
+/// - **QueryMsg::ClusterRegistered { contract_addr }** Returns whether a given address is a cluster
+///         created by the factory, including decommissioned clusters.
+///
 /// - **QueryMsg::ClusterList {}** Returns the list of pairs (cluster contract address, active status).
 ///
+/// - **QueryMsg::ClusterFees { cluster_contract }** Returns the effective create / redeem fee rates
+///         of a cluster.
+///
+/// - **QueryMsg::ClusterFeeList {}** Returns the effective create / redeem fee rates of all clusters.
+///
 /// - **QueryMsg::DistributionInfo {}** Returns last distributed time and reward distribution weights of
 ///         for the Nebula and cluster LP staking pools.
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -950,7 +1205,14 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::ClusterExists { contract_addr } => {
             to_binary(&query_cluster_exists(deps, contract_addr)?)
         }
+        QueryMsg::ClusterRegistered { contract_addr } => {
+            to_binary(&query_cluster_registered(deps, contract_addr)?)
+        }
         QueryMsg::ClusterList {} => to_binary(&query_clusters(deps)?),
+        QueryMsg::ClusterFees { cluster_contract } => {
+            to_binary(&query_cluster_fees(deps, cluster_contract)?)
+        }
+        QueryMsg::ClusterFeeList {} => to_binary(&query_cluster_fee_list(deps)?),
         QueryMsg::DistributionInfo {} => to_binary(&query_distribution_info(deps)?),
     }
 }
@@ -998,6 +1260,26 @@ pub fn query_cluster_exists(
     })
 }
 
+/// ## Description
+/// Returns whether the given address is a cluster contract address created by the factory,
+/// active or decommissioned.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster_address** is an object of type [`String`].
+pub fn query_cluster_registered(
+    deps: Deps,
+    cluster_address: String,
+) -> StdResult<ClusterRegisteredResponse> {
+    Ok(ClusterRegisteredResponse {
+        registered: cluster_registered(
+            deps.storage,
+            &deps.api.addr_validate(cluster_address.as_str())?,
+        )?,
+    })
+}
+
 /// ## Description
 /// Returns the list of pairs (cluster contract address, cluster active status).
 ///
@@ -1009,6 +1291,41 @@ pub fn query_clusters(deps: Deps) -> StdResult<ClusterListResponse> {
     })
 }
 
+/// ## Description
+/// Returns the effective create / redeem fee rates of a cluster.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster_contract** is an object of type [`String`].
+pub fn query_cluster_fees(deps: Deps, cluster_contract: String) -> StdResult<ClusterFeesResponse> {
+    let validated_cluster_contract = deps.api.addr_validate(cluster_contract.as_str())?;
+    let config: Config = read_config(deps.storage)?;
+    let fees = read_cluster_fees(deps.storage, &validated_cluster_contract)?;
+
+    Ok(ClusterFeesResponse {
+        cluster_contract: validated_cluster_contract.to_string(),
+        create_fee_rate: fees
+            .create_fee_rate
+            .unwrap_or_else(|| config.protocol_fee_rate.clone()),
+        redeem_fee_rate: fees.redeem_fee_rate.unwrap_or(config.protocol_fee_rate),
+    })
+}
+
+/// ## Description
+/// Returns the effective create / redeem fee rates of all clusters.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+pub fn query_cluster_fee_list(deps: Deps) -> StdResult<ClusterFeeListResponse> {
+    let fees = get_cluster_data(deps.storage)?
+        .into_iter()
+        .map(|(cluster_contract, _)| query_cluster_fees(deps, cluster_contract))
+        .collect::<StdResult<Vec<ClusterFeesResponse>>>()?;
+
+    Ok(ClusterFeeListResponse { fees })
+}
+
 /// ## Description
 /// Returns distribution information containing
 /// - The last distributed time.
@@ -23,6 +23,8 @@ static KEY_TMP_ASSET: &[u8] = b"tmp_asset";
 static PREFIX_WEIGHT: &[u8] = b"weight";
 /// clusters: Addr
 static PREFIX_CLUSTERS: &[u8] = b"clusters";
+/// cluster fees: ClusterFees
+static PREFIX_CLUSTER_FEES: &[u8] = b"cluster_fees";
 
 //////////////////////////////////////////////////////////////////////
 /// CONFIG
@@ -195,6 +197,11 @@ pub fn cluster_exists(storage: &dyn Storage, contract_addr: &Addr) -> StdResult<
     }
 }
 
+pub fn cluster_registered(storage: &dyn Storage, contract_addr: &Addr) -> StdResult<bool> {
+    let cluster_bucket: ReadonlyBucket<bool> = ReadonlyBucket::new(storage, PREFIX_CLUSTERS);
+    Ok(cluster_bucket.may_load(contract_addr.as_bytes())?.is_some())
+}
+
 pub fn get_cluster_data(storage: &dyn Storage) -> StdResult<Vec<(String, bool)>> {
     let cluster_bucket: ReadonlyBucket<bool> = ReadonlyBucket::new(storage, PREFIX_CLUSTERS);
 
@@ -221,3 +228,34 @@ pub fn record_cluster(storage: &mut dyn Storage, contract_addr: &Addr) -> StdRes
 pub fn deactivate_cluster(storage: &mut dyn Storage, contract_addr: &Addr) -> StdResult<()> {
     Bucket::new(storage, PREFIX_CLUSTERS).save(contract_addr.as_bytes(), &false)
 }
+
+//////////////////////////////////////////////////////////////////////
+/// CLUSTER FEES
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// This structure holds the fee rate overrides of a cluster.
+/// A missing rate falls back to the factory protocol fee rate.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ClusterFees {
+    /// Create fee rate
+    pub create_fee_rate: Option<String>,
+    /// Redeem fee rate
+    pub redeem_fee_rate: Option<String>,
+}
+
+pub fn store_cluster_fees(
+    storage: &mut dyn Storage,
+    contract_addr: &Addr,
+    fees: &ClusterFees,
+) -> StdResult<()> {
+    Bucket::new(storage, PREFIX_CLUSTER_FEES).save(contract_addr.as_bytes(), fees)
+}
+
+pub fn read_cluster_fees(storage: &dyn Storage, contract_addr: &Addr) -> StdResult<ClusterFees> {
+    let fees_bucket: ReadonlyBucket<ClusterFees> =
+        ReadonlyBucket::new(storage, PREFIX_CLUSTER_FEES);
+    Ok(fees_bucket
+        .may_load(contract_addr.as_bytes())?
+        .unwrap_or_default())
+}
@@ -1,10 +1,13 @@
 use astroport::asset::{AssetInfo, PairInfo};
 use astroport::factory::PairType;
+use cluster_math::FPDecimal;
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
     from_binary, from_slice, to_binary, Addr, Coin, ContractResult, Empty, OwnedDeps, Querier,
     QuerierResult, QueryRequest, SystemError, SystemResult, WasmQuery,
 };
+use nebula_protocol::cluster::{ClusterConfig, ConfigResponse as ClusterConfigResponse};
+use nebula_protocol::penalty::{ConfigResponse as PenaltyConfigResponse, PenaltyParams};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -28,6 +31,8 @@ pub fn mock_dependencies(
 pub struct WasmMockQuerier {
     base: MockQuerier<Empty>,
     astroport_factory_querier: AstroportFactoryQuerier,
+    penalty_factory: Option<String>,
+    cluster_tokens: HashMap<String, String>,
 }
 
 #[derive(Clone, Default)]
@@ -71,15 +76,15 @@ impl Querier for WasmMockQuerier {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Pair { asset_infos: [AssetInfo; 2] },
+    Config {},
 }
 
 impl WasmMockQuerier {
     pub fn execute_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
         match &request {
-            QueryRequest::Wasm(WasmQuery::Smart {
-                contract_addr: _,
-                msg,
-            }) => match from_binary(&msg).unwrap() {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => match from_binary(&msg)
+                .unwrap()
+            {
                 QueryMsg::Pair { asset_infos } => {
                     let key = asset_infos[0].to_string() + asset_infos[1].to_string().as_str();
                     match self.astroport_factory_querier.pairs.get(&key) {
@@ -102,6 +107,26 @@ impl WasmMockQuerier {
                         }),
                     }
                 }
+                QueryMsg::Config {} if self.cluster_tokens.contains_key(contract_addr) => {
+                    SystemResult::Ok(ContractResult::from(to_binary(&ClusterConfigResponse {
+                        config: mock_cluster_config(&self.cluster_tokens[contract_addr]),
+                    })))
+                }
+                QueryMsg::Config {} => {
+                    SystemResult::Ok(ContractResult::from(to_binary(&PenaltyConfigResponse {
+                        owner: "owner0000".to_string(),
+                        penalty_params: PenaltyParams::PiecewiseLinear {
+                            penalty_amt_lo: FPDecimal::zero(),
+                            penalty_cutoff_lo: FPDecimal::zero(),
+                            penalty_amt_hi: FPDecimal::one(),
+                            penalty_cutoff_hi: FPDecimal::one(),
+                            reward_amt: FPDecimal::zero(),
+                            reward_cutoff: FPDecimal::zero(),
+                            ema_half_life: None,
+                        },
+                        factory: self.penalty_factory.clone(),
+                    })))
+                }
             },
             _ => self.base.handle_query(request),
         }
@@ -113,11 +138,53 @@ impl WasmMockQuerier {
         WasmMockQuerier {
             base,
             astroport_factory_querier: AstroportFactoryQuerier::default(),
+            penalty_factory: None,
+            cluster_tokens: HashMap::new(),
+        }
+    }
+
+    // configure the cluster tokens of cluster contracts
+    pub fn with_cluster_tokens(&mut self, cluster_tokens: &[(&str, &str)]) {
+        for (cluster, token) in cluster_tokens.iter() {
+            self.cluster_tokens
+                .insert(cluster.to_string(), token.to_string());
         }
     }
 
+    // configure the factory sharing the penalty contract
+    pub fn with_penalty_factory(&mut self, factory: &str) {
+        self.penalty_factory = Some(factory.to_string());
+    }
+
     // configure the astroport pair
     pub fn with_astroport_pairs(&mut self, pairs: &[(&String, &String)]) {
         self.astroport_factory_querier = AstroportFactoryQuerier::new(pairs);
     }
 }
+
+fn mock_cluster_config(cluster_token: &str) -> ClusterConfig {
+    ClusterConfig {
+        name: "cluster".to_string(),
+        description: "description".to_string(),
+        owner: Addr::unchecked("owner0000"),
+        cluster_token: Some(Addr::unchecked(cluster_token)),
+        factory: Addr::unchecked(MOCK_CONTRACT_ADDR),
+        pricing_oracle: Addr::unchecked("oracle0000"),
+        target_oracle: Addr::unchecked("owner0000"),
+        penalty: Addr::unchecked("penalty0000"),
+        active: false,
+        price_freshness: Default::default(),
+        management_fee_rate: Default::default(),
+        caps: Default::default(),
+        reconcile_policy: Default::default(),
+        guardian: None,
+        create_paused: false,
+        custom_redeem_paused: false,
+        swap_fee_rate: Default::default(),
+        target_mode: Default::default(),
+        target_provider: None,
+        redeem_bounty_rate: Default::default(),
+        native_decimals: vec![],
+        referral_fee_rate: Default::default(),
+    }
+}
@@ -3,7 +3,7 @@ use crate::error::ContractError;
 use crate::response::MsgInstantiateContractResponse;
 use crate::state::{
     cluster_exists, read_params, read_tmp_asset, read_tmp_cluster, read_total_weight, read_weight,
-    store_total_weight, store_weight,
+    remove_weight, store_total_weight, store_weight,
 };
 use crate::testing::mock_querier::mock_dependencies;
 use astroport::asset::{Asset, AssetInfo};
@@ -20,8 +20,9 @@ use nebula_protocol::cluster::{
     ExecuteMsg as ClusterExecuteMsg, InstantiateMsg as ClusterInstantiateMsg,
 };
 use nebula_protocol::cluster_factory::{
-    ClusterExistsResponse, ClusterListResponse, ConfigResponse, DistributionInfoResponse,
-    ExecuteMsg, InstantiateMsg, MigrateMsg, Params, QueryMsg,
+    ClusterExistsResponse, ClusterFeeListResponse, ClusterFeesResponse, ClusterListResponse,
+    ClusterRegisteredResponse, ConfigResponse, DistributionInfoResponse, ExecuteMsg,
+    InstantiateMsg, MigrateMsg, Params, QueryMsg,
 };
 use nebula_protocol::penalty::ExecuteMsg as PenaltyExecuteMsg;
 use nebula_protocol::staking::{
@@ -75,6 +76,8 @@ pub fn get_input_params() -> Params {
                 amount: Uint128::new(20),
             },
         ],
+        create_fee_rate: None,
+        redeem_fee_rate: None,
     }
 }
 
@@ -496,6 +499,63 @@ fn test_token_creation_hook() {
     );
 }
 
+#[test]
+fn test_token_creation_hook_shared_penalty() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_astroport_pairs(&[(&"uusdnebula0000".to_string(), &"NEBLP0000".to_string())]);
+    deps.querier.with_penalty_factory(MOCK_CONTRACT_ADDR);
+
+    let msg = InstantiateMsg {
+        base_denom: BASE_DENOM.to_string(),
+        token_code_id: TOKEN_CODE_ID,
+        cluster_code_id: CLUSTER_CODE_ID,
+        protocol_fee_rate: PROTOCOL_FEE_RATE.to_string(),
+        distribution_schedule: vec![],
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let msg = ExecuteMsg::PostInitialize {
+        owner: "owner0000".to_string(),
+        nebula_token: "nebula0000".to_string(),
+        staking_contract: "staking0000".to_string(),
+        commission_collector: "collector0000".to_string(),
+        astroport_factory: "astroportfactory".to_string(),
+    };
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::CreateCluster {
+        params: get_input_params(),
+    };
+    let info = mock_info("owner0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let mut token_inst_res = MsgInstantiateContractResponse::new();
+    token_inst_res.set_contract_address("asset0000".to_string());
+    let reply_msg = Reply {
+        id: 1,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![],
+            data: Some(token_inst_res.write_to_bytes().unwrap().into()),
+        }),
+    };
+
+    // The shared penalty contract keeps its owner
+    let res = reply(deps.as_mut(), mock_env(), reply_msg.clone()).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, 2);
+
+    // A penalty contract shared by another factory is rejected
+    deps.querier.with_penalty_factory("factory0001");
+    let res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Penalty contract is shared by another factory".to_string())
+    );
+}
+
 #[test]
 fn test_set_cluster_token_hook() {
     let mut deps = mock_dependencies(&[]);
@@ -581,6 +641,17 @@ fn test_set_cluster_token_hook() {
                     target_oracle: None,
                     penalty: None,
                     target: None,
+                    price_freshness: None,
+                    management_fee_rate: None,
+                    caps: None,
+                    reconcile_policy: None,
+                    guardian: None,
+                    swap_fee_rate: None,
+                    target_mode: None,
+                    target_provider: None,
+                    redeem_bounty_rate: None,
+                    native_decimals: None,
+                    referral_fee_rate: None,
                 })
                 .unwrap(),
             })),
@@ -716,6 +787,17 @@ fn test_set_cluster_token_hook_without_weight() {
                     target_oracle: None,
                     penalty: None,
                     target: None,
+                    price_freshness: None,
+                    management_fee_rate: None,
+                    caps: None,
+                    reconcile_policy: None,
+                    guardian: None,
+                    swap_fee_rate: None,
+                    target_mode: None,
+                    target_provider: None,
+                    redeem_bounty_rate: None,
+                    native_decimals: None,
+                    referral_fee_rate: None,
                 })
                 .unwrap(),
             })),
@@ -1054,6 +1136,8 @@ fn test_decommission_cluster() {
         (&"uusdcluster_token0000".to_string(), &h("LP0000")),
         (&"uusdcluster_token0001".to_string(), &h("LP0001")),
     ]);
+    deps.querier
+        .with_cluster_tokens(&[("asset0000", "cluster_token0000")]);
 
     let msg = InstantiateMsg {
         base_denom: BASE_DENOM.to_string(),
@@ -1177,6 +1261,242 @@ fn test_decommission_cluster() {
     assert_eq!(res, StdError::generic_err("No distribution info stored"));
 
     assert_eq!(read_total_weight(&deps.storage).unwrap(), 30u32);
+
+    // a decommissioned cluster is still registered
+    let msg = QueryMsg::ClusterRegistered {
+        contract_addr: h("asset0000"),
+    };
+    let res: ClusterRegisteredResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res, ClusterRegisteredResponse { registered: true });
+    let msg = QueryMsg::ClusterRegistered {
+        contract_addr: h("asset0001"),
+    };
+    let res: ClusterRegisteredResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res, ClusterRegisteredResponse { registered: false });
+
+    // unauthorized recommission attempt
+    let msg = ExecuteMsg::RecommissionCluster {
+        cluster_contract: h("asset0000"),
+        cluster_token: h("cluster_token0000"),
+        weight: Some(20),
+    };
+    let info = mock_info("owner0001", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // cannot recommission an unknown cluster
+    let info = mock_info("owner0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::RecommissionCluster {
+            cluster_contract: h("asset0001"),
+            cluster_token: h("cluster_token0001"),
+            weight: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cluster does not exist".to_string())
+    );
+
+    // cannot recommission with the cluster token of another cluster
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::RecommissionCluster {
+            cluster_contract: h("asset0000"),
+            cluster_token: h("cluster_token0001"),
+            weight: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cluster token does not match the cluster".to_string())
+    );
+
+    // cannot count the weight of an already weighted cluster token twice
+    store_weight(&mut deps.storage, &Addr::unchecked("cluster_token0000"), 10).unwrap();
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cluster token already has a distribution weight".to_string())
+    );
+    remove_weight(&mut deps.storage, &Addr::unchecked("cluster_token0000"));
+
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: h("asset0000"),
+            funds: vec![],
+            msg: to_binary(&ClusterExecuteMsg::Recommission {}).unwrap(),
+        }))]
+    );
+
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "recommission_asset"),
+            attr("cluster_token", "cluster_token0000"),
+            attr("cluster_contract", "asset0000"),
+            attr("weight", "20"),
+        ]
+    );
+
+    assert_eq!(
+        cluster_exists(&deps.storage, &Addr::unchecked("asset0000")).unwrap(),
+        true
+    );
+    assert_eq!(
+        read_weight(&deps.storage, &Addr::unchecked("cluster_token0000")).unwrap(),
+        20u32
+    );
+    assert_eq!(read_total_weight(&deps.storage).unwrap(), 50u32);
+
+    // cannot recommission an active cluster
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cluster is not decommissioned".to_string())
+    );
+}
+
+#[test]
+fn test_update_cluster_fees() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_astroport_pairs(&[(&"uusdnebula0000".to_string(), &"NEBLP0000".to_string())]);
+
+    let msg = InstantiateMsg {
+        base_denom: BASE_DENOM.to_string(),
+        token_code_id: TOKEN_CODE_ID,
+        cluster_code_id: CLUSTER_CODE_ID,
+        protocol_fee_rate: PROTOCOL_FEE_RATE.to_string(),
+        distribution_schedule: vec![],
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let msg = ExecuteMsg::PostInitialize {
+        owner: "owner0000".to_string(),
+        nebula_token: "nebula0000".to_string(),
+        staking_contract: "staking0000".to_string(),
+        commission_collector: "collector0000".to_string(),
+        astroport_factory: "astroportfactory".to_string(),
+    };
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // invalid fee rate in the creation params
+    let mut input_params: Params = get_input_params();
+    input_params.redeem_fee_rate = Some("1".to_string());
+    let msg = ExecuteMsg::CreateCluster {
+        params: input_params,
+    };
+    let info = mock_info("owner0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Fee rate must be less than 1".to_string())
+    );
+
+    // create a cluster with a custom redeem fee rate
+    let mut input_params: Params = get_input_params();
+    input_params.redeem_fee_rate = Some("0.02".to_string());
+    let msg = ExecuteMsg::CreateCluster {
+        params: input_params,
+    };
+    let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let mut token_inst_res = MsgInstantiateContractResponse::new();
+    token_inst_res.set_contract_address("asset0000".to_string());
+
+    let reply_msg = Reply {
+        id: 1,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![],
+            data: Some(token_inst_res.write_to_bytes().unwrap().into()),
+        }),
+    };
+    let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+
+    // the create fee rate falls back to the protocol fee rate
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::ClusterFees {
+            cluster_contract: h("asset0000"),
+        },
+    )
+    .unwrap();
+    let fees: ClusterFeesResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        fees,
+        ClusterFeesResponse {
+            cluster_contract: h("asset0000"),
+            create_fee_rate: PROTOCOL_FEE_RATE.to_string(),
+            redeem_fee_rate: h("0.02"),
+        }
+    );
+
+    // unauthorized update attempt
+    let msg = ExecuteMsg::UpdateClusterFees {
+        cluster_contract: h("asset0000"),
+        create_fee_rate: Some(h("0.005")),
+        redeem_fee_rate: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0001", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // unknown cluster
+    let msg_unknown = ExecuteMsg::UpdateClusterFees {
+        cluster_contract: h("asset0001"),
+        create_fee_rate: None,
+        redeem_fee_rate: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg_unknown).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cluster does not exist".to_string())
+    );
+
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_cluster_fees"),
+            attr("cluster_contract", "asset0000"),
+            attr("create_fee_rate", "0.005"),
+            attr("redeem_fee_rate", PROTOCOL_FEE_RATE),
+        ]
+    );
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::ClusterFeeList {}).unwrap();
+    let fee_list: ClusterFeeListResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        fee_list,
+        ClusterFeeListResponse {
+            fees: vec![ClusterFeesResponse {
+                cluster_contract: h("asset0000"),
+                create_fee_rate: h("0.005"),
+                redeem_fee_rate: PROTOCOL_FEE_RATE.to_string(),
+            }],
+        }
+    );
 }
 
 #[test]
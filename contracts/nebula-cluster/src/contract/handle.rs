@@ -4,28 +4,185 @@ use astroport::asset::{Asset, AssetInfo};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, to_binary, CosmosMsg, DepsMut, Env, MessageInfo, Response, StdResult, Storage, Uint128,
-    WasmMsg,
+    attr, from_binary, to_binary, Addr, Attribute, CosmosMsg, Decimal, DepsMut, Env, MessageInfo,
+    QuerierWrapper, Response, StdResult, Storage, Uint128, WasmMsg,
 };
-use cw20::Cw20ExecuteMsg;
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 
-use cluster_math::FPDecimal;
-use nebula_protocol::cluster::ExecuteMsg;
+use cluster_math::{
+    denormalize_amount, denormalize_amounts, normalize_amount, normalize_amounts, FPDecimal,
+};
+use nebula_protocol::cluster::{
+    ClusterCaps, ClusterConfig, ClusterStateResponse, Cw20HookMsg, ExecuteMsg,
+    PriceFreshnessPolicy, ReconcilePolicy, StalePriceFallback, TargetMode, TargetProvider,
+};
 use nebula_protocol::penalty::ExecuteMsg as PenaltyExecuteMsg;
 
-use crate::contract::{query_cluster_state, validate_targets};
+use crate::contract::{
+    compute_cluster_accrued_fee, compute_inventory_drift, query_cluster_state,
+    record_asset_decimals, validate_targets,
+};
 use crate::error::ContractError;
 use crate::ext_query::{
-    query_collector_contract_address, query_create_amount, query_redeem_amount,
+    query_cluster_fees, query_collector_contract_address, query_create_amount,
+    query_cw20_token_supply, query_provider_target, query_redeem_amount, query_swap_amount,
 };
-use crate::state::{config_store, read_config};
 use crate::state::{
-    read_asset_balance, read_target_asset_data, store_asset_balance, store_target_asset_data,
+    add_referral_earnings, next_redeem_request_id, read_asset_balance,
+    read_effective_target_asset_data, read_last_target_refresh, read_redeem_request,
+    read_target_asset_data, read_target_transition, remove_last_target_refresh,
+    remove_redeem_request, remove_target_transition, store_asset_balance, store_last_fee_accrual,
+    store_last_target_refresh, store_redeem_request, store_target_asset_data,
+    store_target_transition, RedeemRequest, TargetTransition,
 };
-use crate::util::vec_to_string;
+use crate::state::{config_store, read_config};
+use crate::util::{transition_fits, vec_to_string, VALUE_WEIGHT_TOTAL_BPS};
+
+/// ## Description
+/// Returns whether asset prices must be fresh according to the cluster
+/// price freshness policy.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **pro_rata** is an object of type [`bool`] which is set for pro-rata redeems.
+pub fn requires_fresh_prices(cfg: &ClusterConfig, pro_rata: bool) -> bool {
+    // - custom redeem / create: need asset prices to convert cluster tokens to assets
+    // - pro-rata redeem: convert cluster tokens to assets based on the asset ratio
+    //      in the current inventory, so stale prices are allowed unless the
+    //      policy rejects them on an active cluster
+    !pro_rata || (cfg.active && cfg.price_freshness.stale_fallback == StalePriceFallback::Reject)
+}
+
+/// ## Description
+/// Validates that a create can be performed on the cluster, shared by the
+/// `RebalanceCreate` execution and its simulation.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+pub fn assert_create_allowed(cfg: &ClusterConfig) -> Result<(), ContractError> {
+    if !cfg.active {
+        // Cannot perform create operation on decommissioned clusters
+        return Err(ContractError::ClusterAlreadyDecommissioned {});
+    }
+    if cfg.create_paused {
+        return Err(ContractError::CreatePaused {});
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Validates that a redeem can be performed on the cluster, shared by the
+/// `RebalanceRedeem` execution and its simulation.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **pro_rata** is an object of type [`bool`] which is set for pro-rata redeems.
+pub fn assert_redeem_allowed(cfg: &ClusterConfig, pro_rata: bool) -> Result<(), ContractError> {
+    // If cluster is not active, must do pro rata redeem
+    if !cfg.active && !pro_rata {
+        return Err(ContractError::Generic(
+            "Cannot call non pro-rata redeem on a decommissioned cluster".to_string(),
+        ));
+    }
+    // Pro-rata redeem stays available while custom redeem is paused
+    if cfg.custom_redeem_paused && !pro_rata {
+        return Err(ContractError::CustomRedeemPaused {});
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Validates that a swap can be performed on the cluster, shared by the
+/// `Swap` execution and its simulation.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+pub fn assert_swap_allowed(cfg: &ClusterConfig) -> Result<(), ContractError> {
+    if !cfg.active {
+        // Cannot perform swap operation on decommissioned clusters
+        return Err(ContractError::ClusterAlreadyDecommissioned {});
+    }
+
+    // A swap deposits one asset and withdraws another one, so it is
+    // unavailable while either create or custom redeem is paused
+    if cfg.create_paused {
+        return Err(ContractError::CreatePaused {});
+    }
+    if cfg.custom_redeem_paused {
+        return Err(ContractError::CustomRedeemPaused {});
+    }
+    Ok(())
+}
 
-/// Prices last 60s before they go from fresh to stale
-const FRESH_TIMESPAN: u64 = 60;
+/// ## Description
+/// Validates that depositing `deposit_amounts` keeps each asset inventory within
+/// its inventory cap.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **target** is a reference to an object of type [`Vec<Asset>`] which is
+///     the cluster target.
+///
+/// - **inv** is a reference to an object of type [`Vec<Uint128>`] which are the
+///     asset inventories ordered by the cluster target.
+///
+/// - **deposit_amounts** is a reference to an object of type [`Vec<Uint128>`] which
+///     are the deposited asset amounts ordered by the cluster target.
+fn assert_inventory_caps(
+    cfg: &ClusterConfig,
+    target: &[Asset],
+    inv: &[Uint128],
+    deposit_amounts: &[Uint128],
+) -> Result<(), ContractError> {
+    for ((asset, inv_amount), deposit_amount) in
+        target.iter().zip(inv.iter()).zip(deposit_amounts.iter())
+    {
+        if let Some(cap) = cfg.caps.inventory_cap_of(&asset.info) {
+            let new_inv = inv_amount.checked_add(*deposit_amount)?;
+            if new_inv > cap {
+                return Err(ContractError::CapExceeded(
+                    asset.info.to_string(),
+                    new_inv,
+                    cap,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Validates that minting `mint_amount` cluster tokens keeps the cluster token
+/// supply within the supply cap.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **cluster_token_supply** is an object of type [`Uint128`] which is the current
+///     cluster token supply.
+///
+/// - **mint_amount** is an object of type [`Uint128`] which is the total amount
+///     of cluster tokens to mint.
+fn assert_supply_cap(
+    cfg: &ClusterConfig,
+    cluster_token_supply: Uint128,
+    mint_amount: Uint128,
+) -> Result<(), ContractError> {
+    if let Some(supply_cap) = cfg.caps.supply_cap {
+        let new_supply = cluster_token_supply.checked_add(mint_amount)?;
+        if new_supply > supply_cap {
+            return Err(ContractError::CapExceeded(
+                "cluster token supply".to_string(),
+                new_supply,
+                supply_cap,
+            ));
+        }
+    }
+    Ok(())
+}
 
 /// ## Description
 /// Exposes all the execute functions available in the contract.
@@ -49,21 +206,61 @@ const FRESH_TIMESPAN: u64 = 60;
 ///             target_oracle,
 ///             penalty,
 ///             target,
+///             price_freshness,
+///             management_fee_rate,
+///             caps,
+///             reconcile_policy,
+///             guardian,
+///             swap_fee_rate,
+///             target_mode,
+///             target_provider,
+///             redeem_bounty_rate,
+///             native_decimals,
+///             referral_fee_rate,
 ///         }** Updates general contract parameters.
 ///
+/// - **ExecuteMsg::ClearTargetProvider {}** Removes the target provider.
+///
 /// - **ExecuteMsg::RebalanceCreate {
 ///             asset_amounts,
 ///             min_tokens,
+///             referrer,
 ///         }** Perform Create operation, i.e. mint the cluster tokens.
 ///
 /// - **ExecuteMsg::RebalanceRedeem {
 ///             max_tokens,
 ///             asset_amounts,
+///             referrer,
 ///         }** Perform Redeem operation, i.e. burn the cluster tokens.
 ///
-/// - **ExecuteMsg::UpdateTarget { target }** Updates the target weights of assets in the cluster.
+/// - **ExecuteMsg::Receive (msg)** Receives CW20 tokens and executes a hook message.
+///
+/// - **ExecuteMsg::AccrueFee {}** Mints the accrued management fee to the collector.
+///
+/// - **ExecuteMsg::ReconcileInventory {}** Settles the difference between the tracked
+///         inventory and the actual asset balances.
+///
+/// - **ExecuteMsg::SetPause {
+///             create_paused,
+///             custom_redeem_paused,
+///         }** Pauses or unpauses create and custom redeem.
+///
+/// - **ExecuteMsg::Swap {
+///             offer_asset,
+///             ask_asset_info,
+///             min_return,
+///         }** Swaps an inventory asset for another one.
+///
+/// - **ExecuteMsg::ExecuteRedeemRequest { request_id }** Executes a queued redeem request.
+///
+/// - **ExecuteMsg::CancelRedeemRequest { request_id }** Cancels a queued redeem request
+///         of the sender.
+///
+/// - **ExecuteMsg::UpdateTarget { target, duration }** Updates the target weights of assets in the cluster.
 ///
 /// - **ExecuteMsg::Decommission {}** Decommission the cluster.
+///
+/// - **ExecuteMsg::Recommission {}** Reactivates a decommissioned cluster.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -81,6 +278,17 @@ pub fn execute(
             target_oracle,
             penalty,
             target,
+            price_freshness,
+            management_fee_rate,
+            caps,
+            reconcile_policy,
+            guardian,
+            swap_fee_rate,
+            target_mode,
+            target_provider,
+            redeem_bounty_rate,
+            native_decimals,
+            referral_fee_rate,
         } => update_config(
             deps,
             env,
@@ -93,17 +301,52 @@ pub fn execute(
             target_oracle,
             penalty,
             target,
+            price_freshness,
+            management_fee_rate,
+            caps,
+            reconcile_policy,
+            guardian,
+            swap_fee_rate,
+            target_mode,
+            target_provider,
+            redeem_bounty_rate,
+            native_decimals,
+            referral_fee_rate,
         ),
+        ExecuteMsg::ClearTargetProvider {} => clear_target_provider(deps, info),
         ExecuteMsg::RebalanceCreate {
             asset_amounts,
             min_tokens,
-        } => create(deps, env, info, asset_amounts, min_tokens),
+            referrer,
+        } => create(deps, env, info, asset_amounts, min_tokens, referrer),
         ExecuteMsg::RebalanceRedeem {
             max_tokens,
             asset_amounts,
-        } => receive_redeem(deps, env, info, max_tokens, asset_amounts),
-        ExecuteMsg::UpdateTarget { target } => update_target(deps, env, info, &target),
-        ExecuteMsg::Decommission {} => decommission(deps, info),
+            referrer,
+        } => receive_redeem(deps, env, info, max_tokens, asset_amounts, referrer),
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::AccrueFee {} => accrue_fee(deps, env),
+        ExecuteMsg::ReconcileInventory {} => reconcile_inventory(deps, env),
+        ExecuteMsg::SetPause {
+            create_paused,
+            custom_redeem_paused,
+        } => set_pause(deps, info, create_paused, custom_redeem_paused),
+        ExecuteMsg::Swap {
+            offer_asset,
+            ask_asset_info,
+            min_return,
+        } => swap(deps, env, info, offer_asset, ask_asset_info, min_return),
+        ExecuteMsg::ExecuteRedeemRequest { request_id } => {
+            execute_redeem_request(deps, env, info, request_id)
+        }
+        ExecuteMsg::CancelRedeemRequest { request_id } => {
+            cancel_redeem_request(deps, info, request_id)
+        }
+        ExecuteMsg::UpdateTarget { target, duration } => {
+            update_target(deps, env, info, &target, duration)
+        }
+        ExecuteMsg::Decommission {} => decommission(deps, env, info),
+        ExecuteMsg::Recommission {} => recommission(deps, env, info),
     }
 }
 
@@ -138,11 +381,44 @@ pub fn execute(
 /// - **target** is an object of type [`Option<Vec<Asset>>`] which is the new target weights
 ///     of the cluster assets.
 ///
+/// - **price_freshness** is an object of type [`Option<PriceFreshnessPolicy>`] which is
+///     the new oracle price freshness policy of the cluster.
+///
+/// - **management_fee_rate** is an object of type [`Option<Decimal>`] which is the new
+///     annualised management fee rate of the cluster.
+///
+/// - **caps** is an object of type [`Option<ClusterCaps>`] which is the new cluster
+///     token supply and asset inventory caps of the cluster.
+///
+/// - **reconcile_policy** is an object of type [`Option<ReconcilePolicy>`] which is
+///     the new policy applied to untracked asset surpluses on reconciliation.
+///
+/// - **guardian** is an object of type [`Option<String>`] which is the address allowed
+///     to pause and unpause create and custom redeem.
+///
+/// - **swap_fee_rate** is an object of type [`Option<Decimal>`] which is the new fee
+///     rate charged on the asked asset of swaps.
+///
+/// - **target_mode** is an object of type [`Option<TargetMode>`] which is the new
+///     interpretation of the asset target weights.
+///
+/// - **target_provider** is an object of type [`Option<TargetProvider>`] which is the
+///     new contract providing the asset target weights.
+///
+/// - **redeem_bounty_rate** is an object of type [`Option<Decimal>`] which is the new
+///     ratio of the escrowed cluster tokens paid to the keeper executing a queued redeem.
+///
+/// - **native_decimals** is an object of type [`Option<Vec<(String, u8)>>`] which are
+///     the new decimals of specific native assets.
+///
+/// - **referral_fee_rate** is an object of type [`Option<Decimal>`] which is the new
+///     share of the create / redeem protocol fee paid to the referrer.
+///
 /// ## Executor
 /// Only the owner can execute this.
 #[allow(clippy::too_many_arguments)]
 pub fn update_config(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     owner: Option<String>,
@@ -153,7 +429,27 @@ pub fn update_config(
     target_oracle: Option<String>,
     penalty: Option<String>,
     target: Option<Vec<Asset>>,
+    price_freshness: Option<PriceFreshnessPolicy>,
+    management_fee_rate: Option<Decimal>,
+    caps: Option<ClusterCaps>,
+    reconcile_policy: Option<ReconcilePolicy>,
+    guardian: Option<String>,
+    swap_fee_rate: Option<Decimal>,
+    target_mode: Option<TargetMode>,
+    target_provider: Option<TargetProvider>,
+    redeem_bounty_rate: Option<Decimal>,
+    native_decimals: Option<Vec<(String, u8)>>,
+    referral_fee_rate: Option<Decimal>,
 ) -> Result<Response, ContractError> {
+    // Accrue the management fee at the previous rate before changing it
+    let messages = match management_fee_rate {
+        Some(_) => {
+            let cfg = read_config(deps.storage)?;
+            accrue_management_fee(deps.branch(), &env, &cfg)?.1
+        }
+        None => vec![],
+    };
+
     let api = deps.api;
 
     // Update cluster config
@@ -198,15 +494,254 @@ pub fn update_config(
             config.penalty = api.addr_validate(penalty.as_str())?;
         }
 
+        if let Some(price_freshness) = price_freshness {
+            // Zero max price ages would reject every oracle price
+            if price_freshness.max_price_age == 0
+                || price_freshness
+                    .asset_max_price_ages
+                    .iter()
+                    .any(|(_, age)| *age == 0)
+            {
+                return Err(ContractError::Generic(
+                    "Max price age must be greater than zero".to_string(),
+                ));
+            }
+            config.price_freshness = price_freshness;
+        }
+
+        if let Some(management_fee_rate) = management_fee_rate {
+            if management_fee_rate >= Decimal::one() {
+                return Err(ContractError::Generic(
+                    "Management fee rate must be less than 1".to_string(),
+                ));
+            }
+            config.management_fee_rate = management_fee_rate;
+        }
+
+        if let Some(caps) = caps {
+            // Each asset can only have a single inventory cap
+            let mut capped_infos: Vec<&AssetInfo> = vec![];
+            for cap in caps.inventory_caps.iter() {
+                if capped_infos.contains(&&cap.info) {
+                    return Err(ContractError::Generic(
+                        "Duplicate inventory caps for an asset".to_string(),
+                    ));
+                }
+                capped_infos.push(&cap.info);
+            }
+            config.caps = caps;
+        }
+
+        if let Some(reconcile_policy) = reconcile_policy {
+            if let ReconcilePolicy::Refund { recipient } = &reconcile_policy {
+                // Validate address format
+                api.addr_validate(recipient.as_str())?;
+            }
+            config.reconcile_policy = reconcile_policy;
+        }
+
+        if let Some(guardian) = guardian {
+            // Validate address format
+            config.guardian = Some(api.addr_validate(guardian.as_str())?);
+        }
+
+        if let Some(swap_fee_rate) = swap_fee_rate {
+            if swap_fee_rate >= Decimal::one() {
+                return Err(ContractError::Generic(
+                    "Swap fee rate must be less than 1".to_string(),
+                ));
+            }
+            config.swap_fee_rate = swap_fee_rate;
+        }
+
+        if let Some(target_mode) = target_mode {
+            // Stored target weights cannot be reinterpreted in another mode
+            if target_mode != config.target_mode && target.is_none() {
+                return Err(ContractError::Generic(
+                    "Changing the target mode requires a new target".to_string(),
+                ));
+            }
+            config.target_mode = target_mode;
+        }
+
+        if let Some(target_provider) = &target_provider {
+            // Validate address format
+            api.addr_validate(target_provider.address.as_str())?;
+            if target_provider.refresh_interval == 0 {
+                return Err(ContractError::Generic(
+                    "Target provider refresh interval must be greater than zero".to_string(),
+                ));
+            }
+            config.target_provider = Some(target_provider.clone());
+        }
+
+        if let Some(redeem_bounty_rate) = redeem_bounty_rate {
+            if redeem_bounty_rate >= Decimal::one() {
+                return Err(ContractError::Generic(
+                    "Redeem bounty rate must be less than 1".to_string(),
+                ));
+            }
+            config.redeem_bounty_rate = redeem_bounty_rate;
+        }
+
+        if let Some(native_decimals) = native_decimals.clone() {
+            config.native_decimals = native_decimals;
+        }
+
+        if let Some(referral_fee_rate) = referral_fee_rate {
+            if referral_fee_rate > Decimal::one() {
+                return Err(ContractError::Generic(
+                    "Referral fee rate must not exceed 1".to_string(),
+                ));
+            }
+            config.referral_fee_rate = referral_fee_rate;
+        }
+
         Ok(config)
     })?;
 
+    // Record the new decimals of the native assets in the cluster
+    if native_decimals.is_some() {
+        let cfg = read_config(deps.storage)?;
+        let native_infos = read_target_asset_data(deps.storage)?
+            .into_iter()
+            .map(|x| x.info)
+            .filter(|x| x.is_native_token())
+            .collect::<Vec<_>>();
+        record_asset_decimals(deps.querier, deps.storage, &cfg, &native_infos)?;
+    }
+
+    // Pull the target weights from a new target provider on the next operation
+    if target_provider.is_some() {
+        remove_last_target_refresh(deps.storage);
+    }
+
     // Update cluster target
     if let Some(target) = target {
-        update_target(deps, env, info, &target)?;
+        update_target(deps, env, info, &target, None)?;
+    }
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![attr("action", "update_config")]))
+}
+
+/// ## Description
+/// Removes the target provider, so that the target weights are only changed by
+/// `UpdateTarget` again.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// ## Executor
+/// Only the owner can execute this.
+pub fn clear_target_provider(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    config_store(deps.storage).update(|mut config| {
+        // Permission check
+        if config.owner != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        config.target_provider = None;
+        Ok(config)
+    })?;
+
+    remove_last_target_refresh(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![attr("action", "clear_target_provider")]))
+}
+
+/// ## Description
+/// Pauses or unpauses create and custom redeem. Pro-rata redeem is always available.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **create_paused** is an object of type [`Option<bool>`] which is whether
+///     create is paused.
+///
+/// - **custom_redeem_paused** is an object of type [`Option<bool>`] which is whether
+///     custom redeem is paused.
+///
+/// ## Executor
+/// Only the guardian or the owner can execute this.
+pub fn set_pause(
+    deps: DepsMut,
+    info: MessageInfo,
+    create_paused: Option<bool>,
+    custom_redeem_paused: Option<bool>,
+) -> Result<Response, ContractError> {
+    let cfg = config_store(deps.storage).update(|mut config| {
+        // Permission check
+        if config.owner != info.sender && config.guardian.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        if let Some(create_paused) = create_paused {
+            config.create_paused = create_paused;
+        }
+
+        if let Some(custom_redeem_paused) = custom_redeem_paused {
+            config.custom_redeem_paused = custom_redeem_paused;
+        }
+
+        Ok(config)
+    })?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_pause"),
+        attr("create_paused", cfg.create_paused.to_string()),
+        attr("custom_redeem_paused", cfg.custom_redeem_paused.to_string()),
+    ]))
+}
+
+/// ## Description
+/// Validates the address of a referrer. Returns a [`ContractError`] if the
+/// referrer is the sender itself.
+///
+/// ## Params
+/// - **deps** is a reference to an object of type [`DepsMut`].
+///
+/// - **sender** is a reference to an object of type [`Addr`] which is the sender
+///     of the create / redeem operation.
+///
+/// - **referrer** is an object of type [`Option<String>`] which is the address
+///     receiving a share of the protocol fee.
+fn validate_referrer(
+    deps: &DepsMut,
+    sender: &Addr,
+    referrer: Option<String>,
+) -> Result<Option<Addr>, ContractError> {
+    let referrer = referrer
+        .map(|x| deps.api.addr_validate(x.as_str()))
+        .transpose()?;
+    if referrer.as_ref() == Some(sender) {
+        return Err(ContractError::Generic("Cannot refer yourself".to_string()));
     }
+    Ok(referrer)
+}
 
-    Ok(Response::new().add_attributes(vec![attr("action", "update_config")]))
+/// ## Description
+/// Returns the share of the protocol fee paid to the referrer, if any.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **referrer** is a reference to an object of type [`Option<Addr>`] which is
+///     the address receiving a share of the protocol fee.
+///
+/// - **protocol_fee** is an object of type [`Uint128`] which is the protocol fee
+///     in cluster tokens.
+fn referral_fee(cfg: &ClusterConfig, referrer: &Option<Addr>, protocol_fee: Uint128) -> Uint128 {
+    match referrer {
+        // -- referral_fee = protocol_fee * referral_fee_rate
+        Some(_) => protocol_fee * cfg.referral_fee_rate,
+        None => Uint128::zero(),
+    }
 }
 
 /// ## Description
@@ -226,13 +761,19 @@ pub fn update_config(
 ///
 /// - **min_tokens** is an object of type [`Option<Uint128>`] which is the required
 ///     minimum amount of minted cluster tokens.
+///
+/// - **referrer** is an object of type [`Option<String>`] which is the address
+///     receiving a share of the protocol fee.
 pub fn create(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     asset_amounts: Vec<Asset>,
     min_tokens: Option<Uint128>,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> {
+    let referrer = validate_referrer(&deps, &info.sender, referrer)?;
+
     // Check `asset_amounts` for duplicate and unsupported assets
     if validate_targets(
         deps.querier,
@@ -245,23 +786,23 @@ pub fn create(
     }
 
     let cfg = read_config(deps.storage)?;
+    assert_create_allowed(&cfg)?;
 
-    if !cfg.active {
-        // Cannot perform create operation on decommissioned clusters
-        return Err(ContractError::ClusterAlreadyDecommissioned {});
-    }
+    // Pull the target weights from the target provider if due
+    refresh_target(deps.branch(), &env, &cfg)?;
 
     // Retrieve the cluster state
     let cluster_state = query_cluster_state(
         deps.as_ref(),
         &env.contract.address.to_string(),
-        env.block.time.seconds() - FRESH_TIMESPAN,
+        env.block.time.seconds(),
+        true,
     )?;
 
-    let prices = cluster_state.prices;
+    let prices = cluster_state.prices.clone();
     let cluster_token_supply = cluster_state.outstanding_balance_tokens;
-    let inv = cluster_state.inv;
-    let target = cluster_state.target;
+    let inv = cluster_state.inv.clone();
+    let target = cluster_state.target.clone();
 
     let target_infos = target.iter().map(|x| x.info.clone()).collect::<Vec<_>>();
 
@@ -280,12 +821,17 @@ pub fn create(
 
     let target_weights = target.iter().map(|x| x.amount).collect::<Vec<_>>();
 
-    let cluster_token = cluster_state.cluster_token;
+    let cluster_token = cluster_state.cluster_token.clone();
+
+    // Order the provided asset amounts following the cluster target
+    let create_asset_amounts = to_create_asset_amounts(&target, &asset_amounts)?;
 
-    // Vector to store create asset weights
-    let mut asset_weights = vec![Uint128::zero(); target_infos.len()];
+    // Validate that the deposits do not exceed the asset inventory caps before
+    // updating the inventory balances
+    assert_inventory_caps(&cfg, &target, &inv, &create_asset_amounts)?;
 
-    let mut messages = vec![];
+    // Mint the accrued management fee, already included in the cluster token supply
+    let (_, mut messages) = accrue_management_fee(deps.branch(), &env, &cfg)?;
 
     // Return an error if assets not in target are sent to the create function
     for coin in info.funds.iter() {
@@ -297,19 +843,10 @@ pub fn create(
     }
 
     // Verify asset transfers and update cluster inventory balance
-    for (i, asset_info) in target_infos.iter().enumerate() {
+    for asset_info in target_infos.iter() {
         for asset in asset_amounts.iter() {
             // Match each provided asset with the its target
             if asset.info.clone() == asset_info.clone() {
-                // Verify the provided non-zero asset amount does not have target of zero
-                if target_weights[i] == Uint128::zero() && asset.amount > Uint128::zero() {
-                    return Err(ContractError::Generic(
-                        format!("Cannot call create with non-zero asset amount when target weight is zero for asset {}", asset.info.to_string()),
-                    ));
-                };
-
-                asset_weights[i] = asset.amount;
-
                 // Transfer assets from sender to this cluster contract
                 if let AssetInfo::Token { contract_addr, .. } = &asset.info {
                     // Execute the asset CW20 contract to transfer the asset amount
@@ -342,40 +879,32 @@ pub fn create(
         }
     }
 
-    let create_asset_amounts = asset_weights.clone();
-
-    // Keep track of the cluster token amount minted to the sender
+    // Keep track of the cluster token amounts minted to the sender and to the referrer
     let mint_amount_to_sender;
+    let mut mint_amount_to_referrer = Uint128::zero();
 
     // Mint cluster tokens and deduct protocol fees
     let mut extra_logs = vec![];
     // If cluster has been initialized
     if !cluster_token_supply.is_zero() {
-        // Query cluster token amounts from a normal mint
-        let create_response = query_create_amount(
+        // Compute the cluster token amounts for the sender and the protocol fee,
+        // within the supply cap
+        let CreateAmount {
+            mint_to_sender,
+            protocol_fee,
+            collector_address,
+            attributes,
+            ..
+        } = compute_create_amount(
             &deps.querier,
-            &cfg.penalty,
             env.block.height,
-            cluster_token_supply,
-            inv.clone(),
-            create_asset_amounts.clone(),
-            prices.clone(),
-            target_weights.clone(),
+            &cfg,
+            &cluster_state,
+            &create_asset_amounts,
         )?;
-        let create_amount = create_response.create_tokens;
-
-        // Retrieve collector contract and fee rate
-        let (collector_address, fee_rate) =
-            query_collector_contract_address(&deps.querier, &cfg.factory)?;
-        let fee_rate = FPDecimal::from_str(&fee_rate)?;
-
-        // Calculate the cluster token amount for sender and fee amount
-        // mint_to_sender = mint_total * (1 - fee_rate)
-        // protocol_fee = mint_total - mint_to_sender == mint_total * fee_rate
-        let _mint_to_sender: u128 =
-            (FPDecimal::from(create_amount.u128()) * (FPDecimal::one() - fee_rate)).into();
-        mint_amount_to_sender = Uint128::from(_mint_to_sender);
-        let protocol_fee = create_amount.checked_sub(mint_amount_to_sender)?;
+        mint_amount_to_sender = mint_to_sender;
+        mint_amount_to_referrer = referral_fee(&cfg, &referrer, protocol_fee);
+        let mint_amount_to_collector = protocol_fee.checked_sub(mint_amount_to_referrer)?;
 
         // Update penalty contract states
         messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
@@ -383,8 +912,11 @@ pub fn create(
             msg: to_binary(&PenaltyExecuteMsg::PenaltyCreate {
                 block_height: env.block.height,
                 cluster_token_supply,
-                inventory: inv,
-                create_asset_amounts,
+                inventory: normalize_amounts(&inv, &cluster_state.asset_decimals)?,
+                create_asset_amounts: normalize_amounts(
+                    &create_asset_amounts,
+                    &cluster_state.asset_decimals,
+                )?,
                 asset_prices: prices,
                 target_weights,
             })?,
@@ -392,19 +924,38 @@ pub fn create(
         }));
 
         // Mint cluster tokens of fee amount to the collector contract
-        if !protocol_fee.is_zero() {
+        if !mint_amount_to_collector.is_zero() {
             messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: cluster_token.to_string(),
                 msg: to_binary(&Cw20ExecuteMsg::Mint {
-                    amount: protocol_fee,
+                    amount: mint_amount_to_collector,
                     recipient: collector_address,
                 })?,
                 funds: vec![],
             }));
         }
 
-        extra_logs = create_response.attributes;
-        extra_logs.push(attr("fee_amt", protocol_fee))
+        // Mint the referrer share of the fee to the referrer
+        if let Some(referrer) = referrer
+            .as_ref()
+            .filter(|_| !mint_amount_to_referrer.is_zero())
+        {
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: cluster_token.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    amount: mint_amount_to_referrer,
+                    recipient: referrer.to_string(),
+                })?,
+                funds: vec![],
+            }));
+        }
+
+        extra_logs = attributes;
+        extra_logs.push(attr("fee_amt", protocol_fee));
+        if let Some(referrer) = &referrer {
+            extra_logs.push(attr("referrer", referrer));
+            extra_logs.push(attr("referral_fee", mint_amount_to_referrer));
+        }
     } else {
         // Cluster has no cluster tokens -- cluster is empty and needs to be initialized.
         // Attempt to initialize it with `min_tokens` as the number of cluster tokens
@@ -412,8 +963,20 @@ pub fn create(
         // c is required to be in ratio with the target weights
         if let Some(proposed_mint_total) = min_tokens {
             let mut val = 0;
-            // Check if ratios, between each asset amount and its target weight, are all the same
+            // Check if ratios, between each asset amount and its target weight, are all the same.
+            // Unit weights derived from value weights are not integer ratios, so only
+            // require a deposit of every weighted asset in value weight target mode
             for i in 0..create_asset_amounts.len() {
+                if cfg.target_mode == TargetMode::ValueWeights {
+                    if !target_weights[i].is_zero() && create_asset_amounts[i].is_zero() {
+                        return Err(ContractError::Generic(format!(
+                            "Initial cluster assets must be nonzero for weighted assets at index {}",
+                            i
+                        )));
+                    }
+                    continue;
+                }
+
                 if (create_asset_amounts[i].u128() % target_weights[i].u128() != 0)
                     || create_asset_amounts[i] == Uint128::zero()
                 {
@@ -438,6 +1001,9 @@ pub fn create(
 
             // Set the cluster token mint amount to the `min_tokens`
             mint_amount_to_sender = proposed_mint_total;
+
+            // Validate that the initial cluster tokens are within the supply cap
+            assert_supply_cap(&cfg, cluster_token_supply, mint_amount_to_sender)?;
         } else {
             return Err(ContractError::Generic(
                 "Cluster is uninitialized. To initialize it with your mint cluster, \
@@ -457,6 +1023,14 @@ pub fn create(
         }
     }
 
+    // Record the referrer share of the fee
+    if let Some(referrer) = referrer
+        .as_ref()
+        .filter(|_| !mint_amount_to_referrer.is_zero())
+    {
+        add_referral_earnings(deps.storage, referrer, mint_amount_to_referrer)?;
+    }
+
     // Mint and send cluster tokens to the sender
     messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
         contract_addr: cluster_token,
@@ -478,10 +1052,7 @@ pub fn create(
 }
 
 /// ## Description
-/// Receives cluster tokens which are burned for assets according to the given
-/// `asset_weights` and cluster penalty parameter. The corresponding assets are
-/// taken from the cluster inventory with the cluster token discount as rewards
-/// based on whether the assets are moved towards/away from the target.
+/// Receives CW20 tokens and executes a hook message.
 ///
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
@@ -490,66 +1061,822 @@ pub fn create(
 ///
 /// - **info** is an object of type [`MessageInfo`].
 ///
-/// - **max_tokens** is an object of type [`Uint128`] which is the required
-///     maximum amount of cluster tokens allowed to burn.
-///
-/// - **asset_amounts** is an object of type [`Option<Vec<Asset>>`] which are the assets amount
-///     the sender wishes to receive.
-pub fn receive_redeem(
+/// - **cw20_msg** is an object of type [`Cw20ReceiveMsg`] which is a hook message to be executed.
+pub fn receive_cw20(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    max_tokens: Uint128,
-    asset_amounts: Option<Vec<Asset>>,
+    cw20_msg: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    let sender = info.sender;
-
     let cfg = read_config(deps.storage)?;
 
-    // If cluster is not active, must do pro rata redeem
-    if !cfg.active && asset_amounts.is_some() {
-        return Err(ContractError::Generic(
-            "Cannot call non pro-rata redeem on a decommissioned cluster".to_string(),
-        ));
+    // Permission check, only the cluster token contract can execute this message
+    if cfg.cluster_token != Some(info.sender) {
+        return Err(ContractError::Unauthorized {});
     }
 
-    let asset_amounts = if !cfg.active { None } else { asset_amounts };
+    match from_binary(&cw20_msg.msg) {
+        // If `Redeem`, burns the sent cluster tokens for assets sent to the recipient
+        Ok(Cw20HookMsg::Redeem {
+            asset_amounts,
+            min_assets,
+            recipient,
+        }) => {
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            let recipient = recipient
+                .map(|x| deps.api.addr_validate(x.as_str()))
+                .transpose()?
+                .unwrap_or_else(|| sender.clone());
+            redeem(
+                deps,
+                env,
+                sender,
+                recipient,
+                cw20_msg.amount,
+                asset_amounts,
+                min_assets,
+                true,
+                None,
+            )
+        }
+        // If `QueueRedeem`, escrows the sent cluster tokens in a redeem request
+        Ok(Cw20HookMsg::QueueRedeem {
+            asset_amounts,
+            min_assets,
+        }) => {
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            queue_redeem(
+                deps,
+                env,
+                sender,
+                cw20_msg.amount,
+                asset_amounts,
+                min_assets,
+            )
+        }
+        Err(_) => Err(ContractError::Generic(
+            "invalid cw20 hook message".to_string(),
+        )),
+    }
+}
+
+/// ## Description
+/// Receives cluster tokens which are burned for assets according to the given
+/// `asset_weights` and cluster penalty parameter. The corresponding assets are
+/// taken from the cluster inventory with the cluster token discount as rewards
+/// based on whether the assets are moved towards/away from the target.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **max_tokens** is an object of type [`Uint128`] which is the required
+///     maximum amount of cluster tokens allowed to burn.
+///
+/// - **asset_amounts** is an object of type [`Option<Vec<Asset>>`] which are the assets amount
+///     the sender wishes to receive.
+///
+/// - **referrer** is an object of type [`Option<String>`] which is the address
+///     receiving a share of the protocol fee.
+pub fn receive_redeem(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    max_tokens: Uint128,
+    asset_amounts: Option<Vec<Asset>>,
+    referrer: Option<String>,
+) -> Result<Response, ContractError> {
+    let referrer = validate_referrer(&deps, &info.sender, referrer)?;
+    redeem(
+        deps,
+        env,
+        info.sender.clone(),
+        info.sender,
+        max_tokens,
+        asset_amounts,
+        None,
+        false,
+        referrer,
+    )
+}
+
+/// ## Description
+/// Burns cluster tokens of the sender for assets sent to the recipient. The cluster
+/// tokens are either taken from the sender allowance, or were already sent to the
+/// cluster in which case the unused amount is refunded to the sender.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **sender** is an object of type [`Addr`] which is the owner of the cluster tokens.
+///
+/// - **recipient** is an object of type [`Addr`] which receives the redeemed assets.
+///
+/// - **max_tokens** is an object of type [`Uint128`] which is the required
+///     maximum amount of cluster tokens allowed to burn.
+///
+/// - **asset_amounts** is an object of type [`Option<Vec<Asset>>`] which are the assets amount
+///     the sender wishes to receive.
+///
+/// - **min_assets** is an object of type [`Option<Vec<Asset>>`] which are the minimum
+///     amounts of assets to receive.
+///
+/// - **tokens_sent** is an object of type [`bool`] which is set when `max_tokens`
+///     cluster tokens were sent to the cluster.
+///
+/// - **referrer** is an object of type [`Option<Addr>`] which is the address
+///     receiving a share of the protocol fee.
+#[allow(clippy::too_many_arguments)]
+fn redeem(
+    mut deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    recipient: Addr,
+    max_tokens: Uint128,
+    asset_amounts: Option<Vec<Asset>>,
+    min_assets: Option<Vec<Asset>>,
+    tokens_sent: bool,
+    referrer: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    assert_redeem_allowed(&cfg, asset_amounts.is_none())?;
+
+    // Pull the target weights from the target provider if due. Pro-rata redeems
+    // do not depend on the target weights, so they never wait on the provider
+    if asset_amounts.is_some() {
+        refresh_target(deps.branch(), &env, &cfg)?;
+    }
 
     let cluster_token = cfg
         .cluster_token
+        .clone()
         .ok_or(ContractError::ClusterTokenNotSet {})?;
 
-    // Use min as stale threshold if pro-rata redeem
-    // - custom redeem: need asset prices to convert cluster tokens to assets
-    // - pro-rata redeem: convert cluster tokens to assets based on the asset ratio
-    //      in the current inventory
-    let stale_threshold = match asset_amounts {
-        Some(_) => env.block.time.seconds() - FRESH_TIMESPAN,
-        None => u64::MIN,
-    };
-
     // Retrieve the cluster state
     let cluster_state = query_cluster_state(
         deps.as_ref(),
         &env.contract.address.to_string(),
-        stale_threshold,
+        env.block.time.seconds(),
+        requires_fresh_prices(&cfg, asset_amounts.is_none()),
+    )?;
+
+    // Order the requested asset amounts following the cluster target
+    let asset_amounts = to_redeem_asset_amounts(&cluster_state.target, &asset_amounts);
+
+    // Compute the assets to return, the total cluster token cost, and the protocol fee
+    let RedeemAmount {
+        redeem_totals,
+        token_cost,
+        protocol_fee: fee_amt,
+        collector_address,
+        attributes,
+        ..
+    } = compute_redeem_amount(
+        &deps.querier,
+        env.block.height,
+        &cfg,
+        &cluster_state,
+        max_tokens,
+        &asset_amounts,
     )?;
 
     let prices = cluster_state.prices;
     let cluster_token_supply = cluster_state.outstanding_balance_tokens;
     let inv = cluster_state.inv;
     let target = cluster_state.target;
+    let asset_decimals = cluster_state.asset_decimals;
 
     let asset_infos = target.iter().map(|x| x.info.clone()).collect::<Vec<_>>();
 
     let target_weights = target.iter().map(|x| x.amount).collect::<Vec<_>>();
 
-    let asset_amounts: Vec<Uint128> = match &asset_amounts {
+    // Validate that the redeemed amounts are at least `min_assets`
+    for min_asset in min_assets.unwrap_or_default() {
+        let redeem_amount = asset_infos
+            .iter()
+            .zip(redeem_totals.iter())
+            .find(|(info, _)| **info == min_asset.info)
+            .map(|(_, amount)| *amount)
+            .unwrap_or_default();
+        if redeem_amount < min_asset.amount {
+            return Err(ContractError::BelowMinAssets(
+                min_asset.info.to_string(),
+                redeem_amount,
+                min_asset.amount,
+            ));
+        }
+    }
+
+    // Mint the accrued management fee, already included in the cluster token supply
+    let (_, mut messages) = accrue_management_fee(deps.branch(), &env, &cfg)?;
+
+    // Send `redeem_totals`, the assets from burning cluster tokens, to recipient
+    messages.extend(
+        redeem_totals
+            .iter()
+            .zip(asset_infos.iter())
+            .filter(|(amt, _asset)| !amt.is_zero()) // remove 0 amounts
+            .map(|(amt, asset_info)| {
+                if let AssetInfo::Token { contract_addr, .. } = &asset_info {
+                    update_asset_balance(deps.storage, &contract_addr.to_string(), *amt, false)?;
+                } else if let AssetInfo::NativeToken { denom } = &asset_info {
+                    update_asset_balance(deps.storage, denom, *amt, false)?;
+                }
+                let asset = Asset {
+                    info: asset_info.clone(),
+                    amount: *amt,
+                };
+
+                match asset.into_msg(&deps.querier, recipient.clone()) {
+                    Ok(msg) => Ok(msg),
+                    Err(e) => Err(ContractError::Std(e)),
+                }
+            })
+            .collect::<Result<Vec<CosmosMsg>, ContractError>>()?,
+    );
+
+    let burn_amount = token_cost.checked_sub(fee_amt)?;
+    let refund_amount = max_tokens.checked_sub(token_cost)?;
+    let referral_amt = referral_fee(&cfg, &referrer, fee_amt);
+    let fee_msg = |recipient: String, amount: Uint128| {
+        if tokens_sent {
+            // Cluster tokens were sent to the cluster
+            Cw20ExecuteMsg::Transfer { recipient, amount }
+        } else {
+            // Cluster tokens are taken from allowance
+            Cw20ExecuteMsg::TransferFrom {
+                owner: sender.to_string(),
+                amount,
+                recipient,
+            }
+        }
+    };
+    let burn_msg = if tokens_sent {
+        Cw20ExecuteMsg::Burn {
+            amount: burn_amount,
+        }
+    } else {
+        Cw20ExecuteMsg::BurnFrom {
+            owner: sender.to_string(),
+            amount: burn_amount,
+        }
+    };
+
+    // Send fee to collector contract
+    let collector_amt = fee_amt.checked_sub(referral_amt)?;
+    if !collector_amt.is_zero() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cluster_token.to_string(),
+            msg: to_binary(&fee_msg(collector_address, collector_amt))?,
+            funds: vec![],
+        }));
+    }
+
+    // Send the referrer share of the fee to the referrer
+    if let Some(referrer) = referrer.as_ref().filter(|_| !referral_amt.is_zero()) {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cluster_token.to_string(),
+            msg: to_binary(&fee_msg(referrer.to_string(), referral_amt))?,
+            funds: vec![],
+        }));
+        add_referral_earnings(deps.storage, referrer, referral_amt)?;
+    }
+
+    // Burn the rest of the redeem amount
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: cluster_token.to_string(),
+        msg: to_binary(&burn_msg)?,
+        funds: vec![],
+    }));
+
+    // Refund the unused sent cluster tokens to the sender
+    if tokens_sent && !refund_amount.is_zero() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cluster_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: sender.to_string(),
+                amount: refund_amount,
+            })?,
+            funds: vec![],
+        }));
+    }
+
+    // Afterwards, notify the penalty contract that this update happened, so
+    // the penalty contract can make stateful updates
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: cfg.penalty.to_string(),
+        msg: to_binary(&PenaltyExecuteMsg::PenaltyRedeem {
+            block_height: env.block.height,
+            cluster_token_supply,
+            inventory: normalize_amounts(&inv, &asset_decimals)?,
+            max_tokens,
+            redeem_asset_amounts: normalize_amounts(&asset_amounts, &asset_decimals)?,
+            asset_prices: prices,
+            target_weights,
+        })?,
+        funds: vec![],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(
+        vec![
+            vec![
+                attr("action", "receive:burn"),
+                attr("sender", sender),
+                attr("burn_amount", burn_amount.to_string()),
+                attr("token_cost", token_cost),
+                attr("kept_as_fee", fee_amt),
+                attr("asset_amounts", vec_to_string(&asset_amounts)),
+                attr("redeem_totals", vec_to_string(&redeem_totals)),
+            ],
+            if tokens_sent {
+                vec![
+                    attr("recipient", recipient),
+                    attr("refund_amount", refund_amount),
+                ]
+            } else {
+                vec![]
+            },
+            match &referrer {
+                Some(referrer) => vec![
+                    attr("referrer", referrer),
+                    attr("referral_fee", referral_amt),
+                ],
+                None => vec![],
+            },
+            attributes,
+        ]
+        .concat(),
+    ))
+}
+
+/// ## Description
+/// Escrows cluster tokens sent by the owner in a redeem request, to be executed
+/// later by anyone once the cluster asset prices are fresh.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **owner** is an object of type [`Addr`] which is the owner of the escrowed
+///     cluster tokens.
+///
+/// - **token_amount** is an object of type [`Uint128`] which is the amount of
+///     cluster tokens sent to the cluster.
+///
+/// - **asset_amounts** is an object of type [`Vec<Asset>`] which are the assets amount
+///     the owner wishes to receive.
+///
+/// - **min_assets** is an object of type [`Option<Vec<Asset>>`] which are the minimum
+///     amounts of assets to receive.
+fn queue_redeem(
+    deps: DepsMut,
+    env: Env,
+    owner: Addr,
+    token_amount: Uint128,
+    asset_amounts: Vec<Asset>,
+    min_assets: Option<Vec<Asset>>,
+) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+
+    // Queued redeems are executed as custom redeems
+    if !cfg.active {
+        return Err(ContractError::Generic(
+            "Cannot queue a redeem on a decommissioned cluster".to_string(),
+        ));
+    }
+    if cfg.custom_redeem_paused {
+        return Err(ContractError::CustomRedeemPaused {});
+    }
+
+    if token_amount.is_zero() {
+        return Err(ContractError::Generic(
+            "Cannot queue a redeem of zero cluster tokens".to_string(),
+        ));
+    }
+
+    // Both the requested and the minimum assets must be in the cluster target
+    let target = read_target_asset_data(deps.storage)?;
+    for asset in asset_amounts.iter().chain(min_assets.iter().flatten()) {
+        if !target.iter().any(|x| x.info == asset.info) {
+            return Err(ContractError::Generic(format!(
+                "Asset {} is not in the cluster",
+                asset.info
+            )));
+        }
+    }
+
+    let request = RedeemRequest {
+        id: next_redeem_request_id(deps.storage)?,
+        owner,
+        token_amount,
+        asset_amounts,
+        min_assets,
+        submitted_at: env.block.time.seconds(),
+    };
+    store_redeem_request(deps.storage, &request)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "queue_redeem"),
+        attr("request_id", request.id.to_string()),
+        attr("owner", request.owner),
+        attr("token_amount", request.token_amount),
+        attr("asset_amounts", vec_to_string(&request.asset_amounts)),
+    ]))
+}
+
+/// ## Description
+/// Executes a queued redeem request as a custom redeem burning the escrowed cluster
+/// tokens for assets sent to the request owner. Unless the sender owns the request,
+/// the redeem bounty is taken from the escrowed cluster tokens and sent to the sender.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **request_id** is an object of type [`u64`] which is the ID of the queued
+///     redeem request.
+///
+/// ## Executor
+/// Anyone can execute this.
+pub fn execute_redeem_request(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    request_id: u64,
+) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    let cluster_token = cfg
+        .cluster_token
+        .clone()
+        .ok_or(ContractError::ClusterTokenNotSet {})?;
+
+    let request = read_redeem_request(deps.storage, request_id)?;
+
+    // -- bounty = token_amount * redeem_bounty_rate
+    let bounty = if info.sender == request.owner {
+        Uint128::zero()
+    } else {
+        request.token_amount * cfg.redeem_bounty_rate
+    };
+
+    let response = redeem(
+        deps.branch(),
+        env,
+        request.owner.clone(),
+        request.owner.clone(),
+        request.token_amount.checked_sub(bounty)?,
+        Some(request.asset_amounts.clone()),
+        request.min_assets.clone(),
+        true,
+        None,
+    )?;
+    remove_redeem_request(deps.storage, &request);
+
+    // Pay the bounty to the keeper
+    let response = if bounty.is_zero() {
+        response
+    } else {
+        response.add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cluster_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: info.sender.to_string(),
+                amount: bounty,
+            })?,
+            funds: vec![],
+        }))
+    };
+
+    Ok(response.add_attributes(vec![
+        attr("request_id", request_id.to_string()),
+        attr("keeper", info.sender),
+        attr("bounty", bounty),
+    ]))
+}
+
+/// ## Description
+/// Cancels a queued redeem request and returns the escrowed cluster tokens to
+/// the request owner.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **request_id** is an object of type [`u64`] which is the ID of the queued
+///     redeem request.
+///
+/// ## Executor
+/// Only the owner of the request can execute this.
+pub fn cancel_redeem_request(
+    deps: DepsMut,
+    info: MessageInfo,
+    request_id: u64,
+) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    let cluster_token = cfg
+        .cluster_token
+        .ok_or(ContractError::ClusterTokenNotSet {})?;
+
+    let request = read_redeem_request(deps.storage, request_id)?;
+    if info.sender != request.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    remove_redeem_request(deps.storage, &request);
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cluster_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: request.owner.to_string(),
+                amount: request.token_amount,
+            })?,
+            funds: vec![],
+        }))
+        .add_attributes(vec![
+            attr("action", "cancel_redeem_request"),
+            attr("request_id", request_id.to_string()),
+            attr("owner", request.owner),
+            attr("token_amount", request.token_amount),
+        ]))
+}
+
+/// ## Description
+/// Swaps an inventory asset offered by the sender for another inventory asset of
+/// the cluster at oracle prices, adjusted by the penalty contract. A swap fee is
+/// deducted from the asked asset and sent to the collector contract.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **offer_asset** is an object of type [`Asset`] which is the asset offered
+///     by the sender.
+///
+/// - **ask_asset_info** is an object of type [`AssetInfo`] which is the asset
+///     the sender wishes to receive.
+///
+/// - **min_return** is an object of type [`Option<Uint128>`] which is the required
+///     minimum amount of the asked asset to receive.
+pub fn swap(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offer_asset: Asset,
+    ask_asset_info: AssetInfo,
+    min_return: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    assert_swap_allowed(&cfg)?;
+
+    // Pull the target weights from the target provider if due
+    refresh_target(deps.branch(), &env, &cfg)?;
+
+    // Retrieve the cluster state
+    let cluster_state = query_cluster_state(
+        deps.as_ref(),
+        &env.contract.address.to_string(),
+        env.block.time.seconds(),
+        true,
+    )?;
+
+    // Compute the asked asset amounts returned to the sender and the swap fee,
+    // within the offer asset inventory cap
+    let SwapAmount {
+        return_amount,
+        protocol_fee: fee_amt,
+        collector_address,
+        offer_asset_amounts,
+        ask_asset_amounts,
+        attributes,
+        ..
+    } = compute_swap_amount(
+        &deps.querier,
+        env.block.height,
+        &cfg,
+        &cluster_state,
+        &offer_asset,
+        &ask_asset_info,
+    )?;
+
+    // Validate that the returned amount is at least `min_return`
+    if let Some(min_return) = min_return {
+        if return_amount < min_return {
+            return Err(ContractError::BelowMinReturn(return_amount, min_return));
+        }
+    }
+
+    // Mint the accrued management fee, already included in the cluster token supply
+    let (_, mut messages) = accrue_management_fee(deps.branch(), &env, &cfg)?;
+
+    // Transfer the offered asset from the sender to this cluster contract
+    match &offer_asset.info {
+        AssetInfo::Token { contract_addr, .. } => {
+            if !info.funds.is_empty() {
+                return Err(ContractError::Generic(
+                    "Unsupported assets were sent to the swap function".to_string(),
+                ));
+            }
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: info.sender.to_string(),
+                    recipient: env.contract.address.to_string(),
+                    amount: offer_asset.amount,
+                })?,
+                funds: vec![],
+            }));
+            update_asset_balance(
+                deps.storage,
+                &contract_addr.to_string(),
+                offer_asset.amount,
+                true,
+            )?;
+        }
+        AssetInfo::NativeToken { denom } => {
+            if info.funds.iter().any(|coin| coin.denom != *denom) {
+                return Err(ContractError::Generic(
+                    "Unsupported assets were sent to the swap function".to_string(),
+                ));
+            }
+            // Validate that native token balance is correct
+            offer_asset.assert_sent_native_token_balance(&info)?;
+            update_asset_balance(deps.storage, denom, offer_asset.amount, true)?;
+        }
+    }
+
+    // Remove the asked asset, including the swap fee, from the inventory
+    let ask_total = return_amount.checked_add(fee_amt)?;
+    match &ask_asset_info {
+        AssetInfo::Token { contract_addr, .. } => {
+            update_asset_balance(deps.storage, &contract_addr.to_string(), ask_total, false)?
+        }
+        AssetInfo::NativeToken { denom } => {
+            update_asset_balance(deps.storage, denom, ask_total, false)?
+        }
+    }
+
+    // Send the asked asset to the sender
+    messages.push(
+        Asset {
+            info: ask_asset_info.clone(),
+            amount: return_amount,
+        }
+        .into_msg(&deps.querier, info.sender.clone())?,
+    );
+
+    // Send the swap fee to the collector contract
+    if !fee_amt.is_zero() {
+        messages.push(
+            Asset {
+                info: ask_asset_info.clone(),
+                amount: fee_amt,
+            }
+            .into_msg(&deps.querier, deps.api.addr_validate(&collector_address)?)?,
+        );
+    }
+
+    // Notify the penalty contract of the swap for its stateful updates
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: cfg.penalty.to_string(),
+        msg: to_binary(&PenaltyExecuteMsg::PenaltySwap {
+            block_height: env.block.height,
+            inventory: normalize_amounts(&cluster_state.inv, &cluster_state.asset_decimals)?,
+            offer_asset_amounts,
+            ask_asset_amounts,
+            asset_prices: cluster_state.prices,
+            target_weights: cluster_state.target.iter().map(|x| x.amount).collect(),
+        })?,
+        funds: vec![],
+    }));
+
+    let mut logs = vec![
+        attr("action", "swap"),
+        attr("sender", &info.sender.to_string()),
+        attr("offer_asset", offer_asset.to_string()),
+        attr("ask_asset", ask_asset_info.to_string()),
+        attr("return_amount", return_amount),
+        attr("fee_amt", fee_amt),
+    ];
+    logs.extend(attributes);
+
+    Ok(Response::new().add_messages(messages).add_attributes(logs))
+}
+
+/// ## Description
+/// A custom struct holding the result of a create operation on an initialized cluster.
+pub struct CreateAmount {
+    /// Cluster tokens minted to the sender after deducting the protocol fee
+    pub mint_to_sender: Uint128,
+    /// Cluster tokens minted to the collector as the protocol fee
+    pub protocol_fee: Uint128,
+    /// Collector contract address receiving the protocol fee
+    pub collector_address: String,
+    /// Reward returned from the penalty contract
+    pub penalty: Uint128,
+    /// Attributes returned from the penalty contract
+    pub attributes: Vec<Attribute>,
+}
+
+/// ## Description
+/// A custom struct holding the result of a redeem operation.
+pub struct RedeemAmount {
+    /// Asset amounts returned to the sender, ordered by the cluster target
+    pub redeem_totals: Vec<Uint128>,
+    /// Total cluster tokens spent by the sender including the protocol fee
+    pub token_cost: Uint128,
+    /// Cluster tokens sent to the collector as the protocol fee
+    pub protocol_fee: Uint128,
+    /// Collector contract address receiving the protocol fee
+    pub collector_address: String,
+    /// Penalty returned from the penalty contract
+    pub penalty: Uint128,
+    /// Attributes returned from the penalty contract
+    pub attributes: Vec<Attribute>,
+}
+
+/// ## Description
+/// A custom struct holding the result of a swap operation.
+pub struct SwapAmount {
+    /// Asked asset amount returned to the sender after deducting the swap fee
+    pub return_amount: Uint128,
+    /// Asked asset amount sent to the collector as the swap fee
+    pub protocol_fee: Uint128,
+    /// Collector contract address receiving the swap fee
+    pub collector_address: String,
+    /// Normalised offered asset amounts ordered by the cluster target
+    pub offer_asset_amounts: Vec<Uint128>,
+    /// Normalised asked asset amounts at oracle prices ordered by the cluster target
+    pub ask_asset_amounts: Vec<Uint128>,
+    /// Penalty returned from the penalty contract
+    pub penalty: Uint128,
+    /// Attributes returned from the penalty contract
+    pub attributes: Vec<Attribute>,
+}
+
+/// ## Description
+/// Orders the provided asset amounts following the cluster target. Assets
+/// not provided are set to zero.
+///
+/// ## Params
+/// - **target** is a reference to an object of type [`Vec<Asset>`] which is
+///     the cluster target.
+///
+/// - **asset_amounts** is a reference to an object of type [`Vec<Asset>`] which
+///     are the provided asset amounts.
+pub fn to_create_asset_amounts(
+    target: &[Asset],
+    asset_amounts: &[Asset],
+) -> Result<Vec<Uint128>, ContractError> {
+    let mut create_asset_amounts = vec![Uint128::zero(); target.len()];
+    for (i, target_asset) in target.iter().enumerate() {
+        for asset in asset_amounts.iter() {
+            if asset.info == target_asset.info {
+                // Verify the provided non-zero asset amount does not have target of zero
+                if target_asset.amount.is_zero() && !asset.amount.is_zero() {
+                    return Err(ContractError::Generic(
+                        format!("Cannot call create with non-zero asset amount when target weight is zero for asset {}", asset.info),
+                    ));
+                };
+                create_asset_amounts[i] = asset.amount;
+                break;
+            }
+        }
+    }
+    Ok(create_asset_amounts)
+}
+
+/// ## Description
+/// Orders the requested redeem asset amounts following the cluster target.
+/// Returns an empty vector for a pro-rata redeem.
+///
+/// ## Params
+/// - **target** is a reference to an object of type [`Vec<Asset>`] which is
+///     the cluster target.
+///
+/// - **asset_amounts** is a reference to an object of type [`Option<Vec<Asset>>`]
+///     which are the requested asset amounts.
+pub fn to_redeem_asset_amounts(
+    target: &[Asset],
+    asset_amounts: &Option<Vec<Asset>>,
+) -> Vec<Uint128> {
+    match asset_amounts {
         Some(weights) => {
-            let mut vec: Vec<Uint128> = vec![Uint128::zero(); asset_infos.len()];
-            for i in 0..asset_infos.len() {
+            let mut vec: Vec<Uint128> = vec![Uint128::zero(); target.len()];
+            for i in 0..target.len() {
                 for weight in weights {
-                    if weight.info == asset_infos[i] {
+                    if weight.info == target[i].info {
                         vec[i] = weight.amount;
                         break;
                     }
@@ -558,34 +1885,151 @@ pub fn receive_redeem(
             vec
         }
         None => vec![],
-    };
+    }
+}
+
+/// ## Description
+/// Computes the cluster tokens minted from depositing `create_asset_amounts` into
+/// an initialized cluster, split between the sender and the protocol fee.
+///
+/// ## Params
+/// - **querier** is a reference to an object of type [`QuerierWrapper`].
+///
+/// - **block_height** is an object of type [`u64`] which is the current block height.
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **cluster_state** is a reference to an object of type [`ClusterStateResponse`].
+///
+/// - **create_asset_amounts** is a reference to an object of type [`Vec<Uint128>`] which
+///     are the deposited asset amounts ordered by the cluster target.
+pub fn compute_create_amount(
+    querier: &QuerierWrapper,
+    block_height: u64,
+    cfg: &ClusterConfig,
+    cluster_state: &ClusterStateResponse,
+    create_asset_amounts: &[Uint128],
+) -> Result<CreateAmount, ContractError> {
+    // Validate that the deposits do not exceed the asset inventory caps
+    assert_inventory_caps(
+        cfg,
+        &cluster_state.target,
+        &cluster_state.inv,
+        create_asset_amounts,
+    )?;
+
+    let target_weights = cluster_state
+        .target
+        .iter()
+        .map(|x| x.amount)
+        .collect::<Vec<_>>();
 
-    // Retrieve collector contract and fee rate
-    let (collector_address, fee_rate) =
-        query_collector_contract_address(&deps.querier, &cfg.factory)?;
+    // Query cluster token amounts from a normal mint, with amounts normalised
+    // to the oracle price unit
+    let create_response = query_create_amount(
+        querier,
+        &cfg.penalty,
+        cluster_state.cluster_contract_address.clone(),
+        block_height,
+        cluster_state.outstanding_balance_tokens,
+        normalize_amounts(&cluster_state.inv, &cluster_state.asset_decimals)?,
+        normalize_amounts(create_asset_amounts, &cluster_state.asset_decimals)?,
+        cluster_state.prices.clone(),
+        target_weights,
+    )?;
+    let create_amount = create_response.create_tokens;
+
+    // Retrieve collector contract and the cluster create fee rate
+    let collector_address = query_collector_contract_address(querier, &cfg.factory)?;
+    let fees = query_cluster_fees(
+        querier,
+        &cfg.factory,
+        &cluster_state.cluster_contract_address,
+    )?;
+    let fee_rate = FPDecimal::from_str(&fees.create_fee_rate)?;
+
+    // Calculate the cluster token amount for sender and fee amount
+    // mint_to_sender = mint_total * (1 - fee_rate)
+    // protocol_fee = mint_total - mint_to_sender == mint_total * fee_rate
+    let _mint_to_sender: u128 =
+        (FPDecimal::from(create_amount.u128()) * (FPDecimal::one() - fee_rate)).into();
+    let mint_to_sender = Uint128::from(_mint_to_sender);
+    let protocol_fee = create_amount.checked_sub(mint_to_sender)?;
+
+    // Validate that the cluster token supply stays within the supply cap
+    assert_supply_cap(cfg, cluster_state.outstanding_balance_tokens, create_amount)?;
+
+    Ok(CreateAmount {
+        mint_to_sender,
+        protocol_fee,
+        collector_address,
+        penalty: create_response.penalty,
+        attributes: create_response.attributes,
+    })
+}
+
+/// ## Description
+/// Computes the assets returned and the cluster tokens spent, including the
+/// protocol fee, from redeeming at most `max_tokens` cluster tokens.
+///
+/// ## Params
+/// - **querier** is a reference to an object of type [`QuerierWrapper`].
+///
+/// - **block_height** is an object of type [`u64`] which is the current block height.
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **cluster_state** is a reference to an object of type [`ClusterStateResponse`].
+///
+/// - **max_tokens** is an object of type [`Uint128`] which is the required
+///     maximum amount of cluster tokens allowed to burn.
+///
+/// - **asset_amounts** is a reference to an object of type [`Vec<Uint128>`] which
+///     are the requested asset amounts ordered by the cluster target, or empty
+///     for a pro-rata redeem.
+pub fn compute_redeem_amount(
+    querier: &QuerierWrapper,
+    block_height: u64,
+    cfg: &ClusterConfig,
+    cluster_state: &ClusterStateResponse,
+    max_tokens: Uint128,
+    asset_amounts: &[Uint128],
+) -> Result<RedeemAmount, ContractError> {
+    let target_weights = cluster_state
+        .target
+        .iter()
+        .map(|x| x.amount)
+        .collect::<Vec<_>>();
+
+    // Retrieve collector contract and the cluster redeem fee rate
+    let collector_address = query_collector_contract_address(querier, &cfg.factory)?;
+    let fees = query_cluster_fees(
+        querier,
+        &cfg.factory,
+        &cluster_state.cluster_contract_address,
+    )?;
 
-    let fee_rate: FPDecimal = FPDecimal::from_str(&fee_rate)?;
+    let fee_rate: FPDecimal = FPDecimal::from_str(&fees.redeem_fee_rate)?;
     let keep_rate: FPDecimal = FPDecimal::one() - fee_rate;
 
     let _token_cap: u128 = (FPDecimal::from(max_tokens.u128()) * keep_rate).into();
     let token_cap: Uint128 = Uint128::from(_token_cap);
 
-    // Query cluster token amounts burned with the maximum as `token_cap`
+    // Query cluster token amounts burned with the maximum as `token_cap`, with
+    // amounts normalised to the oracle price unit
     let redeem_response = query_redeem_amount(
-        &deps.querier,
+        querier,
         &cfg.penalty,
-        env.block.height,
-        cluster_token_supply,
-        inv.clone(),
+        cluster_state.cluster_contract_address.clone(),
+        block_height,
+        cluster_state.outstanding_balance_tokens,
+        normalize_amounts(&cluster_state.inv, &cluster_state.asset_decimals)?,
         token_cap,
-        asset_amounts.clone(),
-        prices.clone(),
-        target_weights.clone(),
+        normalize_amounts(asset_amounts, &cluster_state.asset_decimals)?,
+        cluster_state.prices.clone(),
+        target_weights,
     )?;
 
-    // Maximum redeem amount after deducting fees.
-    let redeem_totals = redeem_response.redeem_assets;
-
     // Sanity check if token_cost is exceeding max_tokens
     let _token_cost: FPDecimal = FPDecimal::from(redeem_response.token_cost.u128()) / keep_rate;
     let mut token_cost: u128 = _token_cost.into();
@@ -598,29 +2042,6 @@ pub fn receive_redeem(
         return Err(ContractError::AboveMaxTokens(token_cost, max_tokens));
     }
 
-    // Send `redeem_totals`, the assets from burning cluster tokens, to sender
-    let mut messages: Vec<CosmosMsg> = redeem_totals
-        .iter()
-        .zip(asset_infos.iter())
-        .filter(|(amt, _asset)| !amt.is_zero()) // remove 0 amounts
-        .map(|(amt, asset_info)| {
-            if let AssetInfo::Token { contract_addr, .. } = &asset_info {
-                update_asset_balance(deps.storage, &contract_addr.to_string(), *amt, false)?;
-            } else if let AssetInfo::NativeToken { denom } = &asset_info {
-                update_asset_balance(deps.storage, denom, *amt, false)?;
-            }
-            let asset = Asset {
-                info: asset_info.clone(),
-                amount: *amt,
-            };
-
-            match asset.into_msg(&deps.querier, sender.clone()) {
-                Ok(msg) => Ok(msg),
-                Err(e) => Err(ContractError::Std(e)),
-            }
-        })
-        .collect::<Result<Vec<CosmosMsg>, ContractError>>()?;
-
     // Compute fee based on the actual redeem amount `token_cost`
     let _fee_amt: FPDecimal = FPDecimal::from(token_cost.u128()) * fee_rate;
     let mut fee_amt: u128 = _fee_amt.into();
@@ -628,61 +2049,143 @@ pub fn receive_redeem(
         fee_amt += 1
     }
 
-    // Send fee to collector contract from allowance
-    let fee_amt: Uint128 = Uint128::from(fee_amt);
-    if !fee_amt.is_zero() {
-        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: cluster_token.to_string(),
-            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
-                owner: sender.to_string(),
-                amount: fee_amt,
-                recipient: collector_address,
-            })?,
-            funds: vec![],
-        }));
+    Ok(RedeemAmount {
+        redeem_totals: denormalize_amounts(
+            &redeem_response.redeem_assets,
+            &cluster_state.asset_decimals,
+        )?,
+        token_cost,
+        protocol_fee: Uint128::from(fee_amt),
+        collector_address,
+        penalty: redeem_response.penalty,
+        attributes: redeem_response.attributes,
+    })
+}
+
+/// ## Description
+/// Computes the asked asset amount returned from swapping `offer_asset` against
+/// the cluster inventory, split between the sender and the swap fee.
+///
+/// ## Params
+/// - **querier** is a reference to an object of type [`QuerierWrapper`].
+///
+/// - **block_height** is an object of type [`u64`] which is the current block height.
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **cluster_state** is a reference to an object of type [`ClusterStateResponse`].
+///
+/// - **offer_asset** is a reference to an object of type [`Asset`] which is the
+///     offered asset.
+///
+/// - **ask_asset_info** is a reference to an object of type [`AssetInfo`] which is
+///     the asked asset.
+pub fn compute_swap_amount(
+    querier: &QuerierWrapper,
+    block_height: u64,
+    cfg: &ClusterConfig,
+    cluster_state: &ClusterStateResponse,
+    offer_asset: &Asset,
+    ask_asset_info: &AssetInfo,
+) -> Result<SwapAmount, ContractError> {
+    let target = &cluster_state.target;
+
+    if offer_asset.info == *ask_asset_info {
+        return Err(ContractError::Generic(
+            "Cannot swap an asset for itself".to_string(),
+        ));
+    }
+    if offer_asset.amount.is_zero() {
+        return Err(ContractError::Generic(
+            "Cannot swap a zero offer amount".to_string(),
+        ));
     }
 
-    // Burn the rest of the redeem amount from allowance
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: cluster_token.to_string(),
-        msg: to_binary(&Cw20ExecuteMsg::BurnFrom {
-            owner: sender.to_string(),
-            amount: token_cost.checked_sub(fee_amt)?,
-        })?,
-        funds: vec![],
-    }));
+    // Locate the offered and asked assets in the cluster target
+    let offer_idx = target
+        .iter()
+        .position(|x| x.info == offer_asset.info)
+        .ok_or_else(|| {
+            ContractError::Generic(format!("Asset {} is not in the cluster", offer_asset.info))
+        })?;
+    let ask_idx = target
+        .iter()
+        .position(|x| x.info == *ask_asset_info)
+        .ok_or_else(|| {
+            ContractError::Generic(format!("Asset {} is not in the cluster", ask_asset_info))
+        })?;
+
+    // Validate that the deposit does not exceed the offer asset inventory cap
+    let mut deposit_amounts = vec![Uint128::zero(); target.len()];
+    deposit_amounts[offer_idx] = offer_asset.amount;
+    assert_inventory_caps(cfg, target, &cluster_state.inv, &deposit_amounts)?;
+
+    // Verify the offered asset does not have target of zero
+    if target[offer_idx].amount.is_zero() {
+        return Err(ContractError::Generic(format!(
+            "Cannot swap in asset {} when its target weight is zero",
+            offer_asset.info
+        )));
+    }
 
-    // Afterwards, notify the penalty contract that this update happened, so
-    // the penalty contract can make stateful updates
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: cfg.penalty.to_string(),
-        msg: to_binary(&PenaltyExecuteMsg::PenaltyRedeem {
-            block_height: env.block.height,
-            cluster_token_supply,
-            inventory: inv,
-            max_tokens,
-            redeem_asset_amounts: asset_amounts.clone(),
-            asset_prices: prices,
-            target_weights,
-        })?,
-        funds: vec![],
-    }));
+    // Convert the normalised offered amount to the asked asset at oracle prices
+    // -- ask_amount = offer_amount * offer_price / ask_price
+    let decimals = &cluster_state.asset_decimals;
+    let offer_amount = normalize_amount(offer_asset.amount, decimals[offer_idx])?;
+    let offer_price = FPDecimal::from_str(&cluster_state.prices[offer_idx])?;
+    let ask_price = FPDecimal::from_str(&cluster_state.prices[ask_idx])?;
+    let _ask_amount: u128 = (FPDecimal::from(offer_amount.u128()) * offer_price / ask_price).into();
+    let normalized_ask_amount = Uint128::from(_ask_amount);
+    let ask_amount = denormalize_amount(normalized_ask_amount, decimals[ask_idx])?;
+    let ask_inventory = cluster_state.inv[ask_idx];
+    if ask_amount.is_zero() || ask_amount > ask_inventory {
+        return Err(ContractError::Generic(format!(
+            "Cluster inventory of {} cannot cover the swap",
+            ask_asset_info
+        )));
+    }
 
-    Ok(Response::new().add_messages(messages).add_attributes(
-        vec![
-            vec![
-                attr("action", "receive:burn"),
-                attr("sender", sender),
-                attr("burn_amount", token_cost.checked_sub(fee_amt)?.to_string()),
-                attr("token_cost", token_cost),
-                attr("kept_as_fee", fee_amt),
-                attr("asset_amounts", vec_to_string(&asset_amounts)),
-                attr("redeem_totals", vec_to_string(&redeem_totals)),
-            ],
-            redeem_response.attributes,
-        ]
-        .concat(),
-    ))
+    let mut offer_asset_amounts = vec![Uint128::zero(); target.len()];
+    offer_asset_amounts[offer_idx] = offer_amount;
+    let mut ask_asset_amounts = vec![Uint128::zero(); target.len()];
+    ask_asset_amounts[ask_idx] = normalized_ask_amount;
+
+    // Query the normalised asked asset amounts adjusted by the penalty
+    let swap_response = query_swap_amount(
+        querier,
+        &cfg.penalty,
+        cluster_state.cluster_contract_address.clone(),
+        block_height,
+        normalize_amounts(&cluster_state.inv, decimals)?,
+        offer_asset_amounts.clone(),
+        ask_asset_amounts.clone(),
+        cluster_state.prices.clone(),
+        target.iter().map(|x| x.amount).collect(),
+    )?;
+    let ask_total = denormalize_amount(swap_response.ask_assets[ask_idx], decimals[ask_idx])?;
+    if ask_total > ask_inventory {
+        return Err(ContractError::Generic(format!(
+            "Cluster inventory of {} cannot cover the swap",
+            ask_asset_info
+        )));
+    }
+
+    // Deduct the swap fee from the asked asset
+    // -- protocol_fee = ask_total * swap_fee_rate
+    let protocol_fee = ask_total * cfg.swap_fee_rate;
+    let return_amount = ask_total.checked_sub(protocol_fee)?;
+
+    let collector_address = query_collector_contract_address(querier, &cfg.factory)?;
+
+    Ok(SwapAmount {
+        return_amount,
+        protocol_fee,
+        collector_address,
+        offer_asset_amounts,
+        ask_asset_amounts,
+        penalty: swap_response.penalty,
+        attributes: swap_response.attributes,
+    })
 }
 
 /// ## Description
@@ -737,6 +2240,9 @@ fn update_asset_balance(
 /// - **target** is a reference to an object of type [`Vec<Asset>`] which is a new
 ///     asset target weights to update.
 ///
+/// - **duration** is an object of type [`Option<u64>`] which is the duration in seconds
+///     of a linear transition from the current effective target weights to the new ones.
+///
 /// ## Executor
 /// Only the owner or the target oracle address can execute this.
 pub fn update_target(
@@ -744,6 +2250,7 @@ pub fn update_target(
     env: Env,
     info: MessageInfo,
     target: &[Asset],
+    duration: Option<u64>,
 ) -> Result<Response, ContractError> {
     let cfg = read_config(deps.storage)?;
     if cfg.cluster_token.is_none() {
@@ -760,6 +2267,45 @@ pub fn update_target(
         return Err(ContractError::Unauthorized {});
     }
 
+    let attributes = apply_target(deps, &env, &cfg, target, duration)?;
+
+    Ok(Response::new().add_attributes(attributes))
+}
+
+/// ## Description
+/// Validates and saves the given asset target weights, starting a linear transition
+/// from the current effective target weights if a duration is given. Returns the
+/// attributes describing the update.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is a reference to an object of type [`Env`].
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **target** is a reference to an object of type [`Vec<Asset>`] which is a new
+///     asset target weights to update.
+///
+/// - **duration** is an object of type [`Option<u64>`] which is the duration in seconds
+///     of a linear transition from the current effective target weights to the new ones.
+fn apply_target(
+    deps: DepsMut,
+    env: &Env,
+    cfg: &ClusterConfig,
+    target: &[Asset],
+    duration: Option<u64>,
+) -> Result<Vec<Attribute>, ContractError> {
+    // Value weights are basis points of the cluster value
+    if cfg.target_mode == TargetMode::ValueWeights
+        && target.iter().map(|x| x.amount.u128()).sum::<u128>() != VALUE_WEIGHT_TOTAL_BPS
+    {
+        return Err(ContractError::Generic(format!(
+            "Value weights must sum to {} basis points",
+            VALUE_WEIGHT_TOTAL_BPS
+        )));
+    }
+
     let mut asset_data = target.to_owned();
 
     // Create new vectors for logging and validation purpose
@@ -772,27 +2318,33 @@ pub fn update_target(
             .unzip();
 
     // Check `updated_asset_infos` for duplicate and unsupported assets
-    if validate_targets(deps.querier, &env, updated_asset_infos.clone()).is_err() {
+    if validate_targets(deps.querier, env, updated_asset_infos.clone()).is_err() {
         return Err(ContractError::InvalidAssets {});
     }
+    record_asset_decimals(deps.querier, deps.storage, cfg, &updated_asset_infos)?;
 
-    // Load previous assets & target
-    let (prev_assets, prev_target): (Vec<AssetInfo>, Vec<Uint128>) =
-        read_target_asset_data(deps.storage)?
-            .iter()
-            .map(|x| (x.info.clone(), x.amount))
-            .unzip();
+    // Load previous assets & current effective target
+    let now = env.block.time.seconds();
+    let prev_asset_data = read_effective_target_asset_data(deps.storage, now)?;
+    let (prev_assets, prev_target): (Vec<AssetInfo>, Vec<Uint128>) = prev_asset_data
+        .iter()
+        .map(|x| (x.info.clone(), x.amount))
+        .unzip();
+
+    let duration = duration.unwrap_or(0);
 
     // When previous assets are not found,
-    // then set that not found item target to zero
-    for prev_asset in prev_assets.iter() {
+    // then set that not found item target to zero.
+    // Keep previous assets still weighted during the transition
+    for (prev_asset, prev_weight) in prev_assets.iter().zip(prev_target.iter()) {
         let inv_balance = match prev_asset {
             AssetInfo::Token { contract_addr } => {
                 read_asset_balance(deps.storage, &contract_addr.to_string())
             }
             AssetInfo::NativeToken { denom } => read_asset_balance(deps.storage, denom),
         }?;
-        if !inv_balance.is_zero() && !updated_asset_infos.contains(prev_asset) {
+        let in_transition = duration > 0 && !prev_weight.is_zero();
+        if (!inv_balance.is_zero() || in_transition) && !updated_asset_infos.contains(prev_asset) {
             let asset_elem = Asset {
                 info: prev_asset.clone(),
                 amount: Uint128::zero(),
@@ -804,15 +2356,80 @@ pub fn update_target(
         }
     }
 
+    if duration > 0 {
+        // Weights are rescaled to a common total during the transition,
+        // so make sure the interpolation cannot overflow
+        if !transition_fits(&prev_asset_data, &asset_data, duration) {
+            return Err(ContractError::Generic(
+                "Target transition weights are too large".to_string(),
+            ));
+        }
+
+        store_target_transition(
+            deps.storage,
+            &TargetTransition {
+                prev_target: prev_asset_data,
+                start_time: now,
+                end_time: now + duration,
+            },
+        )?;
+    } else {
+        remove_target_transition(deps.storage);
+    }
+
     store_target_asset_data(deps.storage, &asset_data)?;
 
-    Ok(Response::new().add_attributes(vec![
+    Ok(vec![
         attr("action", "reset_target"),
         attr("prev_assets", vec_to_string(&prev_assets)),
         attr("prev_targets", vec_to_string(&prev_target)),
         attr("updated_assets", vec_to_string(&updated_asset_infos)),
         attr("updated_targets", vec_to_string(&updated_target_weights)),
-    ]))
+    ])
+}
+
+/// ## Description
+/// Pulls the asset target weights from the target provider of an active cluster,
+/// at most once per refresh interval of the provider. A timed target transition set
+/// by the owner or the target oracle takes precedence, so the provider is not pulled
+/// until the transition has ended.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is a reference to an object of type [`Env`].
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+pub fn refresh_target(
+    mut deps: DepsMut,
+    env: &Env,
+    cfg: &ClusterConfig,
+) -> Result<(), ContractError> {
+    let target_provider = match &cfg.target_provider {
+        Some(target_provider) if cfg.active => target_provider,
+        _ => return Ok(()),
+    };
+
+    // Let an ongoing timed transition complete instead of cancelling it
+    let now = env.block.time.seconds();
+    if let Some(transition) = read_target_transition(deps.storage)? {
+        if now < transition.end_time {
+            return Ok(());
+        }
+    }
+
+    // Keep the cached target weights within the refresh interval
+    if let Some(last_refresh) = read_last_target_refresh(deps.storage)? {
+        if now < last_refresh.saturating_add(target_provider.refresh_interval) {
+            return Ok(());
+        }
+    }
+
+    let target = query_provider_target(&deps.querier, &target_provider.address)?;
+    apply_target(deps.branch(), env, cfg, &target, None)?;
+    store_last_target_refresh(deps.storage, now)?;
+
+    Ok(())
 }
 
 /// ## Description
@@ -822,11 +2439,17 @@ pub fn update_target(
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
 ///
+/// - **env** is an object of type [`Env`].
+///
 /// - **info** is an object of type [`MessageInfo`].
 ///
 /// ## Executor
 /// Only the factory contract can execute this.
-pub fn decommission(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+pub fn decommission(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
     let cfg = read_config(deps.storage)?;
     if cfg.cluster_token.is_none() {
         return Err(ContractError::ClusterTokenNotSet {});
@@ -841,6 +2464,9 @@ pub fn decommission(deps: DepsMut, info: MessageInfo) -> Result<Response, Contra
         return Err(ContractError::ClusterAlreadyDecommissioned {});
     }
 
+    // Mint the management fee accrued until decommission
+    let (_, messages) = accrue_management_fee(deps.branch(), &env, &cfg)?;
+
     // Update the cluster state to be decommissioned / inactive
     config_store(deps.storage).update(|mut config| -> StdResult<_> {
         config.active = false;
@@ -848,5 +2474,183 @@ pub fn decommission(deps: DepsMut, info: MessageInfo) -> Result<Response, Contra
         Ok(config)
     })?;
 
-    Ok(Response::new().add_attributes(vec![attr("action", "decommission_asset")]))
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![attr("action", "decommission_asset")]))
+}
+
+/// ## Description
+/// Reactivates a decommissioned cluster, enabling mints and custom redeems again.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// ## Executor
+/// Only the factory contract can execute this.
+pub fn recommission(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    if cfg.cluster_token.is_none() {
+        return Err(ContractError::ClusterTokenNotSet {});
+    }
+    // Permission check - can only be recommissioned by the factory contract
+    if info.sender != cfg.factory {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Can only recommission a decommissioned cluster
+    if cfg.active {
+        return Err(ContractError::ClusterNotDecommissioned {});
+    }
+
+    // Restart the management fee accrual, no fee is charged for the decommissioned period
+    store_last_fee_accrual(deps.storage, env.block.time.seconds())?;
+
+    // Update the cluster state to be active
+    config_store(deps.storage).update(|mut config| -> StdResult<_> {
+        config.active = true;
+
+        Ok(config)
+    })?;
+
+    Ok(Response::new().add_attributes(vec![attr("action", "recommission_asset")]))
+}
+
+/// ## Description
+/// Mints the accrued management fee to the collector contract.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// ## Executor
+/// Anyone can execute this.
+pub fn accrue_fee(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    if cfg.cluster_token.is_none() {
+        return Err(ContractError::ClusterTokenNotSet {});
+    }
+
+    let (fee_amount, messages) = accrue_management_fee(deps, &env, &cfg)?;
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "accrue_fee"),
+        attr("fee_amount", fee_amount),
+    ]))
+}
+
+/// ## Description
+/// Settles the difference between the tracked inventory and the actual asset balances.
+/// Untracked surpluses are absorbed into the inventory, swept to the collector contract,
+/// or refunded to an address following the cluster reconcile policy. Tracked balances
+/// not held by the cluster are written off the inventory.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// ## Executor
+/// Anyone can execute this.
+pub fn reconcile_inventory(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    let drift = compute_inventory_drift(deps.as_ref(), &env)?;
+
+    // Resolve the recipient of the surpluses, if they leave the cluster
+    let surplus_recipient = match &cfg.reconcile_policy {
+        ReconcilePolicy::Absorb => None,
+        ReconcilePolicy::SweepToCollector => Some(query_collector_contract_address(
+            &deps.querier,
+            &cfg.factory,
+        )?),
+        ReconcilePolicy::Refund { recipient } => Some(recipient.to_string()),
+    };
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut surpluses: Vec<Uint128> = vec![];
+    let mut deficits: Vec<Uint128> = vec![];
+    for asset_drift in drift.iter() {
+        let asset_id = match &asset_drift.info {
+            AssetInfo::Token { contract_addr } => contract_addr.to_string(),
+            AssetInfo::NativeToken { denom } => denom.clone(),
+        };
+
+        if !asset_drift.deficit.is_zero() {
+            // Write off the tracked balance not held by the cluster
+            update_asset_balance(deps.storage, &asset_id, asset_drift.deficit, false)?;
+        } else if !asset_drift.surplus.is_zero() {
+            match &surplus_recipient {
+                Some(recipient) => {
+                    let asset = Asset {
+                        info: asset_drift.info.clone(),
+                        amount: asset_drift.surplus,
+                    };
+                    messages.push(asset.into_msg(&deps.querier, Addr::unchecked(recipient))?);
+                }
+                None => {
+                    // Donate the surplus to the cluster token holders
+                    update_asset_balance(deps.storage, &asset_id, asset_drift.surplus, true)?;
+                }
+            }
+        }
+
+        surpluses.push(asset_drift.surplus);
+        deficits.push(asset_drift.deficit);
+    }
+
+    let asset_infos = drift.into_iter().map(|x| x.info).collect::<Vec<_>>();
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "reconcile_inventory"),
+        attr("assets", vec_to_string(&asset_infos)),
+        attr("surpluses", vec_to_string(&surpluses)),
+        attr("deficits", vec_to_string(&deficits)),
+    ]))
+}
+
+/// ## Description
+/// Returns the management fee accrued since the last accrual, and the message minting
+/// it to the collector contract if any. Records the current time as the last accrual.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is a reference to an object of type [`Env`].
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+fn accrue_management_fee(
+    deps: DepsMut,
+    env: &Env,
+    cfg: &ClusterConfig,
+) -> Result<(Uint128, Vec<CosmosMsg>), ContractError> {
+    let cluster_token = match &cfg.cluster_token {
+        Some(cluster_token) => cluster_token,
+        None => return Ok((Uint128::zero(), vec![])),
+    };
+
+    let now = env.block.time.seconds();
+    let token_supply = query_cw20_token_supply(&deps.querier, cluster_token)?;
+    let fee_amount = compute_cluster_accrued_fee(deps.as_ref(), cfg, token_supply, now)?;
+    store_last_fee_accrual(deps.storage, now)?;
+
+    if fee_amount.is_zero() {
+        return Ok((fee_amount, vec![]));
+    }
+
+    let collector_address = query_collector_contract_address(&deps.querier, &cfg.factory)?;
+    Ok((
+        fee_amount,
+        vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cluster_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                amount: fee_amount,
+                recipient: collector_address,
+            })?,
+            funds: vec![],
+        })],
+    ))
 }
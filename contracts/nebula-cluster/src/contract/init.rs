@@ -1,16 +1,21 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
-use crate::ext_query::query_asset_balance;
+use crate::ext_query::{query_asset_balance, query_cw20_token_decimals};
 use crate::{
     error::ContractError,
-    state::{store_config, store_target_asset_data},
+    state::{store_asset_decimals, store_config, store_last_fee_accrual, store_target_asset_data},
     util::vec_to_string,
 };
 use astroport::asset::AssetInfo;
-use cosmwasm_std::{attr, DepsMut, Env, MessageInfo, QuerierWrapper, Response, Uint128};
+use cluster_math::MAX_DECIMALS;
+use cosmwasm_std::{
+    attr, Decimal, DepsMut, Env, MessageInfo, QuerierWrapper, Response, Storage, Uint128,
+};
 use cw2::set_contract_version;
-use nebula_protocol::cluster::{ClusterConfig, InstantiateMsg};
+use nebula_protocol::cluster::{
+    ClusterCaps, ClusterConfig, InstantiateMsg, PriceFreshnessPolicy, ReconcilePolicy, TargetMode,
+};
 
 /// Contract name that is used for migration.
 const CONTRACT_NAME: &str = "nebula-cluster";
@@ -45,6 +50,47 @@ pub fn validate_targets(
     Ok(())
 }
 
+/// ## Description
+/// Records the decimals of the given assets, from the CW20 token info for tokens
+/// or from the cluster config for native assets.
+///
+/// ## Params
+/// - **querier** is an object of type [`QuerierWrapper`].
+///
+/// - **storage** is a mutable reference to an object implementing trait [`Storage`].
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **asset_infos** is a reference to an object of type [`Vec<AssetInfo>`] which
+///     are the assets to record.
+///
+/// Returns a [`ContractError`] if any asset has more than [`MAX_DECIMALS`] decimals.
+pub fn record_asset_decimals(
+    querier: QuerierWrapper,
+    storage: &mut dyn Storage,
+    cfg: &ClusterConfig,
+    asset_infos: &[AssetInfo],
+) -> Result<(), ContractError> {
+    for asset_info in asset_infos.iter() {
+        let (key, decimals) = match asset_info {
+            AssetInfo::Token { contract_addr } => (
+                contract_addr.to_string(),
+                query_cw20_token_decimals(&querier, contract_addr)?,
+            ),
+            AssetInfo::NativeToken { denom } => (denom.clone(), cfg.native_decimals_of(denom)),
+        };
+        // Bound the decimals so that normalising amounts cannot overflow
+        if decimals > MAX_DECIMALS {
+            return Err(ContractError::Generic(format!(
+                "Asset decimals cannot exceed {}",
+                MAX_DECIMALS
+            )));
+        }
+        store_asset_decimals(storage, &key, decimals)?;
+    }
+    Ok(())
+}
+
 /// ## Description
 /// Creates a new contract with the specified parameters packed in the `msg` variable.
 /// Returns a [`Response`] with the specified attributes if the operation was successful,
@@ -82,6 +128,19 @@ pub fn instantiate(
         target_oracle: deps.api.addr_validate(msg.target_oracle.as_str())?,
         penalty: deps.api.addr_validate(msg.penalty.as_str())?,
         active: true,
+        price_freshness: PriceFreshnessPolicy::default(),
+        management_fee_rate: Decimal::zero(),
+        caps: ClusterCaps::default(),
+        reconcile_policy: ReconcilePolicy::default(),
+        guardian: None,
+        create_paused: false,
+        custom_redeem_paused: false,
+        swap_fee_rate: Decimal::zero(),
+        target_mode: TargetMode::Units,
+        target_provider: None,
+        redeem_bounty_rate: Decimal::zero(),
+        native_decimals: vec![],
+        referral_fee_rate: Decimal::zero(),
     };
 
     // Get asset infos from the provided `target`
@@ -107,12 +166,14 @@ pub fn instantiate(
     if validate_targets(deps.querier, &env, asset_infos.clone()).is_err() {
         return Err(ContractError::InvalidAssets {});
     }
+    record_asset_decimals(deps.querier, deps.storage, &cfg, &asset_infos)?;
 
     let asset_data = msg.target.clone();
 
     // Save the cluster config and asset target weights
     store_config(deps.storage, &cfg)?;
     store_target_asset_data(deps.storage, &asset_data)?;
+    store_last_fee_accrual(deps.storage, env.block.time.seconds())?;
 
     let log = vec![
         attr("name", msg.name),
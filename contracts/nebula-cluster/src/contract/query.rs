@@ -1,13 +1,27 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
-use cosmwasm_std::{to_binary, Binary, Deps, Env, StdError, StdResult, Uint128};
+use cosmwasm_std::{to_binary, Binary, Deps, DepsMut, Env, StdError, StdResult, Uint128};
 
-use crate::ext_query::{query_cw20_token_supply, query_price};
-use crate::state::{read_asset_balance, read_config, read_target_asset_data};
-use astroport::asset::AssetInfo;
+use crate::contract::handle::{
+    assert_create_allowed, assert_redeem_allowed, assert_swap_allowed, compute_create_amount,
+    compute_redeem_amount, compute_swap_amount, refresh_target, requires_fresh_prices,
+    to_create_asset_amounts, to_redeem_asset_amounts, CreateAmount, RedeemAmount, SwapAmount,
+};
+use crate::contract::validate_targets;
+use crate::ext_query::{query_asset_balance, query_cw20_token_supply, query_price};
+use crate::state::{
+    read_asset_balance, read_asset_decimals, read_config, read_effective_target_asset_data,
+    read_last_fee_accrual, read_redeem_request, read_redeem_requests, read_referral_earnings,
+    read_target_asset_data, OverlayStorage, RedeemRequest,
+};
+use crate::util::{compute_accrued_fee, to_unit_weights};
+use astroport::asset::{Asset, AssetInfo};
 use nebula_protocol::cluster::{
-    ClusterInfoResponse, ClusterStateResponse, ConfigResponse, QueryMsg, TargetResponse,
+    AccruedFeeResponse, AssetDrift, ClusterConfig, ClusterInfoResponse, ClusterStateResponse,
+    ConfigResponse, InventoryDriftResponse, QueryMsg, RedeemRequestResponse,
+    RedeemRequestsResponse, ReferralEarningsResponse, SimulateCreateResponse,
+    SimulateRedeemResponse, SimulateSwapResponse, TargetMode, TargetResponse,
 };
 
 /// ## Description
@@ -37,17 +51,82 @@ pub fn h(s: &str) -> String {
 /// - **QueryMsg::ClusterState {}** Returns the current cluster state.
 ///
 /// - **QueryMsg::ClusterInfo {}** Return the cluster information.
+///
+/// - **QueryMsg::SimulateCreate {
+///             asset_amounts,
+///         }** Returns the simulated result of a create operation.
+///
+/// - **QueryMsg::SimulateRedeem {
+///             max_tokens,
+///             asset_amounts,
+///         }** Returns the simulated result of a redeem operation.
+///
+/// - **QueryMsg::SimulateSwap {
+///             offer_asset,
+///             ask_asset_info,
+///         }** Returns the simulated result of a swap operation.
+///
+/// - **QueryMsg::AccruedFee {}** Returns the accrued but not yet minted management fee.
+///
+/// - **QueryMsg::InventoryDrift {}** Returns the difference between the tracked inventory
+///         and the actual asset balances.
+///
+/// - **QueryMsg::RedeemRequest { request_id }** Returns a queued redeem request.
+///
+/// - **QueryMsg::RedeemRequests {
+///             owner,
+///             start_after,
+///             limit,
+///         }** Returns the queued redeem requests, oldest first.
+///
+/// - **QueryMsg::ReferralEarnings { referrer }** Returns the cumulative cluster tokens
+///         earned by a referrer.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::Target {} => to_binary(&query_target(deps)?),
+        QueryMsg::Target {} => to_binary(&query_target(deps, env)?),
         QueryMsg::ClusterState {} => to_binary(&query_cluster_state(
             deps,
             &env.contract.address.to_string(),
-            0,
+            env.block.time.seconds(),
+            false,
         )?),
         QueryMsg::ClusterInfo {} => to_binary(&query_cluster_info(deps)?),
+        QueryMsg::SimulateCreate { asset_amounts } => {
+            to_binary(&query_simulate_create(deps, env, asset_amounts)?)
+        }
+        QueryMsg::SimulateRedeem {
+            max_tokens,
+            asset_amounts,
+        } => to_binary(&query_simulate_redeem(
+            deps,
+            env,
+            max_tokens,
+            asset_amounts,
+        )?),
+        QueryMsg::SimulateSwap {
+            offer_asset,
+            ask_asset_info,
+        } => to_binary(&query_simulate_swap(
+            deps,
+            env,
+            offer_asset,
+            ask_asset_info,
+        )?),
+        QueryMsg::AccruedFee {} => to_binary(&query_accrued_fee(deps, env)?),
+        QueryMsg::InventoryDrift {} => to_binary(&query_inventory_drift(deps, env)?),
+        QueryMsg::RedeemRequest { request_id } => {
+            to_binary(&query_redeem_request(deps, request_id)?)
+        }
+        QueryMsg::RedeemRequests {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query_redeem_requests(deps, owner, start_after, limit)?),
+        QueryMsg::ReferralEarnings { referrer } => {
+            to_binary(&query_referral_earnings(deps, referrer)?)
+        }
     }
 }
 
@@ -62,38 +141,73 @@ fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
 }
 
 /// ## Description
-/// Returns the current asset target weights of the cluster.
+/// Returns the current effective and final asset target weights of the cluster.
 ///
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
-fn query_target(deps: Deps) -> StdResult<TargetResponse> {
-    let target_assets = read_target_asset_data(deps.storage)?;
-    Ok(TargetResponse {
-        target: target_assets,
-    })
+///
+/// - **env** is an object of type [`Env`].
+fn query_target(deps: Deps, env: Env) -> StdResult<TargetResponse> {
+    let cfg = read_config(deps.storage)?;
+    let target_assets = read_effective_target_asset_data(deps.storage, env.block.time.seconds())?;
+    let final_target_assets = read_target_asset_data(deps.storage)?;
+
+    match cfg.target_mode {
+        TargetMode::Units => Ok(TargetResponse {
+            target: target_assets,
+            final_target: final_target_assets,
+            value_weights: None,
+            final_value_weights: None,
+        }),
+        TargetMode::ValueWeights => {
+            // Convert the value weights to unit weights at the current prices
+            let asset_infos = target_assets
+                .iter()
+                .map(|x| x.info.clone())
+                .collect::<Vec<_>>();
+            let prices = asset_infos
+                .iter()
+                .map(|asset_info| {
+                    query_price(&deps.querier, &cfg.pricing_oracle, asset_info)
+                        .map(|(price, _)| price)
+                })
+                .collect::<StdResult<Vec<String>>>()?;
+            Ok(TargetResponse {
+                target: to_unit_weights(&target_assets, &asset_infos, &prices)?,
+                final_target: to_unit_weights(&final_target_assets, &asset_infos, &prices)?,
+                value_weights: Some(target_assets),
+                final_value_weights: Some(final_target_assets),
+            })
+        }
+    }
 }
 
 /// ## Description
-/// Returns the current state of the given cluster contract. Return `StdError` if
-/// there is some asset price older than `stale_threshold`.
+/// Returns the current state of the given cluster contract. If `enforce_freshness`
+/// is set, returns `StdError` if there is some asset price older than its maximum
+/// price age in the cluster price freshness policy.
 ///
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
 /// - **cluster_contract_address** is a reference to an object of type [`String`].
 ///
-/// - **stale_threshold** is an object of type [`u64`].
+/// - **now** is an object of type [`u64`] which is the current block time in seconds.
+///
+/// - **enforce_freshness** is an object of type [`bool`].
 pub fn query_cluster_state(
     deps: Deps,
     cluster_contract_address: &str,
-    stale_threshold: u64,
+    now: u64,
+    enforce_freshness: bool,
 ) -> StdResult<ClusterStateResponse> {
     let cfg = &read_config(deps.storage)?;
 
     let active = cfg.active;
 
-    // Get the current asset target weights `target_asset_data`
-    let target_asset_data = read_target_asset_data(deps.storage)?;
+    // Get the current effective asset target weights `target_asset_data`
+    let target_asset_data = read_effective_target_asset_data(deps.storage, now)?;
+    let final_target_asset_data = read_target_asset_data(deps.storage)?;
     let asset_infos = target_asset_data
         .iter()
         .map(|x| x.info.clone())
@@ -105,27 +219,38 @@ pub fn query_cluster_state(
         .clone()
         .ok_or_else(|| StdError::generic_err("no cluster token exists"))?;
 
-    // Get the current supply of the cluster token
-    let outstanding_balance_tokens = query_cw20_token_supply(&deps.querier, &cluster_token)?;
+    // Get the current supply of the cluster token, including the accrued management fee
+    let token_supply = query_cw20_token_supply(&deps.querier, &cluster_token)?;
+    let accrued_fee = compute_cluster_accrued_fee(deps, cfg, token_supply, now)?;
+    let outstanding_balance_tokens = token_supply.checked_add(accrued_fee)?;
 
-    if !active && stale_threshold != u64::MIN {
+    if !active && enforce_freshness {
         return Err(StdError::generic_err(
-            "Decommissioned cluster should have int min stale threshold",
+            "Decommissioned cluster should not enforce price freshness",
         ));
     }
 
-    // Get asset prices from the pricing oracle contract
-    let prices = asset_infos
-        .iter()
-        .map(|asset_info| {
-            query_price(
-                &deps.querier,
-                &cfg.pricing_oracle,
-                asset_info,
-                stale_threshold,
-            )
-        })
-        .collect::<StdResult<Vec<String>>>()?;
+    // Get asset prices and their ages from the pricing oracle contract
+    let mut prices: Vec<String> = vec![];
+    let mut price_ages: Vec<u64> = vec![];
+    for asset_info in asset_infos.iter() {
+        let (price, last_updated) = query_price(&deps.querier, &cfg.pricing_oracle, asset_info)?;
+        let age = now.saturating_sub(last_updated);
+        if enforce_freshness && age > cfg.price_freshness.max_price_age_of(asset_info) {
+            return Err(StdError::generic_err("oracle prices are stale".to_string()));
+        }
+        prices.push(price);
+        price_ages.push(age);
+    }
+
+    // Convert value weights to the unit weights used by the penalty contract
+    let (target_asset_data, final_target_asset_data) = match cfg.target_mode {
+        TargetMode::Units => (target_asset_data, final_target_asset_data),
+        TargetMode::ValueWeights => (
+            to_unit_weights(&target_asset_data, &asset_infos, &prices)?,
+            to_unit_weights(&final_target_asset_data, &asset_infos, &prices)?,
+        ),
+    };
 
     // Get the current asset inventory
     let inv: Vec<Uint128> = asset_infos
@@ -138,15 +263,224 @@ pub fn query_cluster_state(
         })
         .collect::<StdResult<Vec<Uint128>>>()?;
 
+    // Get the asset decimals to normalise the inventory to the oracle price unit
+    let asset_decimals: Vec<u8> = asset_infos
+        .iter()
+        .map(|asset| match asset {
+            AssetInfo::Token { contract_addr } => {
+                read_asset_decimals(deps.storage, contract_addr.as_str())
+            }
+            AssetInfo::NativeToken { denom } => read_asset_decimals(deps.storage, denom),
+        })
+        .collect::<StdResult<Vec<u8>>>()?;
+
+    // Get the remaining headroom below the cluster token supply and asset inventory caps
+    let supply_headroom = cfg
+        .caps
+        .supply_cap
+        .map(|cap| cap.saturating_sub(outstanding_balance_tokens));
+    let inventory_headroom = asset_infos
+        .iter()
+        .zip(inv.iter())
+        .map(|(asset_info, amount)| {
+            cfg.caps
+                .inventory_cap_of(asset_info)
+                .map(|cap| cap.saturating_sub(*amount))
+        })
+        .collect::<Vec<_>>();
+
     Ok(ClusterStateResponse {
         outstanding_balance_tokens,
+        accrued_fee,
         prices,
         inv,
         target: target_asset_data,
+        final_target: final_target_asset_data,
         penalty: cfg.penalty.to_string(),
         cluster_token: cluster_token.to_string(),
         cluster_contract_address: cluster_contract_address.to_string(),
         active,
+        price_ages,
+        supply_headroom,
+        inventory_headroom,
+        create_paused: cfg.create_paused,
+        custom_redeem_paused: cfg.custom_redeem_paused,
+        asset_decimals,
+    })
+}
+
+/// ## Description
+/// Returns the management fee in cluster tokens accrued since the last accrual.
+/// Decommissioned clusters do not accrue management fees.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **token_supply** is an object of type [`Uint128`] which is the cluster token supply.
+///
+/// - **now** is an object of type [`u64`] which is the current block time in seconds.
+pub fn compute_cluster_accrued_fee(
+    deps: Deps,
+    cfg: &ClusterConfig,
+    token_supply: Uint128,
+    now: u64,
+) -> StdResult<Uint128> {
+    if !cfg.active {
+        return Ok(Uint128::zero());
+    }
+    let last_accrual = read_last_fee_accrual(deps.storage, now)?;
+    Ok(compute_accrued_fee(
+        token_supply,
+        cfg.management_fee_rate,
+        now.saturating_sub(last_accrual),
+    ))
+}
+
+/// ## Description
+/// Returns the management fee rate and the accrued but not yet minted management fee.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is an object of type [`Env`].
+pub fn query_accrued_fee(deps: Deps, env: Env) -> StdResult<AccruedFeeResponse> {
+    let cfg = read_config(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    let accrued_fee = match &cfg.cluster_token {
+        Some(cluster_token) => {
+            let token_supply = query_cw20_token_supply(&deps.querier, cluster_token)?;
+            compute_cluster_accrued_fee(deps, &cfg, token_supply, now)?
+        }
+        None => Uint128::zero(),
+    };
+
+    Ok(AccruedFeeResponse {
+        management_fee_rate: cfg.management_fee_rate,
+        last_accrual: read_last_fee_accrual(deps.storage, now)?,
+        accrued_fee,
+    })
+}
+
+/// ## Description
+/// Returns the tracked inventory and the actual balance of each asset in the cluster target.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is a reference to an object of type [`Env`].
+pub fn compute_inventory_drift(deps: Deps, env: &Env) -> StdResult<Vec<AssetDrift>> {
+    let target_asset_data =
+        read_effective_target_asset_data(deps.storage, env.block.time.seconds())?;
+
+    target_asset_data
+        .into_iter()
+        .map(|asset| {
+            let tracked_balance = match &asset.info {
+                AssetInfo::Token { contract_addr } => {
+                    read_asset_balance(deps.storage, &contract_addr.to_string())?
+                }
+                AssetInfo::NativeToken { denom } => read_asset_balance(deps.storage, denom)?,
+            };
+            let actual_balance =
+                query_asset_balance(&deps.querier, &env.contract.address, &asset.info)?;
+
+            Ok(AssetDrift {
+                info: asset.info,
+                tracked_balance,
+                actual_balance,
+                surplus: actual_balance.saturating_sub(tracked_balance),
+                deficit: tracked_balance.saturating_sub(actual_balance),
+            })
+        })
+        .collect()
+}
+
+/// ## Description
+/// Returns the difference between the tracked inventory and the actual asset balances.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is an object of type [`Env`].
+pub fn query_inventory_drift(deps: Deps, env: Env) -> StdResult<InventoryDriftResponse> {
+    Ok(InventoryDriftResponse {
+        drift: compute_inventory_drift(deps, &env)?,
+    })
+}
+
+/// ## Description
+/// Returns a queued redeem request.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **request_id** is an object of type [`u64`] which is the ID of the request.
+pub fn query_redeem_request(deps: Deps, request_id: u64) -> StdResult<RedeemRequestResponse> {
+    Ok(to_redeem_request_response(read_redeem_request(
+        deps.storage,
+        request_id,
+    )?))
+}
+
+/// ## Description
+/// Returns the queued redeem requests ordered by ID.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **owner** is an object of type [`Option<String>`] which filters the requests
+///     by their owner.
+///
+/// - **start_after** is an object of type [`Option<u64>`] which is a filter for the request ID.
+///
+/// - **limit** is an object of type [`Option<u32>`] which limits the number of requests in the query result.
+pub fn query_redeem_requests(
+    deps: Deps,
+    owner: Option<String>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RedeemRequestsResponse> {
+    let owner = owner
+        .map(|x| deps.api.addr_validate(x.as_str()))
+        .transpose()?;
+    let requests = read_redeem_requests(deps.storage, owner, start_after, limit)?;
+    Ok(RedeemRequestsResponse {
+        requests: requests
+            .into_iter()
+            .map(to_redeem_request_response)
+            .collect(),
+    })
+}
+
+fn to_redeem_request_response(request: RedeemRequest) -> RedeemRequestResponse {
+    RedeemRequestResponse {
+        request_id: request.id,
+        owner: request.owner.to_string(),
+        token_amount: request.token_amount,
+        asset_amounts: request.asset_amounts,
+        min_assets: request.min_assets,
+        submitted_at: request.submitted_at,
+    }
+}
+
+/// ## Description
+/// Returns the cumulative cluster tokens received by a referrer as a share of the protocol fee.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **referrer** is an object of type [`String`] which is the address of the referrer.
+pub fn query_referral_earnings(
+    deps: Deps,
+    referrer: String,
+) -> StdResult<ReferralEarningsResponse> {
+    let validated_referrer = deps.api.addr_validate(referrer.as_str())?;
+    Ok(ReferralEarningsResponse {
+        referrer,
+        earnings: read_referral_earnings(deps.storage, &validated_referrer)?,
     })
 }
 
@@ -164,3 +498,253 @@ pub fn query_cluster_info(deps: Deps) -> StdResult<ClusterInfoResponse> {
         description: description.to_string(),
     })
 }
+
+/// ## Description
+/// Returns the result of a create operation with the given assets, following
+/// the same computation as the `RebalanceCreate` execution.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **asset_amounts** is an object of type [`Vec<Asset>`] which are the assets
+///     to deposit for minting cluster tokens.
+pub fn query_simulate_create(
+    deps: Deps,
+    env: Env,
+    asset_amounts: Vec<Asset>,
+) -> StdResult<SimulateCreateResponse> {
+    // Check `asset_amounts` for duplicate and unsupported assets
+    if validate_targets(
+        deps.querier,
+        &env,
+        asset_amounts.iter().map(|a| a.info.clone()).collect(),
+    )
+    .is_err()
+    {
+        return Err(StdError::generic_err("Invalid assets"));
+    }
+
+    let cfg = read_config(deps.storage)?;
+    assert_create_allowed(&cfg).map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let cluster_state = with_refreshed_target(deps, &env, &cfg, |deps| {
+        query_cluster_state(
+            deps,
+            &env.contract.address.to_string(),
+            env.block.time.seconds(),
+            true,
+        )
+    })?;
+    if cluster_state.outstanding_balance_tokens.is_zero() {
+        return Err(StdError::generic_err(
+            "Cannot simulate create on an uninitialized cluster",
+        ));
+    }
+
+    let create_asset_amounts = to_create_asset_amounts(&cluster_state.target, &asset_amounts)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let CreateAmount {
+        mint_to_sender,
+        protocol_fee,
+        penalty,
+        attributes,
+        ..
+    } = compute_create_amount(
+        &deps.querier,
+        env.block.height,
+        &cfg,
+        &cluster_state,
+        &create_asset_amounts,
+    )
+    .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(SimulateCreateResponse {
+        create_tokens: mint_to_sender,
+        fee_amt: protocol_fee,
+        penalty,
+        create_asset_amounts: to_assets(&cluster_state.target, &create_asset_amounts),
+        attributes,
+    })
+}
+
+/// ## Description
+/// Returns the result of a redeem operation with the given parameters, following
+/// the same computation as the `RebalanceRedeem` execution.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **max_tokens** is an object of type [`Uint128`] which is the required
+///     maximum amount of cluster tokens allowed to burn.
+///
+/// - **asset_amounts** is an object of type [`Option<Vec<Asset>>`] which are the assets amount
+///     to receive, or `None` for a pro-rata redeem.
+pub fn query_simulate_redeem(
+    deps: Deps,
+    env: Env,
+    max_tokens: Uint128,
+    asset_amounts: Option<Vec<Asset>>,
+) -> StdResult<SimulateRedeemResponse> {
+    let cfg = read_config(deps.storage)?;
+    let pro_rata = asset_amounts.is_none();
+    assert_redeem_allowed(&cfg, pro_rata).map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    // Pro-rata redeems do not depend on the target weights, so they never wait on the provider
+    let query_state = |deps: Deps| {
+        query_cluster_state(
+            deps,
+            &env.contract.address.to_string(),
+            env.block.time.seconds(),
+            requires_fresh_prices(&cfg, pro_rata),
+        )
+    };
+    let cluster_state = if pro_rata {
+        query_state(deps)?
+    } else {
+        with_refreshed_target(deps, &env, &cfg, query_state)?
+    };
+
+    let asset_amounts = to_redeem_asset_amounts(&cluster_state.target, &asset_amounts);
+
+    let RedeemAmount {
+        redeem_totals,
+        token_cost,
+        protocol_fee,
+        penalty,
+        attributes,
+        ..
+    } = compute_redeem_amount(
+        &deps.querier,
+        env.block.height,
+        &cfg,
+        &cluster_state,
+        max_tokens,
+        &asset_amounts,
+    )
+    .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(SimulateRedeemResponse {
+        token_cost,
+        burn_amount: token_cost.checked_sub(protocol_fee)?,
+        fee_amt: protocol_fee,
+        penalty,
+        redeem_assets: to_assets(&cluster_state.target, &redeem_totals),
+        attributes,
+    })
+}
+
+/// ## Description
+/// Returns the result of a swap operation with the given parameters, following
+/// the same computation as the `Swap` execution.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **offer_asset** is an object of type [`Asset`] which is the offered asset.
+///
+/// - **ask_asset_info** is an object of type [`AssetInfo`] which is the asked asset.
+pub fn query_simulate_swap(
+    deps: Deps,
+    env: Env,
+    offer_asset: Asset,
+    ask_asset_info: AssetInfo,
+) -> StdResult<SimulateSwapResponse> {
+    let cfg = read_config(deps.storage)?;
+    assert_swap_allowed(&cfg).map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let cluster_state = with_refreshed_target(deps, &env, &cfg, |deps| {
+        query_cluster_state(
+            deps,
+            &env.contract.address.to_string(),
+            env.block.time.seconds(),
+            true,
+        )
+    })?;
+
+    let SwapAmount {
+        return_amount,
+        protocol_fee,
+        penalty,
+        attributes,
+        ..
+    } = compute_swap_amount(
+        &deps.querier,
+        env.block.height,
+        &cfg,
+        &cluster_state,
+        &offer_asset,
+        &ask_asset_info,
+    )
+    .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(SimulateSwapResponse {
+        return_amount,
+        fee_amt: protocol_fee,
+        penalty,
+        attributes,
+    })
+}
+
+/// ## Description
+/// Runs `f` after pulling the target weights from the target provider if due, as
+/// the executions do. The refreshed target is only written to an in-memory overlay
+/// of the cluster storage, so it is never persisted by a query.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is a reference to an object of type [`Env`].
+///
+/// - **cfg** is a reference to an object of type [`ClusterConfig`].
+///
+/// - **f** is a closure querying the cluster with the refreshed target.
+fn with_refreshed_target<T>(
+    deps: Deps,
+    env: &Env,
+    cfg: &ClusterConfig,
+    f: impl FnOnce(Deps) -> StdResult<T>,
+) -> StdResult<T> {
+    let mut storage = OverlayStorage::new(deps.storage);
+    refresh_target(
+        DepsMut {
+            storage: &mut storage,
+            api: deps.api,
+            querier: deps.querier,
+        },
+        env,
+        cfg,
+    )
+    .map_err(|e| StdError::generic_err(e.to_string()))?;
+    f(Deps {
+        storage: &storage,
+        api: deps.api,
+        querier: deps.querier,
+    })
+}
+
+/// ## Description
+/// Pairs the given amounts with the asset infos of the cluster target.
+///
+/// ## Params
+/// - **target** is a reference to an object of type [`Vec<Asset>`] which is
+///     the cluster target.
+///
+/// - **amounts** is a reference to an object of type [`Vec<Uint128>`] which
+///     are amounts ordered by the cluster target.
+fn to_assets(target: &[Asset], amounts: &[Uint128]) -> Vec<Asset> {
+    target
+        .iter()
+        .zip(amounts.iter())
+        .map(|(asset, amount)| Asset {
+            info: asset.info.clone(),
+            amount: *amount,
+        })
+        .collect()
+}
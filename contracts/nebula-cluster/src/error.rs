@@ -20,6 +20,9 @@ pub enum ContractError {
     #[error("This cluster is already a decommissioned cluster")]
     ClusterAlreadyDecommissioned {},
 
+    #[error("This cluster is not a decommissioned cluster")]
+    ClusterNotDecommissioned {},
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -31,6 +34,21 @@ pub enum ContractError {
     )]
     AboveMaxTokens(Uint128, Uint128),
 
+    #[error("Redeemed amount of {0} is below min_assets: {1} (would_redeem) < {2} (min_amount)")]
+    BelowMinAssets(String, Uint128, Uint128),
+
+    #[error("Swap return amount is below min_return: {0} (would_return) < {1} (min_return)")]
+    BelowMinReturn(Uint128, Uint128),
+
+    #[error("Cap exceeded for {0}: {1} (would_hold) > {2} (cap)")]
+    CapExceeded(String, Uint128, Uint128),
+
+    #[error("Create is paused on this cluster")]
+    CreatePaused {},
+
+    #[error("Custom redeem is paused on this cluster, only pro-rata redeem is available")]
+    CustomRedeemPaused {},
+
     #[error("Associated cluster token has not yet been set")]
     ClusterTokenNotSet {},
 }
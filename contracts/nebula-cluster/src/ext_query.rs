@@ -1,15 +1,17 @@
-use astroport::asset::AssetInfo;
+use astroport::asset::{Asset, AssetInfo};
 use cosmwasm_std::{
-    to_binary, Addr, BalanceResponse, BankQuery, QuerierWrapper, QueryRequest, StdError, StdResult,
-    Uint128, WasmQuery,
+    to_binary, Addr, BalanceResponse, BankQuery, QuerierWrapper, QueryRequest, StdResult, Uint128,
+    WasmQuery,
 };
 use cw20::Cw20QueryMsg;
 use cw20::{BalanceResponse as Cw20BalanceResponse, TokenInfoResponse as Cw20TokenInfoResponse};
 use nebula_protocol::{
-    cluster_factory::ConfigResponse as FactoryConfigResponse,
+    cluster_factory::ClusterFeesResponse, cluster_factory::ConfigResponse as FactoryConfigResponse,
     cluster_factory::QueryMsg as FactoryQueryMsg, oracle::PriceResponse,
     oracle::QueryMsg as OracleQueryMsg, penalty::PenaltyCreateResponse,
-    penalty::PenaltyRedeemResponse, penalty::QueryMsg as PenaltyQueryMsg,
+    penalty::PenaltyRedeemResponse, penalty::PenaltySwapResponse,
+    penalty::QueryMsg as PenaltyQueryMsg, target_provider::QueryMsg as TargetProviderQueryMsg,
+    target_provider::TargetWeightsResponse,
 };
 
 //////////////////////////////////////////////////////////////////////
@@ -17,7 +19,8 @@ use nebula_protocol::{
 //////////////////////////////////////////////////////////////////////
 
 /// ## Description
-/// Queries the oracle contract for the current asset price.
+/// Queries the oracle contract for the current asset price. Returns the price
+/// together with the timestamp of its oldest underlying oracle update.
 ///
 /// ## Params
 /// - **querier** is a reference to an object of type [`QuerierWrapper`].
@@ -25,16 +28,11 @@ use nebula_protocol::{
 /// - **pricing_oracle_address** is a reference to an object of type [`Addr`].
 ///
 /// - **asset_info** is a reference to an object of type [`AssetInfo`].
-///
-/// - **stale_threshold** is an object of type [`u64`].
 pub fn query_price(
     querier: &QuerierWrapper,
     pricing_oracle_address: &Addr,
     asset_info: &AssetInfo,
-    // Prices from before < stale_threshold are considered stale
-    // and result in an error
-    stale_threshold: u64,
-) -> StdResult<String> {
+) -> StdResult<(String, u64)> {
     // Perform query
     let res: PriceResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
         contract_addr: pricing_oracle_address.to_string(),
@@ -46,10 +44,8 @@ pub fn query_price(
         })?,
     }))?;
 
-    if std::cmp::min(res.last_updated_quote, res.last_updated_base) < stale_threshold {
-        return Err(StdError::generic_err("oracle prices are stale".to_string()));
-    }
-    Ok(res.rate.to_string().as_str().parse().unwrap())
+    let last_updated = std::cmp::min(res.last_updated_quote, res.last_updated_base);
+    Ok((res.rate.to_string().as_str().parse().unwrap(), last_updated))
 }
 
 /// ## Description
@@ -139,8 +135,23 @@ pub fn query_cw20_token_supply(
 }
 
 /// ## Description
-/// Queries the cluster factory contract for the collector contract address
-/// and the current fee rate.
+/// Queries the token_address contract for the token's decimals.
+///
+/// ## Params
+/// - **querier** is a reference to an object of type [`QuerierWrapper`].
+///
+/// - **asset_address** is a reference to an object of type [`Addr`].
+pub fn query_cw20_token_decimals(querier: &QuerierWrapper, asset_address: &Addr) -> StdResult<u8> {
+    let res: Cw20TokenInfoResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: asset_address.to_string(),
+        msg: to_binary(&Cw20QueryMsg::TokenInfo {})?,
+    }))?;
+
+    Ok(res.decimals)
+}
+
+/// ## Description
+/// Queries the cluster factory contract for the collector contract address.
 ///
 /// ## Params
 /// - **querier** is a reference to an object of type [`QuerierWrapper`].
@@ -149,13 +160,36 @@ pub fn query_cw20_token_supply(
 pub fn query_collector_contract_address(
     querier: &QuerierWrapper,
     factory_address: &Addr,
-) -> StdResult<(String, String)> {
+) -> StdResult<String> {
     let res: FactoryConfigResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
         contract_addr: factory_address.to_string(),
         msg: to_binary(&FactoryQueryMsg::Config {})?,
     }))?;
 
-    Ok((res.commission_collector, res.protocol_fee_rate))
+    Ok(res.commission_collector)
+}
+
+/// ## Description
+/// Queries the cluster factory contract for the effective create / redeem
+/// fee rates of a cluster.
+///
+/// ## Params
+/// - **querier** is a reference to an object of type [`QuerierWrapper`].
+///
+/// - **factory_address** is a reference to an object of type [`Addr`].
+///
+/// - **cluster_contract** is a reference to an object of type [`str`].
+pub fn query_cluster_fees(
+    querier: &QuerierWrapper,
+    factory_address: &Addr,
+    cluster_contract: &str,
+) -> StdResult<ClusterFeesResponse> {
+    querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: factory_address.to_string(),
+        msg: to_binary(&FactoryQueryMsg::ClusterFees {
+            cluster_contract: cluster_contract.to_string(),
+        })?,
+    }))
 }
 
 /// ## Description
@@ -166,6 +200,8 @@ pub fn query_collector_contract_address(
 ///
 /// - **penalty_address** is a reference to an object of type [`Addr`].
 ///
+/// - **cluster_contract** is an object of type [`String`] which is the address of the cluster.
+///
 /// - **block_height** is an object of type [`u64`].
 ///
 /// - **cluster_token_supply** is an object of type [`Uint128`].
@@ -181,6 +217,7 @@ pub fn query_collector_contract_address(
 pub fn query_create_amount(
     querier: &QuerierWrapper,
     penalty_address: &Addr,
+    cluster_contract: String,
     block_height: u64,
     cluster_token_supply: Uint128,
     inventory: Vec<Uint128>,
@@ -197,6 +234,7 @@ pub fn query_create_amount(
             create_asset_amounts,
             asset_prices,
             target_weights,
+            cluster_contract: Some(cluster_contract),
         })?,
     }))?;
 
@@ -211,6 +249,8 @@ pub fn query_create_amount(
 ///
 /// - **penalty_address** is a reference to an object of type [`Addr`].
 ///
+/// - **cluster_contract** is an object of type [`String`] which is the address of the cluster.
+///
 /// - **block_height** is an object of type [`u64`].
 ///
 /// - **cluster_token_supply** is an object of type [`Uint128`].
@@ -228,6 +268,7 @@ pub fn query_create_amount(
 pub fn query_redeem_amount(
     querier: &QuerierWrapper,
     penalty_address: &Addr,
+    cluster_contract: String,
     block_height: u64,
     cluster_token_supply: Uint128,
     inventory: Vec<Uint128>,
@@ -246,8 +287,77 @@ pub fn query_redeem_amount(
             redeem_asset_amounts,
             asset_prices,
             target_weights,
+            cluster_contract: Some(cluster_contract),
         })?,
     }))?;
 
     Ok(res)
 }
+
+/// ## Description
+/// Queries the penalty contract for the amount of ask asset returned by a swap.
+///
+/// ## Params
+/// - **querier** is a reference to an object of type [`QuerierWrapper`].
+///
+/// - **penalty_address** is a reference to an object of type [`Addr`].
+///
+/// - **cluster_contract** is an object of type [`String`] which is the address of the cluster.
+///
+/// - **block_height** is an object of type [`u64`].
+///
+/// - **inventory** is an object of type [`Vec<Uint128>`].
+///
+/// - **offer_asset_amounts** is an object of type [`Vec<Uint128>`].
+///
+/// - **ask_asset_amounts** is an object of type [`Vec<Uint128>`].
+///
+/// - **asset_prices** is an object of type [`Vec<String>`].
+///
+/// - **target_weights** is an object of type [`Vec<Uint128>`].
+#[allow(clippy::too_many_arguments)]
+pub fn query_swap_amount(
+    querier: &QuerierWrapper,
+    penalty_address: &Addr,
+    cluster_contract: String,
+    block_height: u64,
+    inventory: Vec<Uint128>,
+    offer_asset_amounts: Vec<Uint128>,
+    ask_asset_amounts: Vec<Uint128>,
+    asset_prices: Vec<String>,
+    target_weights: Vec<Uint128>,
+) -> StdResult<PenaltySwapResponse> {
+    let res: PenaltySwapResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: penalty_address.to_string(),
+        msg: to_binary(&PenaltyQueryMsg::PenaltyQuerySwap {
+            block_height,
+            inventory,
+            offer_asset_amounts,
+            ask_asset_amounts,
+            asset_prices,
+            target_weights,
+            cluster_contract: Some(cluster_contract),
+        })?,
+    }))?;
+
+    Ok(res)
+}
+
+/// ## Description
+/// Queries the target provider contract for the asset target weights.
+///
+/// ## Params
+/// - **querier** is a reference to an object of type [`QuerierWrapper`].
+///
+/// - **target_provider_address** is a reference to an object of type [`Addr`].
+pub fn query_provider_target(
+    querier: &QuerierWrapper,
+    target_provider_address: &Addr,
+) -> StdResult<Vec<Asset>> {
+    let res: TargetWeightsResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: target_provider_address.to_string(),
+        msg: to_binary(&TargetProviderQueryMsg::TargetWeights {})?,
+    }))?;
+
+    Ok(res.target)
+}
@@ -1,7 +1,16 @@
+use std::collections::BTreeMap;
+
 use astroport::asset::Asset;
-use cosmwasm_std::{StdResult, Storage, Uint128};
-use cosmwasm_storage::{bucket, bucket_read, singleton, singleton_read, Singleton};
+use cluster_math::NORMALIZED_DECIMALS;
+use cosmwasm_std::{Addr, Order, Pair, StdError, StdResult, Storage, Uint128};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, Singleton,
+};
 use nebula_protocol::cluster::ClusterConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::util::interpolate_target;
 
 /// config: ClusterConfig
 pub static CONFIG_KEY: &[u8] = b"config";
@@ -9,9 +18,30 @@ pub static CONFIG_KEY: &[u8] = b"config";
 pub static TARGET_KEY: &[u8] = b"target";
 /// asset data: Vec<AssetData>
 pub static ASSET_DATA_KEY: &[u8] = b"asset_data";
+/// target transition: TargetTransition
+pub static TARGET_TRANSITION_KEY: &[u8] = b"target_transition";
+/// last fee accrual: u64
+pub static LAST_FEE_ACCRUAL_KEY: &[u8] = b"last_fee_accrual";
+/// last target refresh: u64
+pub static LAST_TARGET_REFRESH_KEY: &[u8] = b"last_target_refresh";
+/// next redeem request id: u64
+pub static NEXT_REDEEM_REQUEST_ID_KEY: &[u8] = b"next_redeem_request_id";
 
 /// balance: Uint128
 pub static PREFIX_BALANCE: &[u8] = b"balance";
+/// asset decimals: u8
+pub static PREFIX_ASSET_DECIMALS: &[u8] = b"asset_decimals";
+/// redeem request: Bucket<RedeemRequest>; request_id -> RedeemRequest
+pub static PREFIX_REDEEM_REQUEST: &[u8] = b"redeem_request";
+/// redeem request indexer: Bucket<bool>; owner, request_id -> true
+pub static PREFIX_REDEEM_REQUEST_INDEXER: &[u8] = b"redeem_request_indexer";
+/// referral earnings: Uint128; referrer -> cumulative cluster tokens
+pub static PREFIX_REFERRAL_EARNINGS: &[u8] = b"referral_earnings";
+
+/// Maximum number of results when querying.
+const MAX_LIMIT: u32 = 30;
+/// Default number of results when querying if a limit is not specified.
+const DEFAULT_LIMIT: u32 = 10;
 
 //////////////////////////////////////////////////////////////////////
 /// CONFIG
@@ -29,6 +59,23 @@ pub fn store_config(storage: &mut dyn Storage, config: &ClusterConfig) -> StdRes
     singleton(storage, CONFIG_KEY).save(config)
 }
 
+//////////////////////////////////////////////////////////////////////
+/// MANAGEMENT FEE
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// Returns the last time in seconds the management fee was accrued. Clusters
+/// instantiated before the management fee existed fall back to `now`.
+pub fn read_last_fee_accrual(storage: &dyn Storage, now: u64) -> StdResult<u64> {
+    Ok(singleton_read(storage, LAST_FEE_ACCRUAL_KEY)
+        .may_load()?
+        .unwrap_or(now))
+}
+
+pub fn store_last_fee_accrual(storage: &mut dyn Storage, time: u64) -> StdResult<()> {
+    singleton(storage, LAST_FEE_ACCRUAL_KEY).save(&time)
+}
+
 //////////////////////////////////////////////////////////////////////
 /// ASSET DATA
 //////////////////////////////////////////////////////////////////////
@@ -41,6 +88,74 @@ pub fn store_target_asset_data(storage: &mut dyn Storage, asset_data: &[Asset])
     singleton(storage, ASSET_DATA_KEY).save(&asset_data.to_owned())
 }
 
+/// ## Description
+/// Returns the effective asset target weights at time `now`, interpolated
+/// between the previous and the stored final weights during a target transition.
+pub fn read_effective_target_asset_data(storage: &dyn Storage, now: u64) -> StdResult<Vec<Asset>> {
+    let final_target = read_target_asset_data(storage)?;
+    match read_target_transition(storage)? {
+        Some(transition) => interpolate_target(
+            &transition.prev_target,
+            &final_target,
+            transition.start_time,
+            transition.end_time,
+            now,
+        ),
+        None => Ok(final_target),
+    }
+}
+
+//////////////////////////////////////////////////////////////////////
+/// TARGET TRANSITION
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// A custom struct for storing an ongoing transition from the previous
+/// asset target weights to the stored final weights.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TargetTransition {
+    /// Effective asset target weights when the transition started
+    pub prev_target: Vec<Asset>,
+    /// Transition start time in seconds
+    pub start_time: u64,
+    /// Transition end time in seconds
+    pub end_time: u64,
+}
+
+pub fn read_target_transition(storage: &dyn Storage) -> StdResult<Option<TargetTransition>> {
+    singleton_read(storage, TARGET_TRANSITION_KEY).may_load()
+}
+
+pub fn store_target_transition(
+    storage: &mut dyn Storage,
+    transition: &TargetTransition,
+) -> StdResult<()> {
+    singleton(storage, TARGET_TRANSITION_KEY).save(transition)
+}
+
+pub fn remove_target_transition(storage: &mut dyn Storage) {
+    singleton::<TargetTransition>(storage, TARGET_TRANSITION_KEY).remove()
+}
+
+//////////////////////////////////////////////////////////////////////
+/// TARGET PROVIDER
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// Returns the last time in seconds the target weights were pulled from the
+/// target provider, if any.
+pub fn read_last_target_refresh(storage: &dyn Storage) -> StdResult<Option<u64>> {
+    singleton_read(storage, LAST_TARGET_REFRESH_KEY).may_load()
+}
+
+pub fn store_last_target_refresh(storage: &mut dyn Storage, time: u64) -> StdResult<()> {
+    singleton(storage, LAST_TARGET_REFRESH_KEY).save(&time)
+}
+
+pub fn remove_last_target_refresh(storage: &mut dyn Storage) {
+    singleton::<u64>(storage, LAST_TARGET_REFRESH_KEY).remove()
+}
+
 //////////////////////////////////////////////////////////////////////
 /// ASSET BALANCE (INVENTORY)
 //////////////////////////////////////////////////////////////////////
@@ -58,3 +173,225 @@ pub fn read_asset_balance(storage: &dyn Storage, asset: &str) -> StdResult<Uint1
         .load(asset.as_bytes())
         .unwrap_or_else(|_| Uint128::zero()))
 }
+
+//////////////////////////////////////////////////////////////////////
+/// ASSET DECIMALS
+//////////////////////////////////////////////////////////////////////
+
+pub fn store_asset_decimals(storage: &mut dyn Storage, asset: &str, decimals: u8) -> StdResult<()> {
+    bucket(storage, PREFIX_ASSET_DECIMALS).save(asset.as_bytes(), &decimals)
+}
+
+/// ## Description
+/// Returns the recorded decimals of an asset. Assets recorded before decimals
+/// existed fall back to the normalised precision.
+pub fn read_asset_decimals(storage: &dyn Storage, asset: &str) -> StdResult<u8> {
+    Ok(bucket_read(storage, PREFIX_ASSET_DECIMALS)
+        .may_load(asset.as_bytes())?
+        .unwrap_or(NORMALIZED_DECIMALS))
+}
+
+//////////////////////////////////////////////////////////////////////
+/// REFERRAL EARNINGS
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// Adds cluster tokens paid to a referrer to its cumulative earnings.
+pub fn add_referral_earnings(
+    storage: &mut dyn Storage,
+    referrer: &Addr,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    bucket(storage, PREFIX_REFERRAL_EARNINGS).update(
+        referrer.as_bytes(),
+        |earnings: Option<Uint128>| -> StdResult<Uint128> {
+            Ok(earnings.unwrap_or_default().checked_add(amount)?)
+        },
+    )
+}
+
+/// ## Description
+/// Returns the cumulative cluster tokens paid to a referrer.
+pub fn read_referral_earnings(storage: &dyn Storage, referrer: &Addr) -> StdResult<Uint128> {
+    Ok(bucket_read(storage, PREFIX_REFERRAL_EARNINGS)
+        .may_load(referrer.as_bytes())?
+        .unwrap_or_default())
+}
+
+//////////////////////////////////////////////////////////////////////
+/// REDEEM REQUEST
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// A custom struct for storing a queued redeem request escrowing cluster tokens.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RedeemRequest {
+    /// ID of the request
+    pub id: u64,
+    /// Owner of the escrowed cluster tokens receiving the redeemed assets
+    pub owner: Addr,
+    /// Escrowed cluster tokens
+    pub token_amount: Uint128,
+    /// Asset amounts to receive
+    pub asset_amounts: Vec<Asset>,
+    /// Minimum amounts of assets to receive
+    pub min_assets: Option<Vec<Asset>>,
+    /// Time in seconds the request was queued
+    pub submitted_at: u64,
+}
+
+/// ## Description
+/// Returns the next redeem request ID and increments it.
+pub fn next_redeem_request_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    let mut store = singleton(storage, NEXT_REDEEM_REQUEST_ID_KEY);
+    let id: u64 = store.may_load()?.unwrap_or_default();
+    store.save(&(id + 1))?;
+    Ok(id)
+}
+
+/// ## Description
+/// Saves a redeem request and indexes it by its owner.
+pub fn store_redeem_request(storage: &mut dyn Storage, request: &RedeemRequest) -> StdResult<()> {
+    bucket(storage, PREFIX_REDEEM_REQUEST).save(&request.id.to_be_bytes(), request)?;
+    Bucket::multilevel(
+        storage,
+        &[PREFIX_REDEEM_REQUEST_INDEXER, request.owner.as_bytes()],
+    )
+    .save(&request.id.to_be_bytes(), &true)
+}
+
+pub fn read_redeem_request(storage: &dyn Storage, request_id: u64) -> StdResult<RedeemRequest> {
+    bucket_read(storage, PREFIX_REDEEM_REQUEST)
+        .load(&request_id.to_be_bytes())
+        .map_err(|_| StdError::generic_err(format!("Redeem request {} does not exist", request_id)))
+}
+
+/// ## Description
+/// Removes a redeem request and its owner index.
+pub fn remove_redeem_request(storage: &mut dyn Storage, request: &RedeemRequest) {
+    bucket::<RedeemRequest>(storage, PREFIX_REDEEM_REQUEST).remove(&request.id.to_be_bytes());
+    Bucket::<bool>::multilevel(
+        storage,
+        &[PREFIX_REDEEM_REQUEST_INDEXER, request.owner.as_bytes()],
+    )
+    .remove(&request.id.to_be_bytes())
+}
+
+/// ## Description
+/// Returns a list of redeem requests ordered by ID under the provided criterions.
+///
+/// ## Params
+/// - **storage** is a reference to an object implementing trait [`Storage`].
+///
+/// - **owner** is an object of type [`Option<Addr>`] which filters the requests
+///     by their owner.
+///
+/// - **start_after** is an object of type [`Option<u64>`] which is a filter for the request ID.
+///
+/// - **limit** is an object of type [`Option<u32>`] which limits the number of requests in the query result.
+pub fn read_redeem_requests(
+    storage: &dyn Storage,
+    owner: Option<Addr>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<RedeemRequest>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = calc_range_start(start_after);
+
+    if let Some(owner) = owner {
+        let indexer: ReadonlyBucket<bool> =
+            ReadonlyBucket::multilevel(storage, &[PREFIX_REDEEM_REQUEST_INDEXER, owner.as_bytes()]);
+        indexer
+            .range(start.as_deref(), None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (k, _) = item?;
+                bucket_read(storage, PREFIX_REDEEM_REQUEST).load(&k)
+            })
+            .collect()
+    } else {
+        let requests: ReadonlyBucket<RedeemRequest> =
+            ReadonlyBucket::new(storage, PREFIX_REDEEM_REQUEST);
+        requests
+            .range(start.as_deref(), None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (_, v) = item?;
+                Ok(v)
+            })
+            .collect()
+    }
+}
+
+/// ## Description
+/// Set the first key after the provided key, by appending a byte.
+fn calc_range_start(start_after: Option<u64>) -> Option<Vec<u8>> {
+    start_after.map(|id| {
+        let mut v = id.to_be_bytes().to_vec();
+        v.push(1);
+        v
+    })
+}
+
+/// ## Description
+/// A storage layered over a read-only storage. Writes are kept in memory and
+/// shadow the underlying storage, so queries can replay state changes of an
+/// execution without persisting them.
+pub struct OverlayStorage<'a> {
+    base: &'a dyn Storage,
+    /// Key -> `Some(value)` when written, `None` when removed
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a> OverlayStorage<'a> {
+    pub fn new(base: &'a dyn Storage) -> Self {
+        OverlayStorage {
+            base,
+            writes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Storage for OverlayStorage<'_> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.writes.get(key) {
+            Some(value) => value.clone(),
+            None => self.base.get(key),
+        }
+    }
+
+    #[allow(clippy::unnecessary_map_or)]
+    fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Pair> + 'b> {
+        // Merge the in-memory writes into the underlying range
+        let mut merged: BTreeMap<Vec<u8>, Vec<u8>> =
+            self.base.range(start, end, Order::Ascending).collect();
+        for (key, value) in self.writes.iter() {
+            let in_range = start.map_or(true, |s| key.as_slice() >= s)
+                && end.map_or(true, |e| key.as_slice() < e);
+            if !in_range {
+                continue;
+            }
+            match value {
+                Some(value) => merged.insert(key.clone(), value.clone()),
+                None => merged.remove(key),
+            };
+        }
+        match order {
+            Order::Ascending => Box::new(merged.into_iter()),
+            Order::Descending => Box::new(merged.into_iter().rev()),
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.writes.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.writes.insert(key.to_vec(), None);
+    }
+}
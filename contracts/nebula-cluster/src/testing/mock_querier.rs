@@ -8,9 +8,15 @@ use cw20::BalanceResponse as Cw20BalanceResponse;
 use cw20::{Cw20QueryMsg, TokenInfoResponse};
 use nebula_protocol::{
     cluster::{InstantiateMsg, QueryMsg as ClusterQueryMsg},
-    cluster_factory::ConfigResponse as FactoryConfigResponse,
+    cluster_factory::{
+        ClusterFeesResponse, ConfigResponse as FactoryConfigResponse, QueryMsg as FactoryQueryMsg,
+    },
     oracle::{PriceResponse, QueryMsg as OracleQueryMsg},
-    penalty::{PenaltyCreateResponse, PenaltyRedeemResponse, QueryMsg as PenaltyQueryMsg},
+    penalty::{
+        PenaltyCreateResponse, PenaltyRedeemResponse, PenaltySwapResponse,
+        QueryMsg as PenaltyQueryMsg,
+    },
+    target_provider::{QueryMsg as TargetProviderQueryMsg, TargetWeightsResponse},
 };
 use std::collections::HashMap;
 use std::str::FromStr;
@@ -86,6 +92,14 @@ pub mod consts {
         h("penalty")
     }
 
+    pub fn cluster_fees(cluster_contract: String) -> ClusterFeesResponse {
+        ClusterFeesResponse {
+            cluster_contract,
+            create_fee_rate: "0.01".to_string(),
+            redeem_fee_rate: "0.01".to_string(),
+        }
+    }
+
     pub fn factory_config() -> FactoryConfigResponse {
         FactoryConfigResponse {
             owner: h("gov"),
@@ -152,6 +166,7 @@ pub struct WasmMockQuerier {
     pub balance_querier: BalanceQuerier, // native balances
     pub oracle_querier: OracleQuerier,   // token registered prices
     pub penalty_querier: PenaltyQuerier, // penalty querier
+    pub target_provider_querier: TargetProviderQuerier, // provided target weights
 }
 
 impl Querier for WasmMockQuerier {
@@ -294,6 +309,7 @@ impl WasmMockQuerier {
                                     create_asset_amounts: _,
                                     asset_prices: _,
                                     target_weights: _,
+                                    cluster_contract: _,
                                 }) => {
                                     let response = consts::mint_response();
                                     SystemResult::Ok(ContractResult::from(to_binary(&response)))
@@ -306,6 +322,7 @@ impl WasmMockQuerier {
                                     asset_prices: _,
                                     target_weights: _,
                                     redeem_asset_amounts: _,
+                                    cluster_contract: _,
                                 }) => {
                                     let response = PenaltyRedeemResponse {
                                         redeem_assets: vec![
@@ -321,9 +338,41 @@ impl WasmMockQuerier {
                                     };
                                     SystemResult::Ok(ContractResult::from(to_binary(&response)))
                                 }
-                                _ => {
-                                    panic!("QueryMsg type not implemented");
+                                Ok(PenaltyQueryMsg::PenaltyQuerySwap {
+                                    block_height: _,
+                                    inventory: _,
+                                    offer_asset_amounts: _,
+                                    ask_asset_amounts,
+                                    asset_prices: _,
+                                    target_weights: _,
+                                    cluster_contract: _,
+                                }) => {
+                                    let response = PenaltySwapResponse {
+                                        ask_assets: ask_asset_amounts,
+                                        penalty: Uint128::new(1234),
+                                        attributes: vec![attr("penalty", "1234")],
+                                    };
+                                    SystemResult::Ok(ContractResult::from(to_binary(&response)))
                                 }
+                                _ => match from_binary(&msg) {
+                                    Ok(FactoryQueryMsg::ClusterFees { cluster_contract }) => {
+                                        let response = consts::cluster_fees(cluster_contract);
+                                        SystemResult::Ok(ContractResult::from(to_binary(&response)))
+                                    }
+                                    _ => match from_binary(&msg) {
+                                        Ok(TargetProviderQueryMsg::TargetWeights {}) => {
+                                            let response = TargetWeightsResponse {
+                                                target: self.target_provider_querier.target.clone(),
+                                            };
+                                            SystemResult::Ok(ContractResult::from(to_binary(
+                                                &response,
+                                            )))
+                                        }
+                                        _ => {
+                                            panic!("QueryMsg type not implemented");
+                                        }
+                                    },
+                                },
                             },
                         },
                     },
@@ -394,6 +443,11 @@ pub struct OracleQuerier {
     pub assets: HashMap<String, Decimal>,
 }
 
+#[derive(Default)]
+pub struct TargetProviderQuerier {
+    pub target: Vec<Asset>,
+}
+
 impl WasmMockQuerier {
     pub fn new(base: MockQuerier<TerraQueryWrapper>) -> Self {
         WasmMockQuerier {
@@ -402,6 +456,7 @@ impl WasmMockQuerier {
             oracle_querier: OracleQuerier::default(),
             balance_querier: BalanceQuerier::default(),
             penalty_querier: PenaltyQuerier::default(),
+            target_provider_querier: TargetProviderQuerier::default(),
         }
     }
 
@@ -482,6 +537,11 @@ impl WasmMockQuerier {
         self.penalty_querier.create_tokens = create_tokens;
         self
     }
+
+    pub fn set_provider_target(&mut self, target: Vec<Asset>) -> &mut Self {
+        self.target_provider_querier.target = target;
+        self
+    }
 }
 
 /// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
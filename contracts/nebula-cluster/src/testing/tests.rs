@@ -8,12 +8,16 @@ use astroport::asset::{Asset, AssetInfo};
 use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::*;
 use cw2::{get_contract_version, ContractVersion};
-use cw20::Cw20ExecuteMsg;
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use nebula_protocol::cluster::{
-    ClusterConfig, ClusterInfoResponse, ConfigResponse, InstantiateMsg, MigrateMsg,
+    AccruedFeeResponse, AssetDrift, ClusterCaps, ClusterConfig, ClusterInfoResponse,
+    ConfigResponse, InstantiateMsg, MigrateMsg, PriceFreshnessPolicy, ReconcilePolicy,
+    StalePriceFallback, TargetMode, TargetProvider,
 };
 use nebula_protocol::cluster::{
-    ClusterStateResponse, ExecuteMsg, QueryMsg as ClusterQueryMsg, TargetResponse,
+    ClusterStateResponse, Cw20HookMsg, ExecuteMsg, InventoryDriftResponse,
+    QueryMsg as ClusterQueryMsg, RedeemRequestsResponse, ReferralEarningsResponse,
+    SimulateCreateResponse, SimulateRedeemResponse, SimulateSwapResponse, TargetResponse,
 };
 use nebula_protocol::penalty::ExecuteMsg as PenaltyExecuteMsg;
 use pretty_assertions::assert_eq;
@@ -98,6 +102,19 @@ fn proper_initialization() {
             penalty: Addr::unchecked("penalty"),
             factory: Addr::unchecked("factory"),
             active: true,
+            price_freshness: PriceFreshnessPolicy::default(),
+            management_fee_rate: Decimal::zero(),
+            caps: ClusterCaps::default(),
+            reconcile_policy: ReconcilePolicy::default(),
+            guardian: None,
+            create_paused: false,
+            custom_redeem_paused: false,
+            swap_fee_rate: Decimal::zero(),
+            target_mode: TargetMode::Units,
+            target_provider: None,
+            redeem_bounty_rate: Decimal::zero(),
+            native_decimals: vec![],
+            referral_fee_rate: Decimal::zero(),
         },
         config.config,
     );
@@ -225,6 +242,26 @@ fn update_config() {
                 amount: Uint128::new(20),
             },
         ]),
+        price_freshness: Some(PriceFreshnessPolicy {
+            max_price_age: 120,
+            asset_max_price_ages: vec![(
+                AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+                30,
+            )],
+            stale_fallback: StalePriceFallback::Reject,
+        }),
+        management_fee_rate: Some(Decimal::percent(2)),
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
     };
     let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
     assert_eq!(res, ContractError::Unauthorized {});
@@ -244,7 +281,29 @@ fn update_config() {
             pricing_oracle: Addr::unchecked("oracle0001"),
             target_oracle: Addr::unchecked("owner"),
             penalty: Addr::unchecked("penalty0001"),
-            active: true
+            active: true,
+            price_freshness: PriceFreshnessPolicy {
+                max_price_age: 120,
+                asset_max_price_ages: vec![(
+                    AssetInfo::NativeToken {
+                        denom: "uluna".to_string(),
+                    },
+                    30,
+                )],
+                stale_fallback: StalePriceFallback::Reject,
+            },
+            management_fee_rate: Decimal::percent(2),
+            caps: ClusterCaps::default(),
+            reconcile_policy: ReconcilePolicy::default(),
+            guardian: None,
+            create_paused: false,
+            custom_redeem_paused: false,
+            swap_fee_rate: Decimal::zero(),
+            target_mode: TargetMode::Units,
+            target_provider: None,
+            redeem_bounty_rate: Decimal::zero(),
+            native_decimals: vec![],
+            referral_fee_rate: Decimal::zero(),
         }
     )
 }
@@ -300,6 +359,7 @@ fn initialize_cluster() {
             amount: Uint128::new(1_000_000u128),
         }],
         min_tokens: Some(Uint128::new(1_000_000)),
+        referrer: None,
     };
     let info = mock_info("addr0000", &[coin(1_000_000u128, "uluna")]);
     let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
@@ -328,6 +388,7 @@ fn initialize_cluster() {
             },
         ],
         min_tokens: Some(Uint128::new(1_000_000)),
+        referrer: None,
     };
     let info = mock_info(
         "addr0000",
@@ -360,6 +421,7 @@ fn initialize_cluster() {
     let msg = ExecuteMsg::RebalanceCreate {
         asset_amounts: asset_amounts.clone(),
         min_tokens: None,
+        referrer: None,
     };
     let info = mock_info(
         "addr0000",
@@ -372,6 +434,7 @@ fn initialize_cluster() {
     let msg = ExecuteMsg::RebalanceCreate {
         asset_amounts,
         min_tokens: Some(Uint128::new(1_000_000)),
+        referrer: None,
     };
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
     assert_eq!(
@@ -420,6 +483,7 @@ fn mint() {
             amount: Uint128::new(1_000_000u128),
         }],
         min_tokens: None,
+        referrer: None,
     };
     let info = mock_info(addr, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
@@ -447,6 +511,7 @@ fn mint() {
     let mint_msg = ExecuteMsg::RebalanceCreate {
         asset_amounts: asset_amounts.clone(),
         min_tokens: None,
+        referrer: None,
     };
 
     // unsupported assets sent along with the tx
@@ -589,6 +654,7 @@ fn mint() {
     assert_eq!(
         ClusterStateResponse {
             outstanding_balance_tokens: Uint128::from(1_000_000_000u128),
+            accrued_fee: Uint128::zero(),
             prices: vec![
                 "135.18".to_string(),
                 "1780.03".to_string(),
@@ -603,6 +669,7 @@ fn mint() {
                 Uint128::new(50_090_272),
                 Uint128::new(42_000_000),
             ],
+            asset_decimals: vec![6, 6, 6, 6, 6],
             penalty: "penalty".to_string(),
             cluster_token: "cluster".to_string(),
             target: vec![
@@ -637,8 +704,14 @@ fn mint() {
                     amount: Uint128::new(5,),
                 },
             ],
+            final_target: consts::target_assets_stage(),
             cluster_contract_address: "cosmos2contract".to_string(),
-            active: true
+            active: true,
+            price_ages: vec![0, 0, 0, 0, 0],
+            supply_headroom: None,
+            inventory_headroom: vec![None, None, None, None, None],
+            create_paused: false,
+            custom_redeem_paused: false,
         },
         response
     );
@@ -667,6 +740,7 @@ fn burn() {
     let mint_msg = ExecuteMsg::RebalanceCreate {
         asset_amounts: asset_amounts.clone(),
         min_tokens: None,
+        referrer: None,
     };
 
     let addr = "addr0000";
@@ -713,6 +787,7 @@ fn burn() {
     let msg = ExecuteMsg::RebalanceRedeem {
         max_tokens: Uint128::new(1_000),
         asset_amounts: asset_amounts.clone(),
+        referrer: None,
     };
     let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
     assert_eq!(
@@ -724,6 +799,7 @@ fn burn() {
     let msg = ExecuteMsg::RebalanceRedeem {
         max_tokens: Uint128::new(20_000_000),
         asset_amounts,
+        referrer: None,
     };
     let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
 
@@ -836,6 +912,394 @@ fn burn() {
     );
 }
 
+#[test]
+fn redeem_via_send() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_token_balance(consts::cluster_token(), "addr0000", 20_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    // mint first to have inventory assets to redeem
+    deps.querier.set_mint_amount(Uint128::from(1_000_000u128));
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    let _res = execute(deps.as_mut(), mock_env(), info, mint_msg).unwrap();
+
+    let asset_amounts = Some(
+        consts::target_assets_stage()
+            .into_iter()
+            .zip([20u128, 0, 20, 20, 20])
+            .map(|(asset, amount)| Asset {
+                info: asset.info,
+                amount: Uint128::new(amount),
+            })
+            .collect::<Vec<_>>(),
+    );
+    let receive_msg = |min_amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::new(2_000),
+            msg: to_binary(&Cw20HookMsg::Redeem {
+                asset_amounts: asset_amounts.clone(),
+                min_assets: Some(vec![Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: Addr::unchecked("mAAPL"),
+                    },
+                    amount: Uint128::new(min_amount),
+                }]),
+                recipient: Some("addr0001".to_string()),
+            })
+            .unwrap(),
+        })
+    };
+
+    // only the cluster token can be sent
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, receive_msg(99)).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // redeemed assets are below min_assets
+    let info = mock_info(consts::cluster_token().as_str(), &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), receive_msg(100)).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::BelowMinAssets("mAAPL".to_string(), Uint128::new(99), Uint128::new(100))
+    );
+
+    // successful redeem to the recipient with refund to the sender
+    let res = execute(deps.as_mut(), mock_env(), info, receive_msg(99)).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "receive:burn"),
+            attr("sender", "addr0000"),
+            attr("burn_amount", "1234"),
+            attr("token_cost", "1247"),
+            attr("kept_as_fee", "13"),
+            attr("asset_amounts", "[20, 0, 20, 20, 20]"),
+            attr("redeem_totals", "[99, 0, 97, 96, 95]"),
+            attr("recipient", "addr0001"),
+            attr("refund_amount", "753"),
+            attr("penalty", "1234")
+        ]
+    );
+    assert_eq!(
+        res.messages[..7].to_vec(),
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: h("mAAPL"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::new(99u128)
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: h("mMSFT"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::new(97u128)
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: h("mNFLX"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::new(96u128)
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0001".to_string(),
+                amount: coins(95u128, "uluna")
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: consts::cluster_token(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: h("collector"),
+                    amount: Uint128::new(13u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: consts::cluster_token(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::new(1234u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: consts::cluster_token(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::new(753u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+}
+
+#[test]
+fn simulate_create() {
+    let (mut deps, _) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier.set_oracle_prices(vec![
+        ("mAAPL", Decimal::from_str("135.18").unwrap()),
+        ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+        ("mMSFT", Decimal::from_str("222.42").unwrap()),
+        ("mNFLX", Decimal::from_str("540.82").unwrap()),
+    ]);
+
+    // invalid assets
+    let msg = ClusterQueryMsg::SimulateCreate {
+        asset_amounts: vec![Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("invalidtoken0001"),
+            },
+            amount: Uint128::new(1_000_000u128),
+        }],
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    assert_eq!(res, StdError::generic_err("Invalid assets"));
+
+    // assets are provided in a different order from the target
+    let mut asset_amounts = consts::asset_amounts();
+    asset_amounts.reverse();
+    let msg = ClusterQueryMsg::SimulateCreate { asset_amounts };
+    let res = q!(deps.as_ref(), SimulateCreateResponse, mock_env(), msg);
+    assert_eq!(
+        res,
+        SimulateCreateResponse {
+            create_tokens: Uint128::new(98),
+            fee_amt: Uint128::new(1),
+            penalty: Uint128::new(1234),
+            create_asset_amounts: consts::asset_amounts(),
+            attributes: vec![attr("penalty", "1234")],
+        }
+    );
+
+    // simulated result matches the actual create
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let info = mock_info("addr0000", &[coin(42_000_000, "uluna")]);
+    let res = execute(deps.as_mut(), mock_env(), info, mint_msg).unwrap();
+    assert_eq!(res.attributes[2], attr("mint_to_sender", "98"));
+    assert_eq!(res.attributes[4], attr("fee_amt", "1"));
+}
+
+#[test]
+fn simulate_redeem() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_token_balance(consts::cluster_token(), "addr0000", 20_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    let asset_amounts = Some(vec![
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+            amount: Uint128::new(20),
+        },
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("mAAPL"),
+            },
+            amount: Uint128::new(20),
+        },
+    ]);
+
+    // input max_tokens is too low
+    let msg = ClusterQueryMsg::SimulateRedeem {
+        max_tokens: Uint128::new(1_000),
+        asset_amounts: asset_amounts.clone(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(
+            ContractError::AboveMaxTokens(Uint128::new(1247), Uint128::new(1000)).to_string()
+        )
+    );
+
+    let msg = ClusterQueryMsg::SimulateRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: asset_amounts.clone(),
+    };
+    let res = q!(deps.as_ref(), SimulateRedeemResponse, mock_env(), msg);
+    let redeem_assets = consts::target_assets_stage()
+        .into_iter()
+        .zip([99u128, 0, 97, 96, 95])
+        .map(|(asset, amount)| Asset {
+            info: asset.info,
+            amount: Uint128::new(amount),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        res,
+        SimulateRedeemResponse {
+            token_cost: Uint128::new(1247),
+            burn_amount: Uint128::new(1234),
+            fee_amt: Uint128::new(13),
+            penalty: Uint128::new(1234),
+            redeem_assets,
+            attributes: vec![attr("penalty", "1234")],
+        }
+    );
+
+    // custom redeem is not allowed on a decommissioned cluster
+    let info = mock_info(consts::factory().as_str(), &[]);
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Decommission {}).unwrap();
+    let msg = ClusterQueryMsg::SimulateRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts,
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Cannot call non pro-rata redeem on a decommissioned cluster")
+    );
+}
+
+#[test]
+fn price_freshness_policy() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    // prices are 61 seconds old
+    let mut env = mock_env();
+    env.block.time = Timestamp::from_seconds(1_571_797_480u64);
+
+    // cluster state reports the price ages without enforcing freshness
+    let res = q!(
+        deps.as_ref(),
+        ClusterStateResponse,
+        env.clone(),
+        ClusterQueryMsg::ClusterState {}
+    );
+    assert_eq!(res.price_ages, vec![61, 61, 61, 61, 61]);
+
+    // default policy rejects create, but allows pro-rata redeem
+    let create_msg = ClusterQueryMsg::SimulateCreate {
+        asset_amounts: consts::asset_amounts(),
+    };
+    let res = query(deps.as_ref(), env.clone(), create_msg.clone()).unwrap_err();
+    assert_eq!(res, StdError::generic_err("oracle prices are stale"));
+
+    let pro_rata_msg = ClusterQueryMsg::SimulateRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: None,
+    };
+    query(deps.as_ref(), env.clone(), pro_rata_msg.clone()).unwrap();
+
+    let update_policy = |policy: PriceFreshnessPolicy| ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: Some(policy),
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+
+    // zero max price age is invalid
+    let info = mock_info("owner", &[]);
+    let msg = update_policy(PriceFreshnessPolicy {
+        max_price_age: 0,
+        ..PriceFreshnessPolicy::default()
+    });
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Max price age must be greater than zero".to_string())
+    );
+
+    // longer max price age allows create
+    let msg = update_policy(PriceFreshnessPolicy {
+        max_price_age: 120,
+        ..PriceFreshnessPolicy::default()
+    });
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    query(deps.as_ref(), env.clone(), create_msg.clone()).unwrap();
+
+    // per-asset max price age overrides the default one
+    let msg = update_policy(PriceFreshnessPolicy {
+        max_price_age: 120,
+        asset_max_price_ages: vec![(
+            AssetInfo::Token {
+                contract_addr: Addr::unchecked("mGOOG"),
+            },
+            60,
+        )],
+        stale_fallback: StalePriceFallback::Reject,
+    });
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let res = query(deps.as_ref(), env.clone(), create_msg).unwrap_err();
+    assert_eq!(res, StdError::generic_err("oracle prices are stale"));
+
+    // reject fallback also blocks pro-rata redeem
+    let res = query(deps.as_ref(), env.clone(), pro_rata_msg.clone()).unwrap_err();
+    assert_eq!(res, StdError::generic_err("oracle prices are stale"));
+
+    // decommissioned clusters can always redeem pro-rata
+    let info = mock_info(consts::factory().as_str(), &[]);
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Decommission {}).unwrap();
+    query(deps.as_ref(), env, pro_rata_msg).unwrap();
+}
+
 #[test]
 fn update_target() {
     let new_target: Vec<Asset> = vec![
@@ -896,6 +1360,7 @@ fn update_target() {
 
     let msg = ExecuteMsg::UpdateTarget {
         target: new_target.clone(),
+        duration: None,
     };
     let info = mock_info(consts::owner().as_str(), &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
@@ -920,6 +1385,7 @@ fn update_target() {
     let mint_msg = ExecuteMsg::RebalanceCreate {
         asset_amounts: asset_amounts.clone(),
         min_tokens: None,
+        referrer: None,
     };
 
     let addr = "addr0000";
@@ -935,12 +1401,16 @@ fn update_target() {
             },
             amount: Uint128::new(20),
         }],
+        duration: None,
     };
     let info = mock_info(consts::owner().as_str(), &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
     assert_eq!(res, ContractError::InvalidAssets {});
 
-    let msg = ExecuteMsg::UpdateTarget { target: new_target };
+    let msg = ExecuteMsg::UpdateTarget {
+        target: new_target,
+        duration: None,
+    };
 
     // unauthorized update
     let info = mock_info("imposter0001", &[]);
@@ -976,6 +1446,7 @@ fn update_target() {
             amount: Uint128::new(1_000_000u128),
         }],
         min_tokens: None,
+        referrer: None,
     };
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
     assert_eq!(
@@ -987,31 +1458,2057 @@ fn update_target() {
 }
 
 #[test]
-fn decommission_cluster() {
+fn update_target_with_transition() {
     let (mut deps, _init_res) = mock_init();
-    deps = mock_querier_setup(deps);
 
     deps.querier
         .set_token_supply(consts::cluster_token(), 100_000_000)
         .set_token_balance(consts::cluster_token(), "addr0000", 20_000_000);
 
-    let config = read_config(&deps.storage).unwrap();
-    assert_eq!(config.active, true);
-
-    // mint first to have inventory assets to redeem
-    let asset_amounts = consts::asset_amounts();
-
-    deps.querier.set_mint_amount(Uint128::from(1_000_000u128));
-
-    let mint_msg = ExecuteMsg::RebalanceCreate {
-        asset_amounts: asset_amounts.clone(),
-        min_tokens: None,
-    };
-
-    let addr = "addr0000";
-    let info = mock_info(addr, &[coin(42_000_000u128, "uluna")]);
-    let env = mock_env();
-    let _res = execute(deps.as_mut(), env.clone(), info, mint_msg).unwrap();
+    let new_target = vec![
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("mAAPL"),
+            },
+            amount: Uint128::new(1),
+        },
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("mGOOG"),
+            },
+            amount: Uint128::new(1),
+        },
+    ];
+    let to_target = |weights: [u128; 5]| {
+        ["mAAPL", "mGOOG", "mMSFT", "mNFLX"]
+            .iter()
+            .map(|addr| AssetInfo::Token {
+                contract_addr: Addr::unchecked(*addr),
+            })
+            .chain(std::iter::once(AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            }))
+            .zip(weights)
+            .map(|(info, amount)| Asset {
+                info,
+                amount: Uint128::new(amount),
+            })
+            .collect::<Vec<_>>()
+    };
+    let env_at = |elapsed: u64| {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(elapsed);
+        env
+    };
+
+    // weights too large to be interpolated
+    let msg = ExecuteMsg::UpdateTarget {
+        target: vec![Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("mAAPL"),
+            },
+            amount: Uint128::new(u128::MAX / 2),
+        }],
+        duration: Some(100),
+    };
+    let info = mock_info(consts::owner().as_str(), &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Target transition weights are too large".to_string())
+    );
+
+    // weights whose total overflows
+    let msg = ExecuteMsg::UpdateTarget {
+        target: to_target([u128::MAX / 2 + 1, u128::MAX / 2 + 1, 0, 0, 0]),
+        duration: Some(100),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Target transition weights are too large".to_string())
+    );
+
+    // removed assets are kept with zero final weight during the transition
+    let msg = ExecuteMsg::UpdateTarget {
+        target: new_target,
+        duration: Some(100),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.attributes[3],
+        attr("updated_assets", "[mAAPL, mGOOG, mMSFT, mNFLX, uluna]")
+    );
+
+    // weights are rescaled to a common total, then linearly interpolated
+    let res = q!(
+        deps.as_ref(),
+        TargetResponse,
+        env_at(0),
+        ClusterQueryMsg::Target {}
+    );
+    assert_eq!(res.target, to_target([20, 20, 20, 15, 5]));
+    assert_eq!(res.final_target, to_target([1, 1, 0, 0, 0]));
+
+    let res = q!(
+        deps.as_ref(),
+        TargetResponse,
+        env_at(50),
+        ClusterQueryMsg::Target {}
+    );
+    assert_eq!(res.target, to_target([30, 30, 10, 7, 2]));
+    assert_eq!(res.final_target, to_target([1, 1, 0, 0, 0]));
+
+    let res = q!(
+        deps.as_ref(),
+        TargetResponse,
+        env_at(100),
+        ClusterQueryMsg::Target {}
+    );
+    assert_eq!(res.target, to_target([1, 1, 0, 0, 0]));
+
+    // a new transition starts from the current effective weights,
+    // rescaled from a total of 79 to 79 * 80
+    let msg = ExecuteMsg::UpdateTarget {
+        target: to_target([0, 0, 0, 0, 80]),
+        duration: Some(100),
+    };
+    execute(deps.as_mut(), env_at(50), info.clone(), msg).unwrap();
+    let res = q!(
+        deps.as_ref(),
+        TargetResponse,
+        env_at(50),
+        ClusterQueryMsg::Target {}
+    );
+    assert_eq!(res.target, to_target([2400, 2400, 800, 560, 160]));
+    let res = q!(
+        deps.as_ref(),
+        TargetResponse,
+        env_at(100),
+        ClusterQueryMsg::Target {}
+    );
+    assert_eq!(res.target, to_target([1200, 1200, 400, 280, 3240]));
+
+    // update without duration is applied immediately
+    let msg = ExecuteMsg::UpdateTarget {
+        target: consts::target_assets_stage(),
+        duration: None,
+    };
+    execute(deps.as_mut(), env_at(100), info, msg).unwrap();
+    let res = q!(
+        deps.as_ref(),
+        TargetResponse,
+        env_at(100),
+        ClusterQueryMsg::Target {}
+    );
+    assert_eq!(res.target, consts::target_assets_stage());
+    assert_eq!(res.final_target, consts::target_assets_stage());
+}
+
+#[test]
+fn value_weight_target_mode() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier.set_oracle_prices(vec![
+        ("mAAPL", Decimal::from_str("135.18").unwrap()),
+        ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+        ("mMSFT", Decimal::from_str("222.42").unwrap()),
+        ("mNFLX", Decimal::from_str("540.82").unwrap()),
+    ]);
+
+    let value_weights = consts::target_assets_stage()
+        .into_iter()
+        .zip([2000u128, 2000, 2000, 3000, 1000])
+        .map(|(asset, amount)| Asset {
+            info: asset.info,
+            amount: Uint128::new(amount),
+        })
+        .collect::<Vec<_>>();
+    let update_config = |target: Option<Vec<Asset>>| ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: Some(TargetMode::ValueWeights),
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    let info = mock_info("owner", &[]);
+
+    // the stored unit weights cannot be reinterpreted as value weights
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), update_config(None)).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Changing the target mode requires a new target".to_string())
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        update_config(Some(consts::target_assets_stage())),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Value weights must sum to 10000 basis points".to_string())
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        update_config(Some(value_weights.clone())),
+    )
+    .unwrap();
+
+    // value weights are converted to unit weights at the current prices
+    let unit_weights = consts::target_assets_stage()
+        .into_iter()
+        .zip([
+            14_795_088_030_773u128,
+            1_123_576_568_934,
+            8_991_997_122_560,
+            5_547_132_132_687,
+            16_000_000_000_000,
+        ])
+        .map(|(asset, amount)| Asset {
+            info: asset.info,
+            amount: Uint128::new(amount),
+        })
+        .collect::<Vec<_>>();
+    let res = q!(
+        deps.as_ref(),
+        TargetResponse,
+        mock_env(),
+        ClusterQueryMsg::Target {}
+    );
+    assert_eq!(
+        res,
+        TargetResponse {
+            target: unit_weights.clone(),
+            final_target: unit_weights.clone(),
+            value_weights: Some(value_weights.clone()),
+            final_value_weights: Some(value_weights.clone()),
+        }
+    );
+
+    let res = q!(
+        deps.as_ref(),
+        ClusterStateResponse,
+        mock_env(),
+        ClusterQueryMsg::ClusterState {}
+    );
+    assert_eq!(res.target, unit_weights);
+    assert_eq!(res.final_target, unit_weights);
+
+    // the target oracle also provides value weights
+    let msg = ExecuteMsg::UpdateTarget {
+        target: consts::target_assets_stage(),
+        duration: None,
+    };
+    let info = mock_info(consts::target_oracle().as_str(), &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Value weights must sum to 10000 basis points".to_string())
+    );
+}
+
+#[test]
+fn target_provider() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    let weights = |amounts: [u128; 5]| {
+        consts::target_assets_stage()
+            .into_iter()
+            .zip(amounts)
+            .map(|(asset, amount)| Asset {
+                info: asset.info,
+                amount: Uint128::new(amount),
+            })
+            .collect::<Vec<_>>()
+    };
+    let update_config = |refresh_interval: u64| ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: Some(TargetProvider {
+            address: Addr::unchecked("provider"),
+            refresh_interval,
+        }),
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    let info = mock_info("owner", &[]);
+
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), update_config(0)).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic(
+            "Target provider refresh interval must be greater than zero".to_string()
+        )
+    );
+    execute(deps.as_mut(), mock_env(), info.clone(), update_config(30)).unwrap();
+
+    let query_target =
+        |deps: Deps, env: Env| q!(deps, TargetResponse, env, ClusterQueryMsg::Target {});
+
+    // simulations pull the target as the executions do, without persisting it
+    let initial_target = query_target(deps.as_ref(), mock_env()).target;
+    let mut invalid_target = weights([10, 20, 30, 25, 15]);
+    invalid_target.push(invalid_target[0].clone());
+    deps.querier.set_provider_target(invalid_target);
+    let simulate_msg = ClusterQueryMsg::SimulateCreate {
+        asset_amounts: consts::asset_amounts(),
+    };
+    let res = query(deps.as_ref(), mock_env(), simulate_msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(ContractError::InvalidAssets {}.to_string())
+    );
+
+    deps.querier
+        .set_provider_target(weights([10, 20, 30, 25, 15]));
+    q!(
+        deps.as_ref(),
+        SimulateCreateResponse,
+        mock_env(),
+        simulate_msg
+    );
+    assert_eq!(
+        query_target(deps.as_ref(), mock_env()).target,
+        initial_target
+    );
+
+    // the target is pulled from the provider on create
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let mint_info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mint_info.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_target(deps.as_ref(), mock_env()).target,
+        weights([10, 20, 30, 25, 15])
+    );
+
+    // the pulled target is cached within the refresh interval
+    deps.querier
+        .set_provider_target(weights([20, 20, 20, 20, 20]));
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(29);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mint_info.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_target(deps.as_ref(), env.clone()).target,
+        weights([10, 20, 30, 25, 15])
+    );
+
+    // pro-rata redeems never pull the target
+    env.block.time = env.block.time.plus_seconds(1);
+    let redeem_msg = ExecuteMsg::RebalanceRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: None,
+        referrer: None,
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0000", &[]),
+        redeem_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        query_target(deps.as_ref(), env.clone()).target,
+        weights([10, 20, 30, 25, 15])
+    );
+
+    // the target is pulled again once the refresh interval has passed
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mint_info.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_target(deps.as_ref(), env.clone()).target,
+        weights([20, 20, 20, 20, 20])
+    );
+
+    // a timed transition set by the owner is not cancelled by the provider
+    execute(deps.as_mut(), env.clone(), info, update_config(5)).unwrap();
+    let msg = ExecuteMsg::UpdateTarget {
+        target: weights([10, 20, 30, 25, 15]),
+        duration: Some(20),
+    };
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+    deps.querier
+        .set_provider_target(weights([30, 10, 20, 20, 20]));
+    env.block.time = env.block.time.plus_seconds(10);
+    let transition_target = query_target(deps.as_ref(), env.clone()).target;
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mint_info.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_target(deps.as_ref(), env.clone()).target,
+        transition_target
+    );
+
+    // the provider is pulled again once the transition has ended
+    env.block.time = env.block.time.plus_seconds(10);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mint_info.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_target(deps.as_ref(), env.clone()).target,
+        weights([30, 10, 20, 20, 20])
+    );
+
+    // only the owner can remove the target provider
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(consts::target_oracle().as_str(), &[]),
+        ExecuteMsg::ClearTargetProvider {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::ClearTargetProvider {},
+    )
+    .unwrap();
+
+    // the targets pushed by the target oracle then persist
+    let msg = ExecuteMsg::UpdateTarget {
+        target: weights([15, 25, 30, 20, 10]),
+        duration: None,
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(consts::target_oracle().as_str(), &[]),
+        msg,
+    )
+    .unwrap();
+    env.block.time = env.block.time.plus_seconds(10);
+    execute(deps.as_mut(), env.clone(), mint_info, mint_msg).unwrap();
+    assert_eq!(
+        query_target(deps.as_ref(), env).target,
+        weights([15, 25, 30, 20, 10])
+    );
+}
+
+#[test]
+fn redeem_queue() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    // mint first to have inventory assets to redeem
+    deps.querier.set_mint_amount(Uint128::from(1_000_000u128));
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    execute(deps.as_mut(), mock_env(), info, mint_msg).unwrap();
+
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: Some(Decimal::permille(5)),
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+    let asset_amounts = consts::target_assets_stage()
+        .into_iter()
+        .zip([20u128, 0, 20, 20, 20])
+        .map(|(asset, amount)| Asset {
+            info: asset.info,
+            amount: Uint128::new(amount),
+        })
+        .collect::<Vec<_>>();
+    let queue_msg = |sender: &str, amount: u128, asset_amounts: Vec<Asset>| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::QueueRedeem {
+                asset_amounts,
+                min_assets: None,
+            })
+            .unwrap(),
+        })
+    };
+    let token_info = mock_info(consts::cluster_token().as_str(), &[]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        token_info.clone(),
+        queue_msg(
+            "addr0000",
+            2_000,
+            vec![Asset {
+                info: AssetInfo::Token {
+                    contract_addr: Addr::unchecked("mTSLA"),
+                },
+                amount: Uint128::new(20),
+            }],
+        ),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Asset mTSLA is not in the cluster".to_string())
+    );
+
+    // the minimum assets are validated when queueing
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        token_info.clone(),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::new(2_000),
+            msg: to_binary(&Cw20HookMsg::QueueRedeem {
+                asset_amounts: asset_amounts.clone(),
+                min_assets: Some(vec![Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: Addr::unchecked("mTSLA"),
+                    },
+                    amount: Uint128::new(1),
+                }]),
+            })
+            .unwrap(),
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Asset mTSLA is not in the cluster".to_string())
+    );
+
+    // queued redeems are custom redeems, unavailable while custom redeem is paused
+    let pause_msg = |paused: bool| ExecuteMsg::SetPause {
+        create_paused: None,
+        custom_redeem_paused: Some(paused),
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        pause_msg(true),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        token_info.clone(),
+        queue_msg("addr0000", 2_000, asset_amounts.clone()),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::CustomRedeemPaused {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        pause_msg(false),
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        token_info.clone(),
+        queue_msg("addr0000", 2_000, asset_amounts.clone()),
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "queue_redeem"),
+            attr("request_id", "0"),
+            attr("owner", "addr0000"),
+            attr("token_amount", "2000"),
+            attr(
+                "asset_amounts",
+                "[20mAAPL, 0mGOOG, 20mMSFT, 20mNFLX, 20uluna]"
+            ),
+        ]
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        token_info.clone(),
+        queue_msg("addr0000", 2_000, asset_amounts.clone()),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        token_info,
+        queue_msg("addr0001", 2_000, asset_amounts.clone()),
+    )
+    .unwrap();
+
+    // pending requests are paginated by owner
+    let request_ids =
+        |deps: Deps, owner: Option<&str>, start_after: Option<u64>, limit: Option<u32>| {
+            q!(
+                deps,
+                RedeemRequestsResponse,
+                mock_env(),
+                ClusterQueryMsg::RedeemRequests {
+                    owner: owner.map(|x| x.to_string()),
+                    start_after,
+                    limit,
+                }
+            )
+            .requests
+            .into_iter()
+            .map(|x| x.request_id)
+            .collect::<Vec<_>>()
+        };
+    assert_eq!(request_ids(deps.as_ref(), None, None, None), vec![0, 1, 2]);
+    assert_eq!(
+        request_ids(deps.as_ref(), Some("addr0000"), None, None),
+        vec![0, 1]
+    );
+    assert_eq!(
+        request_ids(deps.as_ref(), Some("addr0000"), Some(0), Some(1)),
+        vec![1]
+    );
+    assert_eq!(
+        request_ids(deps.as_ref(), Some("addr0001"), None, None),
+        vec![2]
+    );
+
+    // only the owner can cancel a request
+    let msg = ExecuteMsg::CancelRedeemRequest { request_id: 1 };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: consts::cluster_token(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0000".to_string(),
+                amount: Uint128::new(2_000),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    assert_eq!(
+        request_ids(deps.as_ref(), Some("addr0000"), None, None),
+        vec![0]
+    );
+
+    // requests cannot execute with stale prices
+    let msg = ExecuteMsg::ExecuteRedeemRequest { request_id: 0 };
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(61);
+    let res = execute(deps.as_mut(), env, mock_info("keeper", &[]), msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Std(StdError::generic_err("oracle prices are stale"))
+    );
+
+    // a keeper executing the request earns the bounty from the escrowed tokens
+    let res = execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "receive:burn"),
+            attr("sender", "addr0000"),
+            attr("burn_amount", "1234"),
+            attr("token_cost", "1247"),
+            attr("kept_as_fee", "13"),
+            attr("asset_amounts", "[20, 0, 20, 20, 20]"),
+            attr("redeem_totals", "[99, 0, 97, 96, 95]"),
+            attr("recipient", "addr0000"),
+            attr("refund_amount", "743"),
+            attr("penalty", "1234"),
+            attr("request_id", "0"),
+            attr("keeper", "keeper"),
+            attr("bounty", "10"),
+        ]
+    );
+    assert_eq!(
+        res.messages.last().unwrap(),
+        &SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: consts::cluster_token(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "keeper".to_string(),
+                amount: Uint128::new(10),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))
+    );
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        ClusterQueryMsg::RedeemRequest { request_id: 0 },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Redeem request 0 does not exist")
+    );
+
+    // owners executing their own request pay no bounty
+    let msg = ExecuteMsg::ExecuteRedeemRequest { request_id: 2 };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap();
+    assert_eq!(res.attributes.last().unwrap(), &attr("bounty", "0"));
+    assert_eq!(
+        request_ids(deps.as_ref(), None, None, None),
+        Vec::<u64>::new()
+    );
+}
+
+#[test]
+fn asset_decimals() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_token(
+            "wETH",
+            token_data(
+                "Wrapped Ether",
+                "wETH",
+                18,
+                1_000_000_000_000_000_000_000,
+                vec![(MOCK_CONTRACT_ADDR, 0)],
+            ),
+        )
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+            ("wETH", Decimal::from_str("2000").unwrap()),
+        ]);
+
+    // mint first to have inventory assets to swap
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let mint_info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    execute(deps.as_mut(), mock_env(), mint_info, mint_msg).unwrap();
+
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: Some(vec![("uluna".to_string(), 8)]),
+        referral_fee_rate: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+    let mut target = consts::target_assets_stage();
+    target.push(Asset {
+        info: AssetInfo::Token {
+            contract_addr: Addr::unchecked("wETH"),
+        },
+        amount: Uint128::new(10),
+    });
+    let msg = ExecuteMsg::UpdateTarget {
+        target,
+        duration: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+    // decimals are recorded from the token info, or the config for natives
+    let res = q!(
+        deps.as_ref(),
+        ClusterStateResponse,
+        mock_env(),
+        ClusterQueryMsg::ClusterState {}
+    );
+    assert_eq!(res.asset_decimals, vec![6, 6, 6, 6, 8, 18]);
+
+    // 1 wETH is normalised before being priced against mMSFT
+    let res = q!(
+        deps.as_ref(),
+        SimulateSwapResponse,
+        mock_env(),
+        ClusterQueryMsg::SimulateSwap {
+            offer_asset: Asset {
+                info: AssetInfo::Token {
+                    contract_addr: Addr::unchecked("wETH"),
+                },
+                amount: Uint128::new(1_000_000_000_000_000_000),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("mMSFT"),
+            },
+        }
+    );
+    assert_eq!(res.return_amount, Uint128::new(8_991_997));
+
+    // assets with too many decimals cannot be added to the target
+    deps.querier.set_token(
+        "wBIG",
+        token_data(
+            "Big Decimals",
+            "wBIG",
+            24,
+            1_000_000_000_000_000_000_000_000,
+            vec![(MOCK_CONTRACT_ADDR, 0)],
+        ),
+    );
+    let mut target = consts::target_assets_stage();
+    target.push(Asset {
+        info: AssetInfo::Token {
+            contract_addr: Addr::unchecked("wBIG"),
+        },
+        amount: Uint128::new(10),
+    });
+    let msg = ExecuteMsg::UpdateTarget {
+        target,
+        duration: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Asset decimals cannot exceed 18".to_string())
+    );
+}
+
+#[test]
+fn referral_fee() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_token_balance(consts::cluster_token(), "addr0000", 20_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+    deps.querier.set_mint_amount(Uint128::from(1_000_000u128));
+
+    let update_msg = |referral_fee_rate: Decimal| ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: Some(referral_fee_rate),
+    };
+
+    // the referral fee rate is a share of the protocol fee
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        update_msg(Decimal::percent(150)),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Referral fee rate must not exceed 1".to_string())
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        update_msg(Decimal::one()),
+    )
+    .unwrap();
+
+    // cannot refer yourself
+    let info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    let mint_msg = |referrer: &str| ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: Some(referrer.to_string()),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        mint_msg("addr0000"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cannot refer yourself".to_string())
+    );
+
+    // the whole protocol fee is minted to the referrer
+    let res = execute(deps.as_mut(), mock_env(), info, mint_msg("referrer0000")).unwrap();
+    assert_eq!(
+        res.attributes[3..].to_vec(),
+        vec![
+            attr("penalty", "1234"),
+            attr("fee_amt", "1"),
+            attr("referrer", "referrer0000"),
+            attr("referral_fee", "1"),
+        ]
+    );
+    assert!(!res.messages.iter().any(|x| matches!(
+        &x.msg,
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. })
+            if *msg == to_binary(&Cw20ExecuteMsg::Mint {
+                amount: Uint128::new(1),
+                recipient: h("collector"),
+            })
+            .unwrap()
+    )));
+    assert_eq!(
+        res.messages[res.messages.len() - 2],
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: consts::cluster_token(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                amount: Uint128::new(1),
+                recipient: h("referrer0000"),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))
+    );
+
+    // half of the redeem fee of 13 is sent to the referrer
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        update_msg(Decimal::percent(50)),
+    )
+    .unwrap();
+    let msg = ExecuteMsg::RebalanceRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: None,
+        referrer: Some(h("referrer0000")),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    let fee_msg = |recipient: &str, amount: u128| {
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: consts::cluster_token(),
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: h("addr0000"),
+                amount: Uint128::new(amount),
+                recipient: h(recipient),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))
+    };
+    assert!(res.messages.contains(&fee_msg("collector", 7)));
+    assert!(res.messages.contains(&fee_msg("referrer0000", 6)));
+    assert!(res.attributes.contains(&attr("referral_fee", "6")));
+
+    // cumulative earnings of the referrer
+    let res = q!(
+        deps.as_ref(),
+        ReferralEarningsResponse,
+        mock_env(),
+        ClusterQueryMsg::ReferralEarnings {
+            referrer: h("referrer0000"),
+        }
+    );
+    assert_eq!(
+        res,
+        ReferralEarningsResponse {
+            referrer: h("referrer0000"),
+            earnings: Uint128::new(7),
+        }
+    );
+}
+
+#[test]
+fn management_fee() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    let update_fee_rate = |rate: Decimal| ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: Some(rate),
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    let env_at = |elapsed: u64| {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(elapsed);
+        env
+    };
+
+    // invalid management fee rate
+    let info = mock_info("owner", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        update_fee_rate(Decimal::one()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Management fee rate must be less than 1".to_string())
+    );
+
+    // nothing accrued at the previous zero rate
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        update_fee_rate(Decimal::percent(10)),
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
+
+    // half a year later, 5% of the supply is accrued
+    let res = q!(
+        deps.as_ref(),
+        AccruedFeeResponse,
+        env_at(15_768_000),
+        ClusterQueryMsg::AccruedFee {}
+    );
+    assert_eq!(
+        res,
+        AccruedFeeResponse {
+            management_fee_rate: Decimal::percent(10),
+            last_accrual: mock_env().block.time.seconds(),
+            accrued_fee: Uint128::new(5_000_000),
+        }
+    );
+
+    // accrued fee is included in the supply used for NAV calculations
+    let res = q!(
+        deps.as_ref(),
+        ClusterStateResponse,
+        env_at(15_768_000),
+        ClusterQueryMsg::ClusterState {}
+    );
+    assert_eq!(res.outstanding_balance_tokens, Uint128::new(105_000_000));
+    assert_eq!(res.accrued_fee, Uint128::new(5_000_000));
+
+    // anyone can mint the accrued fee to the collector
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env_at(15_768_000),
+        info,
+        ExecuteMsg::AccrueFee {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![attr("action", "accrue_fee"), attr("fee_amount", "5000000")]
+    );
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: consts::cluster_token(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                amount: Uint128::new(5_000_000),
+                recipient: h("collector"),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    let res = q!(
+        deps.as_ref(),
+        AccruedFeeResponse,
+        env_at(15_768_000),
+        ClusterQueryMsg::AccruedFee {}
+    );
+    assert_eq!(res.accrued_fee, Uint128::zero());
+    assert_eq!(res.last_accrual, env_at(15_768_000).block.time.seconds());
+
+    // decommission mints the remaining accrued fee, then stops the accrual
+    let info = mock_info(consts::factory().as_str(), &[]);
+    let res = execute(
+        deps.as_mut(),
+        env_at(31_536_000),
+        info,
+        ExecuteMsg::Decommission {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: consts::cluster_token(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                amount: Uint128::new(5_000_000),
+                recipient: h("collector"),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    let res = q!(
+        deps.as_ref(),
+        AccruedFeeResponse,
+        env_at(63_072_000),
+        ClusterQueryMsg::AccruedFee {}
+    );
+    assert_eq!(res.accrued_fee, Uint128::zero());
+}
+
+#[test]
+fn supply_and_inventory_caps() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier.set_oracle_prices(vec![
+        ("mAAPL", Decimal::from_str("135.18").unwrap()),
+        ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+        ("mMSFT", Decimal::from_str("222.42").unwrap()),
+        ("mNFLX", Decimal::from_str("540.82").unwrap()),
+    ]);
+
+    let update_caps = |caps: ClusterCaps| ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: Some(caps),
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    let inventory_cap = |asset_info: AssetInfo, amount: u128| Asset {
+        info: asset_info,
+        amount: Uint128::new(amount),
+    };
+    let maapl = AssetInfo::Token {
+        contract_addr: Addr::unchecked("mAAPL"),
+    };
+    let uluna = AssetInfo::NativeToken {
+        denom: "uluna".to_string(),
+    };
+
+    // duplicate inventory caps
+    let info = mock_info("owner", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        update_caps(ClusterCaps {
+            supply_cap: None,
+            inventory_caps: vec![
+                inventory_cap(maapl.clone(), 1),
+                inventory_cap(maapl.clone(), 2),
+            ],
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Duplicate inventory caps for an asset".to_string())
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        update_caps(ClusterCaps {
+            supply_cap: Some(Uint128::new(1_000_000_099)),
+            inventory_caps: vec![
+                inventory_cap(maapl.clone(), 100_000_000),
+                inventory_cap(uluna.clone(), 50_000_000),
+            ],
+        }),
+    )
+    .unwrap();
+
+    let res = q!(
+        deps.as_ref(),
+        ClusterStateResponse,
+        mock_env(),
+        ClusterQueryMsg::ClusterState {}
+    );
+    assert_eq!(res.supply_headroom, Some(Uint128::new(99)));
+    assert_eq!(
+        res.inventory_headroom,
+        vec![
+            Some(Uint128::new(100_000_000)),
+            None,
+            None,
+            None,
+            Some(Uint128::new(50_000_000)),
+        ]
+    );
+
+    // deposit above the inventory cap
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let info_mint = mock_info("addr0000", &[coin(42_000_000, "uluna")]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info_mint.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CapExceeded(
+            "mAAPL".to_string(),
+            Uint128::new(125_000_000),
+            Uint128::new(100_000_000)
+        )
+    );
+    let simulate_msg = ClusterQueryMsg::SimulateCreate {
+        asset_amounts: consts::asset_amounts(),
+    };
+    let res = query(deps.as_ref(), mock_env(), simulate_msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(
+            ContractError::CapExceeded(
+                "mAAPL".to_string(),
+                Uint128::new(125_000_000),
+                Uint128::new(100_000_000)
+            )
+            .to_string()
+        )
+    );
+
+    // deposit within the caps, minting exactly up to the supply cap
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        update_caps(ClusterCaps {
+            supply_cap: Some(Uint128::new(1_000_000_099)),
+            inventory_caps: vec![
+                inventory_cap(maapl.clone(), 200_000_000),
+                inventory_cap(uluna.clone(), 50_000_000),
+            ],
+        }),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info_mint.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap();
+
+    let res = q!(
+        deps.as_ref(),
+        ClusterStateResponse,
+        mock_env(),
+        ClusterQueryMsg::ClusterState {}
+    );
+    assert_eq!(
+        res.inventory_headroom,
+        vec![
+            Some(Uint128::new(75_000_000)),
+            None,
+            None,
+            None,
+            Some(Uint128::new(8_000_000)),
+        ]
+    );
+
+    // mint above the supply cap, including the protocol fee
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        update_caps(ClusterCaps {
+            supply_cap: Some(Uint128::new(1_000_000_098)),
+            inventory_caps: vec![],
+        }),
+    )
+    .unwrap();
+    let res = query(deps.as_ref(), mock_env(), simulate_msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(
+            ContractError::CapExceeded(
+                "cluster token supply".to_string(),
+                Uint128::new(1_000_000_099),
+                Uint128::new(1_000_000_098)
+            )
+            .to_string()
+        )
+    );
+    let res = execute(deps.as_mut(), mock_env(), info_mint, mint_msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CapExceeded(
+            "cluster token supply".to_string(),
+            Uint128::new(1_000_000_099),
+            Uint128::new(1_000_000_098)
+        )
+    );
+}
+
+#[test]
+fn reconcile_inventory() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    let update_policy = |policy: ReconcilePolicy| ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: Some(policy),
+        guardian: None,
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    let drift = |info: AssetInfo, tracked: u128, actual: u128| AssetDrift {
+        info,
+        tracked_balance: Uint128::new(tracked),
+        actual_balance: Uint128::new(actual),
+        surplus: Uint128::new(actual.saturating_sub(tracked)),
+        deficit: Uint128::new(tracked.saturating_sub(actual)),
+    };
+    let token = |addr: &str| AssetInfo::Token {
+        contract_addr: Addr::unchecked(addr),
+    };
+    let uluna = AssetInfo::NativeToken {
+        denom: "uluna".to_string(),
+    };
+
+    // every asset holds 1_000_000 while the tracked inventory differs
+    store_asset_balance(&mut deps.storage, "mMSFT", &Uint128::new(1_000_000)).unwrap();
+    store_asset_balance(&mut deps.storage, "mNFLX", &Uint128::new(2_000_000)).unwrap();
+
+    let res = q!(
+        deps.as_ref(),
+        InventoryDriftResponse,
+        mock_env(),
+        ClusterQueryMsg::InventoryDrift {}
+    );
+    assert_eq!(
+        res.drift,
+        vec![
+            drift(token("mAAPL"), 0, 1_000_000),
+            drift(token("mGOOG"), 0, 1_000_000),
+            drift(token("mMSFT"), 1_000_000, 1_000_000),
+            drift(token("mNFLX"), 2_000_000, 1_000_000),
+            drift(uluna.clone(), 0, 1_000_000),
+        ]
+    );
+
+    // only the owner can change the policy
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        update_policy(ReconcilePolicy::SweepToCollector),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // refund surpluses and write off deficits
+    let owner = mock_info("owner", &[]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        owner.clone(),
+        update_policy(ReconcilePolicy::Refund {
+            recipient: Addr::unchecked("addr0001"),
+        }),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ReconcileInventory {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: h("mAAPL"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: h("addr0001"),
+                    amount: Uint128::new(1_000_000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: h("mGOOG"),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: h("addr0001"),
+                    amount: Uint128::new(1_000_000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: h("addr0001"),
+                amount: coins(1_000_000, "uluna"),
+            })),
+        ]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "reconcile_inventory"),
+            attr("assets", "[mAAPL, mGOOG, mMSFT, mNFLX, uluna]"),
+            attr("surpluses", "[1000000, 1000000, 0, 0, 1000000]"),
+            attr("deficits", "[0, 0, 0, 1000000, 0]"),
+        ]
+    );
+    assert_eq!(
+        read_asset_balance(&deps.storage, "mNFLX").unwrap(),
+        Uint128::new(1_000_000)
+    );
+
+    // absorb surpluses into the inventory
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        owner.clone(),
+        update_policy(ReconcilePolicy::Absorb),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ReconcileInventory {},
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
+    let res = q!(
+        deps.as_ref(),
+        InventoryDriftResponse,
+        mock_env(),
+        ClusterQueryMsg::InventoryDrift {}
+    );
+    assert!(res
+        .drift
+        .iter()
+        .all(|x| x.surplus.is_zero() && x.deficit.is_zero()));
+
+    // sweep new surpluses to the collector
+    deps.querier
+        .set_token_balance("mAAPL", MOCK_CONTRACT_ADDR, 1_500_000);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        owner,
+        update_policy(ReconcilePolicy::SweepToCollector),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ReconcileInventory {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: h("mAAPL"),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: h("collector"),
+                amount: Uint128::new(500_000),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+}
+
+#[test]
+fn pause_switches() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_token_balance(consts::cluster_token(), "addr0000", 20_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    // mint first to have inventory assets to redeem
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let mint_info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mint_info.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap();
+
+    let pause_msg = ExecuteMsg::SetPause {
+        create_paused: Some(true),
+        custom_redeem_paused: Some(true),
+    };
+
+    // no guardian is set yet
+    let guardian_info = mock_info("guardian", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        guardian_info.clone(),
+        pause_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: Some(h("guardian")),
+        swap_fee_rate: None,
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+    let res = execute(deps.as_mut(), mock_env(), guardian_info.clone(), pause_msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "set_pause"),
+            attr("create_paused", "true"),
+            attr("custom_redeem_paused", "true"),
+        ]
+    );
+
+    let res = q!(
+        deps.as_ref(),
+        ClusterStateResponse,
+        mock_env(),
+        ClusterQueryMsg::ClusterState {}
+    );
+    assert_eq!(res.create_paused, true);
+    assert_eq!(res.custom_redeem_paused, true);
+
+    // create and custom redeem are rejected
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mint_info.clone(),
+        mint_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::CreatePaused {});
+
+    let info = mock_info("addr0000", &[]);
+    let custom_redeem_msg = ExecuteMsg::RebalanceRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: Some(consts::asset_amounts()),
+        referrer: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), custom_redeem_msg).unwrap_err();
+    assert_eq!(res, ContractError::CustomRedeemPaused {});
+
+    // simulations are rejected the same way
+    let msg = ClusterQueryMsg::SimulateCreate {
+        asset_amounts: consts::asset_amounts(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(ContractError::CreatePaused {}.to_string())
+    );
+    let msg = ClusterQueryMsg::SimulateRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: Some(consts::asset_amounts()),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(ContractError::CustomRedeemPaused {}.to_string())
+    );
+    let msg = ClusterQueryMsg::SimulateSwap {
+        offer_asset: Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("mAAPL"),
+            },
+            amount: Uint128::new(1_000_000),
+        },
+        ask_asset_info: AssetInfo::Token {
+            contract_addr: Addr::unchecked("mMSFT"),
+        },
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(ContractError::CreatePaused {}.to_string())
+    );
+
+    // pro-rata redeem stays available
+    let msg = ClusterQueryMsg::SimulateRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: None,
+    };
+    q!(deps.as_ref(), SimulateRedeemResponse, mock_env(), msg);
+    let msg = ExecuteMsg::RebalanceRedeem {
+        max_tokens: Uint128::new(20_000_000),
+        asset_amounts: None,
+        referrer: None,
+    };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // unpause create only
+    let msg = ExecuteMsg::SetPause {
+        create_paused: Some(false),
+        custom_redeem_paused: None,
+    };
+    execute(deps.as_mut(), mock_env(), guardian_info, msg).unwrap();
+    execute(deps.as_mut(), mock_env(), mint_info, mint_msg).unwrap();
+
+    let config = read_config(&deps.storage).unwrap();
+    assert_eq!(config.create_paused, false);
+    assert_eq!(config.custom_redeem_paused, true);
+}
+
+#[test]
+fn swap() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_token_balance(consts::cluster_token(), "addr0000", 20_000_000)
+        .set_oracle_prices(vec![
+            ("mAAPL", Decimal::from_str("135.18").unwrap()),
+            ("mGOOG", Decimal::from_str("1780.03").unwrap()),
+            ("mMSFT", Decimal::from_str("222.42").unwrap()),
+            ("mNFLX", Decimal::from_str("540.82").unwrap()),
+        ]);
+
+    // mint first to have inventory assets to swap
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let mint_info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    execute(deps.as_mut(), mock_env(), mint_info, mint_msg).unwrap();
+
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        name: None,
+        description: None,
+        cluster_token: None,
+        pricing_oracle: None,
+        target_oracle: None,
+        penalty: None,
+        target: None,
+        price_freshness: None,
+        management_fee_rate: None,
+        caps: None,
+        reconcile_policy: None,
+        guardian: None,
+        swap_fee_rate: Some(Decimal::permille(3)),
+        target_mode: None,
+        target_provider: None,
+        redeem_bounty_rate: None,
+        native_decimals: None,
+        referral_fee_rate: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+    let offer_asset = Asset {
+        info: AssetInfo::Token {
+            contract_addr: Addr::unchecked("mAAPL"),
+        },
+        amount: Uint128::new(1_000_000),
+    };
+    let ask_asset_info = AssetInfo::Token {
+        contract_addr: Addr::unchecked("mMSFT"),
+    };
+
+    // cannot swap an asset for itself
+    let msg = ExecuteMsg::Swap {
+        offer_asset: offer_asset.clone(),
+        ask_asset_info: offer_asset.info.clone(),
+        min_return: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cannot swap an asset for itself".to_string())
+    );
+
+    // cannot ask for more than the cluster inventory
+    let msg = ExecuteMsg::Swap {
+        offer_asset: Asset {
+            info: offer_asset.info.clone(),
+            amount: Uint128::new(1_000_000_000),
+        },
+        ask_asset_info: ask_asset_info.clone(),
+        min_return: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cluster inventory of mMSFT cannot cover the swap".to_string())
+    );
+
+    let res = q!(
+        deps.as_ref(),
+        SimulateSwapResponse,
+        mock_env(),
+        ClusterQueryMsg::SimulateSwap {
+            offer_asset: offer_asset.clone(),
+            ask_asset_info: ask_asset_info.clone(),
+        }
+    );
+    assert_eq!(
+        res,
+        SimulateSwapResponse {
+            return_amount: Uint128::new(605_946),
+            fee_amt: Uint128::new(1_823),
+            penalty: Uint128::new(1234),
+            attributes: vec![attr("penalty", "1234")],
+        }
+    );
+
+    // return amount is below min_return
+    let msg = ExecuteMsg::Swap {
+        offer_asset: offer_asset.clone(),
+        ask_asset_info: ask_asset_info.clone(),
+        min_return: Some(Uint128::new(610_000)),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::BelowMinReturn(Uint128::new(605_946), Uint128::new(610_000))
+    );
+
+    let msg = ExecuteMsg::Swap {
+        offer_asset: offer_asset.clone(),
+        ask_asset_info: ask_asset_info.clone(),
+        min_return: Some(Uint128::new(600_000)),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "swap"),
+            attr("sender", "addr0000"),
+            attr("offer_asset", "1000000mAAPL"),
+            attr("ask_asset", "mMSFT"),
+            attr("return_amount", "605946"),
+            attr("fee_amt", "1823"),
+            attr("penalty", "1234"),
+        ]
+    );
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "mAAPL".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: "addr0000".to_string(),
+                    recipient: MOCK_CONTRACT_ADDR.to_string(),
+                    amount: Uint128::new(1_000_000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "mMSFT".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::new(605_946),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "mMSFT".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: h("collector"),
+                    amount: Uint128::new(1_823),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: consts::penalty(),
+                msg: to_binary(&PenaltyExecuteMsg::PenaltySwap {
+                    block_height: mock_env().block.height,
+                    inventory: vec![
+                        Uint128::new(125_000_000),
+                        Uint128::zero(),
+                        Uint128::new(149_000_000),
+                        Uint128::new(50_090_272),
+                        Uint128::new(42_000_000),
+                    ],
+                    offer_asset_amounts: vec![
+                        Uint128::new(1_000_000),
+                        Uint128::zero(),
+                        Uint128::zero(),
+                        Uint128::zero(),
+                        Uint128::zero(),
+                    ],
+                    ask_asset_amounts: vec![
+                        Uint128::zero(),
+                        Uint128::zero(),
+                        Uint128::new(607_769),
+                        Uint128::zero(),
+                        Uint128::zero(),
+                    ],
+                    asset_prices: vec![
+                        "135.18".to_string(),
+                        "1780.03".to_string(),
+                        "222.42".to_string(),
+                        "540.82".to_string(),
+                        "62.5".to_string(),
+                    ],
+                    target_weights: consts::target_assets_stage()
+                        .iter()
+                        .map(|x| x.amount)
+                        .collect(),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    // the inventory records the offered asset and the asked asset with the fee
+    assert_eq!(
+        read_asset_balance(deps.as_ref().storage, &"mAAPL".to_string()).unwrap(),
+        Uint128::new(126_000_000)
+    );
+    assert_eq!(
+        read_asset_balance(deps.as_ref().storage, &"mMSFT".to_string()).unwrap(),
+        Uint128::new(148_392_231)
+    );
+}
+
+#[test]
+fn decommission_cluster() {
+    let (mut deps, _init_res) = mock_init();
+    deps = mock_querier_setup(deps);
+
+    deps.querier
+        .set_token_supply(consts::cluster_token(), 100_000_000)
+        .set_token_balance(consts::cluster_token(), "addr0000", 20_000_000);
+
+    let config = read_config(&deps.storage).unwrap();
+    assert_eq!(config.active, true);
+
+    // mint first to have inventory assets to redeem
+    let asset_amounts = consts::asset_amounts();
+
+    deps.querier.set_mint_amount(Uint128::from(1_000_000u128));
+
+    let mint_msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: asset_amounts.clone(),
+        min_tokens: None,
+        referrer: None,
+    };
+
+    let addr = "addr0000";
+    let info = mock_info(addr, &[coin(42_000_000u128, "uluna")]);
+    let env = mock_env();
+    let _res = execute(deps.as_mut(), env.clone(), info, mint_msg).unwrap();
 
     let msg = ExecuteMsg::Decommission {};
 
@@ -1040,6 +3537,7 @@ fn decommission_cluster() {
     let msg = ExecuteMsg::RebalanceCreate {
         asset_amounts: asset_amounts.clone(),
         min_tokens: None,
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
@@ -1048,6 +3546,7 @@ fn decommission_cluster() {
     let msg = ExecuteMsg::RebalanceRedeem {
         max_tokens: Uint128::new(20_000_000),
         asset_amounts: Some(asset_amounts),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
@@ -1061,6 +3560,7 @@ fn decommission_cluster() {
     let msg = ExecuteMsg::RebalanceRedeem {
         max_tokens: Uint128::new(20_000_000),
         asset_amounts: None,
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -1085,10 +3585,39 @@ fn decommission_cluster() {
         },
         amount: Uint128::new(10),
     }];
-    let msg = ExecuteMsg::UpdateTarget { target: new_target };
+    let msg = ExecuteMsg::UpdateTarget {
+        target: new_target,
+        duration: None,
+    };
     let info = mock_info(consts::owner().as_str(), &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
     assert_eq!(res, ContractError::ClusterAlreadyDecommissioned {});
+
+    // recommission the cluster
+    let msg = ExecuteMsg::Recommission {};
+    let info = mock_info("owner0001", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info(consts::factory().as_str(), &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+    assert_eq!(res.attributes, vec![attr("action", "recommission_asset")]);
+    assert_eq!(res.messages, vec![]);
+
+    let config = read_config(&deps.storage).unwrap();
+    assert_eq!(config.active, true);
+
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::ClusterNotDecommissioned {});
+
+    // custom create is enabled again
+    let msg = ExecuteMsg::RebalanceCreate {
+        asset_amounts: consts::asset_amounts(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let info = mock_info("addr0000", &[coin(42_000_000u128, "uluna")]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 }
 
 #[test]
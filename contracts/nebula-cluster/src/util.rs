@@ -1,3 +1,18 @@
+use std::str::FromStr;
+
+use astroport::asset::{Asset, AssetInfo};
+use cluster_math::FPDecimal;
+use cosmwasm_std::{Decimal, StdError, StdResult, Uint128};
+
+/// Seconds in a year used to annualise the management fee
+pub const SECONDS_PER_YEAR: u64 = 31_536_000;
+
+/// Total of the value weights, in basis points, of a cluster in value weight target mode
+pub const VALUE_WEIGHT_TOTAL_BPS: u128 = 10_000;
+
+/// Unit weights derived from a single basis point of value at a price of 1
+pub const VALUE_WEIGHT_UNIT_SCALE: u128 = 1_000_000_000_000;
+
 /// ## Description
 /// Prints a vector in a pretty format.
 ///
@@ -10,3 +25,198 @@ where
     let str_vec = v.iter().map(|fp| fp.to_string()).collect::<Vec<String>>();
     format!("[{}]", str_vec.join(", "))
 }
+
+/// ## Description
+/// Returns the asset target weights at time `now` during a linear transition from
+/// `prev_target` to `final_target`, ordered by `final_target`. As weights are relative,
+/// both targets are first rescaled to a common total weight.
+/// Returns a [`StdError`] if the interpolated weights overflow.
+///
+/// ## Params
+/// - **prev_target** is a reference to an object of type [`Vec<Asset>`] which are the
+///     asset target weights at `start_time`.
+///
+/// - **final_target** is a reference to an object of type [`Vec<Asset>`] which are the
+///     asset target weights from `end_time`.
+///
+/// - **start_time** is an object of type [`u64`].
+///
+/// - **end_time** is an object of type [`u64`].
+///
+/// - **now** is an object of type [`u64`].
+pub fn interpolate_target(
+    prev_target: &[Asset],
+    final_target: &[Asset],
+    start_time: u64,
+    end_time: u64,
+    now: u64,
+) -> StdResult<Vec<Asset>> {
+    if now >= end_time {
+        return Ok(final_target.to_vec());
+    }
+
+    let duration = (end_time - start_time) as u128;
+    let elapsed = (now.max(start_time) - start_time) as u128;
+
+    final_target
+        .iter()
+        .map(|asset| {
+            let remaining = duration - elapsed;
+            let weight = interpolation_terms(prev_target, final_target, asset, remaining, elapsed)
+                .map(|terms| terms / duration)
+                .ok_or_else(|| StdError::generic_err("Target transition weights are too large"))?;
+            Ok(Asset {
+                info: asset.info.clone(),
+                amount: Uint128::new(weight),
+            })
+        })
+        .collect()
+}
+
+/// ## Description
+/// Returns whether the interpolated weights of a linear transition from `prev_target`
+/// to `final_target` over `duration` seconds can be computed without overflow at any
+/// time of the transition.
+///
+/// ## Params
+/// - **prev_target** is a reference to an object of type [`Vec<Asset>`] which are the
+///     asset target weights at the start of the transition.
+///
+/// - **final_target** is a reference to an object of type [`Vec<Asset>`] which are the
+///     asset target weights at the end of the transition.
+///
+/// - **duration** is an object of type [`u64`] which is the duration of the transition.
+pub fn transition_fits(prev_target: &[Asset], final_target: &[Asset], duration: u64) -> bool {
+    // Each of the summed terms is at most its value over the full duration, so
+    // bound both terms by the full duration at once
+    let duration = duration as u128;
+    final_target.iter().all(|asset| {
+        interpolation_terms(prev_target, final_target, asset, duration, duration).is_some()
+    })
+}
+
+/// ## Description
+/// Returns the summed interpolation terms of the weight of `asset`, `elapsed` seconds
+/// after the start and `remaining` seconds before the end of a transition, or `None` on
+/// overflow. Divided by the duration, they are the interpolated weight. Both targets are
+/// rescaled to the least common multiple of their total weights.
+///
+/// -- terms = prev_weight * prev_scale * remaining + weight * final_scale * elapsed
+fn interpolation_terms(
+    prev_target: &[Asset],
+    final_target: &[Asset],
+    asset: &Asset,
+    remaining: u128,
+    elapsed: u128,
+) -> Option<u128> {
+    let prev_total = prev_target
+        .iter()
+        .try_fold(0u128, |acc, a| acc.checked_add(a.amount.u128()))?;
+    let final_total = final_target
+        .iter()
+        .try_fold(0u128, |acc, a| acc.checked_add(a.amount.u128()))?;
+    if prev_total == 0 || final_total == 0 {
+        // Transitions from or to empty targets apply the final target at once
+        return asset
+            .amount
+            .u128()
+            .checked_mul(remaining.checked_add(elapsed)?);
+    }
+
+    let divisor = gcd(prev_total, final_total);
+    let prev_scale = final_total / divisor;
+    let final_scale = prev_total / divisor;
+
+    let prev_weight = prev_target
+        .iter()
+        .find(|a| a.info == asset.info)
+        .map(|a| a.amount.u128())
+        .unwrap_or(0);
+    prev_weight
+        .checked_mul(prev_scale)?
+        .checked_mul(remaining)?
+        .checked_add(
+            asset
+                .amount
+                .u128()
+                .checked_mul(final_scale)?
+                .checked_mul(elapsed)?,
+        )
+}
+
+/// ## Description
+/// Returns the greatest common divisor of two numbers.
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// ## Description
+/// Returns the management fee in cluster tokens accrued over `elapsed` seconds.
+///
+/// ## Params
+/// - **token_supply** is an object of type [`Uint128`] which is the cluster token supply.
+///
+/// - **management_fee_rate** is an object of type [`Decimal`] which is the annualised
+///     management fee rate.
+///
+/// - **elapsed** is an object of type [`u64`] which is the number of seconds since
+///     the last accrual.
+pub fn compute_accrued_fee(
+    token_supply: Uint128,
+    management_fee_rate: Decimal,
+    elapsed: u64,
+) -> Uint128 {
+    token_supply.multiply_ratio(elapsed, SECONDS_PER_YEAR) * management_fee_rate
+}
+
+/// ## Description
+/// Converts value weights in basis points to unit weights at the given asset prices,
+/// i.e. `unit_weight = value_weight * VALUE_WEIGHT_UNIT_SCALE / price`.
+///
+/// ## Params
+/// - **value_weights** is a reference to an object of type [`Vec<Asset>`] in which
+///     `amount` is the value weight of the asset in basis points.
+///
+/// - **asset_infos** is a reference to an object of type [`Vec<AssetInfo>`] which are
+///     the priced assets.
+///
+/// - **prices** is a reference to an object of type [`Vec<String>`] which are the
+///     prices of `asset_infos`.
+pub fn to_unit_weights(
+    value_weights: &[Asset],
+    asset_infos: &[AssetInfo],
+    prices: &[String],
+) -> StdResult<Vec<Asset>> {
+    value_weights
+        .iter()
+        .map(|asset| {
+            let price = asset_infos
+                .iter()
+                .zip(prices.iter())
+                .find(|(info, _)| **info == asset.info)
+                .map(|(_, price)| FPDecimal::from_str(price))
+                .transpose()?
+                .ok_or_else(|| {
+                    StdError::generic_err(format!("No price found for asset {}", asset.info))
+                })?;
+            if price <= FPDecimal::zero() {
+                return Err(StdError::generic_err(format!(
+                    "Cannot convert the value weight of asset {} at a zero price",
+                    asset.info
+                )));
+            }
+            let unit_weight: u128 = (FPDecimal::from(asset.amount.u128())
+                * FPDecimal::from(VALUE_WEIGHT_UNIT_SCALE)
+                / price)
+                .into();
+            Ok(Asset {
+                info: asset.info.clone(),
+                amount: Uint128::from(unit_weight),
+            })
+        })
+        .collect()
+}
@@ -3,7 +3,7 @@ use cosmwasm_std::{
     Response, StdResult, Uint128, WasmMsg, WasmQuery,
 };
 
-use crate::rebalancers::{assert_cluster_exists, get_cluster_state};
+use crate::rebalancers::{assert_cluster_exists, assert_create_not_paused, get_cluster_state};
 use crate::state::{read_config, record_contribution, Config};
 
 use nebula_protocol::incentives::{ExecuteMsg, PoolType};
@@ -88,6 +88,7 @@ pub fn arb_cluster_create(
 
     // Get the cluster token contract address
     let cluster_state = get_cluster_state(deps.as_ref(), &validated_cluster_contract)?;
+    assert_create_not_paused(&cluster_state)?;
     let cluster_token = deps
         .api
         .addr_validate(cluster_state.cluster_token.as_str())?;
@@ -124,6 +125,7 @@ pub fn arb_cluster_create(
             cluster_contract: validated_cluster_contract.clone(),
             asset_amounts: assets.to_vec(),
             min_tokens: None,
+            referrer: None,
         })?,
         funds: vec![],
     }));
@@ -276,6 +278,7 @@ pub fn arb_cluster_redeem(
             cluster_token,
             max_tokens: None,
             asset_amounts: None,
+            referrer: None,
         })?,
         funds: vec![],
     }));
@@ -10,6 +10,7 @@ use crate::arbitrageurs::{
     arb_cluster_create, arb_cluster_redeem, record_astroport_impact, send_all, swap_all,
 };
 use crate::error::ContractError;
+use crate::migration::{migrate_cluster, swap_into_basket};
 use crate::rebalancers::{
     create, internal_rewarded_create, internal_rewarded_redeem, record_rebalancer_rewards, redeem,
 };
@@ -18,6 +19,7 @@ use crate::state::{
     contributions_read, pool_info_read, read_config, read_current_n, read_from_contribution_bucket,
     read_from_pool_bucket, read_pending_rewards, store_config, store_current_n, Config,
 };
+use crate::zap::{assert_min_return, create_all, swap_all_to, zap_create, zap_redeem};
 use cw2::set_contract_version;
 use cw20::Cw20ReceiveMsg;
 use nebula_protocol::incentives::{
@@ -123,6 +125,7 @@ pub fn instantiate(
 ///             cluster_contract,
 ///             asset_amounts,
 ///             min_tokens,
+///             referrer,
 ///         }** Calls the actual create logic in a cluster contract used in both arbitraging and rebalancing.
 ///
 /// - **ExecuteMsg::_InternalRewardedRedeem {
@@ -131,8 +134,33 @@ pub fn instantiate(
 ///             cluster_token,
 ///             max_tokens,
 ///             asset_amounts,
+///             referrer,
 ///         }** Calls the actual redeem logic in a cluster contract used in both arbitraging and rebalancing.
 ///
+/// - **ExecuteMsg::_CreateAll {
+///             rebalancer,
+///             cluster_contract,
+///             asset_infos,
+///             min_tokens,
+///         }** Calls the actual create logic in a cluster contract with all balances of the specified assets.
+///
+/// - **ExecuteMsg::_SwapAllTo {
+///             asset_infos,
+///             ask_asset_info,
+///             max_spread,
+///         }** Swaps all specified assets into the ask asset.
+///
+/// - **ExecuteMsg::_AssertMinReturn {
+///             asset_info,
+///             min_return,
+///         }** Checks the contract holds at least the minimum amount of an asset.
+///
+/// - **ExecuteMsg::_SwapIntoBasket {
+///             cluster_contract,
+///             asset_infos,
+///             max_spread,
+///         }** Swaps all base denom into the specified assets of a cluster.
+///
 /// - **ExecuteMsg::ArbClusterCreate {
 ///             cluster_contract,
 ///             assets,
@@ -149,13 +177,39 @@ pub fn instantiate(
 ///             cluster_contract,
 ///             asset_amounts,
 ///             min_tokens,
+///             referrer,
 ///         }** Executes the create operation on a specific cluster.
 ///
 /// - **ExecuteMsg::IncentivesRedeem {
 ///             cluster_contract,
 ///             max_tokens,
 ///             asset_amounts,
+///             referrer,
 ///         }** Executes the redeem operation on a specific cluster.
+///
+/// - **ExecuteMsg::ZapCreate {
+///             cluster_contract,
+///             offer_asset,
+///             min_tokens,
+///             max_spread,
+///         }** Swaps a single asset into the cluster assets and executes the create operation.
+///
+/// - **ExecuteMsg::ZapRedeem {
+///             cluster_contract,
+///             max_tokens,
+///             penalty_optimal,
+///             ask_asset_info,
+///             min_output,
+///             max_spread,
+///         }** Executes the redeem operation and swaps all returned assets into a single asset.
+///
+/// - **ExecuteMsg::MigrateCluster {
+///             from_cluster,
+///             to_cluster,
+///             max_tokens,
+///             min_tokens,
+///             max_spread,
+///         }** Redeems cluster tokens of a cluster and creates cluster tokens of another cluster.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -217,6 +271,7 @@ pub fn execute(
             cluster_contract,
             asset_amounts,
             min_tokens,
+            referrer,
         } => internal_rewarded_create(
             deps,
             env,
@@ -225,6 +280,7 @@ pub fn execute(
             cluster_contract,
             &asset_amounts,
             min_tokens,
+            referrer,
         ),
         ExecuteMsg::_InternalRewardedRedeem {
             rebalancer,
@@ -232,6 +288,7 @@ pub fn execute(
             cluster_token,
             max_tokens,
             asset_amounts,
+            referrer,
         } => internal_rewarded_redeem(
             deps,
             env,
@@ -241,7 +298,36 @@ pub fn execute(
             cluster_token,
             max_tokens,
             asset_amounts,
+            referrer,
+        ),
+        ExecuteMsg::_CreateAll {
+            rebalancer,
+            cluster_contract,
+            asset_infos,
+            min_tokens,
+        } => create_all(
+            deps,
+            env,
+            info,
+            rebalancer,
+            cluster_contract,
+            &asset_infos,
+            min_tokens,
         ),
+        ExecuteMsg::_SwapAllTo {
+            asset_infos,
+            ask_asset_info,
+            max_spread,
+        } => swap_all_to(deps, env, info, &asset_infos, &ask_asset_info, max_spread),
+        ExecuteMsg::_AssertMinReturn {
+            asset_info,
+            min_return,
+        } => assert_min_return(deps, env, info, &asset_info, min_return),
+        ExecuteMsg::_SwapIntoBasket {
+            cluster_contract,
+            asset_infos,
+            max_spread,
+        } => swap_into_basket(deps, env, info, cluster_contract, &asset_infos, max_spread),
         ExecuteMsg::ArbClusterCreate {
             cluster_contract,
             assets,
@@ -256,6 +342,7 @@ pub fn execute(
             cluster_contract,
             asset_amounts,
             min_tokens,
+            referrer,
         } => create(
             deps,
             env,
@@ -263,12 +350,70 @@ pub fn execute(
             cluster_contract,
             &asset_amounts,
             min_tokens,
+            referrer,
         ),
         ExecuteMsg::IncentivesRedeem {
             cluster_contract,
             max_tokens,
             asset_amounts,
-        } => redeem(deps, env, info, cluster_contract, max_tokens, asset_amounts),
+            referrer,
+        } => redeem(
+            deps,
+            env,
+            info,
+            cluster_contract,
+            max_tokens,
+            asset_amounts,
+            referrer,
+        ),
+        ExecuteMsg::ZapCreate {
+            cluster_contract,
+            offer_asset,
+            min_tokens,
+            max_spread,
+        } => zap_create(
+            deps,
+            env,
+            info,
+            cluster_contract,
+            offer_asset,
+            min_tokens,
+            max_spread,
+        ),
+        ExecuteMsg::ZapRedeem {
+            cluster_contract,
+            max_tokens,
+            penalty_optimal,
+            ask_asset_info,
+            min_output,
+            max_spread,
+        } => zap_redeem(
+            deps,
+            env,
+            info,
+            cluster_contract,
+            max_tokens,
+            penalty_optimal,
+            ask_asset_info,
+            min_output,
+            max_spread,
+        ),
+        ExecuteMsg::MigrateCluster {
+            from_cluster,
+            to_cluster,
+            max_tokens,
+            min_tokens,
+            max_spread,
+        } => migrate_cluster(
+            deps,
+            env,
+            info,
+            from_cluster,
+            to_cluster,
+            max_tokens,
+            min_tokens,
+            max_spread,
+        ),
     }
 }
 
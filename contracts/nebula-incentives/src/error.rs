@@ -19,4 +19,10 @@ pub enum ContractError {
 
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("Create is paused on cluster {0}")]
+    CreatePaused(String),
+
+    #[error("Custom redeem is paused on cluster {0}")]
+    CustomRedeemPaused(String),
 }
@@ -4,7 +4,9 @@ pub mod rewards;
 pub mod state;
 
 mod arbitrageurs;
+mod migration;
 mod rebalancers;
+mod zap;
 
 #[cfg(test)]
 mod testing;
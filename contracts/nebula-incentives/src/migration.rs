@@ -0,0 +1,353 @@
+use cosmwasm_std::{
+    attr, to_binary, Addr, CosmosMsg, Decimal, DepsMut, Env, MessageInfo, Response, Uint128,
+    WasmMsg,
+};
+
+use crate::error::ContractError;
+use crate::rebalancers::{assert_cluster_exists, assert_create_not_paused, get_cluster_state};
+use crate::state::{read_config, Config};
+use crate::zap::{query_asset_balance, swap_msg};
+
+use astroport::asset::{Asset, AssetInfo};
+use astroport::querier::query_token_balance;
+use cluster_math::{
+    int_vec_to_fpdec, min_imbalance_allocation, normalize_amounts, str_vec_to_fpdec, FPDecimal,
+};
+use cw20::Cw20ExecuteMsg;
+use nebula_protocol::incentives::ExecuteMsg;
+use std::cmp::min;
+
+/// ## Description
+/// Migrates cluster tokens of one cluster into cluster tokens of another cluster.
+/// #### CT A -> Assets of A -> Assets of B -> CT B
+/// 1. Redeem the cluster tokens of `from_cluster` into the pro-rata basket
+/// 2. Swap the redeemed assets that `to_cluster` does not hold into the base denom
+/// 3. Swap the base denom into the assets of `to_cluster` that `from_cluster` does not hold
+/// 4. Mint cluster tokens of `to_cluster` from all received assets, carrying over the
+///     assets held by both clusters in kind
+/// 5. Send the minted cluster tokens and all unused assets to the sender
+///
+/// Assets held by both clusters never touch Astroport.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **from_cluster** is an object of type [`String`] which is the address of
+///     the cluster contract to migrate from.
+///
+/// - **to_cluster** is an object of type [`String`] which is the address of
+///     the cluster contract to migrate to.
+///
+/// - **max_tokens** is an object of type [`Uint128`] which is the maximum allowed
+///     amount of cluster tokens of `from_cluster` to be burned.
+///
+/// - **min_tokens** is an object of type [`Option<Uint128>`] which is the minimum required
+///     amount of cluster tokens of `to_cluster` minted from this migration.
+///
+/// - **max_spread** is an object of type [`Option<Decimal>`] which is the maximum spread
+///     allowed for each swap on Astroport.
+#[allow(clippy::too_many_arguments)]
+pub fn migrate_cluster(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    from_cluster: String,
+    to_cluster: String,
+    max_tokens: Uint128,
+    min_tokens: Option<Uint128>,
+    max_spread: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    // Validate address format
+    let validated_from_cluster = deps.api.addr_validate(from_cluster.as_str())?;
+    let validated_to_cluster = deps.api.addr_validate(to_cluster.as_str())?;
+    if validated_from_cluster == validated_to_cluster {
+        return Err(ContractError::Generic(
+            "Cannot migrate a cluster into itself".to_string(),
+        ));
+    }
+    // Check if the provided addresses are clusters
+    assert_cluster_exists(deps.as_ref(), &validated_from_cluster)?;
+    assert_cluster_exists(deps.as_ref(), &validated_to_cluster)?;
+
+    let contract = env.contract.address.clone();
+
+    let cfg: Config = read_config(deps.storage)?;
+    let base_info = AssetInfo::NativeToken {
+        denom: cfg.base_denom.clone(),
+    };
+
+    // Get the cluster states
+    let from_state = get_cluster_state(deps.as_ref(), &validated_from_cluster)?;
+    let to_state = get_cluster_state(deps.as_ref(), &validated_to_cluster)?;
+    assert_create_not_paused(&to_state)?;
+
+    let from_token = deps.api.addr_validate(from_state.cluster_token.as_str())?;
+    let to_token = deps.api.addr_validate(to_state.cluster_token.as_str())?;
+
+    // Set `max_tokens` to be the minimum between the provided `max_tokens` and the actual sender balance
+    let max_tokens = min(
+        max_tokens,
+        query_token_balance(&deps.querier, from_token.clone(), info.sender.clone())?,
+    );
+    if max_tokens.is_zero() {
+        return Err(ContractError::Generic(
+            "No cluster tokens to migrate".to_string(),
+        ));
+    }
+
+    let from_infos = from_state
+        .target
+        .iter()
+        .map(|x| x.info.clone())
+        .collect::<Vec<_>>();
+    // Assets with zero target weight cannot be used for minting
+    let to_infos = to_state
+        .target
+        .iter()
+        .filter(|x| !x.amount.is_zero())
+        .map(|x| x.info.clone())
+        .collect::<Vec<_>>();
+
+    // This contract holds the Nebula token rewards, which must never be swept to the sender
+    let nebula_token_info = AssetInfo::Token {
+        contract_addr: cfg.nebula_token.clone(),
+    };
+    if from_infos.contains(&nebula_token_info) || to_infos.contains(&nebula_token_info) {
+        return Err(ContractError::Generic(
+            "Cannot migrate a cluster holding the Nebula token".to_string(),
+        ));
+    }
+
+    // Assets only held by `from_cluster` are sold, and assets only held by `to_cluster`
+    // are bought. The base denom is never swapped, so it is not bought either.
+    let sell_infos = from_infos
+        .iter()
+        .filter(|x| !to_infos.contains(x))
+        .cloned()
+        .collect::<Vec<_>>();
+    let buy_infos = to_infos
+        .iter()
+        .filter(|x| !from_infos.contains(x) && **x != base_info)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut messages = vec![
+        // Transfer CT of `from_cluster` of `max_tokens` into this incentives contract
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: from_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: info.sender.to_string(),
+                recipient: contract.to_string(),
+                amount: max_tokens,
+            })?,
+            funds: vec![],
+        }),
+        // Perform the pro-rata redeem operation, returning the unused CT to the sender
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&ExecuteMsg::_InternalRewardedRedeem {
+                rebalancer: info.sender.clone(),
+                cluster_contract: validated_from_cluster,
+                cluster_token: from_token,
+                max_tokens: Some(max_tokens),
+                asset_amounts: None,
+                referrer: None,
+            })?,
+            funds: vec![],
+        }),
+    ];
+
+    // Sell the assets not held by `to_cluster` into the base denom
+    if !sell_infos.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&ExecuteMsg::_SwapAllTo {
+                asset_infos: sell_infos,
+                ask_asset_info: base_info.clone(),
+                max_spread,
+            })?,
+            funds: vec![],
+        }));
+    }
+
+    // Buy the assets not held by `from_cluster` with the base denom
+    if !buy_infos.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&ExecuteMsg::_SwapIntoBasket {
+                cluster_contract: validated_to_cluster.clone(),
+                asset_infos: buy_infos,
+                max_spread,
+            })?,
+            funds: vec![],
+        }));
+    }
+
+    // Send the minted CT, the unspent base denom, and any asset left to the sender
+    let mut send_infos = vec![
+        AssetInfo::Token {
+            contract_addr: to_token,
+        },
+        base_info.clone(),
+    ];
+    send_infos.extend(from_infos.into_iter().filter(|x| *x != base_info));
+
+    messages.extend(vec![
+        // Mint CT of `to_cluster` from all carried and bought assets
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&ExecuteMsg::_CreateAll {
+                rebalancer: info.sender.clone(),
+                cluster_contract: validated_to_cluster,
+                asset_infos: to_infos,
+                min_tokens,
+            })?,
+            funds: vec![],
+        }),
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&ExecuteMsg::_SendAll {
+                asset_infos: send_infos,
+                send_to: info.sender.clone(),
+            })?,
+            funds: vec![],
+        }),
+    ]);
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "migrate_cluster"),
+        attr("sender", info.sender.as_str()),
+        attr("from_cluster", from_cluster),
+        attr("to_cluster", to_cluster),
+        attr("max_tokens", max_tokens),
+    ]))
+}
+
+/// ## Description
+/// Swaps all base denom held by this contract into the specified assets of a cluster.
+/// The base denom is allocated to minimize the cluster imbalance after depositing
+/// all cluster assets held by this contract, but only the specified assets are bought.
+/// If the cluster holds the base denom, its share is kept unswapped.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **cluster_contract** is an object of type [`Addr`] which is the address of
+///     the cluster contract whose assets are bought.
+///
+/// - **asset_infos** is a reference to an array containing objects of type [`AssetInfo`]
+///     which is a list of cluster assets to be bought.
+///
+/// - **max_spread** is an object of type [`Option<Decimal>`] which is the maximum spread
+///     allowed for each swap on Astroport.
+///
+/// ## Executor
+/// Only this contract can execute this.
+pub fn swap_into_basket(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cluster_contract: Addr,
+    asset_infos: &[AssetInfo],
+    max_spread: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    // Permission check
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let cfg: Config = read_config(deps.storage)?;
+    let base_info = AssetInfo::NativeToken {
+        denom: cfg.base_denom.clone(),
+    };
+
+    let base_amount = query_asset_balance(&deps, &env, &base_info)?;
+    if base_amount.is_zero() {
+        return Ok(Response::new().add_attributes(vec![attr("action", "swap_into_basket")]));
+    }
+
+    // Get the cluster state
+    let cluster_state = get_cluster_state(deps.as_ref(), &cluster_contract)?;
+
+    // The assets held by this contract are deposited together with the bought assets.
+    // The base denom held is the capital to allocate, so it is not part of the deposit.
+    let mut inv = cluster_state.inv.clone();
+    for (idx, asset) in cluster_state.target.iter().enumerate() {
+        if asset.info != base_info {
+            inv[idx] += query_asset_balance(&deps, &env, &asset.info)?;
+        }
+    }
+    let target_weights = cluster_state
+        .target
+        .iter()
+        .map(|x| x.amount)
+        .collect::<Vec<_>>();
+
+    let i = int_vec_to_fpdec(&normalize_amounts(&inv, &cluster_state.asset_decimals)?);
+    let p = str_vec_to_fpdec(&cluster_state.prices)?;
+    let w = int_vec_to_fpdec(&target_weights);
+
+    // Compute the capital allocation into each asset minimizing the cluster imbalance
+    let value = FPDecimal::from(base_amount.u128());
+    let allocation = min_imbalance_allocation(&i, &p, &w, value);
+
+    // Only the specified assets and the base denom can receive capital
+    let eligible = cluster_state
+        .target
+        .iter()
+        .map(|x| asset_infos.contains(&x.info) || x.info == base_info)
+        .collect::<Vec<_>>();
+    let mut shares = allocation
+        .iter()
+        .zip(eligible.iter())
+        .map(|(a, e)| if *e { *a } else { FPDecimal::zero() })
+        .collect::<Vec<_>>();
+    // Fall back to the target allocation if the eligible assets have no shortfall
+    if shares.iter().all(|x| x.num.is_zero()) {
+        shares = w
+            .iter()
+            .zip(p.iter())
+            .zip(eligible.iter())
+            .map(|((w, p), e)| if *e { *w * *p } else { FPDecimal::zero() })
+            .collect();
+    }
+    let total_shares = shares.iter().fold(FPDecimal::zero(), |acc, x| acc + *x);
+
+    let mut messages = vec![];
+    if !total_shares.num.is_zero() {
+        for (asset, share) in cluster_state.target.iter().zip(shares.iter()) {
+            if asset.info == base_info || !asset_infos.contains(&asset.info) {
+                continue;
+            }
+
+            // -- swap_amount = base_amount * share / total_shares
+            let swap_amount: u128 = (value * *share / total_shares).into();
+            if swap_amount == 0 {
+                continue;
+            }
+            messages.push(swap_msg(
+                &deps,
+                &cfg,
+                Asset {
+                    info: base_info.clone(),
+                    amount: Uint128::new(swap_amount),
+                },
+                &asset.info,
+                max_spread,
+            )?);
+        }
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "swap_into_basket"),
+        attr("base_amount", base_amount),
+    ]))
+}
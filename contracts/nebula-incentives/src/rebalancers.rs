@@ -15,7 +15,7 @@ use astroport::asset::{Asset, AssetInfo};
 use astroport::querier::query_token_balance;
 
 use crate::error::ContractError;
-use cluster_math::{imbalance, int_vec_to_fpdec, str_vec_to_fpdec};
+use cluster_math::{imbalance, int_vec_to_fpdec, normalize_amounts, str_vec_to_fpdec};
 use nebula_protocol::cluster_factory::ClusterExistsResponse;
 use nebula_protocol::cluster_factory::QueryMsg::ClusterExists;
 use std::cmp::min;
@@ -63,6 +63,60 @@ pub fn assert_cluster_exists(deps: Deps, cluster: &Addr) -> Result<bool, Contrac
     }
 }
 
+/// ## Description
+/// Returns an error if the referrer is not a valid address or is the sender itself.
+/// The cluster only sees this contract as the sender, so it cannot check this.
+///
+/// ## Params
+/// - **deps** is a reference to an object of type [`DepsMut`].
+///
+/// - **info** is a reference to an object of type [`MessageInfo`].
+///
+/// - **referrer** is a reference to an object of type [`Option<String>`] which is
+///     the address receiving a share of the protocol fee.
+pub fn assert_not_self_referral(
+    deps: &DepsMut,
+    info: &MessageInfo,
+    referrer: &Option<String>,
+) -> Result<(), ContractError> {
+    if let Some(referrer) = referrer {
+        if deps.api.addr_validate(referrer.as_str())? == info.sender {
+            return Err(ContractError::Generic("Cannot refer yourself".to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Returns an error if create is paused on a cluster.
+///
+/// ## Params
+/// - **cluster_state** is a reference to an object of type [`ClusterStateResponse`].
+pub fn assert_create_not_paused(cluster_state: &ClusterStateResponse) -> Result<(), ContractError> {
+    if cluster_state.create_paused {
+        return Err(ContractError::CreatePaused(
+            cluster_state.cluster_contract_address.clone(),
+        ));
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Returns an error if custom redeem is paused on a cluster.
+///
+/// ## Params
+/// - **cluster_state** is a reference to an object of type [`ClusterStateResponse`].
+pub fn assert_custom_redeem_not_paused(
+    cluster_state: &ClusterStateResponse,
+) -> Result<(), ContractError> {
+    if cluster_state.custom_redeem_paused {
+        return Err(ContractError::CustomRedeemPaused(
+            cluster_state.cluster_contract_address.clone(),
+        ));
+    }
+    Ok(())
+}
+
 /// ## Description
 /// Computes the current imbalance of a cluster.
 ///
@@ -74,8 +128,11 @@ pub fn assert_cluster_exists(deps: Deps, cluster: &Addr) -> Result<bool, Contrac
 pub fn cluster_imbalance(deps: Deps, cluster_contract: &Addr) -> StdResult<Uint128> {
     let cluster_state = get_cluster_state(deps, cluster_contract)?;
 
-    // Get the current asset inventories in the cluster
-    let i = int_vec_to_fpdec(&cluster_state.inv);
+    // Get the current asset inventories in the cluster, normalised to the oracle price unit
+    let i = int_vec_to_fpdec(&normalize_amounts(
+        &cluster_state.inv,
+        &cluster_state.asset_decimals,
+    )?);
     // Get the current asset prices
     let p = str_vec_to_fpdec(&cluster_state.prices)?;
 
@@ -171,8 +228,12 @@ pub fn record_rebalancer_rewards(
 /// - **min_tokens** is an object of type [`Option<Uint128>`] which is the minimum required
 ///     amount of cluster tokens minted from this create operation.
 ///
+/// - **referrer** is an object of type [`Option<String>`] which is the address
+///     receiving a share of the protocol fee.
+///
 /// ## Executor
 /// Only this contract can execute this.
+#[allow(clippy::too_many_arguments)]
 pub fn internal_rewarded_create(
     deps: DepsMut,
     env: Env,
@@ -181,6 +242,7 @@ pub fn internal_rewarded_create(
     cluster_contract: Addr,
     asset_amounts: &[Asset],
     min_tokens: Option<Uint128>,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> {
     // Permission check
     if info.sender != env.contract.address {
@@ -232,6 +294,7 @@ pub fn internal_rewarded_create(
         msg: to_binary(&ClusterExecuteMsg::RebalanceCreate {
             min_tokens,
             asset_amounts: create_asset_amounts,
+            referrer,
         })?,
         funds,
     }));
@@ -277,6 +340,9 @@ pub fn internal_rewarded_create(
 /// - **asset_amounts** is an object of type [`Option<Vec<Asset>>`] which are the assets amount
 ///     the rebalancer wishes to receive.
 ///
+/// - **referrer** is an object of type [`Option<String>`] which is the address
+///     receiving a share of the protocol fee.
+///
 /// ## Executor
 /// Only this contract can execute this.
 #[allow(clippy::too_many_arguments)]
@@ -289,6 +355,7 @@ pub fn internal_rewarded_redeem(
     cluster_token: Addr,
     max_tokens: Option<Uint128>,
     asset_amounts: Option<Vec<Asset>>,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> {
     // Permission check
     if info.sender != env.contract.address {
@@ -325,6 +392,7 @@ pub fn internal_rewarded_redeem(
                 msg: to_binary(&ClusterExecuteMsg::RebalanceRedeem {
                     max_tokens,
                     asset_amounts,
+                    referrer,
                 })?,
                 funds: vec![],
             }),
@@ -371,6 +439,9 @@ pub fn internal_rewarded_redeem(
 ///
 /// - **min_tokens** is an object of type [`Option<Uint128>`] which is the minimum required
 ///     amount of cluster tokens minted from this create operation.
+///
+/// - **referrer** is an object of type [`Option<String>`] which is the address
+///     receiving a share of the protocol fee.
 pub fn create(
     deps: DepsMut,
     env: Env,
@@ -378,14 +449,17 @@ pub fn create(
     cluster_contract: String,
     asset_amounts: &[Asset],
     min_tokens: Option<Uint128>,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> {
     // Validate address format
     let validated_cluster_contract = deps.api.addr_validate(cluster_contract.as_str())?;
+    assert_not_self_referral(&deps, &info, &referrer)?;
     // Check if it is an active cluster
     assert_cluster_exists(deps.as_ref(), &validated_cluster_contract)?;
 
     // Get the cluster state
     let cluster_state = get_cluster_state(deps.as_ref(), &validated_cluster_contract)?;
+    assert_create_not_paused(&cluster_state)?;
 
     // Validate address format
     let cluster_token = deps
@@ -422,6 +496,7 @@ pub fn create(
             cluster_contract: validated_cluster_contract,
             asset_amounts: asset_amounts.to_vec(),
             min_tokens,
+            referrer,
         })?,
         funds: vec![],
     }));
@@ -469,6 +544,9 @@ pub fn create(
 ///
 /// - **asset_amounts** is an object of type [`Option<Vec<Asset>>`] which are the assets amount
 ///     the rebalancer wishes to receive.
+///
+/// - **referrer** is an object of type [`Option<String>`] which is the address
+///     receiving a share of the protocol fee.
 pub fn redeem(
     deps: DepsMut,
     env: Env,
@@ -476,9 +554,11 @@ pub fn redeem(
     cluster_contract: String,
     max_tokens: Uint128,
     asset_amounts: Option<Vec<Asset>>,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> {
     // Validate address format
     let validated_cluster_contract = deps.api.addr_validate(cluster_contract.as_str())?;
+    assert_not_self_referral(&deps, &info, &referrer)?;
     // Check if it is an active cluster
     assert_cluster_exists(deps.as_ref(), &validated_cluster_contract)?;
 
@@ -492,6 +572,11 @@ pub fn redeem(
         asset_amounts
     };
 
+    // Pro-rata redeem stays available while custom redeem is paused
+    if asset_amounts.is_some() {
+        assert_custom_redeem_not_paused(&cluster_state)?;
+    }
+
     // Validate address format
     let cluster_token = deps
         .api
@@ -531,6 +616,7 @@ pub fn redeem(
                     cluster_token,
                     max_tokens: Some(max_tokens),
                     asset_amounts,
+                    referrer,
                 })?,
                 funds: vec![],
             }),
@@ -10,7 +10,9 @@ use cosmwasm_std::{
 use cosmwasm_storage::to_length_prefixed;
 use cw20::BalanceResponse as CW20BalanceResponse;
 use nebula_protocol::cluster::ClusterStateResponse;
-use nebula_protocol::cluster_factory::ClusterExistsResponse;
+use nebula_protocol::cluster_factory::{
+    ClusterExistsResponse, ClusterFeesResponse, ConfigResponse as FactoryConfigResponse,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -38,6 +40,10 @@ pub struct WasmMockQuerier {
     balance_querier: BalanceQuerier,
     tax_querier: TaxQuerier,
     astroport_factory_querier: AstroportFactoryQuerier,
+    cluster_prices: Option<Vec<String>>,
+    cluster_paused: (bool, bool),
+    cluster_decimals: Vec<u8>,
+    cluster_states: HashMap<String, ClusterStateResponse>,
 }
 
 #[derive(Clone, Default)]
@@ -162,8 +168,10 @@ impl Querier for WasmMockQuerier {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Pair { asset_infos: [AssetInfo; 2] },
+    Config {},
     ClusterState {},
     ClusterExists {},
+    ClusterFees { cluster_contract: String },
     Pool {},
     Balance { address: String },
 }
@@ -241,37 +249,80 @@ impl WasmMockQuerier {
                     }
                 }
                 QueryMsg::ClusterState {} => {
+                    if let Some(response) = self.cluster_states.get(contract_addr) {
+                        return SystemResult::Ok(ContractResult::from(to_binary(response)));
+                    }
+                    let target = vec![
+                        Asset {
+                            info: AssetInfo::Token {
+                                contract_addr: Addr::unchecked("asset0000"),
+                            },
+                            amount: Uint128::new(100),
+                        },
+                        Asset {
+                            info: AssetInfo::Token {
+                                contract_addr: Addr::unchecked("asset0001"),
+                            },
+                            amount: Uint128::new(100),
+                        },
+                        Asset {
+                            info: AssetInfo::NativeToken {
+                                denom: "native_asset0000".to_string(),
+                            },
+                            amount: Uint128::new(100),
+                        },
+                    ];
                     let response = ClusterStateResponse {
                         outstanding_balance_tokens: Uint128::new(1000),
-                        prices: vec!["11.85".to_string(), "3.31".to_string()],
-                        inv: vec![Uint128::new(110), Uint128::new(100), Uint128::new(95)],
+                        accrued_fee: Uint128::zero(),
+                        prices: self
+                            .cluster_prices
+                            .clone()
+                            .unwrap_or_else(|| vec!["11.85".to_string(), "3.31".to_string()]),
+                        inv: vec![Uint128::new(110), Uint128::new(100), Uint128::new(95)]
+                            .into_iter()
+                            .zip(self.cluster_decimals.iter())
+                            .map(|(amount, decimals)| {
+                                amount * Uint128::new(10u128.pow(*decimals as u32 - 6))
+                            })
+                            .collect(),
                         penalty: "penalty".to_string(),
                         cluster_token: "cluster_token".to_string(),
-                        target: vec![
-                            Asset {
-                                info: AssetInfo::Token {
-                                    contract_addr: Addr::unchecked("asset0000"),
-                                },
-                                amount: Uint128::new(100),
-                            },
-                            Asset {
-                                info: AssetInfo::Token {
-                                    contract_addr: Addr::unchecked("asset0001"),
-                                },
-                                amount: Uint128::new(100),
-                            },
-                            Asset {
-                                info: AssetInfo::NativeToken {
-                                    denom: "native_asset0000".to_string(),
-                                },
-                                amount: Uint128::new(100),
-                            },
-                        ],
+                        target: target.clone(),
+                        final_target: target,
                         cluster_contract_address: "cluster".to_string(),
                         active: true,
+                        price_ages: vec![0, 0, 0],
+                        supply_headroom: None,
+                        inventory_headroom: vec![None, None, None],
+                        create_paused: self.cluster_paused.0,
+                        custom_redeem_paused: self.cluster_paused.1,
+                        asset_decimals: self.cluster_decimals.clone(),
                     };
                     SystemResult::Ok(ContractResult::from(to_binary(&response)))
                 }
+                QueryMsg::ClusterFees { cluster_contract } => {
+                    SystemResult::Ok(ContractResult::from(to_binary(&ClusterFeesResponse {
+                        cluster_contract: cluster_contract.to_string(),
+                        create_fee_rate: "0.01".to_string(),
+                        redeem_fee_rate: "0.01".to_string(),
+                    })))
+                }
+                QueryMsg::Config {} => {
+                    SystemResult::Ok(ContractResult::from(to_binary(&FactoryConfigResponse {
+                        owner: "owner".to_string(),
+                        nebula_token: "nebula_token".to_string(),
+                        staking_contract: "staking".to_string(),
+                        commission_collector: "collector".to_string(),
+                        protocol_fee_rate: "0.01".to_string(),
+                        astroport_factory: "astroport_factory".to_string(),
+                        token_code_id: 1,
+                        cluster_code_id: 1,
+                        base_denom: "uusd".to_string(),
+                        genesis_time: 1,
+                        distribution_schedule: vec![],
+                    })))
+                }
                 QueryMsg::ClusterExists {} => {
                     SystemResult::Ok(ContractResult::from(to_binary(&ClusterExistsResponse {
                         exists: true,
@@ -389,6 +440,10 @@ impl WasmMockQuerier {
             balance_querier: BalanceQuerier::default(),
             tax_querier: TaxQuerier::default(),
             astroport_factory_querier: AstroportFactoryQuerier::default(),
+            cluster_prices: None,
+            cluster_paused: (false, false),
+            cluster_decimals: vec![6, 6, 6],
+            cluster_states: HashMap::new(),
         }
     }
 
@@ -411,4 +466,27 @@ impl WasmMockQuerier {
     pub fn with_native_balances(&mut self, balances: &[(&String, &[(&String, &Uint128)])]) {
         self.balance_querier = BalanceQuerier::new(balances);
     }
+
+    // configure the cluster asset prices
+    pub fn with_cluster_prices(&mut self, prices: &[&str]) {
+        self.cluster_prices = Some(prices.iter().map(|p| p.to_string()).collect());
+    }
+
+    // configure the cluster pause switches
+    pub fn with_cluster_paused(&mut self, create_paused: bool, custom_redeem_paused: bool) {
+        self.cluster_paused = (create_paused, custom_redeem_paused);
+    }
+
+    // configure the cluster asset decimals, at least 6, keeping the normalised inventory
+    pub fn with_cluster_decimals(&mut self, decimals: &[u8]) {
+        self.cluster_decimals = decimals.to_vec();
+    }
+
+    // configure the state of another cluster
+    pub fn with_cluster_state(&mut self, cluster_state: ClusterStateResponse) {
+        self.cluster_states.insert(
+            cluster_state.cluster_contract_address.clone(),
+            cluster_state,
+        );
+    }
 }
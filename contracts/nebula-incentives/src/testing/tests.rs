@@ -9,12 +9,12 @@ use astroport::pair::{
 };
 use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    attr, coins, from_binary, to_binary, Addr, BankMsg, CosmosMsg, Decimal, DepsMut, SubMsg,
-    Uint128, WasmMsg,
+    attr, coins, from_binary, to_binary, Addr, BankMsg, CosmosMsg, Decimal, DepsMut, OwnedDeps,
+    StdError, SubMsg, Uint128, WasmMsg,
 };
 use cw2::{get_contract_version, ContractVersion};
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
-use nebula_protocol::cluster::ExecuteMsg as ClusterExecuteMsg;
+use nebula_protocol::cluster::{ClusterStateResponse, ExecuteMsg as ClusterExecuteMsg};
 use nebula_protocol::incentives::{
     ConfigResponse, ContributorPendingRewardsResponse, CurrentContributorInfoResponse, Cw20HookMsg,
     ExecuteMsg, IncentivesPoolInfoResponse, InstantiateMsg, MigrateMsg, PenaltyPeriodResponse,
@@ -363,13 +363,27 @@ fn test_incentives_mint() {
         },
     ];
 
+    // cannot refer yourself
     let msg = ExecuteMsg::IncentivesCreate {
         cluster_contract: "cluster".to_string(),
         asset_amounts: asset_amounts.clone(),
         min_tokens: None,
+        referrer: Some("owner0000".to_string()),
     };
     let info = mock_info("owner0000", &coins(100, &"native_asset0000".to_string()));
     let env = mock_env();
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cannot refer yourself".to_string())
+    );
+
+    let msg = ExecuteMsg::IncentivesCreate {
+        cluster_contract: "cluster".to_string(),
+        asset_amounts: asset_amounts.clone(),
+        min_tokens: None,
+        referrer: Some("referrer0000".to_string()),
+    };
     let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
 
     assert_eq!(
@@ -402,6 +416,7 @@ fn test_incentives_mint() {
                     cluster_contract: Addr::unchecked("cluster"),
                     asset_amounts: asset_amounts,
                     min_tokens: None,
+                    referrer: Some("referrer0000".to_string()),
                 })
                 .unwrap(),
                 funds: vec![],
@@ -469,6 +484,7 @@ fn test_incentives_redeem() {
         cluster_contract: "cluster".to_string(),
         asset_amounts: Some(asset_amounts.clone()),
         max_tokens: Uint128::new(1000),
+        referrer: None,
     };
 
     let info = mock_info("owner0000", &coins(100, &"native_asset0000".to_string()));
@@ -498,6 +514,7 @@ fn test_incentives_redeem() {
                     cluster_token: Addr::unchecked("cluster_token"),
                     max_tokens: Some(Uint128::new(1000)),
                     asset_amounts: Some(asset_amounts.clone()),
+                    referrer: None,
                 })
                 .unwrap(),
                 funds: vec![],
@@ -587,6 +604,7 @@ fn test_incentives_arb_cluster_mint() {
                     cluster_contract: Addr::unchecked("cluster"),
                     asset_amounts: asset_amounts,
                     min_tokens: None,
+                    referrer: None,
                 })
                 .unwrap(),
                 funds: vec![],
@@ -735,6 +753,7 @@ fn test_incentives_arb_cluster_redeem() {
                     cluster_token: Addr::unchecked("cluster_token"),
                     max_tokens: None,
                     asset_amounts: None,
+                    referrer: None,
                 })
                 .unwrap(),
                 funds: vec![],
@@ -1017,6 +1036,7 @@ fn test_incentives_internal_rewarded_mint() {
         asset_amounts: asset_amounts.clone(),
         min_tokens: None,
         rebalancer: Addr::unchecked("rebalancer"),
+        referrer: None,
     };
     let info = mock_info(
         MOCK_CONTRACT_ADDR,
@@ -1074,6 +1094,7 @@ fn test_incentives_internal_rewarded_mint() {
                 msg: to_binary(&ClusterExecuteMsg::RebalanceCreate {
                     min_tokens: None,
                     asset_amounts: create_asset_amounts_after_tax,
+                    referrer: None,
                 })
                 .unwrap(),
                 funds: coins(99, &"native_asset0000".to_string()),
@@ -1133,6 +1154,7 @@ fn test_incentives_internal_rewarded_redeem() {
         rebalancer: Addr::unchecked("rebalancer"),
         cluster_token: Addr::unchecked("cluster_token"),
         max_tokens: None,
+        referrer: None,
     };
     let info = mock_info(
         MOCK_CONTRACT_ADDR,
@@ -1159,6 +1181,7 @@ fn test_incentives_internal_rewarded_redeem() {
                 msg: to_binary(&ClusterExecuteMsg::RebalanceRedeem {
                     max_tokens: Uint128::new(1000),
                     asset_amounts: Some(asset_amounts),
+                    referrer: None,
                 })
                 .unwrap(),
                 funds: vec![],
@@ -1188,6 +1211,68 @@ fn test_incentives_internal_rewarded_redeem() {
     );
 }
 
+#[test]
+fn test_paused_cluster() {
+    let mut deps = mock_dependencies(&[]);
+
+    mock_init(deps.as_mut());
+
+    deps.querier.with_token_balances(&[(
+        &"cluster_token".to_string(),
+        &[(&"owner0000".to_string(), &Uint128::new((1000) as u128))],
+    )]);
+    deps.querier.with_cluster_paused(true, true);
+
+    let asset_amounts = vec![
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("asset0000"),
+            },
+            amount: Uint128::new(100),
+        },
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("asset0001"),
+            },
+            amount: Uint128::new(100),
+        },
+    ];
+
+    let info = mock_info("owner0000", &[]);
+
+    // Create is rejected while paused
+    let msg = ExecuteMsg::IncentivesCreate {
+        cluster_contract: "cluster".to_string(),
+        asset_amounts: asset_amounts.clone(),
+        min_tokens: None,
+        referrer: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(res, ContractError::CreatePaused("cluster".to_string()));
+
+    // Custom redeem is rejected while paused
+    let msg = ExecuteMsg::IncentivesRedeem {
+        cluster_contract: "cluster".to_string(),
+        asset_amounts: Some(asset_amounts),
+        max_tokens: Uint128::new(1000),
+        referrer: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomRedeemPaused("cluster".to_string())
+    );
+
+    // Pro-rata redeem stays available
+    let msg = ExecuteMsg::IncentivesRedeem {
+        cluster_contract: "cluster".to_string(),
+        asset_amounts: None,
+        max_tokens: Uint128::new(1000),
+        referrer: None,
+    };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+}
+
 #[test]
 fn test_record_rebalancer_rewards() {
     let mut deps = mock_dependencies(&[]);
@@ -1297,6 +1382,725 @@ fn test_record_astroport_impact() {
     assert_eq!(contribution.value_contributed, Uint128::new(567));
 }
 
+#[test]
+fn test_zap_create() {
+    let mut deps = mock_dependencies(&[]);
+
+    mock_init(deps.as_mut());
+
+    deps.querier.with_cluster_prices(&["11.85", "3.31", "1"]);
+    deps.querier.with_astroport_pairs(&[
+        (
+            &"uusdasset0000".to_string(),
+            &"uusd_asset0000_pair".to_string(),
+        ),
+        (
+            &"uusdasset0001".to_string(),
+            &"uusd_asset0001_pair".to_string(),
+        ),
+        (
+            &"uusdnative_asset0000".to_string(),
+            &"uusd_native_asset0000_pair".to_string(),
+        ),
+    ]);
+
+    let offer_asset = Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::new(1000),
+    };
+    let msg = ExecuteMsg::ZapCreate {
+        cluster_contract: "cluster".to_string(),
+        offer_asset: offer_asset.clone(),
+        min_tokens: Some(Uint128::new(10)),
+        max_spread: None,
+    };
+
+    // cannot zap with the Nebula token held as rewards
+    let info = mock_info("owner0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ZapCreate {
+            cluster_contract: "cluster".to_string(),
+            offer_asset: Asset {
+                info: AssetInfo::Token {
+                    contract_addr: Addr::unchecked("nebula_token"),
+                },
+                amount: Uint128::new(1000),
+            },
+            min_tokens: None,
+            max_spread: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cannot zap with the Nebula token".to_string())
+    );
+
+    // offer asset is not sent
+    let info = mock_info("owner0000", &[]);
+    let env = mock_env();
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Std(StdError::generic_err(
+            "Native token balance mismatch between the argument and the transferred"
+        ))
+    );
+
+    let info = mock_info("owner0000", &coins(1000, &"uusd".to_string()));
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    // Inventory value [1303.5, 331, 95] is below the target allocation of
+    // the NAV after the deposit [2001.5.., 559.0.., 168.9..] for all assets,
+    // so the offered value is split by the shortfalls
+    let swap = |pair: &str, amount: u128| {
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: pair.to_string(),
+            msg: to_binary(&AstroportExecuteMsg::Swap {
+                offer_asset: Asset {
+                    amount: Uint128::new(amount),
+                    ..offer_asset.clone()
+                },
+                max_spread: None,
+                belief_price: None,
+                to: None,
+            })
+            .unwrap(),
+            funds: coins(amount, "uusd"),
+        }))
+    };
+    assert_eq!(
+        res.messages,
+        vec![
+            swap("uusd_asset0000_pair", 698),
+            swap("uusd_asset0001_pair", 228),
+            swap("uusd_native_asset0000_pair", 73),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_CreateAll {
+                    rebalancer: info.sender.clone(),
+                    cluster_contract: Addr::unchecked("cluster"),
+                    asset_infos: vec![
+                        AssetInfo::Token {
+                            contract_addr: Addr::unchecked("asset0000"),
+                        },
+                        AssetInfo::Token {
+                            contract_addr: Addr::unchecked("asset0001"),
+                        },
+                        AssetInfo::NativeToken {
+                            denom: "native_asset0000".to_string(),
+                        },
+                    ],
+                    min_tokens: Some(Uint128::new(10)),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_SendAll {
+                    asset_infos: vec![
+                        AssetInfo::Token {
+                            contract_addr: Addr::unchecked("cluster_token"),
+                        },
+                        offer_asset.info.clone(),
+                    ],
+                    send_to: info.sender,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+}
+
+#[test]
+fn test_create_all() {
+    let mut deps = mock_dependencies(&[]);
+
+    mock_init(deps.as_mut());
+
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(698))],
+    )]);
+    deps.querier.with_native_balances(&[(
+        &"native_asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(73))],
+    )]);
+
+    let asset_infos = vec![
+        AssetInfo::Token {
+            contract_addr: Addr::unchecked("asset0000"),
+        },
+        AssetInfo::NativeToken {
+            denom: "native_asset0000".to_string(),
+        },
+    ];
+    let msg = ExecuteMsg::_CreateAll {
+        rebalancer: Addr::unchecked("rebalancer"),
+        cluster_contract: Addr::unchecked("cluster"),
+        asset_infos,
+        min_tokens: None,
+    };
+
+    // unauthorized
+    let info = mock_info("owner0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "cluster".to_string(),
+            msg: to_binary(&ClusterExecuteMsg::RebalanceCreate {
+                min_tokens: None,
+                asset_amounts: vec![
+                    Asset {
+                        info: AssetInfo::Token {
+                            contract_addr: Addr::unchecked("asset0000"),
+                        },
+                        amount: Uint128::new(698),
+                    },
+                    Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "native_asset0000".to_string(),
+                        },
+                        amount: Uint128::new(73),
+                    },
+                ],
+                referrer: None,
+            })
+            .unwrap(),
+            funds: coins(73, "native_asset0000"),
+        }))
+    );
+}
+
+#[test]
+fn test_zap_redeem() {
+    let mut deps = mock_dependencies(&[]);
+
+    mock_init(deps.as_mut());
+
+    deps.querier.with_cluster_prices(&["11.85", "3.31", "1"]);
+    deps.querier.with_token_balances(&[(
+        &"cluster_token".to_string(),
+        &[(&"owner0000".to_string(), &Uint128::new(100))],
+    )]);
+
+    let ask_asset_info = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let asset_infos = vec![
+        AssetInfo::Token {
+            contract_addr: Addr::unchecked("asset0000"),
+        },
+        AssetInfo::Token {
+            contract_addr: Addr::unchecked("asset0001"),
+        },
+        AssetInfo::NativeToken {
+            denom: "native_asset0000".to_string(),
+        },
+    ];
+
+    let info = mock_info("owner0000", &[]);
+    let env = mock_env();
+    let msg = ExecuteMsg::ZapRedeem {
+        cluster_contract: "cluster".to_string(),
+        max_tokens: Uint128::new(1000),
+        penalty_optimal: true,
+        ask_asset_info: ask_asset_info.clone(),
+        min_output: Uint128::new(150),
+        max_spread: None,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    // `max_tokens` is capped at the sender balance of 100. The penalty-optimal basket
+    // only takes the assets above their target allocation of the NAV after the redeem
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "cluster_token".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: info.sender.to_string(),
+                    recipient: env.contract.address.to_string(),
+                    amount: Uint128::new(100),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_InternalRewardedRedeem {
+                    rebalancer: info.sender.clone(),
+                    cluster_contract: Addr::unchecked("cluster"),
+                    cluster_token: Addr::unchecked("cluster_token"),
+                    max_tokens: Some(Uint128::new(100)),
+                    asset_amounts: Some(vec![
+                        Asset {
+                            info: asset_infos[0].clone(),
+                            amount: Uint128::new(13),
+                        },
+                        Asset {
+                            info: asset_infos[1].clone(),
+                            amount: Uint128::new(3),
+                        },
+                        Asset {
+                            info: asset_infos[2].clone(),
+                            amount: Uint128::zero(),
+                        },
+                    ]),
+                    referrer: None,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_SwapAllTo {
+                    asset_infos: asset_infos.clone(),
+                    ask_asset_info: ask_asset_info.clone(),
+                    max_spread: None,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_AssertMinReturn {
+                    asset_info: ask_asset_info.clone(),
+                    min_return: Uint128::new(150),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_SendAll {
+                    asset_infos: vec![
+                        ask_asset_info.clone(),
+                        asset_infos[0].clone(),
+                        asset_infos[1].clone(),
+                        asset_infos[2].clone(),
+                    ],
+                    send_to: info.sender.clone(),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    // pro-rata redeem
+    let msg = ExecuteMsg::ZapRedeem {
+        cluster_contract: "cluster".to_string(),
+        max_tokens: Uint128::new(1000),
+        penalty_optimal: false,
+        ask_asset_info,
+        min_output: Uint128::new(150),
+        max_spread: None,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            msg: to_binary(&ExecuteMsg::_InternalRewardedRedeem {
+                rebalancer: info.sender.clone(),
+                cluster_contract: Addr::unchecked("cluster"),
+                cluster_token: Addr::unchecked("cluster_token"),
+                max_tokens: Some(Uint128::new(100)),
+                asset_amounts: None,
+                referrer: None,
+            })
+            .unwrap(),
+            funds: vec![],
+        }))
+    );
+
+    // The penalty-optimal basket is returned in the asset decimals
+    deps.querier.with_cluster_decimals(&[8, 6, 6]);
+    let msg = ExecuteMsg::ZapRedeem {
+        cluster_contract: "cluster".to_string(),
+        max_tokens: Uint128::new(1000),
+        penalty_optimal: true,
+        ask_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        min_output: Uint128::new(150),
+        max_spread: None,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            msg: to_binary(&ExecuteMsg::_InternalRewardedRedeem {
+                rebalancer: info.sender,
+                cluster_contract: Addr::unchecked("cluster"),
+                cluster_token: Addr::unchecked("cluster_token"),
+                max_tokens: Some(Uint128::new(100)),
+                asset_amounts: Some(vec![
+                    Asset {
+                        info: asset_infos[0].clone(),
+                        amount: Uint128::new(1300),
+                    },
+                    Asset {
+                        info: asset_infos[1].clone(),
+                        amount: Uint128::new(3),
+                    },
+                    Asset {
+                        info: asset_infos[2].clone(),
+                        amount: Uint128::zero(),
+                    },
+                ]),
+                referrer: None,
+            })
+            .unwrap(),
+            funds: vec![],
+        }))
+    );
+}
+
+#[test]
+fn test_zap_create_with_decimals() {
+    let mut deps = mock_dependencies(&[]);
+
+    mock_init(deps.as_mut());
+
+    deps.querier.with_cluster_prices(&["11.85", "3.31", "1"]);
+    deps.querier.with_astroport_pairs(&[
+        (
+            &"asset0000asset0001".to_string(),
+            &"asset0000_asset0001_pair".to_string(),
+        ),
+        (
+            &"asset0000native_asset0000".to_string(),
+            &"asset0000_native_asset0000_pair".to_string(),
+        ),
+    ]);
+
+    // The offered asset of the cluster is valued from its normalised amount, so
+    // the same value in 6 or 8 decimals is split the same way
+    let offer_value_of = |deps: &mut OwnedDeps<_, _, _>, amount: u128| {
+        let msg = ExecuteMsg::ZapCreate {
+            cluster_contract: "cluster".to_string(),
+            offer_asset: Asset {
+                info: AssetInfo::Token {
+                    contract_addr: Addr::unchecked("asset0000"),
+                },
+                amount: Uint128::new(amount),
+            },
+            min_tokens: None,
+            max_spread: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
+        res.attributes
+            .into_iter()
+            .find(|attr| attr.key == "offer_value")
+            .unwrap()
+            .value
+    };
+    let offer_value = offer_value_of(&mut deps, 100);
+    deps.querier.with_cluster_decimals(&[8, 6, 6]);
+    assert_eq!(offer_value_of(&mut deps, 10000), offer_value);
+    assert_eq!(offer_value, "1185");
+}
+
+#[test]
+fn test_swap_all_to() {
+    let mut deps = mock_dependencies(&[]);
+
+    mock_init(deps.as_mut());
+
+    deps.querier.with_astroport_pairs(&[(
+        &"asset0000uusd".to_string(),
+        &"asset0000_uusd_pair".to_string(),
+    )]);
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(13))],
+    )]);
+
+    let ask_asset_info = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let msg = ExecuteMsg::_SwapAllTo {
+        asset_infos: vec![
+            AssetInfo::Token {
+                contract_addr: Addr::unchecked("asset0000"),
+            },
+            AssetInfo::Token {
+                contract_addr: Addr::unchecked("asset0001"),
+            },
+            ask_asset_info.clone(),
+        ],
+        ask_asset_info: ask_asset_info.clone(),
+        max_spread: None,
+    };
+
+    // unauthorized
+    let info = mock_info("owner0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // only assets with non-zero balances are swapped
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: "asset0000_uusd_pair".to_string(),
+                amount: Uint128::new(13),
+                msg: to_binary(&AstroportCw20HookMsg::Swap {
+                    max_spread: None,
+                    belief_price: None,
+                    to: None,
+                })
+                .unwrap(),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // min return is not met
+    let msg = ExecuteMsg::_AssertMinReturn {
+        asset_info: AssetInfo::Token {
+            contract_addr: Addr::unchecked("asset0000"),
+        },
+        min_return: Uint128::new(14),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Return amount 13 is below the minimum 14".to_string())
+    );
+}
+
+#[test]
+fn test_migrate_cluster() {
+    let mut deps = mock_dependencies(&[]);
+
+    mock_init(deps.as_mut());
+
+    let asset_info = |addr: &str| AssetInfo::Token {
+        contract_addr: Addr::unchecked(addr),
+    };
+    let uusd_info = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let target = vec![
+        Asset {
+            info: asset_info("asset0000"),
+            amount: Uint128::new(100),
+        },
+        Asset {
+            info: asset_info("asset0002"),
+            amount: Uint128::new(100),
+        },
+        Asset {
+            info: uusd_info.clone(),
+            amount: Uint128::new(1000),
+        },
+    ];
+    deps.querier.with_cluster_state(ClusterStateResponse {
+        outstanding_balance_tokens: Uint128::new(1000),
+        accrued_fee: Uint128::zero(),
+        prices: vec!["10".to_string(), "10".to_string(), "1".to_string()],
+        inv: vec![Uint128::new(100), Uint128::new(100), Uint128::new(1000)],
+        penalty: "penalty".to_string(),
+        cluster_token: "cluster_v2_token".to_string(),
+        target: target.clone(),
+        final_target: target,
+        cluster_contract_address: "cluster_v2".to_string(),
+        active: true,
+        price_ages: vec![0, 0, 0],
+        supply_headroom: None,
+        inventory_headroom: vec![None, None, None],
+        create_paused: false,
+        custom_redeem_paused: false,
+        asset_decimals: vec![6, 6, 6],
+    });
+    deps.querier.with_token_balances(&[
+        (
+            &"cluster_token".to_string(),
+            &[(&"owner0000".to_string(), &Uint128::new(100))],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(10))],
+        ),
+        (&"asset0002".to_string(), &[]),
+    ]);
+    deps.querier.with_native_balances(&[(
+        &"uusd".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(500))],
+    )]);
+    deps.querier.with_astroport_pairs(&[(
+        &"uusdasset0002".to_string(),
+        &"uusd_asset0002_pair".to_string(),
+    )]);
+
+    // cannot migrate into the same cluster
+    let info = mock_info("owner0000", &[]);
+    let env = mock_env();
+    let msg = ExecuteMsg::MigrateCluster {
+        from_cluster: "cluster".to_string(),
+        to_cluster: "cluster".to_string(),
+        max_tokens: Uint128::new(1000),
+        min_tokens: Some(Uint128::new(10)),
+        max_spread: None,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Cannot migrate a cluster into itself".to_string())
+    );
+
+    let msg = ExecuteMsg::MigrateCluster {
+        from_cluster: "cluster".to_string(),
+        to_cluster: "cluster_v2".to_string(),
+        max_tokens: Uint128::new(1000),
+        min_tokens: Some(Uint128::new(10)),
+        max_spread: None,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    // `max_tokens` is capped at the sender balance of 100. The shared asset0000 is
+    // carried over in kind, so only asset0001 and native_asset0000 are sold and
+    // only asset0002 is bought
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "cluster_token".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: info.sender.to_string(),
+                    recipient: env.contract.address.to_string(),
+                    amount: Uint128::new(100),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_InternalRewardedRedeem {
+                    rebalancer: info.sender.clone(),
+                    cluster_contract: Addr::unchecked("cluster"),
+                    cluster_token: Addr::unchecked("cluster_token"),
+                    max_tokens: Some(Uint128::new(100)),
+                    asset_amounts: None,
+                    referrer: None,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_SwapAllTo {
+                    asset_infos: vec![
+                        asset_info("asset0001"),
+                        AssetInfo::NativeToken {
+                            denom: "native_asset0000".to_string(),
+                        },
+                    ],
+                    ask_asset_info: uusd_info.clone(),
+                    max_spread: None,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_SwapIntoBasket {
+                    cluster_contract: Addr::unchecked("cluster_v2"),
+                    asset_infos: vec![asset_info("asset0002")],
+                    max_spread: None,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_CreateAll {
+                    rebalancer: info.sender.clone(),
+                    cluster_contract: Addr::unchecked("cluster_v2"),
+                    asset_infos: vec![
+                        asset_info("asset0000"),
+                        asset_info("asset0002"),
+                        uusd_info.clone(),
+                    ],
+                    min_tokens: Some(Uint128::new(10)),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ExecuteMsg::_SendAll {
+                    asset_infos: vec![
+                        asset_info("cluster_v2_token"),
+                        uusd_info.clone(),
+                        asset_info("asset0000"),
+                        asset_info("asset0001"),
+                        AssetInfo::NativeToken {
+                            denom: "native_asset0000".to_string(),
+                        },
+                    ],
+                    send_to: info.sender.clone(),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    let msg = ExecuteMsg::_SwapIntoBasket {
+        cluster_contract: Addr::unchecked("cluster_v2"),
+        asset_infos: vec![asset_info("asset0002")],
+        max_spread: None,
+    };
+
+    // unauthorized
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // With the carried 10 asset0000, the inventory value [1100, 1000, 1000] is below
+    // the target allocation of the NAV after the deposit [1200, 1200, 1200]. The 500 uusd
+    // is split between the shortfalls of asset0002 and uusd, and the uusd share is kept
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "uusd_asset0002_pair".to_string(),
+            msg: to_binary(&AstroportExecuteMsg::Swap {
+                offer_asset: Asset {
+                    info: uusd_info,
+                    amount: Uint128::new(250),
+                },
+                max_spread: None,
+                belief_price: None,
+                to: None,
+            })
+            .unwrap(),
+            funds: coins(250, "uusd"),
+        }))]
+    );
+}
+
 #[test]
 fn migration() {
     let mut deps = mock_dependencies(&[]);
@@ -0,0 +1,715 @@
+use cosmwasm_std::{
+    attr, to_binary, Addr, Coin, CosmosMsg, Decimal, DepsMut, Env, MessageInfo, QueryRequest,
+    Response, StdResult, Uint128, WasmMsg, WasmQuery,
+};
+
+use crate::error::ContractError;
+use crate::rebalancers::{
+    assert_cluster_exists, assert_create_not_paused, assert_custom_redeem_not_paused,
+    get_cluster_state, internal_rewarded_create,
+};
+use crate::state::{read_config, Config};
+
+use astroport::asset::{Asset, AssetInfo};
+use astroport::pair::{Cw20HookMsg as AstroportCw20HookMsg, ExecuteMsg as AstroportExecuteMsg};
+use astroport::querier::{query_balance, query_pair_info, query_token_balance, simulate};
+use cluster_math::{
+    denormalize_amounts, dot, int_vec_to_fpdec, min_imbalance_allocation, min_imbalance_withdrawal,
+    normalize_amount, normalize_amounts, str_vec_to_fpdec, FPDecimal, NORMALIZED_DECIMALS,
+};
+use cw20::Cw20ExecuteMsg;
+use nebula_protocol::cluster_factory::{ClusterFeesResponse, QueryMsg as FactoryQueryMsg};
+use nebula_protocol::incentives::ExecuteMsg;
+use std::cmp::min;
+use std::str::FromStr;
+
+/// ## Description
+/// Returns an error if the asset is the Nebula token. This contract holds the Nebula
+/// token rewards, which must never be swept to a zap sender.
+///
+/// ## Params
+/// - **cfg** is a reference to an object of type [`Config`].
+///
+/// - **asset_info** is a reference to an object of type [`AssetInfo`].
+fn assert_not_nebula_token(cfg: &Config, asset_info: &AssetInfo) -> Result<(), ContractError> {
+    if *asset_info
+        == (AssetInfo::Token {
+            contract_addr: cfg.nebula_token.clone(),
+        })
+    {
+        return Err(ContractError::Generic(
+            "Cannot zap with the Nebula token".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Executes the create operation from a single offered asset.
+/// #### Offer asset -> Assets -> CT
+/// 1. Compute the basket of the cluster assets minimizing the cluster imbalance,
+///     and hence the notional penalty, after the create operation
+/// 2. Swap the offered asset into the basket on the Astroport pairs
+/// 3. Mint cluster tokens (CT) from all received assets
+/// 4. Send all cluster tokens and the unused offered asset to the sender
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **cluster_contract** is an object of type [`String`] which is the address of
+///     a cluster contract.
+///
+/// - **offer_asset** is an object of type [`Asset`] which is the asset offered
+///     to mint cluster tokens.
+///
+/// - **min_tokens** is an object of type [`Option<Uint128>`] which is the minimum required
+///     amount of cluster tokens minted from this create operation.
+///
+/// - **max_spread** is an object of type [`Option<Decimal>`] which is the maximum spread
+///     allowed for each swap on Astroport.
+pub fn zap_create(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cluster_contract: String,
+    offer_asset: Asset,
+    min_tokens: Option<Uint128>,
+    max_spread: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    // Validate address format
+    let validated_cluster_contract = deps.api.addr_validate(cluster_contract.as_str())?;
+    // Check if the provided address is an active cluster
+    assert_cluster_exists(deps.as_ref(), &validated_cluster_contract)?;
+
+    if offer_asset.amount.is_zero() {
+        return Err(ContractError::Generic(
+            "Offer asset amount cannot be zero".to_string(),
+        ));
+    }
+
+    let mut messages = vec![];
+    let contract = env.contract.address.clone();
+
+    let cfg: Config = read_config(deps.storage)?;
+
+    assert_not_nebula_token(&cfg, &offer_asset.info)?;
+
+    // Get the cluster state
+    let cluster_state = get_cluster_state(deps.as_ref(), &validated_cluster_contract)?;
+    assert_create_not_paused(&cluster_state)?;
+    let cluster_token = deps
+        .api
+        .addr_validate(cluster_state.cluster_token.as_str())?;
+
+    // Transfer the offered asset into this incentives contract
+    match &offer_asset.info {
+        AssetInfo::NativeToken { .. } => offer_asset.assert_sent_native_token_balance(&info)?,
+        AssetInfo::Token { contract_addr } => {
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: info.sender.to_string(),
+                    recipient: contract.to_string(),
+                    amount: offer_asset.amount,
+                })?,
+                funds: vec![],
+            }));
+        }
+    }
+
+    let target_infos = cluster_state
+        .target
+        .iter()
+        .map(|x| x.info.clone())
+        .collect::<Vec<_>>();
+    let target_weights = cluster_state
+        .target
+        .iter()
+        .map(|x| x.amount)
+        .collect::<Vec<_>>();
+
+    let i = int_vec_to_fpdec(&normalize_amounts(
+        &cluster_state.inv,
+        &cluster_state.asset_decimals,
+    )?);
+    let p = str_vec_to_fpdec(&cluster_state.prices)?;
+    let w = int_vec_to_fpdec(&target_weights);
+
+    // Compute the value of the offered asset in the base denom
+    let offer_amount = FPDecimal::from(offer_asset.amount.u128());
+    let offer_value = match target_infos.iter().position(|x| *x == offer_asset.info) {
+        // Prices are per normalised amount of the asset
+        Some(idx) => {
+            let decimals = cluster_state
+                .asset_decimals
+                .get(idx)
+                .copied()
+                .unwrap_or(NORMALIZED_DECIMALS);
+            FPDecimal::from(normalize_amount(offer_asset.amount, decimals)?.u128()) * p[idx]
+        }
+        None => match &offer_asset.info {
+            AssetInfo::NativeToken { denom } if *denom == cfg.base_denom => offer_amount,
+            _ => {
+                // Value the offered asset from its swap to the base denom on Astroport
+                let pair_info = query_pair_info(
+                    &deps.querier,
+                    cfg.astroport_factory.clone(),
+                    &[
+                        offer_asset.info.clone(),
+                        AssetInfo::NativeToken {
+                            denom: cfg.base_denom.clone(),
+                        },
+                    ],
+                )?;
+                let simulation = simulate(&deps.querier, pair_info.contract_addr, &offer_asset)?;
+                FPDecimal::from(simulation.return_amount.u128())
+            }
+        },
+    };
+
+    if offer_value.num.is_zero() {
+        return Err(ContractError::Generic(
+            "Offer asset has no value".to_string(),
+        ));
+    }
+
+    // Compute the capital allocation into each asset minimizing the cluster imbalance
+    let allocation = min_imbalance_allocation(&i, &p, &w, offer_value);
+
+    // Swap the offered asset into each asset of the basket
+    let mut basket_infos = vec![];
+    for (idx, asset_info) in target_infos.iter().enumerate() {
+        // Skip assets with zero target weight as they cannot be used for minting
+        if target_weights[idx].is_zero() {
+            continue;
+        }
+
+        // The offered asset is used as is if it is in the cluster target
+        if *asset_info == offer_asset.info {
+            basket_infos.push(asset_info.clone());
+            continue;
+        }
+
+        // -- swap_amount = offer_amount * allocation / offer_value
+        let swap_amount: u128 = (offer_amount * allocation[idx] / offer_value).into();
+        if swap_amount == 0 {
+            continue;
+        }
+        messages.push(swap_msg(
+            &deps,
+            &cfg,
+            Asset {
+                info: offer_asset.info.clone(),
+                amount: Uint128::new(swap_amount),
+            },
+            asset_info,
+            max_spread,
+        )?);
+        basket_infos.push(asset_info.clone());
+    }
+
+    // Mint cluster tokens from all assets received from the swaps
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        msg: to_binary(&ExecuteMsg::_CreateAll {
+            rebalancer: info.sender.clone(),
+            cluster_contract: validated_cluster_contract,
+            asset_infos: basket_infos,
+            min_tokens,
+        })?,
+        funds: vec![],
+    }));
+
+    // Send all minted cluster tokens and the unused offered asset to the sender
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        msg: to_binary(&ExecuteMsg::_SendAll {
+            asset_infos: vec![
+                AssetInfo::Token {
+                    contract_addr: cluster_token,
+                },
+                offer_asset.info.clone(),
+            ],
+            send_to: info.sender.clone(),
+        })?,
+        funds: vec![],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "zap_create"),
+        attr("sender", info.sender.as_str()),
+        attr("offer_asset", offer_asset.to_string()),
+        attr("offer_value", offer_value.to_string()),
+    ]))
+}
+
+/// ## Description
+/// Executes the redeem operation and sells all returned assets into a single asset.
+/// #### CT -> Assets -> Ask asset
+/// 1. Redeem the cluster tokens (CT) into either the pro-rata basket, or the basket
+///     minimizing the cluster imbalance, and hence the notional penalty
+/// 2. Swap all the redeemed assets into the ask asset on the Astroport pairs
+/// 3. Assert the total amount of the ask asset is at least `min_output`
+/// 4. Send all the ask asset and the unused cluster tokens to the sender
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **cluster_contract** is an object of type [`String`] which is the address of
+///     a cluster contract.
+///
+/// - **max_tokens** is an object of type [`Uint128`] which is the maximum allowed
+///     amount of cluster tokens to be burned.
+///
+/// - **penalty_optimal** is an object of type [`bool`] which determines whether to redeem
+///     the basket minimizing the penalty instead of the pro-rata basket.
+///
+/// - **ask_asset_info** is an object of type [`AssetInfo`] which is the asset to receive.
+///
+/// - **min_output** is an object of type [`Uint128`] which is the minimum amount of
+///     the ask asset received from the whole route.
+///
+/// - **max_spread** is an object of type [`Option<Decimal>`] which is the maximum spread
+///     allowed for each swap on Astroport.
+#[allow(clippy::too_many_arguments)]
+pub fn zap_redeem(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cluster_contract: String,
+    max_tokens: Uint128,
+    penalty_optimal: bool,
+    ask_asset_info: AssetInfo,
+    min_output: Uint128,
+    max_spread: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    // Validate address format
+    let validated_cluster_contract = deps.api.addr_validate(cluster_contract.as_str())?;
+    // Check if the provided address is an active cluster
+    assert_cluster_exists(deps.as_ref(), &validated_cluster_contract)?;
+
+    let contract = env.contract.address.clone();
+
+    let cfg: Config = read_config(deps.storage)?;
+    assert_not_nebula_token(&cfg, &ask_asset_info)?;
+
+    // Get the cluster state
+    let cluster_state = get_cluster_state(deps.as_ref(), &validated_cluster_contract)?;
+    let cluster_token = deps
+        .api
+        .addr_validate(cluster_state.cluster_token.as_str())?;
+
+    // Set `max_tokens` to be the minimum between the provided `max_tokens` and the actual sender balance
+    let max_tokens = min(
+        max_tokens,
+        query_token_balance(&deps.querier, cluster_token.clone(), info.sender.clone())?,
+    );
+
+    let asset_infos = cluster_state
+        .target
+        .iter()
+        .map(|x| x.info.clone())
+        .collect::<Vec<_>>();
+
+    // The penalty optimal basket requires custom redeem on an active cluster
+    if penalty_optimal && cluster_state.active {
+        assert_custom_redeem_not_paused(&cluster_state)?;
+    }
+
+    // Only allow pro-rata redeem if cluster is not active
+    let asset_amounts = if penalty_optimal && cluster_state.active {
+        let target_weights = cluster_state
+            .target
+            .iter()
+            .map(|x| x.amount)
+            .collect::<Vec<_>>();
+
+        let i = int_vec_to_fpdec(&normalize_amounts(
+            &cluster_state.inv,
+            &cluster_state.asset_decimals,
+        )?);
+        let p = str_vec_to_fpdec(&cluster_state.prices)?;
+        let w = int_vec_to_fpdec(&target_weights);
+        let n = FPDecimal::from(cluster_state.outstanding_balance_tokens.u128());
+
+        // Retrieve the redeem fee rate of the cluster
+        let cluster_fees: ClusterFeesResponse =
+            deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: cfg.factory.to_string(),
+                msg: to_binary(&FactoryQueryMsg::ClusterFees {
+                    cluster_contract: validated_cluster_contract.to_string(),
+                })?,
+            }))?;
+        let keep_rate = FPDecimal::one() - FPDecimal::from_str(&cluster_fees.redeem_fee_rate)?;
+
+        // The basket value is chosen so that the cluster token cost, ceiled when redeeming
+        // and again when adding the protocol fee, never exceeds `max_tokens`. The basket
+        // minimizes the imbalance, so it always receives a reward instead of a penalty.
+        // -- value = ((max_tokens - 1) * keep_rate - 1) * NAV / CT_total_supply
+        let m = FPDecimal::from(max_tokens.u128());
+        let token_budget = (m - FPDecimal::one()) * keep_rate - FPDecimal::one();
+        if token_budget.sign == 0 || token_budget.num.is_zero() {
+            return Err(ContractError::Generic(
+                "Too few cluster tokens to redeem".to_string(),
+            ));
+        }
+        let value = token_budget * dot(&i, &p) / n;
+
+        // Convert the withdrawn capital into normalised amounts, then back to the asset decimals
+        let withdrawal = min_imbalance_withdrawal(&i, &p, &w, value)
+            .iter()
+            .zip(p.iter())
+            .map(|(v, price)| Uint128::new((*v / *price).into()))
+            .collect::<Vec<_>>();
+        Some(
+            asset_infos
+                .iter()
+                .zip(denormalize_amounts(
+                    &withdrawal,
+                    &cluster_state.asset_decimals,
+                )?)
+                .map(|(asset_info, amount)| Asset {
+                    info: asset_info.clone(),
+                    amount,
+                })
+                .collect::<Vec<_>>(),
+        )
+    } else {
+        None
+    };
+
+    let mut send_infos = vec![ask_asset_info.clone()];
+    send_infos.extend(
+        asset_infos
+            .iter()
+            .filter(|x| **x != ask_asset_info)
+            .cloned(),
+    );
+
+    Ok(Response::new()
+        .add_messages(vec![
+            // Transfer CT tokens of `max_tokens` into this incentives contract
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: cluster_token.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: info.sender.to_string(),
+                    recipient: contract.to_string(),
+                    amount: max_tokens,
+                })?,
+                funds: vec![],
+            }),
+            // Perform the redeem operation, returning the unused CT to the sender
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract.to_string(),
+                msg: to_binary(&ExecuteMsg::_InternalRewardedRedeem {
+                    rebalancer: info.sender.clone(),
+                    cluster_contract: validated_cluster_contract,
+                    cluster_token,
+                    max_tokens: Some(max_tokens),
+                    asset_amounts,
+                    referrer: None,
+                })?,
+                funds: vec![],
+            }),
+            // Swap all the redeemed assets into the ask asset
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract.to_string(),
+                msg: to_binary(&ExecuteMsg::_SwapAllTo {
+                    asset_infos,
+                    ask_asset_info: ask_asset_info.clone(),
+                    max_spread,
+                })?,
+                funds: vec![],
+            }),
+            // Verify the total received ask asset
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract.to_string(),
+                msg: to_binary(&ExecuteMsg::_AssertMinReturn {
+                    asset_info: ask_asset_info,
+                    min_return: min_output,
+                })?,
+                funds: vec![],
+            }),
+            // Send the ask asset and any asset left from the swaps to the sender
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract.to_string(),
+                msg: to_binary(&ExecuteMsg::_SendAll {
+                    asset_infos: send_infos,
+                    send_to: info.sender.clone(),
+                })?,
+                funds: vec![],
+            }),
+        ])
+        .add_attributes(vec![
+            attr("action", "zap_redeem"),
+            attr("sender", info.sender.as_str()),
+            attr("max_tokens", max_tokens),
+            attr("min_output", min_output),
+        ]))
+}
+
+/// ## Description
+/// Swaps all specified assets held by this contract into the ask asset on Astroport.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **asset_infos** is a reference to an array containing objects of type [`AssetInfo`]
+///     which is a list of assets to be swapped.
+///
+/// - **ask_asset_info** is a reference to an object of type [`AssetInfo`] which is
+///     the asset to receive.
+///
+/// - **max_spread** is an object of type [`Option<Decimal>`] which is the maximum spread
+///     allowed for each swap on Astroport.
+///
+/// ## Executor
+/// Only this contract can execute this.
+pub fn swap_all_to(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset_infos: &[AssetInfo],
+    ask_asset_info: &AssetInfo,
+    max_spread: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    // Permission check
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let cfg: Config = read_config(deps.storage)?;
+
+    let mut messages = vec![];
+    for asset_info in asset_infos.iter().filter(|x| *x != ask_asset_info) {
+        let amount = query_asset_balance(&deps, &env, asset_info)?;
+        if amount.is_zero() {
+            continue;
+        }
+        messages.push(swap_msg(
+            &deps,
+            &cfg,
+            Asset {
+                info: asset_info.clone(),
+                amount,
+            },
+            ask_asset_info,
+            max_spread,
+        )?);
+    }
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![attr("action", "swap_all_to")]))
+}
+
+/// ## Description
+/// Returns an error if this contract holds less than `min_return` of the specified asset.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **asset_info** is a reference to an object of type [`AssetInfo`] which is
+///     the asset to check.
+///
+/// - **min_return** is an object of type [`Uint128`] which is the minimum required amount.
+///
+/// ## Executor
+/// Only this contract can execute this.
+pub fn assert_min_return(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset_info: &AssetInfo,
+    min_return: Uint128,
+) -> Result<Response, ContractError> {
+    // Permission check
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let amount = query_asset_balance(&deps, &env, asset_info)?;
+    if amount < min_return {
+        return Err(ContractError::Generic(format!(
+            "Return amount {} is below the minimum {}",
+            amount, min_return
+        )));
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "assert_min_return"),
+        attr("return_amount", amount),
+    ]))
+}
+
+/// ## Description
+/// Returns the balance of the specified asset held by this contract.
+///
+/// ## Params
+/// - **deps** is a reference to an object of type [`DepsMut`].
+///
+/// - **env** is a reference to an object of type [`Env`].
+///
+/// - **asset_info** is a reference to an object of type [`AssetInfo`].
+pub fn query_asset_balance(
+    deps: &DepsMut,
+    env: &Env,
+    asset_info: &AssetInfo,
+) -> StdResult<Uint128> {
+    match asset_info {
+        AssetInfo::Token { contract_addr } => query_token_balance(
+            &deps.querier,
+            contract_addr.clone(),
+            env.contract.address.clone(),
+        ),
+        AssetInfo::NativeToken { denom } => {
+            query_balance(&deps.querier, env.contract.address.clone(), denom.clone())
+        }
+    }
+}
+
+/// ## Description
+/// Returns a message swapping `offer_asset` into `ask_asset_info` on the corresponding
+/// Astroport pair. The returned asset is sent to this contract.
+///
+/// ## Params
+/// - **deps** is a reference to an object of type [`DepsMut`].
+///
+/// - **cfg** is a reference to an object of type [`Config`].
+///
+/// - **offer_asset** is an object of type [`Asset`] which is the asset to swap.
+///
+/// - **ask_asset_info** is a reference to an object of type [`AssetInfo`] which is
+///     the asset to receive.
+///
+/// - **max_spread** is an object of type [`Option<Decimal>`] which is the maximum spread
+///     allowed for the swap.
+pub fn swap_msg(
+    deps: &DepsMut,
+    cfg: &Config,
+    offer_asset: Asset,
+    ask_asset_info: &AssetInfo,
+    max_spread: Option<Decimal>,
+) -> StdResult<CosmosMsg> {
+    // Retrieve the offer-ask pair info
+    let pair_info = query_pair_info(
+        &deps.querier,
+        cfg.astroport_factory.clone(),
+        &[offer_asset.info.clone(), ask_asset_info.clone()],
+    )?;
+
+    match &offer_asset.info {
+        AssetInfo::NativeToken { denom } => {
+            // Deduct tax first
+            let amount = (offer_asset.deduct_tax(&deps.querier)?).amount;
+            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: pair_info.contract_addr.to_string(),
+                msg: to_binary(&AstroportExecuteMsg::Swap {
+                    offer_asset: Asset {
+                        amount,
+                        ..offer_asset.clone()
+                    },
+                    max_spread,
+                    belief_price: None,
+                    to: None,
+                })?,
+                funds: vec![Coin {
+                    denom: denom.clone(),
+                    amount,
+                }],
+            }))
+        }
+        AssetInfo::Token { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: pair_info.contract_addr.to_string(),
+                amount: offer_asset.amount,
+                msg: to_binary(&AstroportCw20HookMsg::Swap {
+                    max_spread,
+                    belief_price: None,
+                    to: None,
+                })?,
+            })?,
+            funds: vec![],
+        })),
+    }
+}
+
+/// ## Description
+/// Calls the create logic in a cluster contract with all balances of the specified
+/// assets held by this contract.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **rebalancer** is an object of type [`Addr`] which is the address of a user
+///     performing a rebalance.
+///
+/// - **cluster_contract** is an object of type [`Addr`] which is the address of
+///     the cluster contract corresponding to the rebalance.
+///
+/// - **asset_infos** is a reference to an array containing objects of type [`AssetInfo`]
+///     which is a list of assets used to mint cluster tokens.
+///
+/// - **min_tokens** is an object of type [`Option<Uint128>`] which is the minimum required
+///     amount of cluster tokens minted from this create operation.
+///
+/// ## Executor
+/// Only this contract can execute this.
+pub fn create_all(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rebalancer: Addr,
+    cluster_contract: Addr,
+    asset_infos: &[AssetInfo],
+    min_tokens: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    // Permission check
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Get the asset amounts that the contract is holding
+    let mut asset_amounts = vec![];
+    for asset_info in asset_infos {
+        let amount = query_asset_balance(&deps, &env, asset_info)?;
+        if !amount.is_zero() {
+            asset_amounts.push(Asset {
+                info: asset_info.clone(),
+                amount,
+            });
+        }
+    }
+
+    internal_rewarded_create(
+        deps,
+        env,
+        info,
+        rebalancer,
+        cluster_contract,
+        &asset_amounts,
+        min_tokens,
+        None,
+    )
+}
@@ -0,0 +1,468 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    attr, to_binary, Addr, Api, Binary, Deps, DepsMut, Env, MessageInfo, QueryRequest, Response,
+    StdError, StdResult, Uint128, WasmQuery,
+};
+
+use crate::error::ContractError;
+use crate::state::{read_config, store_config, Config};
+use astroport::asset::{Asset, AssetInfo};
+use cluster_math::normalize_amount;
+use cw2::set_contract_version;
+use cw20::{Cw20QueryMsg, TokenInfoResponse};
+use nebula_protocol::market_cap_provider::{
+    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg,
+};
+use nebula_protocol::oracle::{PriceResponse, QueryMsg as OracleQueryMsg};
+use nebula_protocol::target_provider::TargetWeightsResponse;
+
+/// Contract name that is used for migration.
+const CONTRACT_NAME: &str = "nebula-market-cap-provider";
+/// Contract version that is used for migration.
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Total of the value weights in basis points
+const TOTAL_WEIGHT_BPS: u64 = 10_000;
+
+/// Precision of the value weights in basis points before rounding
+const WEIGHT_PRECISION: u128 = 1_000_000_000u128;
+
+/// ## Description
+/// Creates a new contract with the specified parameters packed in the `msg` variable.
+/// Returns a [`Response`] with the specified attributes if the operation was successful,
+/// or a [`ContractError`] if the contract was not created.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **_env** is an object of type [`Env`].
+///
+/// - **_info** is an object of type [`MessageInfo`].
+///
+/// - **msg**  is a message of type [`InstantiateMsg`] which contains the parameters used for creating the contract.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let (assets, weight_caps) = validate_assets(deps.api, &msg.assets, &msg.weight_caps)?;
+    let cfg = Config {
+        // Validate address format
+        owner: deps.api.addr_validate(msg.owner.as_str())?,
+        oracle: deps.api.addr_validate(msg.oracle.as_str())?,
+        base_denom: msg.base_denom,
+        assets,
+        weight_caps,
+    };
+
+    store_config(deps.storage, &cfg)?;
+
+    let log = vec![attr("owner", msg.owner)];
+
+    Ok(Response::new().add_attributes(log))
+}
+
+/// ## Description
+/// Validates the asset tokens and their weight caps. Returns the validated addresses,
+/// or a [`ContractError`] if an asset is duplicated, a cap does not match a listed
+/// asset, or the caps cannot cover the total weight.
+///
+/// ## Params
+/// - **api** is an object of type [`&dyn Api`].
+///
+/// - **assets** is a reference to an object of type [`Vec<String>`] which are the
+///     asset tokens.
+///
+/// - **weight_caps** is a reference to an object of type [`Vec<(String, u64)>`] which
+///     are the maximum value weights of specific asset tokens in basis points.
+#[allow(clippy::type_complexity)]
+fn validate_assets(
+    api: &dyn Api,
+    assets: &[String],
+    weight_caps: &[(String, u64)],
+) -> Result<(Vec<Addr>, Vec<(Addr, u64)>), ContractError> {
+    let mut asset_addrs: Vec<Addr> = vec![];
+    for asset in assets.iter() {
+        // Validate address format
+        let asset_addr = api.addr_validate(asset.as_str())?;
+        if asset_addrs.contains(&asset_addr) {
+            return Err(ContractError::Generic(
+                "Assets cannot contain duplicate assets".to_string(),
+            ));
+        }
+        asset_addrs.push(asset_addr);
+    }
+    if asset_addrs.is_empty() {
+        return Err(ContractError::Generic("Assets cannot be empty".to_string()));
+    }
+
+    let mut capped: Vec<(Addr, u64)> = vec![];
+    for (asset, cap) in weight_caps.iter() {
+        let asset_addr = api.addr_validate(asset.as_str())?;
+        if !asset_addrs.contains(&asset_addr) || capped.iter().any(|(a, _)| *a == asset_addr) {
+            return Err(ContractError::Generic(
+                "Each weight cap must match a single listed asset".to_string(),
+            ));
+        }
+        if *cap > TOTAL_WEIGHT_BPS {
+            return Err(ContractError::Generic(format!(
+                "Weight caps cannot exceed {} basis points",
+                TOTAL_WEIGHT_BPS
+            )));
+        }
+        capped.push((asset_addr, *cap));
+    }
+
+    // The assets must be able to hold the total weight within their caps
+    let total_caps: u64 = asset_addrs
+        .iter()
+        .map(|asset| {
+            capped
+                .iter()
+                .find(|(a, _)| a == asset)
+                .map(|(_, cap)| *cap)
+                .unwrap_or(TOTAL_WEIGHT_BPS)
+        })
+        .sum();
+    if total_caps < TOTAL_WEIGHT_BPS {
+        return Err(ContractError::Generic(format!(
+            "Weight caps must sum to at least {} basis points",
+            TOTAL_WEIGHT_BPS
+        )));
+    }
+
+    Ok((asset_addrs, capped))
+}
+
+/// ## Description
+/// Exposes all the execute functions available in the contract.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **_env** is an object of type [`Env`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **msg** is an object of type [`ExecuteMsg`].
+///
+/// ## Commands
+/// - **ExecuteMsg::UpdateConfig {
+///             owner,
+///             oracle,
+///             base_denom,
+///             assets,
+///             weight_caps,
+///         }** Updates general market cap target provider contract parameters.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::UpdateConfig {
+            owner,
+            oracle,
+            base_denom,
+            assets,
+            weight_caps,
+        } => update_config(deps, info, owner, oracle, base_denom, assets, weight_caps),
+    }
+}
+
+/// ## Description
+/// Updates general contract settings. Returns a [`ContractError`] on failure.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **info** is an object of type [`MessageInfo`].
+///
+/// - **owner** is an object of type [`Option<String>`] which is the contract owner.
+///
+/// - **oracle** is an object of type [`Option<String>`] which is an address
+///     of a Nebula oracle contract.
+///
+/// - **base_denom** is an object of type [`Option<String>`] which is the quote denom
+///     of the asset prices.
+///
+/// - **assets** is an object of type [`Option<Vec<String>>`] which are the asset
+///     tokens weighted by their market cap.
+///
+/// - **weight_caps** is an object of type [`Option<Vec<(String, u64)>>`] which are
+///     the maximum value weights of specific asset tokens in basis points.
+///
+/// ## Executor
+/// Only the owner can execute this.
+pub fn update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    owner: Option<String>,
+    oracle: Option<String>,
+    base_denom: Option<String>,
+    assets: Option<Vec<String>>,
+    weight_caps: Option<Vec<(String, u64)>>,
+) -> Result<Response, ContractError> {
+    let mut config: Config = read_config(deps.storage)?;
+
+    // Permission check
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if let Some(owner) = owner {
+        // Validate address format
+        config.owner = deps.api.addr_validate(owner.as_str())?;
+    }
+
+    if let Some(oracle) = oracle {
+        // Validate address format
+        config.oracle = deps.api.addr_validate(oracle.as_str())?;
+    }
+
+    if let Some(base_denom) = base_denom {
+        config.base_denom = base_denom;
+    }
+
+    if assets.is_some() || weight_caps.is_some() {
+        // Caps are validated against the updated assets
+        let assets = assets.unwrap_or_else(|| {
+            config
+                .assets
+                .iter()
+                .map(|asset| asset.to_string())
+                .collect()
+        });
+        let weight_caps = weight_caps.unwrap_or_else(|| {
+            config
+                .weight_caps
+                .iter()
+                .map(|(asset, cap)| (asset.to_string(), *cap))
+                .collect()
+        });
+        let (assets, weight_caps) = validate_assets(deps.api, &assets, &weight_caps)?;
+        config.assets = assets;
+        config.weight_caps = weight_caps;
+    }
+
+    store_config(deps.storage, &config)?;
+    Ok(Response::new().add_attributes(vec![attr("action", "update_config")]))
+}
+
+/// ## Description
+/// Exposes all the queries available in the contract.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **_env** is an object of type [`Env`].
+///
+/// - **msg** is an object of type [`QueryMsg`].
+///
+/// ## Commands
+/// - **QueryMsg::Config {}** Returns general contract parameters using a custom [`ConfigResponse`] structure.
+///
+/// - **QueryMsg::TargetWeights {}** Returns the capped market cap value weights of the assets.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::TargetWeights {} => to_binary(&query_target_weights(deps)?),
+    }
+}
+
+/// ## Description
+/// Returns general contract parameters using a custom [`ConfigResponse`] structure.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let state = read_config(deps.storage)?;
+    let resp = ConfigResponse {
+        owner: state.owner.to_string(),
+        oracle: state.oracle.to_string(),
+        base_denom: state.base_denom,
+        assets: state.assets.iter().map(|asset| asset.to_string()).collect(),
+        weight_caps: state
+            .weight_caps
+            .iter()
+            .map(|(asset, cap)| (asset.to_string(), *cap))
+            .collect(),
+    };
+
+    Ok(resp)
+}
+
+/// ## Description
+/// Returns the value weights of the assets in basis points, proportional to their
+/// market cap and limited by their weight caps. Prices are per normalised amount, so the
+/// total supply is normalised with the token decimals first.
+/// -- market_cap = normalize(total_supply) * price
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+fn query_target_weights(deps: Deps) -> StdResult<TargetWeightsResponse> {
+    let config = read_config(deps.storage)?;
+
+    let mut market_caps: Vec<Uint128> = vec![];
+    for asset in config.assets.iter() {
+        // Get the total supply of the asset token
+        let token_info: TokenInfoResponse =
+            deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: asset.to_string(),
+                msg: to_binary(&Cw20QueryMsg::TokenInfo {})?,
+            }))?;
+
+        // Get the latest price of the asset token in the base denom
+        let price: PriceResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: config.oracle.to_string(),
+            msg: to_binary(&OracleQueryMsg::Price {
+                base_asset: AssetInfo::Token {
+                    contract_addr: asset.clone(),
+                },
+                quote_asset: AssetInfo::NativeToken {
+                    denom: config.base_denom.clone(),
+                },
+            })?,
+        }))?;
+
+        market_caps
+            .push(normalize_amount(token_info.total_supply, token_info.decimals)? * price.rate);
+    }
+
+    let weight_caps = config
+        .assets
+        .iter()
+        .map(|asset| config.weight_cap_of(asset))
+        .collect::<Vec<_>>();
+    let weights = compute_capped_weights(&market_caps, &weight_caps)?;
+
+    Ok(TargetWeightsResponse {
+        target: config
+            .assets
+            .into_iter()
+            .zip(weights)
+            .map(|(asset, weight)| Asset {
+                info: AssetInfo::Token {
+                    contract_addr: asset,
+                },
+                amount: Uint128::from(weight),
+            })
+            .collect(),
+    })
+}
+
+/// ## Description
+/// Returns the value weights in basis points, summing to the total weight, proportional
+/// to the market caps. Weights above their cap are set to the cap, and the excess is
+/// redistributed to the other assets proportionally to their market cap.
+///
+/// ## Params
+/// - **market_caps** is a reference to an object of type [`Vec<Uint128>`] which are
+///     the market caps of the assets.
+///
+/// - **weight_caps** is a reference to an object of type [`Vec<Option<u64>>`] which are
+///     the maximum value weights of the assets in basis points.
+#[allow(clippy::unnecessary_map_or)]
+pub fn compute_capped_weights(
+    market_caps: &[Uint128],
+    weight_caps: &[Option<u64>],
+) -> StdResult<Vec<u64>> {
+    let total_weight = Uint128::from(TOTAL_WEIGHT_BPS as u128 * WEIGHT_PRECISION);
+    let caps = weight_caps
+        .iter()
+        .map(|cap| cap.map(|cap| Uint128::from(cap as u128 * WEIGHT_PRECISION)))
+        .collect::<Vec<_>>();
+
+    // Weights of the assets, in basis points scaled by `WEIGHT_PRECISION`
+    let mut weights = vec![Uint128::zero(); market_caps.len()];
+    let mut at_cap = vec![false; market_caps.len()];
+    loop {
+        // Split the weight left by the capped assets among the others
+        let capped_weight: Uint128 = weights
+            .iter()
+            .zip(at_cap.iter())
+            .filter(|(_, at_cap)| **at_cap)
+            .map(|(weight, _)| *weight)
+            .sum();
+        let free_market_cap: Uint128 = market_caps
+            .iter()
+            .zip(at_cap.iter())
+            .filter(|(_, at_cap)| !**at_cap)
+            .map(|(market_cap, _)| *market_cap)
+            .sum();
+        if free_market_cap.is_zero() {
+            return Err(StdError::generic_err("Assets have no market cap to weight"));
+        }
+        let free_weight = total_weight.checked_sub(capped_weight)?;
+
+        let mut capped_any = false;
+        for i in 0..market_caps.len() {
+            if at_cap[i] {
+                continue;
+            }
+            weights[i] = free_weight.multiply_ratio(market_caps[i], free_market_cap);
+            if let Some(cap) = caps[i] {
+                if weights[i] > cap {
+                    weights[i] = cap;
+                    at_cap[i] = true;
+                    capped_any = true;
+                }
+            }
+        }
+        if !capped_any {
+            break;
+        }
+    }
+
+    // Round down to basis points, and hand the rounding remainder out one basis
+    // point at a time to the assets with the largest truncated fractions
+    let mut bps_weights = weights
+        .iter()
+        .map(|weight| (weight.u128() / WEIGHT_PRECISION) as u64)
+        .collect::<Vec<_>>();
+    let mut remainder = TOTAL_WEIGHT_BPS - bps_weights.iter().sum::<u64>();
+    let mut order = (0..weights.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| std::cmp::Reverse(weights[*i].u128() % WEIGHT_PRECISION));
+    while remainder > 0 {
+        let mut assigned = false;
+        for i in order.iter() {
+            if remainder == 0 {
+                break;
+            }
+            if weight_caps[*i].map_or(true, |cap| bps_weights[*i] < cap) {
+                bps_weights[*i] += 1;
+                remainder -= 1;
+                assigned = true;
+            }
+        }
+        if !assigned {
+            return Err(StdError::generic_err(
+                "Weight caps cannot cover the total weight",
+            ));
+        }
+    }
+
+    Ok(bps_weights)
+}
+
+/// ## Description
+/// Exposes the migrate functionality in the contract.
+///
+/// ## Params
+/// - **_deps** is an object of type [`DepsMut`].
+///
+/// - **_env** is an object of type [`Env`].
+///
+/// - **_msg** is an object of type [`MigrateMsg`].
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+    Ok(Response::default())
+}
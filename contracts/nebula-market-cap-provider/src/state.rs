@@ -0,0 +1,49 @@
+use cosmwasm_std::{Addr, StdResult, Storage};
+use cosmwasm_storage::{singleton, singleton_read};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// config: Config
+pub static KEY_CONFIG: &[u8] = b"config";
+
+//////////////////////////////////////////////////////////////////////
+/// CONFIG
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// A custom struct for storing market cap target provider contract setting.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    /// Owner of the contract
+    pub owner: Addr,
+    /// Nebula oracle contract used to price the assets
+    pub oracle: Addr,
+    /// Quote denom of the asset prices, UST (uusd)
+    pub base_denom: String,
+    /// CW20 asset tokens weighted by their market cap
+    pub assets: Vec<Addr>,
+    /// Maximum value weights, in basis points, of specific asset tokens
+    pub weight_caps: Vec<(Addr, u64)>,
+}
+
+impl Config {
+    /// ## Description
+    /// Returns the maximum value weight of an asset token in basis points, if capped.
+    ///
+    /// ## Params
+    /// - **asset** is a reference to an object of type [`Addr`].
+    pub fn weight_cap_of(&self, asset: &Addr) -> Option<u64> {
+        self.weight_caps
+            .iter()
+            .find(|(capped, _)| capped == asset)
+            .map(|(_, cap)| *cap)
+    }
+}
+
+pub fn store_config(storage: &mut dyn Storage, config: &Config) -> StdResult<()> {
+    singleton(storage, KEY_CONFIG).save(config)
+}
+
+pub fn read_config(storage: &dyn Storage) -> StdResult<Config> {
+    singleton_read(storage, KEY_CONFIG).load()
+}
@@ -0,0 +1,140 @@
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{
+    from_binary, from_slice, to_binary, Coin, ContractResult, Decimal, Empty, OwnedDeps, Querier,
+    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
+};
+use cw20::{Cw20QueryMsg, TokenInfoResponse};
+use nebula_protocol::oracle::{PriceResponse, QueryMsg as OracleQueryMsg};
+use std::collections::HashMap;
+
+/// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
+/// this uses our CustomQuerier.
+pub fn mock_dependencies(
+    contract_balance: &[Coin],
+) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    let contract_addr = MOCK_CONTRACT_ADDR.to_string();
+    let custom_querier: WasmMockQuerier =
+        WasmMockQuerier::new(MockQuerier::new(&[(&contract_addr, contract_balance)]));
+
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: custom_querier,
+    }
+}
+
+pub struct WasmMockQuerier {
+    pub base: MockQuerier<Empty>,
+    pub token_querier: TokenQuerier,
+    pub oracle_querier: OracleQuerier,
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {:?}", e),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        self.execute_query(&request)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct TokenQuerier {
+    // token address => total supply
+    pub supplies: HashMap<String, Uint128>,
+    // token address => decimals, 6 if not set
+    pub decimals: HashMap<String, u8>,
+}
+
+#[derive(Clone, Default)]
+pub struct OracleQuerier {
+    // token address => price in uusd
+    pub prices: HashMap<String, Decimal>,
+}
+
+impl WasmMockQuerier {
+    pub fn execute_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
+        match &request {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => match from_binary(msg) {
+                Ok(OracleQueryMsg::Price {
+                    base_asset,
+                    quote_asset,
+                }) => match self.oracle_querier.prices.get(&base_asset.to_string()) {
+                    Some(rate) if quote_asset.to_string() == "uusd" => {
+                        SystemResult::Ok(ContractResult::from(to_binary(&PriceResponse {
+                            rate: *rate,
+                            last_updated_base: u64::MAX,
+                            last_updated_quote: u64::MAX,
+                        })))
+                    }
+                    _ => SystemResult::Err(SystemError::InvalidRequest {
+                        error: "No oracle price exists".to_string(),
+                        request: msg.as_slice().into(),
+                    }),
+                },
+                _ => match from_binary(msg) {
+                    Ok(Cw20QueryMsg::TokenInfo {}) => {
+                        match self.token_querier.supplies.get(contract_addr) {
+                            Some(total_supply) => SystemResult::Ok(ContractResult::from(
+                                to_binary(&TokenInfoResponse {
+                                    name: contract_addr.to_string(),
+                                    symbol: "TOKEN".to_string(),
+                                    decimals: self
+                                        .token_querier
+                                        .decimals
+                                        .get(contract_addr)
+                                        .copied()
+                                        .unwrap_or(6),
+                                    total_supply: *total_supply,
+                                }),
+                            )),
+                            None => SystemResult::Err(SystemError::InvalidRequest {
+                                error: "No token info exists".to_string(),
+                                request: msg.as_slice().into(),
+                            }),
+                        }
+                    }
+                    _ => panic!("QueryMsg type not implemented"),
+                },
+            },
+            _ => self.base.handle_query(request),
+        }
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn new(base: MockQuerier<Empty>) -> Self {
+        WasmMockQuerier {
+            base,
+            token_querier: TokenQuerier::default(),
+            oracle_querier: OracleQuerier::default(),
+        }
+    }
+
+    pub fn set_token_supply(&mut self, token_address: &str, supply: u128) -> &mut Self {
+        self.token_querier
+            .supplies
+            .insert(token_address.to_string(), Uint128::new(supply));
+        self
+    }
+
+    pub fn set_token_decimals(&mut self, token_address: &str, decimals: u8) -> &mut Self {
+        self.token_querier
+            .decimals
+            .insert(token_address.to_string(), decimals);
+        self
+    }
+
+    pub fn set_oracle_price(&mut self, token_address: &str, price: Decimal) -> &mut Self {
+        self.oracle_querier
+            .prices
+            .insert(token_address.to_string(), price);
+        self
+    }
+}
@@ -0,0 +1,244 @@
+use crate::contract::{execute, instantiate, query};
+use crate::error::ContractError;
+use crate::state::{read_config, Config};
+use crate::testing::mock_querier::mock_dependencies;
+use astroport::asset::{Asset, AssetInfo};
+use cosmwasm_std::testing::{mock_env, mock_info};
+use cosmwasm_std::{from_binary, Addr, Decimal, Uint128};
+use nebula_protocol::market_cap_provider::{ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
+use nebula_protocol::target_provider::TargetWeightsResponse;
+
+fn init_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        owner: "owner0000".to_string(),
+        oracle: "oracle0000".to_string(),
+        base_denom: "uusd".to_string(),
+        assets: vec![
+            "asset0000".to_string(),
+            "asset0001".to_string(),
+            "asset0002".to_string(),
+        ],
+        weight_caps: vec![],
+    }
+}
+
+fn target(weights: Vec<u128>) -> Vec<Asset> {
+    ["asset0000", "asset0001", "asset0002"]
+        .iter()
+        .zip(weights)
+        .map(|(asset, weight)| Asset {
+            info: AssetInfo::Token {
+                contract_addr: Addr::unchecked(*asset),
+            },
+            amount: Uint128::new(weight),
+        })
+        .collect()
+}
+
+#[test]
+fn proper_initialization() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("sender0000", &[]);
+    let msg = init_msg();
+    let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(0, res.messages.len());
+
+    let config: Config = read_config(&deps.storage).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            owner: Addr::unchecked("owner0000"),
+            oracle: Addr::unchecked("oracle0000"),
+            base_denom: "uusd".to_string(),
+            assets: vec![
+                Addr::unchecked("asset0000"),
+                Addr::unchecked("asset0001"),
+                Addr::unchecked("asset0002"),
+            ],
+            weight_caps: vec![],
+        }
+    );
+}
+
+#[test]
+fn bad_initialization() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("sender0000", &[]);
+
+    let mut msg = init_msg();
+    msg.assets.push("asset0000".to_string());
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Assets cannot contain duplicate assets".to_string())
+    );
+
+    let mut msg = init_msg();
+    msg.weight_caps = vec![("asset0003".to_string(), 5000)];
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Each weight cap must match a single listed asset".to_string())
+    );
+
+    let mut msg = init_msg();
+    msg.weight_caps = vec![
+        ("asset0000".to_string(), 3000),
+        ("asset0001".to_string(), 3000),
+        ("asset0002".to_string(), 3000),
+    ];
+    let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Weight caps must sum to at least 10000 basis points".to_string())
+    );
+}
+
+#[test]
+fn update_config() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("sender0000", &[]);
+    let msg = init_msg();
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // unauthorized update
+    let info = mock_info("imposter0000", &[]);
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: Some("imposter0000".to_string()),
+        oracle: None,
+        base_denom: None,
+        assets: None,
+        weight_caps: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // caps are validated against the updated assets
+    let info = mock_info("owner0000", &[]);
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        oracle: None,
+        base_denom: None,
+        assets: Some(vec!["asset0000".to_string(), "asset0001".to_string()]),
+        weight_caps: Some(vec![("asset0002".to_string(), 5000)]),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("Each weight cap must match a single listed asset".to_string())
+    );
+
+    // successful update
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: Some("owner0001".to_string()),
+        oracle: Some("oracle0001".to_string()),
+        base_denom: Some("ukrw".to_string()),
+        assets: None,
+        weight_caps: Some(vec![("asset0002".to_string(), 5000)]),
+    };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        ConfigResponse {
+            owner: "owner0001".to_string(),
+            oracle: "oracle0001".to_string(),
+            base_denom: "ukrw".to_string(),
+            assets: vec![
+                "asset0000".to_string(),
+                "asset0001".to_string(),
+                "asset0002".to_string(),
+            ],
+            weight_caps: vec![("asset0002".to_string(), 5000)],
+        }
+    );
+}
+
+#[test]
+fn query_target_weights() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .set_token_supply("asset0000", 1_000_000)
+        .set_token_supply("asset0001", 2_000_000)
+        .set_token_supply("asset0002", 1_000_000)
+        .set_oracle_price("asset0000", Decimal::one())
+        .set_oracle_price("asset0001", Decimal::one())
+        .set_oracle_price("asset0002", Decimal::from_ratio(2u128, 1u128));
+
+    let info = mock_info("sender0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, init_msg()).unwrap();
+
+    // weights are proportional to the market caps
+    let res: TargetWeightsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TargetWeights {}).unwrap())
+            .unwrap();
+    assert_eq!(res.target, target(vec![2000, 4000, 4000]));
+
+    // the excess weight of a capped asset is split among the others
+    let info = mock_info("owner0000", &[]);
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        oracle: None,
+        base_denom: None,
+        assets: None,
+        weight_caps: Some(vec![("asset0001".to_string(), 3000)]),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let res: TargetWeightsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TargetWeights {}).unwrap())
+            .unwrap();
+    assert_eq!(res.target, target(vec![2333, 3000, 4667]));
+
+    // the redistributed weight is capped as well
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        oracle: None,
+        base_denom: None,
+        assets: None,
+        weight_caps: Some(vec![
+            ("asset0001".to_string(), 3000),
+            ("asset0002".to_string(), 4000),
+        ]),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let res: TargetWeightsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TargetWeights {}).unwrap())
+            .unwrap();
+    assert_eq!(res.target, target(vec![3000, 3000, 4000]));
+
+    // the prices are quoted in the configured base denom
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        oracle: None,
+        base_denom: Some("ukrw".to_string()),
+        assets: None,
+        weight_caps: None,
+    };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    query(deps.as_ref(), mock_env(), QueryMsg::TargetWeights {}).unwrap_err();
+}
+
+#[test]
+fn query_target_weights_with_decimals() {
+    // The same market caps as `query_target_weights`, with supplies in other decimals
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .set_token_supply("asset0000", 1_000_000_000_000_000_000)
+        .set_token_decimals("asset0000", 18)
+        .set_token_supply("asset0001", 200)
+        .set_token_decimals("asset0001", 2)
+        .set_token_supply("asset0002", 1_000_000)
+        .set_oracle_price("asset0000", Decimal::one())
+        .set_oracle_price("asset0001", Decimal::one())
+        .set_oracle_price("asset0002", Decimal::from_ratio(2u128, 1u128));
+
+    let info = mock_info("sender0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, init_msg()).unwrap();
+
+    let res: TargetWeightsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TargetWeights {}).unwrap())
+            .unwrap();
+    assert_eq!(res.target, target(vec![2000, 4000, 4000]));
+}
@@ -2,17 +2,22 @@
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
     attr, to_binary, Addr, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, QueryRequest,
-    Response, StdResult, Uint128, WasmQuery,
+    Response, StdError, StdResult, Uint128, WasmQuery,
 };
 
-use crate::state::{read_config, store_config, Config};
+use crate::state::{read_config, store_config, Config, DEFAULT_MAX_CLUSTER_DEPTH};
 
 use crate::error::ContractError;
 use astroport::asset::AssetInfo;
+use cluster_math::normalize_amounts;
 use cw2::set_contract_version;
+use cw20::{Cw20QueryMsg, MinterResponse};
+use nebula_protocol::cluster::{ClusterStateResponse, QueryMsg as ClusterQueryMsg, TargetResponse};
+use nebula_protocol::cluster_factory::{ClusterExistsResponse, QueryMsg as FactoryQueryMsg};
 use nebula_protocol::oracle::{
     ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, PriceResponse, QueryMsg,
 };
+use std::str::FromStr;
 use tefi_oracle::hub::{
     HubQueryMsg as TeFiOracleQueryMsg, PriceResponse as TeFiOraclePriceResponse,
 };
@@ -53,6 +58,11 @@ pub fn instantiate(
         owner: deps.api.addr_validate(msg.owner.as_str())?,
         oracle_addr: deps.api.addr_validate(msg.oracle_addr.as_str())?,
         base_denom: msg.base_denom,
+        factory: msg
+            .factory
+            .map(|factory| deps.api.addr_validate(factory.as_str()))
+            .transpose()?,
+        max_cluster_depth: msg.max_cluster_depth.unwrap_or(DEFAULT_MAX_CLUSTER_DEPTH),
     };
 
     store_config(deps.storage, &cfg)?;
@@ -79,6 +89,8 @@ pub fn instantiate(
 ///             owner,
 ///             oracle_addr,
 ///             base_denom,
+///             factory,
+///             max_cluster_depth,
 ///         }** Updates general oracle contract parameters.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
@@ -92,7 +104,17 @@ pub fn execute(
             owner,
             oracle_addr,
             base_denom,
-        } => update_config(deps, info, owner, oracle_addr, base_denom),
+            factory,
+            max_cluster_depth,
+        } => update_config(
+            deps,
+            info,
+            owner,
+            oracle_addr,
+            base_denom,
+            factory,
+            max_cluster_depth,
+        ),
     }
 }
 
@@ -111,6 +133,12 @@ pub fn execute(
 ///
 /// - **base_denom** is an object of type [`Option<String>`] which is a base denom, UST.
 ///
+/// - **factory** is an object of type [`Option<String>`] which is an address
+///     of the cluster factory contract.
+///
+/// - **max_cluster_depth** is an object of type [`Option<u8>`] which is the maximum
+///     nesting depth of cluster tokens.
+///
 /// ## Executor
 /// Only the owner can execute this.
 pub fn update_config(
@@ -119,6 +147,8 @@ pub fn update_config(
     owner: Option<String>,
     oracle_addr: Option<String>,
     base_denom: Option<String>,
+    factory: Option<String>,
+    max_cluster_depth: Option<u8>,
 ) -> Result<Response, ContractError> {
     let mut config: Config = read_config(deps.storage)?;
 
@@ -141,6 +171,15 @@ pub fn update_config(
         config.base_denom = base_denom;
     }
 
+    if let Some(factory) = factory {
+        // Validate address format
+        config.factory = Some(deps.api.addr_validate(factory.as_str())?);
+    }
+
+    if let Some(max_cluster_depth) = max_cluster_depth {
+        config.max_cluster_depth = max_cluster_depth;
+    }
+
     store_config(deps.storage, &config)?;
     Ok(Response::new().add_attributes(vec![attr("action", "update_config")]))
 }
@@ -151,7 +190,7 @@ pub fn update_config(
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
-/// - **_env** is an object of type [`Env`].
+/// - **env** is an object of type [`Env`].
 ///
 /// - **msg** is an object of type [`QueryMsg`].
 ///
@@ -163,13 +202,13 @@ pub fn update_config(
 ///             quote_asset,
 ///         }** Returns the latest oracle price of `base_asset` in `quote_asset` unit.
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
         QueryMsg::Price {
             base_asset,
             quote_asset,
-        } => to_binary(&query_price(deps, base_asset, quote_asset)?),
+        } => to_binary(&query_price(deps, env, base_asset, quote_asset)?),
     }
 }
 
@@ -184,6 +223,8 @@ fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
         owner: state.owner.to_string(),
         oracle_addr: state.oracle_addr.to_string(),
         base_denom: state.base_denom,
+        factory: state.factory.map(|factory| factory.to_string()),
+        max_cluster_depth: state.max_cluster_depth,
     };
 
     Ok(resp)
@@ -196,19 +237,22 @@ fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
+/// - **env** is an object of type [`Env`].
+///
 /// - **base_asset** is an object of type [`AssetInfo`] which is an asset to be queried.
 ///
 /// - **quote_asset** is an object of type [`AssetInfo`] which is an asset used as
 ///     a price unit.
 fn query_price(
     deps: Deps,
+    env: Env,
     base_asset: AssetInfo,
     quote_asset: AssetInfo,
 ) -> StdResult<PriceResponse> {
     // Get latest price of `base_asset` in uusd
-    let (price_base, last_updated_base) = query_asset_price(deps, base_asset)?;
+    let (price_base, last_updated_base) = query_asset_price(deps, &env, base_asset)?;
     // Get latest price of `quote_asset` in uusd
-    let (price_quote, last_updated_quote) = query_asset_price(deps, quote_asset)?;
+    let (price_quote, last_updated_quote) = query_asset_price(deps, &env, quote_asset)?;
 
     // Compute the price
     // -- rate = price_base / price_quote
@@ -230,15 +274,25 @@ fn query_price(
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
+/// - **env** is a reference to an object of type [`Env`].
+///
 /// - **asset** is an object of type [`AssetInfo`] which is the asset to be queried for its price.
-fn query_asset_price(deps: Deps, asset: AssetInfo) -> StdResult<(Decimal, u64)> {
+fn query_asset_price(deps: Deps, env: &Env, asset: AssetInfo) -> StdResult<(Decimal, u64)> {
     let config: Config = read_config(deps.storage)?;
 
     match asset {
         // If native, query on-chain
         AssetInfo::NativeToken { denom } => query_native_price(deps, denom, &config),
-        // Otherwise, query from Tefi oracle hub
-        AssetInfo::Token { contract_addr } => query_cw20_price(deps, contract_addr, &config),
+        AssetInfo::Token { contract_addr } => {
+            match query_token_cluster(deps, &contract_addr, &config)? {
+                // If a cluster token, price at its net asset value
+                Some(cluster_contract) => {
+                    query_cluster_token_price(deps, env, &contract_addr, &cluster_contract, &config)
+                }
+                // Otherwise, query from Tefi oracle hub
+                None => query_cw20_price(deps, contract_addr, &config),
+            }
+        }
     }
 }
 
@@ -286,6 +340,167 @@ fn query_cw20_price(deps: Deps, contract_addr: Addr, config: &Config) -> StdResu
     Ok((res.rate, res.last_updated))
 }
 
+/// ## Description
+/// Returns the cluster contract of a CW20 token if the token is the cluster token
+/// of an active cluster registered on the factory contract, or `None` otherwise.
+/// The cluster contract of a cluster token is its minter.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **contract_addr** is a reference to an object of type [`Addr`] which is an address
+///     of a CW20 token contract.
+///
+/// - **config** is a reference to an object of type [`Config`] which is the configuration
+///     of this oracle contract, including the cluster factory contract.
+fn query_token_cluster(
+    deps: Deps,
+    contract_addr: &Addr,
+    config: &Config,
+) -> StdResult<Option<Addr>> {
+    // Cluster tokens are not recognised without a cluster factory
+    let factory = match &config.factory {
+        Some(factory) => factory,
+        None => return Ok(None),
+    };
+
+    // Tokens without a queryable minter cannot be cluster tokens
+    let minter: Option<MinterResponse> = deps
+        .querier
+        .query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&Cw20QueryMsg::Minter {})?,
+        }))
+        .unwrap_or(None);
+    let minter = match minter {
+        Some(minter) => deps.api.addr_validate(minter.minter.as_str())?,
+        None => return Ok(None),
+    };
+
+    let res: ClusterExistsResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: factory.to_string(),
+        msg: to_binary(&FactoryQueryMsg::ClusterExists {
+            contract_addr: minter.to_string(),
+        })?,
+    }))?;
+
+    Ok(if res.exists { Some(minter) } else { None })
+}
+
+/// ## Description
+/// Returns an error if the cluster tokens held by a cluster, directly or through
+/// other clusters, are nested deeper than the maximum cluster depth.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster_contract** is a reference to an object of type [`Addr`] which is an address
+///     of a cluster contract.
+///
+/// - **depth** is an object of type [`u8`] which is the nesting depth of the cluster.
+///
+/// - **config** is a reference to an object of type [`Config`] which is the configuration
+///     of this oracle contract.
+fn assert_cluster_depth(
+    deps: Deps,
+    cluster_contract: &Addr,
+    depth: u8,
+    config: &Config,
+) -> StdResult<()> {
+    if depth > config.max_cluster_depth {
+        return Err(StdError::generic_err(format!(
+            "Cluster tokens are nested deeper than the maximum depth of {}",
+            config.max_cluster_depth
+        )));
+    }
+
+    let res: TargetResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: cluster_contract.to_string(),
+        msg: to_binary(&ClusterQueryMsg::Target {})?,
+    }))?;
+
+    for asset in res.target.iter() {
+        if let AssetInfo::Token { contract_addr } = &asset.info {
+            if let Some(inner_cluster) = query_token_cluster(deps, contract_addr, config)? {
+                assert_cluster_depth(deps, &inner_cluster, depth + 1, config)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Returns the net asset value of a cluster token in uusd, along with the update
+/// time of the oldest price among the cluster inventory assets.
+/// -- NAV = sum(price_i * inventory_i) / cluster token supply
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **env** is a reference to an object of type [`Env`].
+///
+/// - **cluster_token** is a reference to an object of type [`Addr`] which is an address
+///     of a cluster token contract.
+///
+/// - **cluster_contract** is a reference to an object of type [`Addr`] which is an address
+///     of the cluster contract of the cluster token.
+///
+/// - **config** is a reference to an object of type [`Config`] which is the configuration
+///     of this oracle contract.
+fn query_cluster_token_price(
+    deps: Deps,
+    env: &Env,
+    cluster_token: &Addr,
+    cluster_contract: &Addr,
+    config: &Config,
+) -> StdResult<(Decimal, u64)> {
+    // The cluster prices its inventory through its pricing oracle, which recurses
+    // into nested cluster tokens, so bound the nesting before querying its state
+    assert_cluster_depth(deps, cluster_contract, 1, config)?;
+
+    let state: ClusterStateResponse =
+        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: cluster_contract.to_string(),
+            msg: to_binary(&ClusterQueryMsg::ClusterState {})?,
+        }))?;
+
+    if state.cluster_token != cluster_token.as_str() {
+        return Err(StdError::generic_err(
+            "Token is not the cluster token of its minter cluster",
+        ));
+    }
+    if state.outstanding_balance_tokens.is_zero() {
+        return Err(StdError::generic_err(
+            "Cannot price a cluster token without outstanding supply",
+        ));
+    }
+
+    // Compute the total value of the cluster inventory in uusd, scaled by `DECIMAL_FRACTIONAL`,
+    // from the inventory normalised to the oracle price unit
+    let inv = normalize_amounts(&state.inv, &state.asset_decimals)?;
+    let mut total_value = Uint128::zero();
+    for (price, amount) in state.prices.iter().zip(inv.iter()) {
+        let amount = amount.checked_mul(DECIMAL_FRACTIONAL)?;
+        total_value = total_value.checked_add(amount * Decimal::from_str(price)?)?;
+    }
+
+    // The price is as stale as the oldest price among the inventory assets
+    let last_updated = match state.price_ages.iter().max() {
+        Some(age) => env.block.time.seconds().saturating_sub(*age),
+        None => u64::MAX,
+    };
+
+    Ok((
+        Decimal::from_ratio(
+            total_value,
+            state
+                .outstanding_balance_tokens
+                .checked_mul(DECIMAL_FRACTIONAL)?,
+        ),
+        last_updated,
+    ))
+}
+
 /// ## Description
 /// Exposes the migrate functionality in the contract.
 ///
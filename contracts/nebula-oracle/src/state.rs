@@ -6,6 +6,9 @@ use serde::{Deserialize, Serialize};
 /// config: Config
 pub static KEY_CONFIG: &[u8] = b"config";
 
+/// Default maximum nesting depth of cluster tokens, e.g. a cluster of clusters
+pub const DEFAULT_MAX_CLUSTER_DEPTH: u8 = 2;
+
 //////////////////////////////////////////////////////////////////////
 /// CONFIG
 //////////////////////////////////////////////////////////////////////
@@ -20,6 +23,16 @@ pub struct Config {
     pub oracle_addr: Addr,
     /// Base denom, UST
     pub base_denom: String,
+    /// Cluster factory contract, used to recognise cluster tokens
+    #[serde(default)]
+    pub factory: Option<Addr>,
+    /// Maximum nesting depth of cluster tokens priced from their underlying assets
+    #[serde(default = "default_max_cluster_depth")]
+    pub max_cluster_depth: u8,
+}
+
+fn default_max_cluster_depth() -> u8 {
+    DEFAULT_MAX_CLUSTER_DEPTH
 }
 
 pub fn store_config(storage: &mut dyn Storage, config: &Config) -> StdResult<()> {
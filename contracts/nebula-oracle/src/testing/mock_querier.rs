@@ -3,6 +3,9 @@ use cosmwasm_std::{
     from_binary, from_slice, to_binary, Coin, ContractResult, Decimal, OwnedDeps, Querier,
     QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
 };
+use cw20::MinterResponse;
+use nebula_protocol::cluster::{ClusterStateResponse, TargetResponse};
+use nebula_protocol::cluster_factory::ClusterExistsResponse;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -36,6 +39,7 @@ pub struct WasmMockQuerier {
     pub base: MockQuerier<TerraQueryWrapper>,
     pub terra_oracle_querier: TerraOracleQuerier,
     pub tefi_oracle_querier: TefiOracleQuerier,
+    pub cluster_querier: ClusterQuerier,
 }
 
 impl Querier for WasmMockQuerier {
@@ -64,6 +68,14 @@ pub struct TefiOracleQuerier {
     pub assets: HashMap<String, Decimal>,
 }
 
+#[derive(Clone, Default)]
+pub struct ClusterQuerier {
+    // token address => minter address
+    pub minters: HashMap<String, String>,
+    // cluster contract address => cluster state
+    pub clusters: HashMap<String, ClusterStateResponse>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
@@ -71,6 +83,12 @@ pub enum QueryMsg {
         asset_token: String,
         timeframe: Option<u64>,
     },
+    Minter {},
+    ClusterExists {
+        contract_addr: String,
+    },
+    Target {},
+    ClusterState {},
 }
 
 impl WasmMockQuerier {
@@ -114,27 +132,55 @@ impl WasmMockQuerier {
                     panic!("DO NOT ENTER HERE")
                 }
             }
-            QueryRequest::Wasm(WasmQuery::Smart {
-                contract_addr: _,
-                msg,
-            }) => match from_binary(&msg).unwrap() {
-                QueryMsg::Price { asset_token, .. } => match self
-                    .tefi_oracle_querier
-                    .assets
-                    .get(&asset_token.to_string())
-                {
-                    Some(price) => {
-                        SystemResult::Ok(ContractResult::from(to_binary(&PriceResponse {
-                            rate: price.clone(),
-                            last_updated: u64::MAX,
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                match from_binary(&msg).unwrap() {
+                    QueryMsg::Price { asset_token, .. } => match self
+                        .tefi_oracle_querier
+                        .assets
+                        .get(&asset_token.to_string())
+                    {
+                        Some(price) => {
+                            SystemResult::Ok(ContractResult::from(to_binary(&PriceResponse {
+                                rate: price.clone(),
+                                last_updated: u64::MAX,
+                            })))
+                        }
+                        None => SystemResult::Err(SystemError::InvalidRequest {
+                            error: "No oracle price exists".to_string(),
+                            request: msg.as_slice().into(),
+                        }),
+                    },
+                    QueryMsg::Minter {} => {
+                        let res = self
+                            .cluster_querier
+                            .minters
+                            .get(contract_addr)
+                            .map(|minter| MinterResponse {
+                                minter: minter.to_string(),
+                                cap: None,
+                            });
+                        SystemResult::Ok(ContractResult::from(to_binary(&res)))
+                    }
+                    QueryMsg::ClusterExists { contract_addr } => {
+                        SystemResult::Ok(ContractResult::from(to_binary(&ClusterExistsResponse {
+                            exists: self.cluster_querier.clusters.contains_key(&contract_addr),
+                        })))
+                    }
+                    QueryMsg::Target {} => {
+                        let state = self.cluster_querier.clusters.get(contract_addr).unwrap();
+                        SystemResult::Ok(ContractResult::from(to_binary(&TargetResponse {
+                            target: state.target.clone(),
+                            final_target: state.final_target.clone(),
+                            value_weights: None,
+                            final_value_weights: None,
                         })))
                     }
-                    None => SystemResult::Err(SystemError::InvalidRequest {
-                        error: "No oracle price exists".to_string(),
-                        request: msg.as_slice().into(),
-                    }),
-                },
-            },
+                    QueryMsg::ClusterState {} => {
+                        let state = self.cluster_querier.clusters.get(contract_addr).unwrap();
+                        SystemResult::Ok(ContractResult::from(to_binary(state)))
+                    }
+                }
+            }
             _ => self.base.handle_query(request),
         }
     }
@@ -146,6 +192,7 @@ impl WasmMockQuerier {
             base,
             tefi_oracle_querier: TefiOracleQuerier::default(),
             terra_oracle_querier: TerraOracleQuerier::default(),
+            cluster_querier: ClusterQuerier::default(),
         }
     }
 
@@ -180,4 +227,22 @@ impl WasmMockQuerier {
         }
         self
     }
+
+    pub fn set_token_minter(&mut self, token_address: &str, minter: &str) -> &mut Self {
+        self.cluster_querier
+            .minters
+            .insert(token_address.to_string(), minter.to_string());
+        self
+    }
+
+    pub fn set_cluster_state(
+        &mut self,
+        cluster_contract: &str,
+        state: ClusterStateResponse,
+    ) -> &mut Self {
+        self.cluster_querier
+            .clusters
+            .insert(cluster_contract.to_string(), state);
+        self
+    }
 }
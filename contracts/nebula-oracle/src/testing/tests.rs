@@ -2,10 +2,11 @@ use crate::contract::{execute, instantiate, migrate, query};
 use crate::error::ContractError;
 use crate::state::{read_config, Config};
 use crate::testing::mock_querier::mock_dependencies;
-use astroport::asset::AssetInfo;
+use astroport::asset::{Asset, AssetInfo};
 use cosmwasm_std::testing::{mock_env, mock_info};
-use cosmwasm_std::{from_binary, Addr, Decimal, StdError};
+use cosmwasm_std::{from_binary, Addr, Decimal, StdError, Uint128};
 use cw2::{get_contract_version, ContractVersion};
+use nebula_protocol::cluster::ClusterStateResponse;
 use nebula_protocol::oracle::{
     ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, PriceResponse, QueryMsg,
 };
@@ -16,6 +17,44 @@ fn init_msg() -> InstantiateMsg {
         owner: "owner0000".to_string(),
         oracle_addr: "oracle0000".to_string(),
         base_denom: "uusd".to_string(),
+        factory: Some("factory0000".to_string()),
+        max_cluster_depth: None,
+    }
+}
+
+fn cluster_state(
+    cluster_token: &str,
+    assets: Vec<AssetInfo>,
+    prices: Vec<&str>,
+    inv: Vec<u128>,
+    supply: u128,
+    price_ages: Vec<u64>,
+) -> ClusterStateResponse {
+    let inv_len = inv.len();
+    let target = assets
+        .into_iter()
+        .map(|info| Asset {
+            info,
+            amount: Uint128::new(1),
+        })
+        .collect::<Vec<_>>();
+    ClusterStateResponse {
+        outstanding_balance_tokens: Uint128::new(supply),
+        accrued_fee: Uint128::zero(),
+        prices: prices.iter().map(|p| p.to_string()).collect(),
+        inv: inv.into_iter().map(Uint128::new).collect(),
+        penalty: "penalty0000".to_string(),
+        cluster_token: cluster_token.to_string(),
+        target: target.clone(),
+        final_target: target,
+        cluster_contract_address: "cluster".to_string(),
+        active: true,
+        price_ages,
+        supply_headroom: None,
+        inventory_headroom: vec![],
+        create_paused: false,
+        custom_redeem_paused: false,
+        asset_decimals: vec![6; inv_len],
     }
 }
 
@@ -34,6 +73,8 @@ fn proper_initialization() {
             owner: Addr::unchecked("owner0000"),
             oracle_addr: Addr::unchecked("oracle0000"),
             base_denom: "uusd".to_string(),
+            factory: Some(Addr::unchecked("factory0000")),
+            max_cluster_depth: 2,
         }
     );
 }
@@ -52,6 +93,8 @@ fn update_config() {
         owner: Some("imposter0000".to_string()),
         oracle_addr: Some("oracle0001".to_string()),
         base_denom: None,
+        factory: None,
+        max_cluster_depth: None,
     };
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
     assert_eq!(res, ContractError::Unauthorized {});
@@ -62,6 +105,8 @@ fn update_config() {
         owner: Some("owner0001".to_string()),
         oracle_addr: Some("oracle0001".to_string()),
         base_denom: Some("uusd".to_string()),
+        factory: Some("factory0001".to_string()),
+        max_cluster_depth: Some(3),
     };
     let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
     let config = read_config(&deps.storage).unwrap();
@@ -70,7 +115,9 @@ fn update_config() {
         Config {
             owner: Addr::unchecked("owner0001"),
             oracle_addr: Addr::unchecked("oracle0001"),
-            base_denom: "uusd".to_string()
+            base_denom: "uusd".to_string(),
+            factory: Some(Addr::unchecked("factory0001")),
+            max_cluster_depth: 3,
         }
     )
 }
@@ -93,6 +140,8 @@ fn query_config() {
             owner: "owner0000".to_string(),
             oracle_addr: "oracle0000".to_string(),
             base_denom: "uusd".to_string(),
+            factory: Some("factory0000".to_string()),
+            max_cluster_depth: 2,
         }
     )
 }
@@ -176,6 +225,139 @@ fn query_price() {
     );
 }
 
+#[test]
+fn query_cluster_token_price() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("sender0000", &[]);
+    let msg = init_msg();
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    deps.querier.set_terra_oracle_prices(vec![
+        ("uluna", Decimal::from_str("2").unwrap()),
+        ("uusd", Decimal::from_str("1.00").unwrap()),
+    ]);
+
+    // cluster0000 holds 100 token0001 and 50 uluna, valued at 76652 uusd
+    deps.querier
+        .set_token_minter("ctoken0000", "cluster0000")
+        .set_cluster_state(
+            "cluster0000",
+            cluster_state(
+                "ctoken0000",
+                vec![
+                    AssetInfo::Token {
+                        contract_addr: Addr::unchecked("token0001"),
+                    },
+                    AssetInfo::NativeToken {
+                        denom: "uluna".to_string(),
+                    },
+                ],
+                vec!["765.52", "2"],
+                vec![100, 50],
+                1_000,
+                vec![30, 0],
+            ),
+        );
+
+    // cluster0001 holds 10 ctoken0000, valued at 766.52 uusd
+    deps.querier
+        .set_token_minter("ctoken0001", "cluster0001")
+        .set_cluster_state(
+            "cluster0001",
+            cluster_state(
+                "ctoken0001",
+                vec![AssetInfo::Token {
+                    contract_addr: Addr::unchecked("ctoken0000"),
+                }],
+                vec!["76.652"],
+                vec![10],
+                100,
+                vec![30],
+            ),
+        );
+
+    let env = mock_env();
+    let now = env.block.time.seconds();
+
+    // cluster token is priced at its net asset value
+    let msg = QueryMsg::Price {
+        base_asset: AssetInfo::Token {
+            contract_addr: Addr::unchecked("ctoken0000"),
+        },
+        quote_asset: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    };
+    let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let price: PriceResponse = from_binary(&res).unwrap();
+    assert_eq!(price.rate, Decimal::from_str("76.652").unwrap());
+    // staleness propagates from the oldest underlying price
+    assert_eq!(price.last_updated_base, now - 30);
+
+    // quoted in another cluster token
+    let msg = QueryMsg::Price {
+        base_asset: AssetInfo::NativeToken {
+            denom: "uluna".to_string(),
+        },
+        quote_asset: AssetInfo::Token {
+            contract_addr: Addr::unchecked("ctoken0000"),
+        },
+    };
+    let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let price: PriceResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        price.rate,
+        Decimal::from_ratio(2_000_000_000u128, 76_652_000_000u128)
+    );
+    assert_eq!(price.last_updated_quote, now - 30);
+
+    // nested cluster token
+    let msg = QueryMsg::Price {
+        base_asset: AssetInfo::Token {
+            contract_addr: Addr::unchecked("ctoken0001"),
+        },
+        quote_asset: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    };
+    let res = query(deps.as_ref(), env.clone(), msg.clone()).unwrap();
+    let price: PriceResponse = from_binary(&res).unwrap();
+    assert_eq!(price.rate, Decimal::from_str("7.6652").unwrap());
+
+    // a token minted by a cluster but not its cluster token
+    deps.querier.set_token_minter("fake0000", "cluster0000");
+    let fake_msg = QueryMsg::Price {
+        base_asset: AssetInfo::Token {
+            contract_addr: Addr::unchecked("fake0000"),
+        },
+        quote_asset: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    };
+    let res = query(deps.as_ref(), env.clone(), fake_msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Token is not the cluster token of its minter cluster")
+    );
+
+    // nesting deeper than the maximum depth
+    let info = mock_info("owner0000", &[]);
+    let update_msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        oracle_addr: None,
+        base_denom: None,
+        factory: None,
+        max_cluster_depth: Some(1),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, update_msg).unwrap();
+
+    let res = query(deps.as_ref(), env, msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Cluster tokens are nested deeper than the maximum depth of 1")
+    );
+}
+
 #[test]
 fn migration() {
     let mut deps = mock_dependencies(&[]);
@@ -2,26 +2,40 @@
 use cosmwasm_std::entry_point;
 
 use cosmwasm_std::{
-    attr, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
-    Uint128,
+    attr, to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, QueryRequest, Response,
+    StdError, StdResult, Uint128, WasmQuery,
 };
 
+use crate::curve::{
+    penalty_area, penalty_cutoffs, penalty_rate, reward_area, reward_params,
+    validate_penalty_params,
+};
 use crate::error::ContractError;
-use crate::state::{config_store, read_config, store_config, PenaltyConfig};
+use crate::state::{
+    config_store, read_config, read_legacy_config, read_penalty_state, remove_cluster_params,
+    store_cluster_params, store_config, store_ema_state, EmaState, PenaltyConfig,
+};
 use cluster_math::{
-    add, div_const, dot, imbalance, int_vec_to_fpdec, mul_const, str_vec_to_fpdec, sub, FPDecimal,
+    add, div_const, dot, imbalance, int_vec_to_fpdec, min_imbalance_allocation,
+    min_imbalance_withdrawal, mul_const, str_vec_to_fpdec, sub, FPDecimal,
 };
 use cw2::set_contract_version;
+use nebula_protocol::cluster_factory::{ClusterRegisteredResponse, QueryMsg as FactoryQueryMsg};
 use nebula_protocol::penalty::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, ParamsResponse, PenaltyCreateResponse,
-    PenaltyNotionalResponse, PenaltyParams, PenaltyRedeemResponse, QueryMsg,
+    ConfigResponse, EmaHalfLife, ExecuteMsg, InstantiateMsg, MigrateMsg, OptimalBasketResponse,
+    ParamsResponse, PenaltyCreateResponse, PenaltyCurvePoint, PenaltyCurveResponse,
+    PenaltyNotionalResponse, PenaltyParams, PenaltyRedeemResponse, PenaltySwapResponse,
+    ProjectedEmaResponse, QueryMsg, RebalanceBudget,
 };
-use std::cmp::{max, min};
+use std::cmp::min;
+use std::str::FromStr;
 
 /// Contract name that is used for migration.
 const CONTRACT_NAME: &str = "nebula-penalty";
 /// Contract version that is used for migration.
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Maximum number of points sampled by the penalty curve query.
+const MAX_CURVE_POINTS: u32 = 100;
 
 /// ## Description
 /// Creates a new contract with the specified parameters packed in the `msg` variable.
@@ -45,11 +59,7 @@ pub fn instantiate(
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    if msg.penalty_params.penalty_amt_hi != FPDecimal::one() {
-        return Err(ContractError::Generic(
-            "penalty amount must reach one".to_string(),
-        ));
-    }
+    validate_penalty_params(&msg.penalty_params)?;
 
     let cfg = PenaltyConfig {
         owner: deps.api.addr_validate(msg.owner.as_str())?,
@@ -60,38 +70,71 @@ pub fn instantiate(
 
         // Know to fast forward to current net asset value if last_block == 0
         last_block: 0u64,
+        last_time: 0u64,
+
+        // Clusters of the factory share the contract if given
+        factory: msg
+            .factory
+            .map(|factory| deps.api.addr_validate(factory.as_str()))
+            .transpose()?,
     };
     store_config(deps.storage, &cfg)?;
     Ok(Response::default())
 }
 
 /// ## Description
-/// Compute EMA at the specific block height.
+/// Compute EMA at the specific block height and time.
 ///
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`] which is the height to compute EMA at.
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds to compute EMA at.
+///
 /// - **net_asset_val** (NAV) is an object of type [`FPDecimal`] which is the sum of assets in
 ///     the inventory times their prices -- sum(asset_inv_i * price_i).
-pub fn get_ema(deps: Deps, block_height: u64, net_asset_val: FPDecimal) -> StdResult<FPDecimal> {
-    let cfg = read_config(deps.storage)?;
+pub fn get_ema(
+    deps: Deps,
+    cluster: Option<&Addr>,
+    block_height: u64,
+    block_time: u64,
+    net_asset_val: FPDecimal,
+) -> StdResult<FPDecimal> {
+    let (penalty_params, state) = read_penalty_state(deps.storage, cluster)?;
     // Get the previous rebalanced EMA
-    let prev_ema = cfg.ema;
+    let prev_ema = state.ema;
     // Get the previous rebalanced block
-    let prev_block = cfg.last_block;
+    let prev_block = state.last_block;
     if prev_block != 0u64 {
-        // How many blocks has passed from the previous rebalance
-        // -- dt = block_height - prev_block
-        let dt = FPDecimal::from((block_height - prev_block) as u128);
-
-        // Hard code one hour (600 blocks)
-        // -- tau = -600
-        let tau = FPDecimal::from(-600i128);
         // Weight ratio for EMA
-        // -- factor = exp(dt/tau) = 1 / exp(dt/600)
-        let factor = FPDecimal::_exp(dt / tau);
+        let factor = match penalty_params.ema_half_life() {
+            None => {
+                // How many blocks has passed from the previous rebalance
+                // -- dt = block_height - prev_block
+                let dt = FPDecimal::from(elapsed(block_height, prev_block)? as u128);
+
+                // Default to one hour (600 blocks)
+                // -- tau = -600
+                let tau = FPDecimal::from(-600i128);
+                // -- factor = exp(dt/tau) = 1 / exp(dt/600)
+                FPDecimal::_exp(dt / tau)
+            }
+            Some(EmaHalfLife::Blocks(half_life)) => {
+                decay_factor(elapsed(block_height, prev_block)?, half_life)
+            }
+            Some(EmaHalfLife::Seconds(half_life)) => {
+                if state.last_time == 0u64 {
+                    // No block time recorded yet, keep the previous EMA
+                    FPDecimal::one()
+                } else {
+                    decay_factor(elapsed(block_time, state.last_time)?, half_life)
+                }
+            }
+        };
 
         // Compute EMA
         // -- EMA = factor * prev_ema + (1 - factor) * NAV
@@ -102,13 +145,40 @@ pub fn get_ema(deps: Deps, block_height: u64, net_asset_val: FPDecimal) -> StdRe
     }
 }
 
+/// ## Description
+/// Returns the blocks or seconds elapsed since the previous rebalance.
+/// Returns a [`StdError`] if `now` is before the previous rebalance.
+///
+/// ## Params
+/// - **now** is an object of type [`u64`] which is the current height or time.
+///
+/// - **prev** is an object of type [`u64`] which is the height or time of the previous rebalance.
+fn elapsed(now: u64, prev: u64) -> StdResult<u64> {
+    now.checked_sub(prev)
+        .ok_or_else(|| StdError::generic_err("Cannot compute the EMA before the last rebalance"))
+}
+
+/// ## Description
+/// Returns the weight of the previous EMA after `dt` blocks or seconds.
+/// -- factor = exp(-dt * ln(2) / half_life) = 2^(-dt / half_life)
+///
+/// ## Params
+/// - **dt** is an object of type [`u64`] which is the elapsed blocks or seconds.
+///
+/// - **half_life** is an object of type [`u64`] which is the EMA half-life in the same unit.
+fn decay_factor(dt: u64, half_life: u64) -> FPDecimal {
+    let exponent = FPDecimal::from(dt as u128) * FPDecimal::_ln(FPDecimal::from(2i128))
+        / FPDecimal::from(half_life as u128);
+    FPDecimal::_exp(FPDecimal::zero() - exponent)
+}
+
 /// ## Description
 /// Exposes all the execute functions available in the contract.
 ///
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
 ///
-/// - **_env** is an object of type [`Env`].
+/// - **env** is an object of type [`Env`].
 ///
 /// - **info** is an object of type [`MessageInfo`].
 ///
@@ -120,6 +190,11 @@ pub fn get_ema(deps: Deps, block_height: u64, net_asset_val: FPDecimal) -> StdRe
 ///             penalty_params,
 ///         }** Updates general penalty contract parameters.
 ///
+/// - **ExecuteMsg::UpdateClusterParams {
+///             cluster_contract,
+///             penalty_params,
+///         }** Overrides the penalty contract parameters of a cluster sharing the contract.
+///
 /// - **ExecuteMsg::PenaltyCreate {
 ///             block_height,
 ///             cluster_token_supply,
@@ -139,27 +214,57 @@ pub fn get_ema(deps: Deps, block_height: u64, net_asset_val: FPDecimal) -> StdRe
 ///             target_weights,
 ///         }** Updates penalty contract states, EMA and last block, after a redeem operation.
 ///
+/// - **ExecuteMsg::PenaltySwap {
+///             block_height,
+///             inventory,
+///             offer_asset_amounts,
+///             ask_asset_amounts,
+///             asset_prices,
+///             target_weights,
+///         }** Updates penalty contract states, EMA and last block, after a swap operation.
+///
 /// ## Executor
-/// Only the owner can execute this.
+/// Only the owner can execute this. If the penalty contract is shared, `PenaltyCreate`,
+/// `PenaltyRedeem` and `PenaltySwap` are instead executed by clusters registered in the factory.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     let cfg = read_config(deps.storage)?;
 
     // Permission check
-    if info.sender != cfg.owner {
-        return Err(ContractError::Unauthorized {});
-    }
+    let cluster = match &cfg.factory {
+        // Clusters registered in the factory rebalance a shared penalty contract
+        Some(factory)
+            if !matches!(
+                msg,
+                ExecuteMsg::UpdateConfig { .. } | ExecuteMsg::UpdateClusterParams { .. }
+            ) =>
+        {
+            assert_factory_cluster(deps.as_ref(), factory, &info.sender)?;
+            Some(info.sender)
+        }
+        _ => {
+            if info.sender != cfg.owner {
+                return Err(ContractError::Unauthorized {});
+            }
+            None
+        }
+    };
+    let cluster = cluster.as_ref();
 
     match msg {
         ExecuteMsg::UpdateConfig {
             owner,
             penalty_params,
         } => update_config(deps, owner, penalty_params),
+        ExecuteMsg::UpdateClusterParams {
+            cluster_contract,
+            penalty_params,
+        } => update_cluster_params(deps, cluster_contract, penalty_params),
         ExecuteMsg::PenaltyCreate {
             block_height,
             cluster_token_supply,
@@ -169,7 +274,9 @@ pub fn execute(
             target_weights,
         } => execute_mint(
             deps,
+            cluster,
             block_height,
+            env.block.time.seconds(),
             &cluster_token_supply,
             &inventory,
             &create_asset_amounts,
@@ -186,7 +293,9 @@ pub fn execute(
             target_weights,
         } => execute_redeem(
             deps,
+            cluster,
             block_height,
+            env.block.time.seconds(),
             &cluster_token_supply,
             &inventory,
             &max_tokens,
@@ -194,6 +303,24 @@ pub fn execute(
             &asset_prices,
             &target_weights,
         ),
+        ExecuteMsg::PenaltySwap {
+            block_height,
+            inventory,
+            offer_asset_amounts,
+            ask_asset_amounts,
+            asset_prices,
+            target_weights,
+        } => execute_swap(
+            deps,
+            cluster,
+            block_height,
+            env.block.time.seconds(),
+            &inventory,
+            &offer_asset_amounts,
+            &ask_asset_amounts,
+            &asset_prices,
+            &target_weights,
+        ),
     }
 }
 
@@ -212,6 +339,10 @@ pub fn update_config(
     owner: Option<String>,
     penalty_params: Option<PenaltyParams>,
 ) -> Result<Response, ContractError> {
+    if let Some(penalty_params) = &penalty_params {
+        validate_penalty_params(penalty_params)?;
+    }
+
     let api = deps.api;
     config_store(deps.storage).update(|mut config| -> StdResult<_> {
         if let Some(owner) = owner {
@@ -229,14 +360,84 @@ pub fn update_config(
     Ok(Response::new().add_attributes(vec![attr("action", "update_config")]))
 }
 
+/// ## Description
+/// Overrides the penalty contract parameters of a cluster sharing the penalty contract,
+/// or removes its override to fall back to the default parameters.
+/// Returns a [`ContractError`] on failure.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **cluster_contract** is an object of type [`String`] which is the address of the cluster.
+///
+/// - **penalty_params** is an object of type [`Option<PenaltyParams>`] which are the
+///     parameters of the cluster.
+pub fn update_cluster_params(
+    deps: DepsMut,
+    cluster_contract: String,
+    penalty_params: Option<PenaltyParams>,
+) -> Result<Response, ContractError> {
+    let cfg = read_config(deps.storage)?;
+    if cfg.factory.is_none() {
+        return Err(ContractError::Generic(
+            "Cluster parameters require a shared penalty contract".to_string(),
+        ));
+    }
+
+    // Validate address format
+    let cluster = deps.api.addr_validate(cluster_contract.as_str())?;
+    match penalty_params {
+        Some(penalty_params) => {
+            validate_penalty_params(&penalty_params)?;
+            store_cluster_params(deps.storage, &cluster, &penalty_params)?;
+        }
+        None => remove_cluster_params(deps.storage, &cluster),
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_cluster_params"),
+        attr("cluster_contract", cluster.to_string()),
+    ]))
+}
+
+/// ## Description
+/// Returns a [`ContractError`] if the sender is not a cluster registered in the factory.
+/// Decommissioned clusters stay registered so that they can still be redeemed.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **factory** is a reference to an object of type [`Addr`] which is the factory contract.
+///
+/// - **sender** is a reference to an object of type [`Addr`] which is the sender.
+fn assert_factory_cluster(deps: Deps, factory: &Addr, sender: &Addr) -> Result<(), ContractError> {
+    let res: ClusterRegisteredResponse =
+        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: factory.to_string(),
+            msg: to_binary(&FactoryQueryMsg::ClusterRegistered {
+                contract_addr: sender.to_string(),
+            })?,
+        }))?;
+
+    if !res.registered {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
 /// ## Description
 /// Updates penalty contract states, EMA and last block, after a create operation.
 ///
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`] is a specific height to compute mint at.
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
 /// - [DEPRECATED] ~~**_cluster_token_supply** is a reference to an object of type [`Uint128`] which is the current
 ///     total supply for a cluster token.~~
 ///
@@ -251,9 +452,12 @@ pub fn update_config(
 ///
 /// - [DEPRECATED] ~~**_target_weights** is a reference to an array containing objects of type [`Uint128`] which are
 ///     the current target weights of the assets in a cluster.~~
+#[allow(clippy::too_many_arguments)]
 pub fn execute_mint(
     deps: DepsMut,
+    cluster: Option<&Addr>,
     block_height: u64,
+    block_time: u64,
     _cluster_token_supply: &Uint128,
     inventory: &[Uint128],
     _create_asset_amounts: &[Uint128],
@@ -266,7 +470,7 @@ pub fn execute_mint(
     let p = str_vec_to_fpdec(asset_prices)?;
 
     // Compute and update EMA and last block of the penalty contract
-    update_ema(deps, block_height, dot(&i, &p))
+    update_ema(deps, cluster, block_height, block_time, dot(&i, &p))
 }
 
 /// ## Description
@@ -275,8 +479,13 @@ pub fn execute_mint(
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`] is a specific height to compute mint at.
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
 /// - [DEPRECATED] ~~**_cluster_token_supply** is a reference to an object of type [`Uint128`] which is the current
 ///     total supply for a cluster token.~~
 ///
@@ -297,7 +506,9 @@ pub fn execute_mint(
 #[allow(clippy::too_many_arguments)]
 pub fn execute_redeem(
     deps: DepsMut,
+    cluster: Option<&Addr>,
     block_height: u64,
+    block_time: u64,
     _cluster_token_supply: &Uint128,
     inventory: &[Uint128],
     _max_tokens: &Uint128,
@@ -311,7 +522,55 @@ pub fn execute_redeem(
     let p = str_vec_to_fpdec(asset_prices)?;
 
     // Compute and update EMA and last block of the penalty contract
-    update_ema(deps, block_height, dot(&i, &p))
+    update_ema(deps, cluster, block_height, block_time, dot(&i, &p))
+}
+
+/// ## Description
+/// Updates penalty contract states, EMA and last block, after a swap operation.
+///
+/// ## Params
+/// - **deps** is an object of type [`DepsMut`].
+///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
+/// - **block_height** is an object of type [`u64`] is a specific height to compute swap at.
+///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
+/// - **inventory** is a reference to an array containing objects of type [`Uint128`] which is the
+///     current inventory of inventory assets in a cluster.
+///
+/// - **_offer_asset_amounts** is a reference to an array containing objects of type [`Uint128`] which
+///     are the offered asset amounts.
+///
+/// - **_ask_asset_amounts** is a reference to an array containing objects of type [`Uint128`] which
+///     are the asked asset amounts of the same value as the offered assets.
+///
+/// - **asset_prices** is a reference to an array containing objects of type [`String`] which are the
+///     prices of the inventory assets in a cluster.
+///
+/// - **_target_weights** is a reference to an array containing objects of type [`Uint128`] which are
+///     the current target weights of the assets in a cluster.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_swap(
+    deps: DepsMut,
+    cluster: Option<&Addr>,
+    block_height: u64,
+    block_time: u64,
+    inventory: &[Uint128],
+    _offer_asset_amounts: &[Uint128],
+    _ask_asset_amounts: &[Uint128],
+    asset_prices: &[String],
+    _target_weights: &[Uint128],
+) -> Result<Response, ContractError> {
+    // Retrieve the current asset inventory as `Vec<FPDecimal>`
+    let i = int_vec_to_fpdec(inventory);
+    // Retrieve the current asset prices as `Vec<FPDecimal>`
+    let p = str_vec_to_fpdec(asset_prices)?;
+
+    // Compute and update EMA and last block of the penalty contract
+    update_ema(deps, cluster, block_height, block_time, dot(&i, &p))
 }
 
 /// ## Description
@@ -320,24 +579,39 @@ pub fn execute_redeem(
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`] which is a height to compute a new EMA.
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds to compute a new EMA.
+///
 /// - **net_asset_val** (NAV) is an object of type [`FPDecimal`] which is the sum of assets in
 ///     the inventory times their prices -- sum(asset_inv_i * price_i).
 pub fn update_ema(
     deps: DepsMut,
+    cluster: Option<&Addr>,
     block_height: u64,
+    block_time: u64,
     net_asset_val: FPDecimal,
 ) -> Result<Response, ContractError> {
-    let mut cfg = read_config(deps.storage)?;
-    // Calculate and save the new EMA at the given `block_height`
-    cfg.ema = get_ema(deps.as_ref(), block_height, net_asset_val)?;
-    // Store `block_height` as the new last block
-    cfg.last_block = block_height;
+    // Calculate and save the new EMA at the given `block_height`, with
+    // `block_height` and `block_time` as the new last block
+    let state = EmaState {
+        ema: get_ema(
+            deps.as_ref(),
+            cluster,
+            block_height,
+            block_time,
+            net_asset_val,
+        )?,
+        last_block: block_height,
+        last_time: block_time,
+    };
 
     // Save the state
-    store_config(deps.storage, &cfg)?;
-    Ok(Response::new().add_attributes(vec![attr("new_ema", cfg.ema.to_string())]))
+    store_ema_state(deps.storage, cluster, &state)?;
+    Ok(Response::new().add_attributes(vec![attr("new_ema", state.ema.to_string())]))
 }
 
 /// ## Description
@@ -346,12 +620,12 @@ pub fn update_ema(
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
-/// - **_env** is an object of type [`Env`].
+/// - **env** is an object of type [`Env`].
 ///
 /// - **msg** is an object of type [`QueryMsg`].
 ///
 /// ## Commands
-/// - **QueryMsg::Params {}** Returns general contract parameters using a custom [`ParamsResponse`] structure.
+/// - **QueryMsg::Params { cluster_contract }** Returns general contract parameters using a custom [`ParamsResponse`] structure.
 ///
 /// - **QueryMsg::PenaltyQueryCreate {
 ///             block_height,
@@ -372,6 +646,15 @@ pub fn update_ema(
 ///             target_weights,
 ///         }** Calculates the actual redeem amount after taking penalty into consideration.
 ///
+/// - **QueryMsg::PenaltyQuerySwap {
+///             block_height,
+///             inventory,
+///             offer_asset_amounts,
+///             ask_asset_amounts,
+///             asset_prices,
+///             target_weights,
+///         }** Calculates the actual asked asset amounts after taking penalty into consideration.
+///
 /// - **QueryMsg::QueryNotionalPenalty {
 ///             block_height,
 ///             inventory0,
@@ -379,11 +662,35 @@ pub fn update_ema(
 ///             target_weights,
 ///             asset_prices,
 ///         }** Calculates the notional penalty based on the inventory change at the given height.
+///
+/// - **QueryMsg::ProjectedEma {
+///             block_height,
+///             block_time,
+///             net_asset_val,
+///         }** Returns the EMA projected to a future height or time.
+///
+/// - **QueryMsg::PenaltyCurve {
+///             inventory,
+///             asset_prices,
+///             target_weights,
+///             points,
+///         }** Samples the penalty curve of a cluster at the current height.
+///
+/// - **QueryMsg::OptimalBasket {
+///             cluster_token_supply,
+///             inventory,
+///             asset_prices,
+///             target_weights,
+///             budget,
+///         }** Returns the create or redeem basket within a budget that minimizes the imbalance.
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::Params {} => to_binary(&get_params(deps)?),
+        QueryMsg::Params { cluster_contract } => to_binary(&get_params(
+            deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
+        )?),
         QueryMsg::PenaltyQueryCreate {
             block_height,
             cluster_token_supply,
@@ -391,9 +698,12 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             create_asset_amounts,
             asset_prices,
             target_weights,
+            cluster_contract,
         } => to_binary(&compute_mint(
             deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
             block_height,
+            env.block.time.seconds(),
             &cluster_token_supply,
             &inventory,
             &create_asset_amounts,
@@ -408,9 +718,12 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             redeem_asset_amounts,
             asset_prices,
             target_weights,
+            cluster_contract,
         } => to_binary(&compute_redeem(
             deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
             block_height,
+            env.block.time.seconds(),
             &cluster_token_supply,
             &inventory,
             &max_tokens,
@@ -418,20 +731,88 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             &asset_prices,
             &target_weights,
         )?),
+        QueryMsg::PenaltyQuerySwap {
+            block_height,
+            inventory,
+            offer_asset_amounts,
+            ask_asset_amounts,
+            asset_prices,
+            target_weights,
+            cluster_contract,
+        } => to_binary(&compute_swap(
+            deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
+            block_height,
+            env.block.time.seconds(),
+            &inventory,
+            &offer_asset_amounts,
+            &ask_asset_amounts,
+            &asset_prices,
+            &target_weights,
+        )?),
+        QueryMsg::ProjectedEma {
+            block_height,
+            block_time,
+            net_asset_val,
+            cluster_contract,
+        } => to_binary(&query_projected_ema(
+            deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
+            block_height.unwrap_or(env.block.height),
+            block_time.unwrap_or_else(|| env.block.time.seconds()),
+            net_asset_val,
+        )?),
         QueryMsg::PenaltyQueryNotional {
             block_height,
             inventory0,
             inventory1,
             asset_prices,
             target_weights,
+            cluster_contract,
         } => to_binary(&compute_notional_penalty(
             deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
             block_height,
+            env.block.time.seconds(),
             &inventory0,
             &inventory1,
             &asset_prices,
             &target_weights,
         )?),
+        QueryMsg::PenaltyCurve {
+            inventory,
+            asset_prices,
+            target_weights,
+            points,
+            cluster_contract,
+        } => to_binary(&query_penalty_curve(
+            deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
+            env.block.height,
+            env.block.time.seconds(),
+            &inventory,
+            &asset_prices,
+            &target_weights,
+            points,
+        )?),
+        QueryMsg::OptimalBasket {
+            cluster_token_supply,
+            inventory,
+            asset_prices,
+            target_weights,
+            budget,
+            cluster_contract,
+        } => to_binary(&query_optimal_basket(
+            deps,
+            query_cluster(deps, cluster_contract)?.as_ref(),
+            env.block.height,
+            env.block.time.seconds(),
+            &cluster_token_supply,
+            &inventory,
+            &asset_prices,
+            &target_weights,
+            budget,
+        )?),
     }
 }
 
@@ -445,6 +826,7 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let resp = ConfigResponse {
         owner: state.owner.to_string(),
         penalty_params: state.penalty_params,
+        factory: state.factory.map(|factory| factory.to_string()),
     };
 
     Ok(resp)
@@ -455,12 +837,70 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
 ///
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
-pub fn get_params(deps: Deps) -> StdResult<ParamsResponse> {
-    let cfg = read_config(deps.storage)?;
+///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+pub fn get_params(deps: Deps, cluster: Option<&Addr>) -> StdResult<ParamsResponse> {
+    let (penalty_params, state) = read_penalty_state(deps.storage, cluster)?;
     Ok(ParamsResponse {
-        penalty_params: cfg.penalty_params,
-        last_block: cfg.last_block,
-        ema: cfg.ema.to_string(),
+        penalty_params,
+        last_block: state.last_block,
+        last_time: state.last_time,
+        ema: state.ema.to_string(),
+    })
+}
+
+/// ## Description
+/// Returns the cluster a query computes for if the penalty contract is shared, or `None`
+/// if a single cluster owns it. Returns a [`StdError`] if a shared penalty contract
+/// is not given the cluster.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster_contract** is an object of type [`Option<String>`] which is the address of the cluster.
+fn query_cluster(deps: Deps, cluster_contract: Option<String>) -> StdResult<Option<Addr>> {
+    let cfg = read_config(deps.storage)?;
+    match (cfg.factory, cluster_contract) {
+        (None, _) => Ok(None),
+        (Some(_), Some(cluster_contract)) => {
+            Ok(Some(deps.api.addr_validate(cluster_contract.as_str())?))
+        }
+        (Some(_), None) => Err(StdError::generic_err(
+            "A shared penalty contract requires the cluster contract",
+        )),
+    }
+}
+
+/// ## Description
+/// Returns the EMA projected to a future height or time using a custom [`ProjectedEmaResponse`]
+/// structure, assuming the net asset value stays at `net_asset_val` until then.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
+/// - **block_height** is an object of type [`u64`] which is the height to project the EMA to.
+///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds to project the EMA to.
+///
+/// - **net_asset_val** is an object of type [`String`] which is the net asset value of the cluster
+///     until the projected height or time.
+pub fn query_projected_ema(
+    deps: Deps,
+    cluster: Option<&Addr>,
+    block_height: u64,
+    block_time: u64,
+    net_asset_val: String,
+) -> StdResult<ProjectedEmaResponse> {
+    let net_asset_val = FPDecimal::from_str(&net_asset_val)?;
+    let ema = get_ema(deps, cluster, block_height, block_time, net_asset_val)?;
+    Ok(ProjectedEmaResponse {
+        block_height,
+        block_time,
+        ema: ema.to_string(),
     })
 }
 
@@ -470,8 +910,13 @@ pub fn get_params(deps: Deps) -> StdResult<ParamsResponse> {
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`] is a specific height to compute mint at.
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
 /// - **cluster_token_supply** is a reference to an object of type [`Uint128`] which is the current
 ///     total supply for a cluster token.
 ///
@@ -486,9 +931,12 @@ pub fn get_params(deps: Deps) -> StdResult<ParamsResponse> {
 ///
 /// - **target_weights** is a reference to an array containing objects of type [`Uint128`] which are
 ///     the current target weights of the assets in a cluster.
+#[allow(clippy::too_many_arguments)]
 pub fn compute_mint(
     deps: Deps,
+    cluster: Option<&Addr>,
     block_height: u64,
+    block_time: u64,
     cluster_token_supply: &Uint128,
     inventory: &[Uint128],
     create_asset_amounts: &[Uint128],
@@ -512,7 +960,8 @@ pub fn compute_mint(
     // Compute penalty / reward from this rebalance
     // -- penalty if < 0
     // -- reward if > 0
-    let (penalty, _, _) = notional_penalty(deps, block_height, &i0, &i1, &w, &p)?;
+    let (penalty, _, _) =
+        notional_penalty(deps, cluster, block_height, block_time, &i0, &i1, &w, &p)?;
     // Compute the value of the provided assets with penalty
     // -- notional_value = value_of_the_provided_assets + penalty
     //                   = sum(provided_asset_i * price_i) + penalty
@@ -542,8 +991,13 @@ pub fn compute_mint(
 /// ## Params
 /// - **deps** is an object of type [`DepsMut`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`] is a specific height to compute mint at.
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
 /// - **cluster_token_supply** is a reference to an object of type [`Uint128`] which is the current
 ///     total supply for a cluster token.
 ///
@@ -564,7 +1018,9 @@ pub fn compute_mint(
 #[allow(clippy::many_single_char_names, clippy::too_many_arguments)]
 pub fn compute_redeem(
     deps: Deps,
+    cluster: Option<&Addr>,
     block_height: u64,
+    block_time: u64,
     cluster_token_supply: &Uint128,
     inventory: &[Uint128],
     max_tokens: &Uint128,
@@ -608,7 +1064,8 @@ pub fn compute_redeem(
         // Compute penalty / reward from this rebalance
         // -- penalty if < 0
         // -- reward if > 0
-        let (penalty, _, _) = notional_penalty(deps, block_height, &i0, &i1, &w, &p)?;
+        let (penalty, _, _) =
+            notional_penalty(deps, cluster, block_height, block_time, &i0, &i1, &w, &p)?;
         // Compute the value of the returned assets with penalty
         // -- notional_value = value_of_the_returned_assets - penalty
         //                   = sum(provided_asset_i * price_i) - penalty
@@ -643,14 +1100,108 @@ pub fn compute_redeem(
     };
 }
 
+/// ## Description
+/// Calculates the actual asked asset amounts of a swap after taking penalty into consideration.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
+/// - **block_height** is an object of type [`u64`] is a specific height to compute swap at.
+///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
+/// - **inventory** is a reference to an array containing objects of type [`Uint128`] which is the
+///     current inventory of inventory assets in a cluster.
+///
+/// - **offer_asset_amounts** is a reference to an array containing objects of type [`Uint128`] which
+///     are the offered asset amounts.
+///
+/// - **ask_asset_amounts** is a reference to an array containing objects of type [`Uint128`] which
+///     are the asked asset amounts of the same value as the offered assets at oracle prices.
+///
+/// - **asset_prices** is a reference to an array containing objects of type [`String`] which are the
+///     prices of the inventory assets in a cluster.
+///
+/// - **target_weights** is a reference to an array containing objects of type [`Uint128`] which are
+///     the current target weights of the assets in a cluster.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_swap(
+    deps: Deps,
+    cluster: Option<&Addr>,
+    block_height: u64,
+    block_time: u64,
+    inventory: &[Uint128],
+    offer_asset_amounts: &[Uint128],
+    ask_asset_amounts: &[Uint128],
+    asset_prices: &[String],
+    target_weights: &[Uint128],
+) -> StdResult<PenaltySwapResponse> {
+    // The current inventory before swapping
+    let i0 = int_vec_to_fpdec(inventory);
+    // The offered assets
+    let c = int_vec_to_fpdec(offer_asset_amounts);
+    // The asked assets at oracle prices
+    let r = int_vec_to_fpdec(ask_asset_amounts);
+    // The current prices of the assets in the cluster
+    let p = str_vec_to_fpdec(asset_prices)?;
+    // The target weights of the assets
+    let w = int_vec_to_fpdec(target_weights);
+
+    // New inventory after adding the offered assets and removing the asked assets
+    let i1 = sub(&add(&i0, &c), &r);
+
+    // Compute penalty / reward from this rebalance
+    // -- penalty if < 0
+    // -- reward if > 0
+    let (penalty, _, _) =
+        notional_penalty(deps, cluster, block_height, block_time, &i0, &i1, &w, &p)?;
+    // Compute the value of the offered assets with penalty
+    // -- notional_value = value_of_the_offered_assets + penalty
+    //                   = sum(offered_asset_i * price_i) + penalty
+    let notional_value = dot(&c, &p) + penalty;
+    if notional_value <= FPDecimal::zero() {
+        return Err(StdError::generic_err(
+            "Swap value does not cover the penalty",
+        ));
+    }
+
+    // Scale the asked assets to the value of the offered assets with penalty
+    // -- ask_assets = ask_asset_amounts * (notional_value / value_of_the_asked_assets)
+    let ask_assets = div_const(&mul_const(&r, notional_value), dot(&r, &p));
+
+    Ok(PenaltySwapResponse {
+        ask_assets: ask_assets
+            .iter()
+            .map(|&x| Uint128::new(x.into()))
+            .collect::<Vec<Uint128>>(),
+        penalty: Uint128::new(
+            (if penalty.sign == 1 {
+                penalty
+            } else {
+                FPDecimal::zero()
+            })
+            .into(),
+        ),
+        attributes: vec![attr("penalty", &format!("{}", penalty))],
+    })
+}
+
 /// ## Description
 /// Calculates penalty / reward for any rebalance operation on a cluster.
 ///
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`].
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
 /// - **inventory0** is a reference to an array containing objects of type [`Uint128`] which is
 ///     the current inventory of a cluster.
 ///
@@ -662,9 +1213,12 @@ pub fn compute_redeem(
 ///
 /// - **target_weights** is a reference to an array containing objects of type [`Uint128`] which is
 ///     a list of asset target weights of a cluster.
+#[allow(clippy::too_many_arguments)]
 pub fn compute_notional_penalty(
     deps: Deps,
+    cluster: Option<&Addr>,
     block_height: u64,
+    block_time: u64,
     inventory0: &[Uint128],
     inventory1: &[Uint128],
     asset_prices: &[String],
@@ -682,7 +1236,8 @@ pub fn compute_notional_penalty(
     // Compute penalty / reward from this rebalance
     // -- penalty if < 0
     // -- reward if > 0
-    let (penalty, imb0, imb1) = notional_penalty(deps, block_height, &i0, &i1, &w, &p)?;
+    let (penalty, imb0, imb1) =
+        notional_penalty(deps, cluster, block_height, block_time, &i0, &i1, &w, &p)?;
 
     Ok(PenaltyNotionalResponse {
         penalty: Uint128::new(
@@ -705,8 +1260,13 @@ pub fn compute_notional_penalty(
 /// ## Params
 /// - **deps** is an object of type [`Deps`].
 ///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
 /// - **block_height** is an object of type [`u64`].
 ///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
 /// - **i0** is a reference to an array containing objects of type [`FPDecimal`] which is
 ///     the current inventory of a cluster.
 ///
@@ -718,92 +1278,353 @@ pub fn compute_notional_penalty(
 ///
 /// - **p** is a reference to an array containing objects of type [`FPDecimal`] which is
 ///     a list of asset prices of a cluster.
+#[allow(clippy::too_many_arguments)]
 pub fn notional_penalty(
     deps: Deps,
+    cluster: Option<&Addr>,
     block_height: u64,
+    block_time: u64,
     i0: &[FPDecimal],
     i1: &[FPDecimal],
     w: &[FPDecimal],
     p: &[FPDecimal],
 ) -> StdResult<(FPDecimal, FPDecimal, FPDecimal)> {
-    let cfg = read_config(deps.storage)?;
+    let (penalty_params, _) = read_penalty_state(deps.storage, cluster)?;
 
     // Compute the current imbalance with `i0`
     let imb0 = imbalance(i0, p, w);
     // Compute the imbalance after the rebalance with `i1`
     let imb1 = imbalance(i1, p, w);
 
+    let e = effective_e(deps, cluster, block_height, block_time, dot(i0, p))?;
+
+    if imb0 < imb1 {
+        // Imbalance increases, use the area under the penalty curve
+        Ok((
+            FPDecimal::zero() - penalty_area(&penalty_params, imb0, imb1, e)?,
+            imb0,
+            imb1,
+        ))
+    } else {
+        // Imbalance decreases, use reward function
+        Ok((reward_area(&penalty_params, imb0, imb1, e), imb0, imb1))
+    }
+}
+
+/// ## Description
+/// Returns e, the minimum of the EMA and the net asset value, which scales the cutoffs
+/// of the penalty curve.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
+/// - **block_height** is an object of type [`u64`].
+///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
+/// - **nav** is an object of type [`FPDecimal`] which is the current net asset value of a cluster.
+fn effective_e(
+    deps: Deps,
+    cluster: Option<&Addr>,
+    block_height: u64,
+    block_time: u64,
+    nav: FPDecimal,
+) -> StdResult<FPDecimal> {
     // e is the minimum of the EMA and the net asset value
     // -- It is important to not let e exceed NAV to prevent someone
     //    pumping e to "stretch" penalty_cutoff_hi and then using it to
     //    duck the cluster imbalance too high issue
-    let nav = dot(i0, p);
-    let e = min(get_ema(deps, block_height, nav)?, nav);
-
-    let PenaltyParams {
-        penalty_amt_lo,
-        penalty_cutoff_lo,
-        penalty_amt_hi,
-        penalty_cutoff_hi,
-        reward_amt,
-        reward_cutoff,
-    } = cfg.penalty_params;
-
-    if imb0 < imb1 {
-        // Imbalance increases, use penalty function
-        let cutoff_lo = penalty_cutoff_lo * e;
-        let cutoff_hi = penalty_cutoff_hi * e;
+    Ok(min(
+        get_ema(deps, cluster, block_height, block_time, nav)?,
+        nav,
+    ))
+}
 
-        if imb1 > cutoff_hi {
-            return Err(StdError::generic_err("cluster imbalance too high"));
-        }
+/// ## Description
+/// Samples the penalty curve of a cluster using a custom [`PenaltyCurveResponse`] structure.
+/// Returns a [`StdError`] if `points` is out of range or the cluster is empty.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
+/// - **block_height** is an object of type [`u64`] which is the height to compute EMA at.
+///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
+/// - **inventory** is a reference to an array containing objects of type [`Uint128`] which is the
+///     current inventory of inventory assets in a cluster.
+///
+/// - **asset_prices** is a reference to an array containing objects of type [`String`] which are the
+///     prices of the inventory assets in a cluster.
+///
+/// - **target_weights** is a reference to an array containing objects of type [`Uint128`] which are
+///     the current target weights of the assets in a cluster.
+///
+/// - **points** is an object of type [`u32`] which is the number of evenly spaced imbalances to
+///     sample from zero to the highest cutoff.
+#[allow(clippy::too_many_arguments)]
+pub fn query_penalty_curve(
+    deps: Deps,
+    cluster: Option<&Addr>,
+    block_height: u64,
+    block_time: u64,
+    inventory: &[Uint128],
+    asset_prices: &[String],
+    target_weights: &[Uint128],
+    points: u32,
+) -> StdResult<PenaltyCurveResponse> {
+    if !(2..=MAX_CURVE_POINTS).contains(&points) {
+        return Err(StdError::generic_err(format!(
+            "Penalty curve points must be between 2 and {}",
+            MAX_CURVE_POINTS
+        )));
+    }
 
-        // Penalty function is broken into three pieces, where its flat, linear, and then flat
-        // Compute the area under each piece separately
+    let i = int_vec_to_fpdec(inventory);
+    let p = str_vec_to_fpdec(asset_prices)?;
+    let w = int_vec_to_fpdec(target_weights);
 
-        let penalty_1 = (min(imb1, cutoff_lo) - min(imb0, cutoff_lo)) * penalty_amt_lo;
+    let nav = dot(&i, &p);
+    if nav == FPDecimal::zero() {
+        return Err(StdError::generic_err(
+            "Cannot sample the penalty curve of an empty cluster",
+        ));
+    }
 
-        // Clip to only middle portion
-        let imb0_mid = min(max(imb0, cutoff_lo), cutoff_hi);
-        let imb1_mid = min(max(imb1, cutoff_lo), cutoff_hi);
+    let (penalty_params, _) = read_penalty_state(deps.storage, cluster)?;
+    let e = effective_e(deps, cluster, block_height, block_time, nav)?;
+    let (cutoff_lo, cutoff_hi) = penalty_cutoffs(&penalty_params);
+    let (_, reward_cutoff) = reward_params(&penalty_params);
+    let cutoff_hi = cutoff_hi * e;
 
-        let amt_gap = penalty_amt_hi - penalty_amt_lo;
-        let cutoff_gap = cutoff_hi - cutoff_lo;
+    // Sample evenly spaced imbalances from zero to the highest cutoff
+    let step = cutoff_hi / FPDecimal::from((points - 1) as u128);
+    let points = (0..points)
+        .map(|k| {
+            let imb = if k == points - 1 {
+                cutoff_hi
+            } else {
+                step * FPDecimal::from(k as u128)
+            };
+            Ok(PenaltyCurvePoint {
+                imbalance: imb.to_string(),
+                penalty_rate: penalty_rate(&penalty_params, imb, e)?.to_string(),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
 
-        // Value of y when x is at imb0_mid and imb1_mid respectively
-        let imb0_mid_height = (imb0_mid - cutoff_lo) * amt_gap / cutoff_gap + penalty_amt_lo;
-        let imb1_mid_height = (imb1_mid - cutoff_lo) * amt_gap / cutoff_gap + penalty_amt_lo;
+    Ok(PenaltyCurveResponse {
+        imbalance: imbalance(&i, &p, &w).to_string(),
+        e: e.to_string(),
+        penalty_cutoff_lo: (cutoff_lo * e).to_string(),
+        penalty_cutoff_hi: cutoff_hi.to_string(),
+        reward_cutoff: (reward_cutoff * e).to_string(),
+        points,
+    })
+}
 
-        // Area of a trapezoid
-        let penalty_2 = (imb0_mid_height + imb1_mid_height) * (imb1_mid - imb0_mid).div(2);
+/// ## Description
+/// Returns the create or redeem basket within a budget that minimizes the cluster imbalance
+/// using a custom [`OptimalBasketResponse`] structure.
+///
+/// Under the L1 imbalance, moving capital into assets below their target allocation, or out of
+/// assets above it, reduces the imbalance one for one, so any split of the budget that does not
+/// overshoot the targets reaches the minimum imbalance. Since the reward only grows as the
+/// imbalance falls, the same basket also maximizes the reward. A redeem budget of `max_tokens`
+/// is converted to its value at the net asset value before the reward, after deducting the
+/// redeem fee and the rounding of the cluster token cost.
+///
+/// ## Params
+/// - **deps** is an object of type [`Deps`].
+///
+/// - **cluster** is an object of type [`Option<&Addr>`] which is the cluster sharing the penalty
+///     contract, or `None` if a single cluster owns it.
+///
+/// - **block_height** is an object of type [`u64`] which is the height to compute the reward at.
+///
+/// - **block_time** is an object of type [`u64`] which is the block time in seconds.
+///
+/// - **cluster_token_supply** is a reference to an object of type [`Uint128`] which is the current
+///     total supply for a cluster token.
+///
+/// - **inventory** is a reference to an array containing objects of type [`Uint128`] which is the
+///     current inventory of inventory assets in a cluster.
+///
+/// - **asset_prices** is a reference to an array containing objects of type [`String`] which are the
+///     prices of the inventory assets in a cluster.
+///
+/// - **target_weights** is a reference to an array containing objects of type [`Uint128`] which are
+///     the current target weights of the assets in a cluster.
+///
+/// - **budget** is an object of type [`RebalanceBudget`] which is the budget of the create or redeem.
+#[allow(clippy::too_many_arguments)]
+pub fn query_optimal_basket(
+    deps: Deps,
+    cluster: Option<&Addr>,
+    block_height: u64,
+    block_time: u64,
+    cluster_token_supply: &Uint128,
+    inventory: &[Uint128],
+    asset_prices: &[String],
+    target_weights: &[Uint128],
+    budget: RebalanceBudget,
+) -> StdResult<OptimalBasketResponse> {
+    let n = FPDecimal::from(cluster_token_supply.u128());
+    let i0 = int_vec_to_fpdec(inventory);
+    let p = str_vec_to_fpdec(asset_prices)?;
+    let w = int_vec_to_fpdec(target_weights);
 
-        let penalty_3 = (max(imb1, cutoff_hi) - max(imb0, cutoff_hi)) * penalty_amt_hi;
-        Ok((
-            FPDecimal::zero() - (penalty_1 + penalty_2 + penalty_3),
-            imb0,
-            imb1,
-        ))
-    } else {
-        // Imbalance decreases, use reward function
-        let cutoff = reward_cutoff * e;
-        Ok((
-            (max(imb0, cutoff) - max(imb1, cutoff)) * reward_amt,
-            imb0,
-            imb1,
-        ))
+    if p.iter().any(|price| price.sign == 0 || price.num.is_zero()) {
+        return Err(StdError::generic_err("Asset prices must be positive"));
     }
+    let nav = dot(&i0, &p);
+    if n.num.is_zero() || nav.num.is_zero() {
+        return Err(StdError::generic_err(
+            "Cannot compute the optimal basket of an empty cluster",
+        ));
+    }
+
+    // Share of the cluster tokens spent by a redeem that is burned, the rest being the redeem fee
+    let keep_rate = match &budget {
+        RebalanceBudget::Redeem {
+            redeem_fee_rate: Some(redeem_fee_rate),
+            ..
+        } => {
+            let fee_rate = FPDecimal::from_str(redeem_fee_rate)?;
+            if (fee_rate.sign == 0 && !fee_rate.num.is_zero()) || fee_rate >= FPDecimal::one() {
+                return Err(StdError::generic_err(
+                    "Redeem fee rate must be within [0, 1)",
+                ));
+            }
+            FPDecimal::one() - fee_rate
+        }
+        _ => FPDecimal::one(),
+    };
+
+    // Compute the capital to move in or out of each asset minimizing the cluster imbalance
+    let allocation = match &budget {
+        RebalanceBudget::Create { value } => {
+            let value = FPDecimal::from_str(value)?;
+            if value.sign == 0 && !value.num.is_zero() {
+                return Err(StdError::generic_err("Create value must not be negative"));
+            }
+            min_imbalance_allocation(&i0, &p, &w, value)
+        }
+        RebalanceBudget::Redeem { max_tokens, .. } => {
+            if max_tokens > cluster_token_supply {
+                return Err(StdError::generic_err(
+                    "Cannot redeem more than the cluster token supply",
+                ));
+            }
+            // The basket value is chosen so that the cluster token cost, ceiled when redeeming
+            // and again when adding the redeem fee, never exceeds `max_tokens`
+            // -- value = ((max_tokens - 1) * keep_rate - 1) * NAV / CT_total_supply
+            let m = FPDecimal::from(max_tokens.u128());
+            let token_budget = (m - FPDecimal::one()) * keep_rate - FPDecimal::one();
+            if token_budget.sign == 0 || token_budget.num.is_zero() {
+                return Err(StdError::generic_err("Too few cluster tokens to redeem"));
+            }
+            let value = token_budget * nav / n;
+            min_imbalance_withdrawal(&i0, &p, &w, value)
+        }
+    };
+
+    // Convert the capital into asset amounts, rounding down
+    let basket = allocation
+        .iter()
+        .zip(p.iter())
+        .map(|(v, price)| Uint128::new((*v / *price).into()))
+        .collect::<Vec<Uint128>>();
+    let b = int_vec_to_fpdec(&basket);
+
+    // Price the basket the same way as the create or redeem operation
+    let (i1, tokens, reward) = match budget {
+        RebalanceBudget::Create { .. } => {
+            let res = compute_mint(
+                deps,
+                cluster,
+                block_height,
+                block_time,
+                cluster_token_supply,
+                inventory,
+                &basket,
+                asset_prices,
+                target_weights,
+            )?;
+            (add(&i0, &b), res.create_tokens, res.penalty)
+        }
+        RebalanceBudget::Redeem { max_tokens, .. } => {
+            let res = compute_redeem(
+                deps,
+                cluster,
+                block_height,
+                block_time,
+                cluster_token_supply,
+                inventory,
+                &max_tokens,
+                &basket,
+                asset_prices,
+                target_weights,
+            )?;
+            // Add the redeem fee to the burned cluster tokens, rounding up as the cluster does
+            let _token_cost: FPDecimal = FPDecimal::from(res.token_cost.u128()) / keep_rate;
+            let mut token_cost: u128 = _token_cost.into();
+            if FPDecimal::from(token_cost) != _token_cost {
+                token_cost += 1u128;
+            }
+            (sub(&i0, &b), Uint128::new(token_cost), res.penalty)
+        }
+    };
+
+    Ok(OptimalBasketResponse {
+        basket,
+        tokens,
+        imbalance: imbalance(&i1, &p, &w).to_string(),
+        reward,
+    })
 }
 
 /// ## Description
 /// Exposes the migrate functionality in the contract.
+/// Configs stored before penalty curve families were introduced keep their curve
+/// as [`PenaltyParams::PiecewiseLinear`].
 ///
 /// ## Params
-/// - **_deps** is an object of type [`DepsMut`].
+/// - **deps** is an object of type [`DepsMut`].
 ///
 /// - **_env** is an object of type [`Env`].
 ///
 /// - **_msg** is an object of type [`MigrateMsg`].
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+    if read_config(deps.storage).is_err() {
+        let legacy_cfg = read_legacy_config(deps.storage)?;
+        let params = legacy_cfg.penalty_params;
+        store_config(
+            deps.storage,
+            &PenaltyConfig {
+                owner: legacy_cfg.owner,
+                penalty_params: PenaltyParams::PiecewiseLinear {
+                    penalty_amt_lo: params.penalty_amt_lo,
+                    penalty_cutoff_lo: params.penalty_cutoff_lo,
+                    penalty_amt_hi: params.penalty_amt_hi,
+                    penalty_cutoff_hi: params.penalty_cutoff_hi,
+                    reward_amt: params.reward_amt,
+                    reward_cutoff: params.reward_cutoff,
+                    ema_half_life: None,
+                },
+                ema: legacy_cfg.ema,
+                last_block: legacy_cfg.last_block,
+                last_time: 0u64,
+                factory: None,
+            },
+        )?;
+    }
     Ok(Response::default())
 }
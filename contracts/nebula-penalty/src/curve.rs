@@ -0,0 +1,477 @@
+use cluster_math::FPDecimal;
+use cosmwasm_std::{StdError, StdResult};
+use nebula_protocol::penalty::{EmaHalfLife, PenaltyParams};
+use std::cmp::{max, min};
+
+use crate::error::ContractError;
+
+/// ## Description
+/// Validates the penalty parameters of any penalty curve family and its EMA half-life.
+/// Returns a [`ContractError`] if the penalty curve is malformed.
+///
+/// ## Params
+/// - **params** is a reference to an object of type [`PenaltyParams`] which are the
+///     penalty parameters to validate.
+pub fn validate_penalty_params(params: &PenaltyParams) -> Result<(), ContractError> {
+    // The penalty amount when the imbalance is at its highest
+    let penalty_amt_hi = match params {
+        PenaltyParams::PiecewiseLinear {
+            penalty_amt_hi,
+            penalty_cutoff_lo,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            validate_penalty_cutoff_hi(penalty_cutoff_hi)?;
+            if *penalty_cutoff_lo >= *penalty_cutoff_hi {
+                return Err(ContractError::Generic(
+                    "penalty low cutoff must be below the high cutoff".to_string(),
+                ));
+            }
+            *penalty_amt_hi
+        }
+        PenaltyParams::Quadratic {
+            penalty_amt_hi,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            validate_penalty_cutoff_hi(penalty_cutoff_hi)?;
+            *penalty_amt_hi
+        }
+        PenaltyParams::Tanh {
+            penalty_amt_hi,
+            penalty_width,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            if !is_positive(penalty_width) {
+                return Err(ContractError::Generic(
+                    "penalty width must be positive".to_string(),
+                ));
+            }
+            validate_penalty_cutoff_hi(penalty_cutoff_hi)?;
+            *penalty_amt_hi
+        }
+        PenaltyParams::Table { knots, .. } => {
+            if knots.len() < 2 {
+                return Err(ContractError::Generic(
+                    "penalty table must have at least two knots".to_string(),
+                ));
+            }
+            if knots[0].imbalance != FPDecimal::zero() {
+                return Err(ContractError::Generic(
+                    "penalty table must start at zero imbalance".to_string(),
+                ));
+            }
+            if knots
+                .windows(2)
+                .any(|pair| pair[0].imbalance >= pair[1].imbalance)
+            {
+                return Err(ContractError::Generic(
+                    "penalty table imbalances must be increasing".to_string(),
+                ));
+            }
+            knots[knots.len() - 1].penalty_amt
+        }
+    };
+
+    if let Some(EmaHalfLife::Blocks(0) | EmaHalfLife::Seconds(0)) = params.ema_half_life() {
+        return Err(ContractError::Generic(
+            "EMA half-life must be positive".to_string(),
+        ));
+    }
+
+    if penalty_amt_hi != FPDecimal::one() {
+        return Err(ContractError::Generic(
+            "penalty amount must reach one".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Returns whether the given decimal is strictly positive.
+fn is_positive(x: &FPDecimal) -> bool {
+    x.sign == 1 && !x.num.is_zero()
+}
+
+/// ## Description
+/// Validates that the high cutoff of a penalty curve is positive, as the highest
+/// imbalance of the curve is `penalty_cutoff_hi * E`.
+///
+/// ## Params
+/// - **penalty_cutoff_hi** is a reference to an object of type [`FPDecimal`] which is
+///     the high cutoff of the penalty curve.
+fn validate_penalty_cutoff_hi(penalty_cutoff_hi: &FPDecimal) -> Result<(), ContractError> {
+    if !is_positive(penalty_cutoff_hi) {
+        return Err(ContractError::Generic(
+            "penalty high cutoff must be positive".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// ## Description
+/// Computes the area under the penalty curve between two imbalances, which is the penalty
+/// of a rebalance increasing the imbalance from `imb0` to `imb1`.
+/// Returns a [`StdError`] if `imb1` exceeds the highest imbalance of the curve.
+///
+/// ## Params
+/// - **params** is a reference to an object of type [`PenaltyParams`] which are the
+///     penalty parameters.
+///
+/// - **imb0** is an object of type [`FPDecimal`] which is the imbalance before the rebalance.
+///
+/// - **imb1** is an object of type [`FPDecimal`] which is the imbalance after the rebalance.
+///
+/// - **e** is an object of type [`FPDecimal`] which is the minimum of the EMA and
+///     the net asset value, used to scale the cutoffs.
+pub fn penalty_area(
+    params: &PenaltyParams,
+    imb0: FPDecimal,
+    imb1: FPDecimal,
+    e: FPDecimal,
+) -> StdResult<FPDecimal> {
+    match params {
+        PenaltyParams::PiecewiseLinear {
+            penalty_amt_lo,
+            penalty_cutoff_lo,
+            penalty_amt_hi,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            let cutoff_lo = *penalty_cutoff_lo * e;
+            let cutoff_hi = *penalty_cutoff_hi * e;
+
+            if imb1 > cutoff_hi {
+                return Err(StdError::generic_err("cluster imbalance too high"));
+            }
+
+            // Penalty function is broken into three pieces, where its flat, linear, and then flat
+            // Compute the area under each piece separately
+
+            let penalty_1 = (min(imb1, cutoff_lo) - min(imb0, cutoff_lo)) * *penalty_amt_lo;
+
+            // Clip to only middle portion
+            let imb0_mid = min(max(imb0, cutoff_lo), cutoff_hi);
+            let imb1_mid = min(max(imb1, cutoff_lo), cutoff_hi);
+
+            // Value of y when x is at imb0_mid and imb1_mid respectively
+            let imb0_mid_height = segment_height(
+                imb0_mid,
+                cutoff_lo,
+                cutoff_hi,
+                *penalty_amt_lo,
+                *penalty_amt_hi,
+            );
+            let imb1_mid_height = segment_height(
+                imb1_mid,
+                cutoff_lo,
+                cutoff_hi,
+                *penalty_amt_lo,
+                *penalty_amt_hi,
+            );
+
+            // Area of a trapezoid
+            let penalty_2 = (imb0_mid_height + imb1_mid_height) * (imb1_mid - imb0_mid).div(2);
+
+            let penalty_3 = (max(imb1, cutoff_hi) - max(imb0, cutoff_hi)) * *penalty_amt_hi;
+            Ok(penalty_1 + penalty_2 + penalty_3)
+        }
+        PenaltyParams::Quadratic {
+            penalty_amt_lo,
+            penalty_amt_hi,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            let cutoff_hi = *penalty_cutoff_hi * e;
+
+            if imb1 > cutoff_hi {
+                return Err(StdError::generic_err("cluster imbalance too high"));
+            }
+
+            // Integrate in units of the cutoff to keep the cubes small
+            // -- area = lo * (imb1 - imb0) + (hi - lo) * cutoff_hi * (u1^3 - u0^3) / 3
+            let u0 = imb0 / cutoff_hi;
+            let u1 = imb1 / cutoff_hi;
+            let cubes_gap = u1 * u1 * u1 - u0 * u0 * u0;
+
+            Ok((imb1 - imb0) * *penalty_amt_lo
+                + (*penalty_amt_hi - *penalty_amt_lo) * cutoff_hi * cubes_gap.div(3))
+        }
+        PenaltyParams::Tanh {
+            penalty_amt_lo,
+            penalty_amt_hi,
+            penalty_cutoff_mid,
+            penalty_width,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            let cutoff_hi = *penalty_cutoff_hi * e;
+
+            if imb1 > cutoff_hi {
+                return Err(StdError::generic_err("cluster imbalance too high"));
+            }
+
+            let mid = *penalty_cutoff_mid * e;
+            let width = *penalty_width * e;
+
+            // The antiderivative of tanh((x - mid) / width) is width * ln(cosh((x - mid) / width))
+            // -- area = (lo + hi) / 2 * (imb1 - imb0)
+            //           + (hi - lo) / 2 * width * (ln_cosh(z1) - ln_cosh(z0))
+            let ln_cosh_gap = ln_cosh((imb1 - mid) / width) - ln_cosh((imb0 - mid) / width);
+
+            Ok((*penalty_amt_lo + *penalty_amt_hi) * (imb1 - imb0).div(2)
+                + (*penalty_amt_hi - *penalty_amt_lo) * width * ln_cosh_gap.div(2))
+        }
+        PenaltyParams::Table { knots, .. } => {
+            if imb1 > knots[knots.len() - 1].imbalance * e {
+                return Err(StdError::generic_err("cluster imbalance too high"));
+            }
+
+            // Sum the areas of the trapezoids of every segment clipped to [imb0, imb1]
+            let mut penalty = FPDecimal::zero();
+            for pair in knots.windows(2) {
+                let cutoff_lo = pair[0].imbalance * e;
+                let cutoff_hi = pair[1].imbalance * e;
+
+                let imb0_seg = min(max(imb0, cutoff_lo), cutoff_hi);
+                let imb1_seg = min(max(imb1, cutoff_lo), cutoff_hi);
+                if imb0_seg == imb1_seg {
+                    continue;
+                }
+
+                // Value of y when x is at imb0_seg and imb1_seg respectively
+                let imb0_seg_height = segment_height(
+                    imb0_seg,
+                    cutoff_lo,
+                    cutoff_hi,
+                    pair[0].penalty_amt,
+                    pair[1].penalty_amt,
+                );
+                let imb1_seg_height = segment_height(
+                    imb1_seg,
+                    cutoff_lo,
+                    cutoff_hi,
+                    pair[0].penalty_amt,
+                    pair[1].penalty_amt,
+                );
+
+                penalty =
+                    penalty + (imb0_seg_height + imb1_seg_height) * (imb1_seg - imb0_seg).div(2);
+            }
+            Ok(penalty)
+        }
+    }
+}
+
+/// ## Description
+/// Computes the marginal penalty rate at an imbalance, which is the height of the penalty
+/// curve that [`penalty_area`] integrates.
+/// Returns a [`StdError`] if `imb` exceeds the highest imbalance of the curve.
+///
+/// ## Params
+/// - **params** is a reference to an object of type [`PenaltyParams`] which are the
+///     penalty parameters.
+///
+/// - **imb** is an object of type [`FPDecimal`] which is the imbalance.
+///
+/// - **e** is an object of type [`FPDecimal`] which is the minimum of the EMA and
+///     the net asset value, used to scale the cutoffs.
+pub fn penalty_rate(params: &PenaltyParams, imb: FPDecimal, e: FPDecimal) -> StdResult<FPDecimal> {
+    let (_, cutoff_hi) = penalty_cutoffs(params);
+    if imb > cutoff_hi * e {
+        return Err(StdError::generic_err("cluster imbalance too high"));
+    }
+
+    match params {
+        PenaltyParams::PiecewiseLinear {
+            penalty_amt_lo,
+            penalty_cutoff_lo,
+            penalty_amt_hi,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            let cutoff_lo = *penalty_cutoff_lo * e;
+            if imb <= cutoff_lo {
+                return Ok(*penalty_amt_lo);
+            }
+            Ok(segment_height(
+                imb,
+                cutoff_lo,
+                *penalty_cutoff_hi * e,
+                *penalty_amt_lo,
+                *penalty_amt_hi,
+            ))
+        }
+        PenaltyParams::Quadratic {
+            penalty_amt_lo,
+            penalty_amt_hi,
+            penalty_cutoff_hi,
+            ..
+        } => {
+            // -- rate = lo + (hi - lo) * (imb / cutoff_hi)^2
+            let u = imb / (*penalty_cutoff_hi * e);
+            Ok(*penalty_amt_lo + (*penalty_amt_hi - *penalty_amt_lo) * u * u)
+        }
+        PenaltyParams::Tanh {
+            penalty_amt_lo,
+            penalty_amt_hi,
+            penalty_cutoff_mid,
+            penalty_width,
+            ..
+        } => {
+            // -- rate = (lo + hi) / 2 + (hi - lo) / 2 * tanh((imb - mid) / width)
+            let z = (imb - *penalty_cutoff_mid * e) / (*penalty_width * e);
+            Ok((*penalty_amt_lo + *penalty_amt_hi).div(2)
+                + (*penalty_amt_hi - *penalty_amt_lo) * tanh(z).div(2))
+        }
+        PenaltyParams::Table { knots, .. } => {
+            let pair = knots
+                .windows(2)
+                .find(|pair| imb <= pair[1].imbalance * e)
+                .unwrap_or(&knots[knots.len() - 2..]);
+            Ok(segment_height(
+                imb,
+                pair[0].imbalance * e,
+                pair[1].imbalance * e,
+                pair[0].penalty_amt,
+                pair[1].penalty_amt,
+            ))
+        }
+    }
+}
+
+/// ## Description
+/// Returns the imbalances, as fractions of `e`, where the penalty curve starts rising
+/// and where it ends.
+///
+/// ## Params
+/// - **params** is a reference to an object of type [`PenaltyParams`] which are the
+///     penalty parameters.
+pub fn penalty_cutoffs(params: &PenaltyParams) -> (FPDecimal, FPDecimal) {
+    match params {
+        PenaltyParams::PiecewiseLinear {
+            penalty_cutoff_lo,
+            penalty_cutoff_hi,
+            ..
+        } => (*penalty_cutoff_lo, *penalty_cutoff_hi),
+        PenaltyParams::Quadratic {
+            penalty_cutoff_hi, ..
+        }
+        | PenaltyParams::Tanh {
+            penalty_cutoff_hi, ..
+        } => (FPDecimal::zero(), *penalty_cutoff_hi),
+        PenaltyParams::Table { knots, .. } => {
+            (knots[0].imbalance, knots[knots.len() - 1].imbalance)
+        }
+    }
+}
+
+/// ## Description
+/// Computes the reward of a rebalance decreasing the imbalance from `imb0` to `imb1`.
+///
+/// ## Params
+/// - **params** is a reference to an object of type [`PenaltyParams`] which are the
+///     penalty parameters.
+///
+/// - **imb0** is an object of type [`FPDecimal`] which is the imbalance before the rebalance.
+///
+/// - **imb1** is an object of type [`FPDecimal`] which is the imbalance after the rebalance.
+///
+/// - **e** is an object of type [`FPDecimal`] which is the minimum of the EMA and
+///     the net asset value, used to scale the cutoff.
+pub fn reward_area(
+    params: &PenaltyParams,
+    imb0: FPDecimal,
+    imb1: FPDecimal,
+    e: FPDecimal,
+) -> FPDecimal {
+    let (reward_amt, reward_cutoff) = reward_params(params);
+    let cutoff = reward_cutoff * e;
+    (max(imb0, cutoff) - max(imb1, cutoff)) * reward_amt
+}
+
+/// ## Description
+/// Returns the reward amount and the reward cutoff, as a fraction of `e`, of any penalty
+/// curve family.
+///
+/// ## Params
+/// - **params** is a reference to an object of type [`PenaltyParams`] which are the
+///     penalty parameters.
+pub fn reward_params(params: &PenaltyParams) -> (FPDecimal, FPDecimal) {
+    match params {
+        PenaltyParams::PiecewiseLinear {
+            reward_amt,
+            reward_cutoff,
+            ..
+        }
+        | PenaltyParams::Quadratic {
+            reward_amt,
+            reward_cutoff,
+            ..
+        }
+        | PenaltyParams::Tanh {
+            reward_amt,
+            reward_cutoff,
+            ..
+        }
+        | PenaltyParams::Table {
+            reward_amt,
+            reward_cutoff,
+            ..
+        } => (*reward_amt, *reward_cutoff),
+    }
+}
+
+/// ## Description
+/// Computes the height at `x` of the line between (`cutoff_lo`, `amt_lo`) and
+/// (`cutoff_hi`, `amt_hi`).
+///
+/// ## Params
+/// - **x** is an object of type [`FPDecimal`] which is the imbalance.
+///
+/// - **cutoff_lo** is an object of type [`FPDecimal`] which is the imbalance at the start of the line.
+///
+/// - **cutoff_hi** is an object of type [`FPDecimal`] which is the imbalance at the end of the line.
+///
+/// - **amt_lo** is an object of type [`FPDecimal`] which is the penalty amount at the start of the line.
+///
+/// - **amt_hi** is an object of type [`FPDecimal`] which is the penalty amount at the end of the line.
+fn segment_height(
+    x: FPDecimal,
+    cutoff_lo: FPDecimal,
+    cutoff_hi: FPDecimal,
+    amt_lo: FPDecimal,
+    amt_hi: FPDecimal,
+) -> FPDecimal {
+    (x - cutoff_lo) * (amt_hi - amt_lo) / (cutoff_hi - cutoff_lo) + amt_lo
+}
+
+/// ## Description
+/// Computes ln(cosh(z)) without overflowing for large |z|.
+/// -- ln(cosh(z)) = |z| + ln(1 + exp(-2|z|)) - ln(2)
+///
+/// ## Params
+/// - **z** is an object of type [`FPDecimal`].
+fn ln_cosh(z: FPDecimal) -> FPDecimal {
+    let z_abs = z.abs();
+    let two = FPDecimal::from(2i128);
+    z_abs + FPDecimal::_ln(FPDecimal::one() + FPDecimal::_exp(FPDecimal::zero() - two * z_abs))
+        - FPDecimal::_ln(two)
+}
+
+/// ## Description
+/// Computes tanh(z) without overflowing for large |z|.
+/// -- tanh(z) = sign(z) * (1 - exp(-2|z|)) / (1 + exp(-2|z|))
+///
+/// ## Params
+/// - **z** is an object of type [`FPDecimal`].
+fn tanh(z: FPDecimal) -> FPDecimal {
+    let decay = FPDecimal::_exp(FPDecimal::zero() - FPDecimal::from(2i128) * z.abs());
+    let tanh_abs = (FPDecimal::one() - decay) / (FPDecimal::one() + decay);
+    if z.sign == 0 {
+        FPDecimal::zero() - tanh_abs
+    } else {
+        tanh_abs
+    }
+}
@@ -1,4 +1,5 @@
 pub mod contract;
+pub mod curve;
 pub mod error;
 pub mod state;
 
@@ -3,11 +3,15 @@ use serde::{Deserialize, Serialize};
 
 use cluster_math::FPDecimal;
 use cosmwasm_std::{Addr, StdResult, Storage};
-use cosmwasm_storage::{singleton, singleton_read, Singleton};
+use cosmwasm_storage::{bucket, bucket_read, singleton, singleton_read, Singleton};
 use nebula_protocol::penalty::PenaltyParams;
 
 /// config: PenaltyConfig
 pub static CONFIG_KEY: &[u8] = b"config";
+/// cluster EMA state: Bucket<EmaState>; cluster -> EMA state
+pub static PREFIX_CLUSTER_EMA: &[u8] = b"cluster_ema";
+/// cluster penalty parameters: Bucket<PenaltyParams>; cluster -> penalty parameters override
+pub static PREFIX_CLUSTER_PARAMS: &[u8] = b"cluster_params";
 
 //////////////////////////////////////////////////////////////////////
 /// CONFIG
@@ -26,6 +30,41 @@ pub struct PenaltyConfig {
     pub ema: FPDecimal,
     /// Last rebalanced block
     pub last_block: u64,
+    /// Last rebalanced block time in seconds
+    #[serde(default)]
+    pub last_time: u64,
+    /// Factory contract whose clusters share the penalty contract
+    #[serde(default)]
+    pub factory: Option<Addr>,
+}
+
+/// ## Description
+/// A custom struct of the penalty contract setting stored before penalty curve
+/// families were introduced, only read when migrating.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyPenaltyConfig {
+    /// Owner of the contract, cluster contract
+    pub owner: Addr,
+    /// Piecewise-linear parameters of the panalty contract
+    pub penalty_params: LegacyPenaltyParams,
+
+    /// Last rebalanced EMA
+    pub ema: FPDecimal,
+    /// Last rebalanced block
+    pub last_block: u64,
+}
+
+/// ## Description
+/// A custom struct of the piecewise-linear penalty parameters stored before penalty
+/// curve families were introduced.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyPenaltyParams {
+    pub penalty_amt_lo: FPDecimal,
+    pub penalty_cutoff_lo: FPDecimal,
+    pub penalty_amt_hi: FPDecimal,
+    pub penalty_cutoff_hi: FPDecimal,
+    pub reward_amt: FPDecimal,
+    pub reward_cutoff: FPDecimal,
 }
 
 pub fn config_store(storage: &mut dyn Storage) -> Singleton<PenaltyConfig> {
@@ -39,3 +78,94 @@ pub fn read_config(storage: &dyn Storage) -> StdResult<PenaltyConfig> {
 pub fn store_config(storage: &mut dyn Storage, config: &PenaltyConfig) -> StdResult<()> {
     singleton(storage, CONFIG_KEY).save(config)
 }
+
+pub fn read_legacy_config(storage: &dyn Storage) -> StdResult<LegacyPenaltyConfig> {
+    singleton_read(storage, CONFIG_KEY).load()
+}
+
+//////////////////////////////////////////////////////////////////////
+/// CLUSTER STATE
+//////////////////////////////////////////////////////////////////////
+
+/// ## Description
+/// A custom struct for storing the EMA of the net asset value of a cluster.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EmaState {
+    /// Last rebalanced EMA
+    pub ema: FPDecimal,
+    /// Last rebalanced block
+    pub last_block: u64,
+    /// Last rebalanced block time in seconds
+    pub last_time: u64,
+}
+
+/// ## Description
+/// Returns the penalty parameters and the EMA state of a cluster sharing the penalty
+/// contract, or of the penalty contract itself if `cluster` is not given.
+pub fn read_penalty_state(
+    storage: &dyn Storage,
+    cluster: Option<&Addr>,
+) -> StdResult<(PenaltyParams, EmaState)> {
+    let cfg = read_config(storage)?;
+    match cluster {
+        Some(cluster) => {
+            let params = read_cluster_params(storage, cluster)?.unwrap_or(cfg.penalty_params);
+            let state = bucket_read(storage, PREFIX_CLUSTER_EMA)
+                .may_load(cluster.as_bytes())?
+                // Know to fast forward to current net asset value if last_block == 0
+                .unwrap_or(EmaState {
+                    ema: FPDecimal::zero(),
+                    last_block: 0u64,
+                    last_time: 0u64,
+                });
+            Ok((params, state))
+        }
+        None => Ok((
+            cfg.penalty_params,
+            EmaState {
+                ema: cfg.ema,
+                last_block: cfg.last_block,
+                last_time: cfg.last_time,
+            },
+        )),
+    }
+}
+
+/// ## Description
+/// Stores the EMA state of a cluster sharing the penalty contract, or of the penalty
+/// contract itself if `cluster` is not given.
+pub fn store_ema_state(
+    storage: &mut dyn Storage,
+    cluster: Option<&Addr>,
+    state: &EmaState,
+) -> StdResult<()> {
+    match cluster {
+        Some(cluster) => bucket(storage, PREFIX_CLUSTER_EMA).save(cluster.as_bytes(), state),
+        None => {
+            let mut cfg = read_config(storage)?;
+            cfg.ema = state.ema;
+            cfg.last_block = state.last_block;
+            cfg.last_time = state.last_time;
+            store_config(storage, &cfg)
+        }
+    }
+}
+
+pub fn read_cluster_params(
+    storage: &dyn Storage,
+    cluster: &Addr,
+) -> StdResult<Option<PenaltyParams>> {
+    bucket_read(storage, PREFIX_CLUSTER_PARAMS).may_load(cluster.as_bytes())
+}
+
+pub fn store_cluster_params(
+    storage: &mut dyn Storage,
+    cluster: &Addr,
+    params: &PenaltyParams,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_CLUSTER_PARAMS).save(cluster.as_bytes(), params)
+}
+
+pub fn remove_cluster_params(storage: &mut dyn Storage, cluster: &Addr) {
+    bucket::<PenaltyParams>(storage, PREFIX_CLUSTER_PARAMS).remove(cluster.as_bytes())
+}
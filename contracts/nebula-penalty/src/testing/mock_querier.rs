@@ -1,7 +1,10 @@
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    from_slice, Coin, Empty, OwnedDeps, Querier, QuerierResult, QueryRequest, SystemError,
-    SystemResult,
+    from_binary, from_slice, to_binary, Coin, ContractResult, Empty, OwnedDeps, Querier,
+    QuerierResult, QueryRequest, SystemError, SystemResult, WasmQuery,
+};
+use nebula_protocol::cluster_factory::{
+    ClusterExistsResponse, ClusterRegisteredResponse, QueryMsg as FactoryQueryMsg,
 };
 
 /// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
@@ -22,6 +25,7 @@ pub fn mock_dependencies(
 
 pub struct WasmMockQuerier {
     base: MockQuerier<Empty>,
+    clusters: Vec<(String, bool)>,
 }
 
 impl Querier for WasmMockQuerier {
@@ -42,12 +46,44 @@ impl Querier for WasmMockQuerier {
 
 impl WasmMockQuerier {
     pub fn execute_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
-        self.base.handle_query(request)
+        match &request {
+            QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: _,
+                msg,
+            }) => match from_binary(msg) {
+                Ok(FactoryQueryMsg::ClusterExists { contract_addr }) => {
+                    SystemResult::Ok(ContractResult::from(to_binary(&ClusterExistsResponse {
+                        exists: self
+                            .clusters
+                            .iter()
+                            .any(|(addr, active)| *addr == contract_addr && *active),
+                    })))
+                }
+                Ok(FactoryQueryMsg::ClusterRegistered { contract_addr }) => SystemResult::Ok(
+                    ContractResult::from(to_binary(&ClusterRegisteredResponse {
+                        registered: self.clusters.iter().any(|(addr, _)| *addr == contract_addr),
+                    })),
+                ),
+                _ => panic!("DO NOT ENTER HERE"),
+            },
+            _ => self.base.handle_query(request),
+        }
     }
 }
 
 impl WasmMockQuerier {
     pub fn new(base: MockQuerier<Empty>) -> Self {
-        WasmMockQuerier { base }
+        WasmMockQuerier {
+            base,
+            clusters: vec![],
+        }
+    }
+
+    // configure the clusters registered in the factory and whether they are active
+    pub fn with_clusters(&mut self, clusters: &[(&str, bool)]) {
+        self.clusters = clusters
+            .iter()
+            .map(|(addr, active)| (addr.to_string(), *active))
+            .collect();
     }
 }
@@ -1,18 +1,26 @@
 use crate::contract::{
     execute, get_ema, instantiate, migrate, notional_penalty, query, update_ema,
 };
+use crate::curve::{penalty_area, penalty_rate};
 use crate::error::ContractError;
-use crate::state::{read_config, PenaltyConfig};
+use crate::state::{
+    read_config, LegacyPenaltyConfig, LegacyPenaltyParams, PenaltyConfig, CONFIG_KEY,
+};
 use crate::testing::mock_querier::mock_dependencies;
 use cluster_math::{
     dot, imbalance, int32_vec_to_fpdec, int_vec_to_fpdec, str_vec_to_fpdec, FPDecimal,
 };
 use cosmwasm_std::testing::{mock_env, mock_info};
-use cosmwasm_std::{attr, from_binary, Addr, DepsMut, Env, StdError, Timestamp, Uint128};
+use cosmwasm_std::{
+    attr, from_binary, Addr, DepsMut, Env, OwnedDeps, StdError, Timestamp, Uint128,
+};
+use cosmwasm_storage::singleton;
 use cw2::{get_contract_version, ContractVersion};
 use nebula_protocol::penalty::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, ParamsResponse, PenaltyCreateResponse,
-    PenaltyNotionalResponse, PenaltyParams, PenaltyRedeemResponse, QueryMsg,
+    ConfigResponse, EmaHalfLife, ExecuteMsg, InstantiateMsg, MigrateMsg, OptimalBasketResponse,
+    ParamsResponse, PenaltyCreateResponse, PenaltyCurvePoint, PenaltyCurveResponse, PenaltyKnot,
+    PenaltyNotionalResponse, PenaltyParams, PenaltyRedeemResponse, PenaltySwapResponse,
+    ProjectedEmaResponse, QueryMsg, RebalanceBudget,
 };
 use std::str::FromStr;
 
@@ -22,6 +30,7 @@ fn mock_init(deps: DepsMut) {
     let msg = InstantiateMsg {
         owner: TEST_CREATOR.to_string(),
         penalty_params: init_params(),
+        factory: None,
     };
 
     let info = mock_info(TEST_CREATOR, &[]);
@@ -37,13 +46,14 @@ fn mock_env_height(height: u64, time: u64) -> Env {
 }
 
 fn init_params() -> PenaltyParams {
-    PenaltyParams {
+    PenaltyParams::PiecewiseLinear {
         penalty_amt_lo: FPDecimal::from_str("0.1").unwrap(),
         penalty_cutoff_lo: FPDecimal::from_str("0.01").unwrap(),
         penalty_amt_hi: FPDecimal::from_str("1").unwrap(),
         penalty_cutoff_hi: FPDecimal::from_str("0.1").unwrap(),
         reward_amt: FPDecimal::from_str("0.05").unwrap(),
         reward_cutoff: FPDecimal::from_str("0.02").unwrap(),
+        ema_half_life: None,
     }
 }
 
@@ -51,6 +61,7 @@ fn init_msg() -> InstantiateMsg {
     InstantiateMsg {
         owner: "penalty_owner".to_string(),
         penalty_params: init_params(),
+        factory: None,
     }
 }
 
@@ -68,16 +79,19 @@ fn proper_initialization() {
         config,
         PenaltyConfig {
             owner: Addr::unchecked("penalty_owner"),
-            penalty_params: PenaltyParams {
+            penalty_params: PenaltyParams::PiecewiseLinear {
                 penalty_amt_lo: FPDecimal::from_str("0.1").unwrap(),
                 penalty_cutoff_lo: FPDecimal::from_str("0.01").unwrap(),
                 penalty_amt_hi: FPDecimal::from_str("1").unwrap(),
                 penalty_cutoff_hi: FPDecimal::from_str("0.1").unwrap(),
                 reward_amt: FPDecimal::from_str("0.05").unwrap(),
                 reward_cutoff: FPDecimal::from_str("0.02").unwrap(),
+                ema_half_life: None,
             },
             ema: FPDecimal::zero(),
             last_block: 0u64,
+            last_time: 0u64,
+            factory: None,
         }
     );
 }
@@ -88,14 +102,16 @@ fn bad_initialization() {
 
     let msg = InstantiateMsg {
         owner: "".to_string(),
-        penalty_params: PenaltyParams {
+        penalty_params: PenaltyParams::PiecewiseLinear {
             penalty_amt_lo: FPDecimal::from_str("0.1").unwrap(),
             penalty_cutoff_lo: FPDecimal::from_str("0.01").unwrap(),
             penalty_amt_hi: FPDecimal::from_str("0.9").unwrap(),
             penalty_cutoff_hi: FPDecimal::from_str("0.1").unwrap(),
             reward_amt: FPDecimal::from_str("0.05").unwrap(),
             reward_cutoff: FPDecimal::from_str("0.02").unwrap(),
+            ema_half_life: None,
         },
+        factory: None,
     };
     let info = mock_info("addr0000", &[]);
     let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
@@ -126,19 +142,97 @@ fn test_ema_math() {
     let msg = init_msg();
     let info = mock_info("addr0000", &[]);
     instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-    let ema = get_ema(deps.as_ref(), 50, FPDecimal::from(100u128)).unwrap();
+    let ema = get_ema(deps.as_ref(), None, 50, 0, FPDecimal::from(100u128)).unwrap();
 
     // Should return net asset value if not updated at all
     let res = FPDecimal::from_str("100").unwrap();
     assert_eq!(res, ema);
 
     // Check EMA 70 blocks in the future
-    update_ema(deps.as_mut(), 50, FPDecimal::from(100u128)).unwrap();
-    let ema = get_ema(deps.as_ref(), 120, FPDecimal::from(120u128)).unwrap();
+    update_ema(deps.as_mut(), None, 50, 0, FPDecimal::from(100u128)).unwrap();
+    let ema = get_ema(deps.as_ref(), None, 120, 0, FPDecimal::from(120u128)).unwrap();
     let res = FPDecimal::from_str("102.2023645802395236").unwrap();
     assert_eq!(res, ema);
 }
 
+fn init_params_with_half_life(ema_half_life: EmaHalfLife) -> PenaltyParams {
+    PenaltyParams::PiecewiseLinear {
+        penalty_amt_lo: FPDecimal::from_str("0.1").unwrap(),
+        penalty_cutoff_lo: FPDecimal::from_str("0.01").unwrap(),
+        penalty_amt_hi: FPDecimal::from_str("1").unwrap(),
+        penalty_cutoff_hi: FPDecimal::from_str("0.1").unwrap(),
+        reward_amt: FPDecimal::from_str("0.05").unwrap(),
+        reward_cutoff: FPDecimal::from_str("0.02").unwrap(),
+        ema_half_life: Some(ema_half_life),
+    }
+}
+
+#[test]
+fn test_ema_half_life() {
+    let mut deps = mock_dependencies(&[]);
+    let msg = InstantiateMsg {
+        owner: TEST_CREATOR.to_string(),
+        penalty_params: init_params_with_half_life(EmaHalfLife::Blocks(100)),
+        factory: None,
+    };
+    let info = mock_info(TEST_CREATOR, &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    // One half-life in blocks halves the weight of the previous EMA
+    update_ema(deps.as_mut(), None, 50, 1000, FPDecimal::from(100u128)).unwrap();
+    let ema = get_ema(deps.as_ref(), None, 150, 1000, FPDecimal::from(200u128)).unwrap();
+    assert_eq!(ema, FPDecimal::from_str("149.9999999971897004").unwrap());
+
+    // Cannot compute the EMA before the last rebalance
+    let err = get_ema(deps.as_ref(), None, 40, 1000, FPDecimal::from(200u128)).unwrap_err();
+    assert_eq!(
+        err,
+        StdError::generic_err("Cannot compute the EMA before the last rebalance")
+    );
+
+    // Half-life measured in seconds ignores the heights
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        penalty_params: Some(init_params_with_half_life(EmaHalfLife::Seconds(60))),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let ema = get_ema(deps.as_ref(), None, 51, 1060, FPDecimal::from(200u128)).unwrap();
+    assert_eq!(ema, FPDecimal::from_str("149.9999999971897004").unwrap());
+
+    // Project the EMA two half-lives ahead at the current height
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::ProjectedEma {
+            block_height: None,
+            block_time: Some(1120),
+            net_asset_val: "200".to_string(),
+            cluster_contract: None,
+        },
+    )
+    .unwrap();
+    let response: ProjectedEmaResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        response,
+        ProjectedEmaResponse {
+            block_height: mock_env().block.height,
+            block_time: 1120,
+            ema: "174.9999999971897006".to_string(),
+        }
+    );
+
+    // Half-life must be positive
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        penalty_params: Some(init_params_with_half_life(EmaHalfLife::Seconds(0))),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("EMA half-life must be positive".to_string())
+    );
+}
+
 #[test]
 fn test_notional_penalty_math() {
     let mut deps = mock_dependencies(&[]);
@@ -154,13 +248,13 @@ fn test_notional_penalty_math() {
     let p = str_vec_to_fpdec(&["8.7".to_string(), "2.1".to_string(), "3.5".to_string()]).unwrap();
 
     let i1 = int32_vec_to_fpdec(&[90, 100, 110]);
-    let (penalty, _, _) = notional_penalty(deps.as_ref(), 0u64, &i0, &i1, &w, &p).unwrap();
+    let (penalty, _, _) = notional_penalty(deps.as_ref(), None, 0u64, 0, &i0, &i1, &w, &p).unwrap();
     let res = FPDecimal::from_str("-32.747139223416719612").unwrap();
     assert_eq!(res, penalty);
 
     // Test penalty but now imbalance is too high
     let i1 = int32_vec_to_fpdec(&[80, 100, 120]);
-    let penalty = notional_penalty(deps.as_ref(), 0u64, &i0, &i1, &w, &p);
+    let penalty = notional_penalty(deps.as_ref(), None, 0u64, 0, &i0, &i1, &w, &p);
 
     match penalty {
         Ok(_) => panic!("Must return error"),
@@ -170,17 +264,17 @@ fn test_notional_penalty_math() {
 
     // Test reward by correcting imbalance
     let i1 = int32_vec_to_fpdec(&[98, 100, 102]);
-    let (reward, _, _) = notional_penalty(deps.as_ref(), 0u64, &i0, &i1, &w, &p).unwrap();
+    let (reward, _, _) = notional_penalty(deps.as_ref(), None, 0u64, 0, &i0, &i1, &w, &p).unwrap();
     let res = FPDecimal::from_str("1.36418181815").unwrap();
     assert_eq!(res, reward);
 
     //// Try everything again smaller nav and updated_ema
     let curr_inv = int32_vec_to_fpdec(&[47, 50, 53]);
     let nav = dot(&curr_inv, &p);
-    update_ema(deps.as_mut(), 60, nav).unwrap();
+    update_ema(deps.as_mut(), None, 60, 0, nav).unwrap();
 
     let i1 = int32_vec_to_fpdec(&[90, 100, 110]);
-    let penalty = notional_penalty(deps.as_ref(), 160u64, &i0, &i1, &w, &p);
+    let penalty = notional_penalty(deps.as_ref(), None, 160u64, 0, &i0, &i1, &w, &p);
     match penalty {
         Ok(_) => panic!("Must return error"),
         Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "cluster imbalance too high"),
@@ -188,16 +282,291 @@ fn test_notional_penalty_math() {
     };
 
     let i1 = int32_vec_to_fpdec(&[95, 100, 108]);
-    let (penalty, _, _) = notional_penalty(deps.as_ref(), 160u64, &i0, &i1, &w, &p).unwrap();
+    let (penalty, _, _) =
+        notional_penalty(deps.as_ref(), None, 160u64, 0, &i0, &i1, &w, &p).unwrap();
     let res = FPDecimal::from_str("-9.769495573051444318").unwrap();
     assert_eq!(res, penalty);
 
     let i1 = int32_vec_to_fpdec(&[102, 100, 96]);
-    let (reward, _, _) = notional_penalty(deps.as_ref(), 160u64, &i0, &i1, &w, &p).unwrap();
+    let (reward, _, _) =
+        notional_penalty(deps.as_ref(), None, 160u64, 0, &i0, &i1, &w, &p).unwrap();
     let res = FPDecimal::from_str("1.235034965").unwrap();
     assert_eq!(res, reward);
 }
 
+fn d(s: &str) -> FPDecimal {
+    FPDecimal::from_str(s).unwrap()
+}
+
+#[test]
+fn test_penalty_curve_families() {
+    let e = d("1000");
+
+    // A table with the knots of the piecewise-linear curve integrates to the same penalty
+    let table = PenaltyParams::Table {
+        knots: vec![
+            PenaltyKnot {
+                imbalance: d("0"),
+                penalty_amt: d("0.1"),
+            },
+            PenaltyKnot {
+                imbalance: d("0.01"),
+                penalty_amt: d("0.1"),
+            },
+            PenaltyKnot {
+                imbalance: d("0.1"),
+                penalty_amt: d("1"),
+            },
+        ],
+        reward_amt: d("0.05"),
+        reward_cutoff: d("0.02"),
+        ema_half_life: None,
+    };
+    for (imb0, imb1) in [("0", "5"), ("5", "40"), ("0", "100"), ("20", "60")] {
+        assert_eq!(
+            penalty_area(&table, d(imb0), d(imb1), e).unwrap(),
+            penalty_area(&init_params(), d(imb0), d(imb1), e).unwrap()
+        );
+    }
+    // -- 0.1 * 10 + (0.1 + 1) / 2 * 90
+    assert_eq!(
+        penalty_area(&table, d("0"), d("100"), e).unwrap(),
+        d("50.5")
+    );
+    assert_eq!(
+        penalty_area(&table, d("0"), d("101"), e).unwrap_err(),
+        StdError::generic_err("cluster imbalance too high")
+    );
+
+    // -- 0.1 * 100 + 0.9 * 100 / 3
+    let quadratic = PenaltyParams::Quadratic {
+        penalty_amt_lo: d("0.1"),
+        penalty_amt_hi: d("1"),
+        penalty_cutoff_hi: d("0.1"),
+        reward_amt: d("0.05"),
+        reward_cutoff: d("0.02"),
+        ema_half_life: None,
+    };
+    assert_eq!(
+        penalty_area(&quadratic, d("0"), d("100"), e).unwrap(),
+        d("39.99999999999999997")
+    );
+    assert_eq!(
+        penalty_area(&quadratic, d("0"), d("101"), e).unwrap_err(),
+        StdError::generic_err("cluster imbalance too high")
+    );
+
+    // The tanh curve is symmetric around its mid cutoff
+    // -- (0.1 + 1) / 2 * 40
+    let tanh = PenaltyParams::Tanh {
+        penalty_amt_lo: d("0.1"),
+        penalty_amt_hi: d("1"),
+        penalty_cutoff_mid: d("0.05"),
+        penalty_width: d("0.01"),
+        penalty_cutoff_hi: d("0.1"),
+        reward_amt: d("0.05"),
+        reward_cutoff: d("0.02"),
+        ema_half_life: None,
+    };
+    assert_eq!(penalty_area(&tanh, d("30"), d("70"), e).unwrap(), d("22"));
+    assert_eq!(
+        penalty_area(&tanh, d("0"), d("101"), e).unwrap_err(),
+        StdError::generic_err("cluster imbalance too high")
+    );
+
+    // The marginal penalty rate is the height of the curve
+    assert_eq!(penalty_rate(&quadratic, d("50"), e).unwrap(), d("0.325"));
+    assert_eq!(penalty_rate(&tanh, d("50"), e).unwrap(), d("0.55"));
+    for params in [&init_params(), &table] {
+        assert_eq!(penalty_rate(params, d("5"), e).unwrap(), d("0.1"));
+        assert_eq!(penalty_rate(params, d("55"), e).unwrap(), d("0.55"));
+        assert_eq!(penalty_rate(params, d("100"), e).unwrap(), d("1"));
+        assert_eq!(
+            penalty_rate(params, d("101"), e).unwrap_err(),
+            StdError::generic_err("cluster imbalance too high")
+        );
+    }
+
+    // Splitting a rebalance in two does not change the total penalty
+    for params in [init_params(), table, quadratic, tanh] {
+        let whole = penalty_area(&params, d("3"), d("90"), e).unwrap();
+        let split = penalty_area(&params, d("3"), d("47"), e).unwrap()
+            + penalty_area(&params, d("47"), d("90"), e).unwrap();
+        assert!((whole - split).abs() < d("0.000000000001"));
+
+        // The area over a small step is close to the rate times the step
+        let rate = penalty_rate(&params, d("47"), e).unwrap();
+        let area = penalty_area(&params, d("47"), d("47.001"), e).unwrap();
+        assert!((area - rate * d("0.001")).abs() < d("0.000001"));
+    }
+}
+
+#[test]
+fn test_query_penalty_curve() {
+    let mut deps = mock_dependencies(&[]);
+    mock_init(deps.as_mut());
+
+    let curve_msg = |points: u32, inventory: &[u128]| QueryMsg::PenaltyCurve {
+        inventory: inventory.iter().map(|a| Uint128::new(*a)).collect(),
+        asset_prices: vec!["8.7".to_string(), "2.1".to_string(), "3.5".to_string()],
+        target_weights: vec![Uint128::new(100), Uint128::new(100), Uint128::new(100)],
+        points,
+        cluster_contract: None,
+    };
+
+    // Before any rebalance, e is the net asset value
+    // -- e = 1000 * 8.7 + 1010 * 2.1 + 994 * 3.5 = 14300
+    let res = query(deps.as_ref(), mock_env(), curve_msg(3, &[1000, 1010, 994])).unwrap();
+    let res: PenaltyCurveResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        res,
+        PenaltyCurveResponse {
+            imbalance: "42".to_string(),
+            e: "14300".to_string(),
+            penalty_cutoff_lo: "143".to_string(),
+            penalty_cutoff_hi: "1430".to_string(),
+            reward_cutoff: "286".to_string(),
+            points: vec![
+                PenaltyCurvePoint {
+                    imbalance: "0".to_string(),
+                    penalty_rate: "0.1".to_string(),
+                },
+                PenaltyCurvePoint {
+                    imbalance: "715".to_string(),
+                    penalty_rate: "0.5".to_string(),
+                },
+                PenaltyCurvePoint {
+                    imbalance: "1430".to_string(),
+                    penalty_rate: "1".to_string(),
+                },
+            ],
+        }
+    );
+
+    for points in [1, 101] {
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            curve_msg(points, &[1000, 1010, 994]),
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            StdError::generic_err("Penalty curve points must be between 2 and 100")
+        );
+    }
+
+    let res = query(deps.as_ref(), mock_env(), curve_msg(3, &[0, 0, 0]));
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Cannot sample the penalty curve of an empty cluster")
+    );
+}
+
+#[test]
+fn bad_penalty_curves() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("addr0000", &[]);
+
+    let knots = |points: &[(&str, &str)]| PenaltyParams::Table {
+        knots: points
+            .iter()
+            .map(|(imbalance, penalty_amt)| PenaltyKnot {
+                imbalance: d(imbalance),
+                penalty_amt: d(penalty_amt),
+            })
+            .collect(),
+        reward_amt: d("0.05"),
+        reward_cutoff: d("0.02"),
+        ema_half_life: None,
+    };
+
+    for (penalty_params, err) in [
+        (
+            knots(&[("0", "1")]),
+            "penalty table must have at least two knots",
+        ),
+        (
+            knots(&[("0.01", "0.1"), ("0.1", "1")]),
+            "penalty table must start at zero imbalance",
+        ),
+        (
+            knots(&[("0", "0.1"), ("0.1", "0.5"), ("0.1", "1")]),
+            "penalty table imbalances must be increasing",
+        ),
+        (
+            knots(&[("0", "0.1"), ("0.1", "0.9")]),
+            "penalty amount must reach one",
+        ),
+        (
+            PenaltyParams::Quadratic {
+                penalty_amt_lo: d("0.1"),
+                penalty_amt_hi: d("0.9"),
+                penalty_cutoff_hi: d("0.1"),
+                reward_amt: d("0.05"),
+                reward_cutoff: d("0.02"),
+                ema_half_life: None,
+            },
+            "penalty amount must reach one",
+        ),
+        (
+            PenaltyParams::Quadratic {
+                penalty_amt_lo: d("0.1"),
+                penalty_amt_hi: d("1"),
+                penalty_cutoff_hi: d("0"),
+                reward_amt: d("0.05"),
+                reward_cutoff: d("0.02"),
+                ema_half_life: None,
+            },
+            "penalty high cutoff must be positive",
+        ),
+        (
+            PenaltyParams::Tanh {
+                penalty_amt_lo: d("0.1"),
+                penalty_amt_hi: d("1"),
+                penalty_cutoff_mid: d("0.05"),
+                penalty_width: d("0.02"),
+                penalty_cutoff_hi: d("-0.1"),
+                reward_amt: d("0.05"),
+                reward_cutoff: d("0.02"),
+                ema_half_life: None,
+            },
+            "penalty high cutoff must be positive",
+        ),
+        (
+            PenaltyParams::PiecewiseLinear {
+                penalty_amt_lo: d("0.1"),
+                penalty_cutoff_lo: d("0.01"),
+                penalty_amt_hi: d("1"),
+                penalty_cutoff_hi: d("0"),
+                reward_amt: d("0.05"),
+                reward_cutoff: d("0.02"),
+                ema_half_life: None,
+            },
+            "penalty high cutoff must be positive",
+        ),
+        (
+            PenaltyParams::PiecewiseLinear {
+                penalty_amt_lo: d("0.1"),
+                penalty_cutoff_lo: d("0.1"),
+                penalty_amt_hi: d("1"),
+                penalty_cutoff_hi: d("0.1"),
+                reward_amt: d("0.05"),
+                reward_cutoff: d("0.02"),
+                ema_half_life: None,
+            },
+            "penalty low cutoff must be below the high cutoff",
+        ),
+    ] {
+        let msg = InstantiateMsg {
+            owner: "penalty_owner".to_string(),
+            penalty_params,
+            factory: None,
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::Generic(err.to_string()));
+    }
+}
+
 #[test]
 fn test_mint_actions() {
     let mut deps = mock_dependencies(&[]);
@@ -215,7 +584,7 @@ fn test_mint_actions() {
     let curr_inv = &[Uint128::new(1000), Uint128::new(1010), Uint128::new(994)];
 
     let nav = dot(&int_vec_to_fpdec(curr_inv), &p);
-    update_ema(deps.as_mut(), 60, nav).unwrap();
+    update_ema(deps.as_mut(), None, 60, 0, nav).unwrap();
 
     let create_asset_amounts = &[Uint128::new(1000), Uint128::new(1010), Uint128::new(994)];
 
@@ -229,6 +598,7 @@ fn test_mint_actions() {
             create_asset_amounts: create_asset_amounts.to_vec(),
             asset_prices: p_strs.to_vec(),
             target_weights: weights.to_vec(),
+            cluster_contract: None,
         },
     )
     .unwrap();
@@ -252,6 +622,7 @@ fn test_mint_actions() {
             create_asset_amounts: create_asset_amounts.to_vec(),
             asset_prices: p_strs.to_vec(),
             target_weights: weights.to_vec(),
+            cluster_contract: None,
         },
     )
     .unwrap();
@@ -299,7 +670,7 @@ fn test_redeem_actions() {
     let curr_inv = &[Uint128::new(1000), Uint128::new(1010), Uint128::new(994)];
 
     let nav = dot(&int_vec_to_fpdec(curr_inv), &p);
-    update_ema(deps.as_mut(), 60, nav).unwrap();
+    update_ema(deps.as_mut(), None, 60, 0, nav).unwrap();
 
     let redeem_asset_amounts = &[Uint128::new(100), Uint128::new(100), Uint128::new(100)];
 
@@ -314,6 +685,7 @@ fn test_redeem_actions() {
             max_tokens: Uint128::new(10000),
             asset_prices: p_strs.to_vec(),
             target_weights: weights.to_vec(),
+            cluster_contract: None,
         },
     )
     .unwrap();
@@ -340,6 +712,7 @@ fn test_redeem_actions() {
             max_tokens: Uint128::new(100000),
             asset_prices: p_strs.to_vec(),
             target_weights: weights.to_vec(),
+            cluster_contract: None,
         },
     )
     .unwrap();
@@ -377,6 +750,102 @@ fn test_redeem_actions() {
     }
 }
 
+#[test]
+fn test_swap_actions() {
+    let mut deps = mock_dependencies(&[]);
+    mock_init(deps.as_mut());
+    let env = mock_env_height(0, 10000);
+    let info = mock_info(TEST_CREATOR, &[]);
+
+    // Target weights and prices
+    let p_strs = &["8.7".to_string(), "2.1".to_string(), "3.5".to_string()];
+    let weights = &[Uint128::new(100), Uint128::new(100), Uint128::new(100)];
+
+    let p = str_vec_to_fpdec(p_strs).unwrap();
+
+    // Set up EMA
+    let curr_inv = &[Uint128::new(1000), Uint128::new(1010), Uint128::new(994)];
+
+    let nav = dot(&int_vec_to_fpdec(curr_inv), &p);
+    update_ema(deps.as_mut(), None, 60, 0, nav).unwrap();
+
+    // Swap 10 of the first asset for 41 of the second asset at oracle prices
+    let offer_asset_amounts = &[Uint128::new(10), Uint128::zero(), Uint128::zero()];
+    let ask_asset_amounts = &[Uint128::zero(), Uint128::new(41), Uint128::zero()];
+
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::PenaltyQuerySwap {
+            block_height: 120,
+            inventory: curr_inv.to_vec(),
+            offer_asset_amounts: offer_asset_amounts.to_vec(),
+            ask_asset_amounts: ask_asset_amounts.to_vec(),
+            asset_prices: p_strs.to_vec(),
+            target_weights: weights.to_vec(),
+            cluster_contract: None,
+        },
+    )
+    .unwrap();
+
+    // Imbalance increases, the asked amount is reduced by the penalty
+    let response: PenaltySwapResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        response.ask_assets,
+        vec![Uint128::zero(), Uint128::new(35), Uint128::zero()]
+    );
+    assert_eq!(response.penalty, Uint128::zero());
+    assert_eq!(
+        response.attributes,
+        vec![attr("penalty", "-13.40318277998784334")]
+    );
+
+    // Swap back the second asset for the first asset
+    let curr_inv = &[Uint128::new(1010), Uint128::new(970), Uint128::new(994)];
+    let offer_asset_amounts = &[Uint128::zero(), Uint128::new(41), Uint128::zero()];
+    let ask_asset_amounts = &[Uint128::new(10), Uint128::zero(), Uint128::zero()];
+
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::PenaltyQuerySwap {
+            block_height: 120,
+            inventory: curr_inv.to_vec(),
+            offer_asset_amounts: offer_asset_amounts.to_vec(),
+            ask_asset_amounts: ask_asset_amounts.to_vec(),
+            asset_prices: p_strs.to_vec(),
+            target_weights: weights.to_vec(),
+            cluster_contract: None,
+        },
+    )
+    .unwrap();
+
+    // Imbalance decreases, no reward below the reward cutoff
+    let response: PenaltySwapResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        response.ask_assets,
+        vec![Uint128::new(9), Uint128::zero(), Uint128::zero()]
+    );
+    assert_eq!(response.penalty, Uint128::zero());
+
+    let msg = ExecuteMsg::PenaltySwap {
+        block_height: 120,
+        inventory: curr_inv.to_vec(),
+        offer_asset_amounts: offer_asset_amounts.to_vec(),
+        ask_asset_amounts: ask_asset_amounts.to_vec(),
+        asset_prices: p_strs.to_vec(),
+        target_weights: weights.to_vec(),
+    };
+
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    for log in res.attributes.iter() {
+        match log.key.as_str() {
+            "new_ema" => assert_eq!("14300.285487745892121269", log.value),
+            &_ => panic!("Invalid value found in log"),
+        }
+    }
+}
+
 #[test]
 fn test_query_penalty() {
     let mut deps = mock_dependencies(&[]);
@@ -392,7 +861,7 @@ fn test_query_penalty() {
     // Set up EMA
     let curr_inv = &[Uint128::new(47), Uint128::new(50), Uint128::new(53)];
     let nav = dot(&int_vec_to_fpdec(curr_inv), &p);
-    update_ema(deps.as_mut(), 60, nav).unwrap();
+    update_ema(deps.as_mut(), None, 60, 0, nav).unwrap();
 
     let inv0 = &[Uint128::new(95), Uint128::new(100), Uint128::new(105)];
 
@@ -407,6 +876,7 @@ fn test_query_penalty() {
             inventory1: inv1.to_vec(),
             asset_prices: p_strs.to_vec(),
             target_weights: weights.to_vec(),
+            cluster_contract: None,
         },
     );
     match res {
@@ -426,6 +896,7 @@ fn test_query_penalty() {
             inventory1: inv1.to_vec(),
             asset_prices: p_strs.to_vec(),
             target_weights: weights.to_vec(),
+            cluster_contract: None,
         },
     )
     .unwrap();
@@ -447,6 +918,7 @@ fn test_query_penalty() {
             inventory1: inv1.to_vec(),
             asset_prices: p_strs.to_vec(),
             target_weights: weights.to_vec(),
+            cluster_contract: None,
         },
     )
     .unwrap();
@@ -455,23 +927,184 @@ fn test_query_penalty() {
     assert_eq!(response.attributes, vec![attr("penalty", "1.235034965")]);
 }
 
+#[test]
+fn test_query_optimal_basket() {
+    let mut deps = mock_dependencies(&[]);
+    mock_init(deps.as_mut());
+
+    let prices = vec!["8.7".to_string(), "2.1".to_string(), "3.5".to_string()];
+    let weights = vec![Uint128::new(100), Uint128::new(100), Uint128::new(100)];
+    let inventory = vec![Uint128::new(1000), Uint128::new(1200), Uint128::new(800)];
+    let basket_msg = |budget: RebalanceBudget| QueryMsg::OptimalBasket {
+        cluster_token_supply: Uint128::new(1000000),
+        inventory: inventory.clone(),
+        asset_prices: prices.clone(),
+        target_weights: weights.clone(),
+        budget,
+        cluster_contract: None,
+    };
+
+    // Create only tops up the underweight assets
+    // -- Each unit of value in the basket reduces the imbalance by one
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        basket_msg(RebalanceBudget::Create {
+            value: "500".to_string(),
+        }),
+    )
+    .unwrap();
+    let res: OptimalBasketResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        res,
+        OptimalBasketResponse {
+            basket: vec![Uint128::new(8), Uint128::zero(), Uint128::new(121)],
+            tokens: Uint128::new(36902),
+            imbalance: "777.411188811188811188".to_string(),
+            reward: Uint128::new(24),
+        }
+    );
+
+    // A pro-rata basket of the same value earns no reward
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::PenaltyQueryCreate {
+            block_height: mock_env().block.height,
+            cluster_token_supply: Uint128::new(1000000),
+            inventory: inventory.clone(),
+            create_asset_amounts: vec![Uint128::new(35), Uint128::new(42), Uint128::new(28)],
+            asset_prices: prices.clone(),
+            target_weights: weights.clone(),
+            cluster_contract: None,
+        },
+    )
+    .unwrap();
+    let res: PenaltyCreateResponse = from_binary(&res).unwrap();
+    assert_eq!(res.penalty, Uint128::zero());
+
+    // Redeem only withdraws the overweight assets, burning less than the budget thanks to the reward
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        basket_msg(RebalanceBudget::Redeem {
+            max_tokens: Uint128::new(50000),
+            redeem_fee_rate: None,
+        }),
+    )
+    .unwrap();
+    let res: OptimalBasketResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        res,
+        OptimalBasketResponse {
+            basket: vec![Uint128::new(41), Uint128::new(162), Uint128::zero()],
+            tokens: Uint128::new(48491),
+            imbalance: "921.797202797202797202".to_string(),
+            reward: Uint128::new(17),
+        }
+    );
+
+    // The redeem fee and the rounding of the token cost stay within the budget
+    for (max_tokens, redeem_fee_rate) in [(50000u128, "0.01"), (1000, "0.003"), (4, "0")] {
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            basket_msg(RebalanceBudget::Redeem {
+                max_tokens: Uint128::new(max_tokens),
+                redeem_fee_rate: Some(redeem_fee_rate.to_string()),
+            }),
+        )
+        .unwrap();
+        let res: OptimalBasketResponse = from_binary(&res).unwrap();
+        assert!(res.tokens <= Uint128::new(max_tokens));
+    }
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        basket_msg(RebalanceBudget::Redeem {
+            max_tokens: Uint128::new(50000),
+            redeem_fee_rate: Some("0.01".to_string()),
+        }),
+    )
+    .unwrap();
+    let res: OptimalBasketResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        res,
+        OptimalBasketResponse {
+            basket: vec![Uint128::new(40), Uint128::new(161), Uint128::zero()],
+            tokens: Uint128::new(48223),
+            imbalance: "927.083916083916083916".to_string(),
+            reward: Uint128::new(16),
+        }
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        basket_msg(RebalanceBudget::Redeem {
+            max_tokens: Uint128::new(2),
+            redeem_fee_rate: None,
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Too few cluster tokens to redeem")
+    );
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        basket_msg(RebalanceBudget::Redeem {
+            max_tokens: Uint128::new(50000),
+            redeem_fee_rate: Some("1".to_string()),
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Redeem fee rate must be within [0, 1)")
+    );
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        basket_msg(RebalanceBudget::Redeem {
+            max_tokens: Uint128::new(1000001),
+            redeem_fee_rate: None,
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Cannot redeem more than the cluster token supply")
+    );
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        basket_msg(RebalanceBudget::Create {
+            value: "-1".to_string(),
+        }),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Create value must not be negative")
+    );
+}
+
 #[test]
 fn test_update_config() {
     let mut deps = mock_dependencies(&[]);
     mock_init(deps.as_mut());
 
-    let penalty_params = PenaltyParams {
+    let penalty_params = PenaltyParams::PiecewiseLinear {
         penalty_amt_lo: FPDecimal::from_str("0.2").unwrap(),
         penalty_cutoff_lo: FPDecimal::from_str("0.02").unwrap(),
         penalty_amt_hi: FPDecimal::from_str("1").unwrap(),
         penalty_cutoff_hi: FPDecimal::from_str("0.2").unwrap(),
         reward_amt: FPDecimal::from_str("0.04").unwrap(),
         reward_cutoff: FPDecimal::from_str("0.03").unwrap(),
+        ema_half_life: None,
     };
 
     let msg = ExecuteMsg::UpdateConfig {
         owner: Some("newowner0000".to_string()),
-        penalty_params: Some(penalty_params),
+        penalty_params: Some(penalty_params.clone()),
     };
 
     // unauthorized update
@@ -481,12 +1114,174 @@ fn test_update_config() {
 
     // successful update
     let info = mock_info("creator", &[]);
-    let _res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-    let query_msg = QueryMsg::Params {};
+    let query_msg = QueryMsg::Params {
+        cluster_contract: None,
+    };
     let query_res: ParamsResponse =
         from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
     assert_eq!(query_res.penalty_params, penalty_params);
+
+    // invalid penalty curve
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        penalty_params: Some(PenaltyParams::Tanh {
+            penalty_amt_lo: FPDecimal::from_str("0.1").unwrap(),
+            penalty_amt_hi: FPDecimal::from_str("1").unwrap(),
+            penalty_cutoff_mid: FPDecimal::from_str("0.05").unwrap(),
+            penalty_width: FPDecimal::zero(),
+            penalty_cutoff_hi: FPDecimal::from_str("0.1").unwrap(),
+            reward_amt: FPDecimal::from_str("0.05").unwrap(),
+            reward_cutoff: FPDecimal::from_str("0.02").unwrap(),
+            ema_half_life: None,
+        }),
+    };
+    let info = mock_info("newowner0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Generic("penalty width must be positive".to_string())
+    );
+}
+
+#[test]
+fn test_shared_penalty() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_clusters(&[
+        ("cluster0000", true),
+        ("cluster0001", true),
+        ("cluster0003", false),
+    ]);
+
+    let msg = InstantiateMsg {
+        owner: TEST_CREATOR.to_string(),
+        penalty_params: init_params(),
+        factory: Some("factory0000".to_string()),
+    };
+    let info = mock_info(TEST_CREATOR, &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let p_strs = &["8.7".to_string(), "2.1".to_string(), "3.5".to_string()];
+    let weights = &[Uint128::new(100), Uint128::new(100), Uint128::new(100)];
+    let inventory = &[Uint128::new(1000), Uint128::new(1010), Uint128::new(994)];
+    let msg = ExecuteMsg::PenaltyCreate {
+        block_height: 60,
+        cluster_token_supply: Uint128::new(1000000),
+        inventory: inventory.to_vec(),
+        create_asset_amounts: vec![],
+        asset_prices: p_strs.to_vec(),
+        target_weights: weights.to_vec(),
+    };
+
+    // Only clusters registered in the factory can rebalance, not even the owner
+    for sender in ["cluster0002", TEST_CREATOR] {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(sender, &[]),
+            msg.clone(),
+        );
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+    }
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cluster0000", &[]),
+        msg,
+    )
+    .unwrap();
+
+    // A decommissioned cluster can still be redeemed pro-rata
+    let msg = ExecuteMsg::PenaltyRedeem {
+        block_height: 60,
+        cluster_token_supply: Uint128::new(1000000),
+        inventory: inventory.to_vec(),
+        max_tokens: Uint128::new(1000),
+        redeem_asset_amounts: vec![],
+        asset_prices: p_strs.to_vec(),
+        target_weights: weights.to_vec(),
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cluster0003", &[]),
+        msg,
+    )
+    .unwrap();
+
+    // Each cluster keeps its own EMA state
+    let params = |deps: &OwnedDeps<_, _, _>, cluster: &str| -> ParamsResponse {
+        let msg = QueryMsg::Params {
+            cluster_contract: Some(cluster.to_string()),
+        };
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+    };
+    let res = params(&deps, "cluster0000");
+    assert_eq!(res.last_block, 60);
+    assert_eq!(res.ema, "14300");
+    assert_eq!(res.penalty_params, init_params());
+    let res = params(&deps, "cluster0001");
+    assert_eq!(res.last_block, 0);
+    assert_eq!(res.ema, "0");
+
+    // Queries must name the cluster
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Params {
+            cluster_contract: None,
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("A shared penalty contract requires the cluster contract")
+    );
+
+    // Only the owner overrides the parameters of a cluster
+    let override_params = init_params_with_half_life(EmaHalfLife::Blocks(100));
+    let msg = ExecuteMsg::UpdateClusterParams {
+        cluster_contract: "cluster0001".to_string(),
+        penalty_params: Some(override_params.clone()),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cluster0001", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_cluster_params"),
+            attr("cluster_contract", "cluster0001"),
+        ]
+    );
+    assert_eq!(params(&deps, "cluster0001").penalty_params, override_params);
+    assert_eq!(params(&deps, "cluster0000").penalty_params, init_params());
+
+    // Removing the override falls back to the default parameters
+    let msg = ExecuteMsg::UpdateClusterParams {
+        cluster_contract: "cluster0001".to_string(),
+        penalty_params: None,
+    };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(params(&deps, "cluster0001").penalty_params, init_params());
+
+    // A single cluster penalty contract has no cluster parameters
+    let mut deps = mock_dependencies(&[]);
+    mock_init(deps.as_mut());
+    let msg = ExecuteMsg::UpdateClusterParams {
+        cluster_contract: "cluster0001".to_string(),
+        penalty_params: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Generic("Cluster parameters require a shared penalty contract".to_string())
+    );
 }
 
 #[test]
@@ -502,7 +1297,8 @@ fn test_query_config() {
         config,
         ConfigResponse {
             owner: TEST_CREATOR.to_string(),
-            penalty_params: init_params()
+            penalty_params: init_params(),
+            factory: None,
         }
     );
 }
@@ -527,3 +1323,47 @@ fn migration() {
     // we can just call .unwrap() to assert this was a success
     let _res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
 }
+
+#[test]
+fn migration_from_legacy_params() {
+    let mut deps = mock_dependencies(&[]);
+    mock_init(deps.as_mut());
+
+    // Config stored before penalty curve families
+    singleton(&mut deps.storage, CONFIG_KEY)
+        .save(&LegacyPenaltyConfig {
+            owner: Addr::unchecked("penalty_owner"),
+            penalty_params: LegacyPenaltyParams {
+                penalty_amt_lo: d("0.1"),
+                penalty_cutoff_lo: d("0.01"),
+                penalty_amt_hi: d("1"),
+                penalty_cutoff_hi: d("0.1"),
+                reward_amt: d("0.05"),
+                reward_cutoff: d("0.02"),
+            },
+            ema: d("100"),
+            last_block: 50u64,
+        })
+        .unwrap();
+    assert!(read_config(&deps.storage).is_err());
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(
+        read_config(&deps.storage).unwrap(),
+        PenaltyConfig {
+            owner: Addr::unchecked("penalty_owner"),
+            penalty_params: init_params(),
+            ema: d("100"),
+            last_block: 50u64,
+            last_time: 0u64,
+            factory: None,
+        }
+    );
+
+    // Migrating again keeps the config
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(
+        read_config(&deps.storage).unwrap().penalty_params,
+        init_params()
+    );
+}
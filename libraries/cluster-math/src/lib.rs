@@ -1,7 +1,7 @@
 pub mod fp_decimal;
 pub mod vector;
 
-use cosmwasm_std::{StdResult, Uint128};
+use cosmwasm_std::{StdError, StdResult, Uint128};
 pub use fp_decimal::*;
 use std::str::FromStr;
 pub use vector::*;
@@ -41,6 +41,96 @@ pub fn imbalance(i: &[FPDecimal], p: &[FPDecimal], w: &[FPDecimal]) -> FPDecimal
     sum(&abs(&err_portfolio)) / wp
 }
 
+/// ## Description
+/// Calculates how much capital of the additional `value` to allocate into each asset
+/// so that the cluster imbalance after the deposit is minimized.
+///
+/// Adding capital can only reduce the imbalance of assets below their target allocation
+/// of the new NAV, and the sum of those shortfalls is never less than `value`. Hence,
+/// splitting `value` proportionally to the shortfalls reaches the minimum imbalance.
+///
+/// ## Params
+/// - **i** is a reference to an array containing objects of type [`FPDecimal`] which
+///     is the asset inventory.
+///
+/// - **p** is a reference to an array containing objects of type [`FPDecimal`] which
+///     are the prices of the assets.
+///
+/// - **w** is a reference to an array containing objects of type [`FPDecimal`] which
+///     are the target weights of the assets.
+///
+/// - **value** is an object of type [`FPDecimal`] which is the capital to allocate.
+pub fn min_imbalance_allocation(
+    i: &[FPDecimal],
+    p: &[FPDecimal],
+    w: &[FPDecimal],
+    value: FPDecimal,
+) -> Vec<FPDecimal> {
+    // Optimal capital allocation of the NAV after the deposit
+    // -- A_opt = elem_mul(targets, prices) * (dot(inventory, prices) + value) / wp
+    let a_opt = mul_const(&mul(w, p), (dot(i, p) + value) / dot(w, p));
+
+    // Shortfall of each asset from its optimal capital allocation
+    let shortfall = sub(&a_opt, &mul(i, p))
+        .into_iter()
+        .map(|x| if x.sign == 1 { x } else { FPDecimal::zero() })
+        .collect::<Vec<FPDecimal>>();
+
+    let total_shortfall = sum(&shortfall);
+    if total_shortfall.num.is_zero() {
+        return vec![FPDecimal::zero(); i.len()];
+    }
+    shortfall
+        .into_iter()
+        .map(|x| x * value / total_shortfall)
+        .collect()
+}
+
+/// ## Description
+/// Calculates how much capital of `value` to withdraw from each asset so that
+/// the cluster imbalance after the withdrawal is minimized.
+///
+/// Withdrawing capital can only reduce the imbalance of assets above their target allocation
+/// of the new NAV, and the sum of those excesses is never less than `value`. Hence,
+/// splitting `value` proportionally to the excesses reaches the minimum imbalance.
+///
+/// ## Params
+/// - **i** is a reference to an array containing objects of type [`FPDecimal`] which
+///     is the asset inventory.
+///
+/// - **p** is a reference to an array containing objects of type [`FPDecimal`] which
+///     are the prices of the assets.
+///
+/// - **w** is a reference to an array containing objects of type [`FPDecimal`] which
+///     are the target weights of the assets.
+///
+/// - **value** is an object of type [`FPDecimal`] which is the capital to withdraw.
+pub fn min_imbalance_withdrawal(
+    i: &[FPDecimal],
+    p: &[FPDecimal],
+    w: &[FPDecimal],
+    value: FPDecimal,
+) -> Vec<FPDecimal> {
+    // Optimal capital allocation of the NAV after the withdrawal
+    // -- A_opt = elem_mul(targets, prices) * (dot(inventory, prices) - value) / wp
+    let a_opt = mul_const(&mul(w, p), (dot(i, p) - value) / dot(w, p));
+
+    // Excess of each asset over its optimal capital allocation
+    let excess = sub(&mul(i, p), &a_opt)
+        .into_iter()
+        .map(|x| if x.sign == 1 { x } else { FPDecimal::zero() })
+        .collect::<Vec<FPDecimal>>();
+
+    let total_excess = sum(&excess);
+    if total_excess.num.is_zero() {
+        return vec![FPDecimal::zero(); i.len()];
+    }
+    excess
+        .into_iter()
+        .map(|x| x * value / total_excess)
+        .collect()
+}
+
 /// ## Description
 /// Converts an int32 array to a FPDecimal array.
 ///
@@ -71,3 +161,202 @@ pub fn str_vec_to_fpdec(arr: &[String]) -> StdResult<Vec<FPDecimal>> {
         .map(|val| FPDecimal::from_str(val))
         .collect::<StdResult<Vec<FPDecimal>>>()
 }
+
+/// Decimal precision of the oracle price unit, to which asset amounts are
+/// normalised before computing values
+pub const NORMALIZED_DECIMALS: u8 = 6;
+
+/// Highest decimal precision of an asset that can be normalised
+pub const MAX_DECIMALS: u8 = 18;
+
+/// ## Description
+/// Returns the factor between the normalised precision and `decimals`.
+/// Returns a [`StdError`] if the factor overflows.
+///
+/// ## Params
+/// - **decimals** is an object of type [`u8`] which is the asset decimal precision.
+#[allow(clippy::manual_abs_diff)]
+fn decimals_factor(decimals: u8) -> StdResult<Uint128> {
+    let diff = if decimals >= NORMALIZED_DECIMALS {
+        decimals - NORMALIZED_DECIMALS
+    } else {
+        NORMALIZED_DECIMALS - decimals
+    };
+    10u128
+        .checked_pow(diff as u32)
+        .map(Uint128::new)
+        .ok_or_else(|| StdError::generic_err("Asset decimals are too large"))
+}
+
+/// ## Description
+/// Converts an asset amount with `decimals` decimal precision to the normalised
+/// precision [`NORMALIZED_DECIMALS`], rounding down.
+/// Returns a [`StdError`] on overflow.
+///
+/// ## Params
+/// - **amount** is an object of type [`Uint128`] which is the asset amount.
+///
+/// - **decimals** is an object of type [`u8`] which is the asset decimal precision.
+pub fn normalize_amount(amount: Uint128, decimals: u8) -> StdResult<Uint128> {
+    let factor = decimals_factor(decimals)?;
+    if decimals >= NORMALIZED_DECIMALS {
+        Ok(amount.checked_div(factor)?)
+    } else {
+        Ok(amount.checked_mul(factor)?)
+    }
+}
+
+/// ## Description
+/// Converts a normalised asset amount back to the asset `decimals` decimal
+/// precision, rounding down.
+/// Returns a [`StdError`] on overflow.
+///
+/// ## Params
+/// - **amount** is an object of type [`Uint128`] which is the normalised amount.
+///
+/// - **decimals** is an object of type [`u8`] which is the asset decimal precision.
+pub fn denormalize_amount(amount: Uint128, decimals: u8) -> StdResult<Uint128> {
+    let factor = decimals_factor(decimals)?;
+    if decimals >= NORMALIZED_DECIMALS {
+        Ok(amount.checked_mul(factor)?)
+    } else {
+        Ok(amount.checked_div(factor)?)
+    }
+}
+
+/// ## Description
+/// Normalises an array of asset amounts. Amounts without decimals are assumed
+/// to have the normalised precision.
+/// Returns a [`StdError`] on overflow.
+///
+/// ## Params
+/// - **arr** is a reference to an array containing objects of type [`Uint128`].
+///
+/// - **decimals** is a reference to an array containing objects of type [`u8`]
+///     which are the decimal precisions of the assets.
+pub fn normalize_amounts(arr: &[Uint128], decimals: &[u8]) -> StdResult<Vec<Uint128>> {
+    arr.iter()
+        .enumerate()
+        .map(|(i, val)| {
+            normalize_amount(
+                *val,
+                decimals.get(i).copied().unwrap_or(NORMALIZED_DECIMALS),
+            )
+        })
+        .collect()
+}
+
+/// ## Description
+/// Converts an array of normalised asset amounts back to the asset decimal
+/// precisions. Amounts without decimals are assumed to have the normalised precision.
+/// Returns a [`StdError`] on overflow.
+///
+/// ## Params
+/// - **arr** is a reference to an array containing objects of type [`Uint128`].
+///
+/// - **decimals** is a reference to an array containing objects of type [`u8`]
+///     which are the decimal precisions of the assets.
+pub fn denormalize_amounts(arr: &[Uint128], decimals: &[u8]) -> StdResult<Vec<Uint128>> {
+    arr.iter()
+        .enumerate()
+        .map(|(i, val)| {
+            denormalize_amount(
+                *val,
+                decimals.get(i).copied().unwrap_or(NORMALIZED_DECIMALS),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        denormalize_amount, denormalize_amounts, imbalance, min_imbalance_allocation,
+        min_imbalance_withdrawal, normalize_amount, normalize_amounts, FPDecimal,
+    };
+    use cosmwasm_std::{OverflowError, OverflowOperation, StdError, Uint128};
+
+    #[test]
+    fn test_normalize_amounts() {
+        let amounts = vec![
+            Uint128::new(1_500_000),
+            Uint128::new(1_500_000_000_000_000_123),
+            Uint128::new(150),
+            Uint128::new(7),
+        ];
+        let decimals = vec![6, 18, 2];
+
+        let normalized = normalize_amounts(&amounts, &decimals).unwrap();
+        assert_eq!(
+            normalized,
+            vec![
+                Uint128::new(1_500_000),
+                Uint128::new(1_500_000),
+                Uint128::new(1_500_000),
+                Uint128::new(7),
+            ]
+        );
+        assert_eq!(
+            denormalize_amounts(&normalized, &decimals).unwrap(),
+            vec![
+                Uint128::new(1_500_000),
+                Uint128::new(1_500_000_000_000_000_000),
+                Uint128::new(150),
+                Uint128::new(7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_overflow() {
+        assert_eq!(
+            normalize_amount(Uint128::MAX, 0).unwrap_err(),
+            StdError::overflow(OverflowError::new(
+                OverflowOperation::Mul,
+                Uint128::MAX,
+                Uint128::new(1_000_000)
+            ))
+        );
+        assert_eq!(
+            denormalize_amount(Uint128::new(1), 45).unwrap_err(),
+            StdError::generic_err("Asset decimals are too large")
+        );
+        assert_eq!(
+            normalize_amount(Uint128::new(1), 45).unwrap_err(),
+            StdError::generic_err("Asset decimals are too large")
+        );
+    }
+
+    #[test]
+    fn test_min_imbalance_allocation() {
+        let i = vec![FPDecimal::from(10u128), FPDecimal::from(30u128)];
+        let p = vec![FPDecimal::from(2u128), FPDecimal::one()];
+        let w = vec![FPDecimal::one(), FPDecimal::one()];
+
+        // NAV 50 -> 70, target allocation [70 * 2 / 3, 70 / 3], current allocation [20, 30]
+        let alloc = min_imbalance_allocation(&i, &p, &w, FPDecimal::from(20u128));
+        assert_eq!(alloc, vec![FPDecimal::from(20u128), FPDecimal::zero()]);
+
+        // Deposit larger than the shortfall is split to keep both assets on target
+        let alloc = min_imbalance_allocation(&i, &p, &w, FPDecimal::from(40u128));
+        let i1 = vec![i[0] + alloc[0] / p[0], i[1] + alloc[1] / p[1]];
+        assert_eq!(imbalance(&i1, &p, &w), FPDecimal::zero());
+    }
+
+    #[test]
+    fn test_min_imbalance_withdrawal() {
+        let i = vec![FPDecimal::from(10u128), FPDecimal::from(30u128)];
+        let p = vec![FPDecimal::from(2u128), FPDecimal::one()];
+        let w = vec![FPDecimal::one(), FPDecimal::one()];
+
+        // NAV 50 -> 40, target allocation [40 * 2 / 3, 40 / 3], current allocation [20, 30]
+        let alloc = min_imbalance_withdrawal(&i, &p, &w, FPDecimal::from(10u128));
+        assert_eq!(alloc, vec![FPDecimal::zero(), FPDecimal::from(10u128)]);
+
+        // Withdrawal larger than the excess is split to keep both assets on target
+        let alloc = min_imbalance_withdrawal(&i, &p, &w, FPDecimal::from(20u128));
+        let i1 = vec![i[0] - alloc[0] / p[0], i[1] - alloc[1] / p[1]];
+        assert_eq!(imbalance(&i1, &p, &w), FPDecimal::zero());
+    }
+}
@@ -1,5 +1,7 @@
-use astroport::asset::Asset;
-use cosmwasm_std::{Addr, Uint128};
+use astroport::asset::{Asset, AssetInfo};
+use cluster_math::NORMALIZED_DECIMALS;
+use cosmwasm_std::{Addr, Attribute, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -39,6 +41,7 @@ pub struct InstantiateMsg {
 /// This structure describes the execute messages of the contract.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     /////////////////////
     /// OWNER CALLABLE
@@ -62,12 +65,53 @@ pub enum ExecuteMsg {
         penalty: Option<String>,
         /// Asset target weights
         target: Option<Vec<Asset>>, // recomposition oracle
+        /// Oracle price freshness policy
+        price_freshness: Option<PriceFreshnessPolicy>,
+        /// Annualised management fee rate
+        management_fee_rate: Option<Decimal>,
+        /// Cluster token supply and asset inventory caps
+        caps: Option<ClusterCaps>,
+        /// Policy applied to untracked asset surpluses on reconciliation
+        reconcile_policy: Option<ReconcilePolicy>,
+        /// address allowed to pause and unpause create and custom redeem
+        guardian: Option<String>,
+        /// Fee rate charged on the asked asset of swaps
+        swap_fee_rate: Option<Decimal>,
+        /// Interpretation of the asset target weights, changing it requires a new `target`
+        target_mode: Option<TargetMode>,
+        /// Contract providing the asset target weights, pulled on create and redeem
+        target_provider: Option<TargetProvider>,
+        /// Ratio of the escrowed cluster tokens paid to the keeper executing a queued redeem
+        redeem_bounty_rate: Option<Decimal>,
+        /// Decimals of specific native assets, others having 6 decimals
+        native_decimals: Option<Vec<(String, u8)>>,
+        /// Share of the create / redeem protocol fee paid to the referrer
+        referral_fee_rate: Option<Decimal>,
     },
+    /// ClearTargetProvider removes the target provider, leaving the asset target
+    /// weights to `UpdateTarget`.
+    ClearTargetProvider {},
     /// UpdateTarget changes the asset target weights.
     /// -- can also be called by target oracle.
     UpdateTarget {
-        // new asset target weights to be update
+        // new asset target weights to be update, in basis points of value
+        // in value weight target mode
         target: Vec<Asset>,
+        // duration in seconds to linearly move from the current weights to the new weights
+        duration: Option<u64>,
+    },
+
+    /////////////////////
+    /// GUARDIAN CALLABLE
+    /////////////////////
+
+    /// SetPause pauses or unpauses create and custom redeem.
+    /// -- can also be called by the owner.
+    SetPause {
+        /// whether create is paused
+        create_paused: Option<bool>,
+        /// whether custom (non pro-rata) redeem is paused
+        custom_redeem_paused: Option<bool>,
     },
 
     /////////////////////
@@ -76,6 +120,8 @@ pub enum ExecuteMsg {
 
     /// Decommission set the cluster to be inactive
     Decommission {},
+    /// Recommission set a decommissioned cluster to be active again
+    Recommission {},
 
     /////////////////////
     /// USER CALLABLE
@@ -89,6 +135,8 @@ pub enum ExecuteMsg {
         asset_amounts: Vec<Asset>,
         /// Minimum cluster tokens to receive
         min_tokens: Option<Uint128>,
+        /// Address receiving a share of the protocol fee
+        referrer: Option<String>,
     },
     /// RebalanceRedeem performs the redeem operation burning provided cluster tokens
     /// for assets
@@ -97,6 +145,38 @@ pub enum ExecuteMsg {
         max_tokens: Uint128,
         /// Proposed set of asset weights to use
         asset_amounts: Option<Vec<Asset>>,
+        /// Address receiving a share of the protocol fee
+        referrer: Option<String>,
+    },
+    /// Receive calls a hook message after receiving CW20 asset.
+    Receive(Cw20ReceiveMsg),
+    /// AccrueFee mints the accrued management fee to the collector.
+    AccrueFee {},
+    /// ReconcileInventory settles the difference between the tracked inventory
+    /// and the actual asset balances following the cluster reconcile policy.
+    ReconcileInventory {},
+    /// Swap exchanges an inventory asset for another one at oracle prices
+    /// adjusted by the penalty contract.
+    Swap {
+        /// Asset offered to the cluster (cluster must be granted allowance or
+        /// sent native assets within the MsgExecuteContract)
+        offer_asset: Asset,
+        /// Asset asked from the cluster inventory
+        ask_asset_info: AssetInfo,
+        /// Minimum amount of the asked asset to receive
+        min_return: Option<Uint128>,
+    },
+    /// ExecuteRedeemRequest executes a queued redeem request, paying the redeem
+    /// bounty to the sender unless the sender owns the request.
+    ExecuteRedeemRequest {
+        /// ID of the queued redeem request
+        request_id: u64,
+    },
+    /// CancelRedeemRequest cancels a queued redeem request of the sender and
+    /// returns the escrowed cluster tokens.
+    CancelRedeemRequest {
+        /// ID of the queued redeem request
+        request_id: u64,
     },
 }
 
@@ -104,7 +184,26 @@ pub enum ExecuteMsg {
 /// This structure describes the possible hook messages for CW20 contract.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum Cw20HookMsg {}
+pub enum Cw20HookMsg {
+    /// Redeem performs the redeem operation burning the sent cluster tokens
+    /// for assets, and refunds the unused cluster tokens to the sender
+    Redeem {
+        /// Proposed set of asset weights to use
+        asset_amounts: Option<Vec<Asset>>,
+        /// Minimum amounts of assets to receive
+        min_assets: Option<Vec<Asset>>,
+        /// Address receiving the redeemed assets, defaults to the sender
+        recipient: Option<String>,
+    },
+    /// QueueRedeem escrows the sent cluster tokens in a redeem request executed
+    /// later, once the cluster asset prices are fresh
+    QueueRedeem {
+        /// Asset amounts to receive
+        asset_amounts: Vec<Asset>,
+        /// Minimum amounts of assets to receive
+        min_assets: Option<Vec<Asset>>,
+    },
+}
 
 /// ## Description
 /// This structure describes the available query messages for the cluster contract.
@@ -119,6 +218,49 @@ pub enum QueryMsg {
     ClusterState {},
     /// ClusterInfo returns the name and description of the cluster.
     ClusterInfo {},
+    /// SimulateCreate returns the result of a create operation with the given assets.
+    SimulateCreate {
+        /// Assets to deposit for minting cluster tokens
+        asset_amounts: Vec<Asset>,
+    },
+    /// SimulateRedeem returns the result of a redeem operation with the given parameters.
+    SimulateRedeem {
+        /// Maximum amount of cluster tokens allowed to burn
+        max_tokens: Uint128,
+        /// Assets to receive, or `None` for a pro-rata redeem
+        asset_amounts: Option<Vec<Asset>>,
+    },
+    /// SimulateSwap returns the result of a swap operation with the given assets.
+    SimulateSwap {
+        /// Asset offered to the cluster
+        offer_asset: Asset,
+        /// Asset asked from the cluster inventory
+        ask_asset_info: AssetInfo,
+    },
+    /// AccruedFee returns the accrued but not yet minted management fee.
+    AccruedFee {},
+    /// InventoryDrift returns the difference between the tracked inventory
+    /// and the actual asset balances of the cluster.
+    InventoryDrift {},
+    /// RedeemRequest returns a queued redeem request.
+    RedeemRequest {
+        /// ID of the queued redeem request
+        request_id: u64,
+    },
+    /// RedeemRequests returns the queued redeem requests, oldest first.
+    RedeemRequests {
+        /// Owner of the requests, all owners if not provided
+        owner: Option<String>,
+        /// Request ID to start after
+        start_after: Option<u64>,
+        /// Maximum number of requests to return
+        limit: Option<u32>,
+    },
+    /// ReferralEarnings returns the cumulative cluster tokens earned by a referrer.
+    ReferralEarnings {
+        /// Referrer address
+        referrer: String,
+    },
 }
 
 /// ## Description
@@ -133,16 +275,27 @@ pub struct ConfigResponse {
 /// A custom struct for each query response that returns the current target weights.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct TargetResponse {
-    /// The vector of `Asset` in which `amount` is the target weight
+    /// The vector of `Asset` in which `amount` is the current effective target weight
     pub target: Vec<Asset>,
+    /// The vector of `Asset` in which `amount` is the final target weight
+    /// once the ongoing target transition completes
+    pub final_target: Vec<Asset>,
+    /// The vector of `Asset` in which `amount` is the current effective value
+    /// weight in basis points, only set in value weight target mode
+    pub value_weights: Option<Vec<Asset>>,
+    /// The vector of `Asset` in which `amount` is the final value weight in
+    /// basis points, only set in value weight target mode
+    pub final_value_weights: Option<Vec<Asset>>,
 }
 
 /// ## Description
 /// A custom struct for each query response that returns the current cluster state.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ClusterStateResponse {
-    /// The current total supply of the cluster token
+    /// The current total supply of the cluster token, including the accrued management fee
     pub outstanding_balance_tokens: Uint128,
+    /// The accrued management fee not yet minted to the collector
+    pub accrued_fee: Uint128,
     /// Prices of the assets in the cluster
     pub prices: Vec<String>,
     /// Current inventory / asset balances
@@ -151,12 +304,104 @@ pub struct ClusterStateResponse {
     pub penalty: String,
     /// Cluster token address
     pub cluster_token: String,
-    /// The current asset target weights
+    /// The current effective asset target weights in unit amounts
     pub target: Vec<Asset>,
+    /// The final asset target weights in unit amounts once the ongoing target
+    /// transition completes
+    pub final_target: Vec<Asset>,
     /// The address of this cluster contract
     pub cluster_contract_address: String,
     /// The cluster active status - not active if decommissioned
     pub active: bool,
+    /// Age of each asset price in seconds
+    pub price_ages: Vec<u64>,
+    /// Cluster tokens that can still be minted before reaching the supply cap,
+    /// or `None` if the supply is uncapped
+    pub supply_headroom: Option<Uint128>,
+    /// Amount of each asset that can still be deposited before reaching its
+    /// inventory cap, or `None` if the asset is uncapped
+    pub inventory_headroom: Vec<Option<Uint128>>,
+    /// Whether create is paused
+    pub create_paused: bool,
+    /// Whether custom (non pro-rata) redeem is paused
+    pub custom_redeem_paused: bool,
+    /// Decimals of the assets in the cluster, used to normalise the inventory
+    /// to the oracle price unit
+    #[serde(default)]
+    pub asset_decimals: Vec<u8>,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns the accrued management fee.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AccruedFeeResponse {
+    /// Annualised management fee rate
+    pub management_fee_rate: Decimal,
+    /// Last time in seconds the management fee was minted
+    pub last_accrual: u64,
+    /// Cluster tokens accrued as the management fee and not yet minted
+    pub accrued_fee: Uint128,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns the difference between
+/// the tracked inventory and the actual asset balances of the cluster.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InventoryDriftResponse {
+    /// Inventory drift of each asset ordered by the cluster target
+    pub drift: Vec<AssetDrift>,
+}
+
+/// ## Description
+/// A custom struct for storing the inventory drift of an asset.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetDrift {
+    /// Asset information
+    pub info: AssetInfo,
+    /// Asset balance tracked in the cluster inventory
+    pub tracked_balance: Uint128,
+    /// Actual asset balance held by the cluster contract
+    pub actual_balance: Uint128,
+    /// Actual balance not tracked in the inventory
+    pub surplus: Uint128,
+    /// Tracked balance not held by the cluster contract
+    pub deficit: Uint128,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns a queued redeem request.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RedeemRequestResponse {
+    /// ID of the request
+    pub request_id: u64,
+    /// Owner of the escrowed cluster tokens receiving the redeemed assets
+    pub owner: String,
+    /// Escrowed cluster tokens
+    pub token_amount: Uint128,
+    /// Asset amounts to receive
+    pub asset_amounts: Vec<Asset>,
+    /// Minimum amounts of assets to receive
+    pub min_assets: Option<Vec<Asset>>,
+    /// Time in seconds the request was queued
+    pub submitted_at: u64,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns queued redeem requests.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RedeemRequestsResponse {
+    /// Queued redeem requests ordered by ID
+    pub requests: Vec<RedeemRequestResponse>,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns the referral earnings of an address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralEarningsResponse {
+    /// Referrer address
+    pub referrer: String,
+    /// Cumulative cluster tokens received as a share of the protocol fee
+    pub earnings: Uint128,
 }
 
 /// ## Description
@@ -169,6 +414,54 @@ pub struct ClusterInfoResponse {
     pub description: String,
 }
 
+/// ## Description
+/// A custom struct for each query response that returns the simulated result of a create operation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateCreateResponse {
+    /// Cluster tokens minted to the sender
+    pub create_tokens: Uint128,
+    /// Cluster tokens minted to the collector as the protocol fee
+    pub fee_amt: Uint128,
+    /// Reward from the penalty contract
+    pub penalty: Uint128,
+    /// Deposited asset amounts ordered by the cluster target
+    pub create_asset_amounts: Vec<Asset>,
+    /// Attributes from the penalty contract
+    pub attributes: Vec<Attribute>,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns the simulated result of a redeem operation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateRedeemResponse {
+    /// Total cluster tokens spent by the sender including the protocol fee
+    pub token_cost: Uint128,
+    /// Cluster tokens burned
+    pub burn_amount: Uint128,
+    /// Cluster tokens sent to the collector as the protocol fee
+    pub fee_amt: Uint128,
+    /// Penalty from the penalty contract
+    pub penalty: Uint128,
+    /// Assets returned to the sender ordered by the cluster target
+    pub redeem_assets: Vec<Asset>,
+    /// Attributes from the penalty contract
+    pub attributes: Vec<Attribute>,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns the simulated result of a swap operation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateSwapResponse {
+    /// Amount of the asked asset returned to the sender
+    pub return_amount: Uint128,
+    /// Amount of the asked asset sent to the collector as the swap fee
+    pub fee_amt: Uint128,
+    /// Reward from the penalty contract
+    pub penalty: Uint128,
+    /// Attributes from the penalty contract
+    pub attributes: Vec<Attribute>,
+}
+
 /// ## Description
 /// A custom struct for storing cluster setting.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -191,6 +484,159 @@ pub struct ClusterConfig {
     pub penalty: Addr,
     /// The cluster active status - not active if decommissioned
     pub active: bool,
+    /// Oracle price freshness policy of the cluster
+    #[serde(default)]
+    pub price_freshness: PriceFreshnessPolicy,
+    /// Annualised management fee rate, as a ratio of the cluster token supply
+    #[serde(default)]
+    pub management_fee_rate: Decimal,
+    /// Cluster token supply and asset inventory caps
+    #[serde(default)]
+    pub caps: ClusterCaps,
+    /// Policy applied to untracked asset surpluses on reconciliation
+    #[serde(default)]
+    pub reconcile_policy: ReconcilePolicy,
+    /// An address allowed to pause and unpause create and custom redeem
+    #[serde(default)]
+    pub guardian: Option<Addr>,
+    /// Whether create is paused
+    #[serde(default)]
+    pub create_paused: bool,
+    /// Whether custom (non pro-rata) redeem is paused
+    #[serde(default)]
+    pub custom_redeem_paused: bool,
+    /// Fee rate charged on the asked asset of swaps
+    #[serde(default)]
+    pub swap_fee_rate: Decimal,
+    /// Interpretation of the asset target weights
+    #[serde(default)]
+    pub target_mode: TargetMode,
+    /// Contract providing the asset target weights, pulled on create and redeem
+    #[serde(default)]
+    pub target_provider: Option<TargetProvider>,
+    /// Ratio of the escrowed cluster tokens paid to the keeper executing a queued redeem
+    #[serde(default)]
+    pub redeem_bounty_rate: Decimal,
+    /// Decimals of specific native assets, others having 6 decimals
+    #[serde(default)]
+    pub native_decimals: Vec<(String, u8)>,
+    /// Share of the create / redeem protocol fee paid to the referrer
+    #[serde(default)]
+    pub referral_fee_rate: Decimal,
+}
+
+impl ClusterConfig {
+    /// Returns the decimals of the given native asset.
+    pub fn native_decimals_of(&self, denom: &str) -> u8 {
+        self.native_decimals
+            .iter()
+            .find(|(x, _)| x == denom)
+            .map(|(_, decimals)| *decimals)
+            .unwrap_or(NORMALIZED_DECIMALS)
+    }
+}
+
+/// ## Description
+/// A custom struct for storing the target provider of a cluster, a contract answering
+/// the `TargetWeights {}` query of the target provider interface.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TargetProvider {
+    /// Target provider contract address
+    pub address: Addr,
+    /// Minimum time in seconds between two pulls of the target weights
+    pub refresh_interval: u64,
+}
+
+/// ## Description
+/// This enum describes how the asset target weights of a cluster are interpreted.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetMode {
+    /// Target weights are unit amounts of each asset
+    #[default]
+    Units,
+    /// Target weights are basis points of the cluster value summing to 10000,
+    /// converted to unit amounts at the current oracle prices
+    ValueWeights,
+}
+
+/// ## Description
+/// This enum describes how untracked asset surpluses, e.g. tokens sent directly
+/// to the cluster, are settled on inventory reconciliation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcilePolicy {
+    /// Add the surplus to the cluster inventory as a donation to the cluster token holders
+    #[default]
+    Absorb,
+    /// Send the surplus to the collector contract
+    SweepToCollector,
+    /// Send the surplus to the given address
+    Refund { recipient: Addr },
+}
+
+/// ## Description
+/// A custom struct for storing the cluster token supply and asset inventory caps of a cluster.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ClusterCaps {
+    /// Maximum outstanding cluster token supply, uncapped if not provided
+    pub supply_cap: Option<Uint128>,
+    /// Maximum inventory of specific assets, uncapped for the assets not listed
+    pub inventory_caps: Vec<Asset>,
+}
+
+impl ClusterCaps {
+    /// Returns the inventory cap of the given asset, if any.
+    pub fn inventory_cap_of(&self, asset_info: &AssetInfo) -> Option<Uint128> {
+        self.inventory_caps
+            .iter()
+            .find(|cap| cap.info == *asset_info)
+            .map(|cap| cap.amount)
+    }
+}
+
+/// ## Description
+/// This enum describes the cluster behaviour when some asset prices are stale.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StalePriceFallback {
+    /// Reject all operations, including pro-rata redeem
+    Reject,
+    /// Only allow pro-rata redeem, which does not depend on asset prices
+    ProRataOnly,
+}
+
+/// ## Description
+/// A custom struct for storing the oracle price freshness policy of a cluster.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceFreshnessPolicy {
+    /// Maximum age of asset prices in seconds
+    pub max_price_age: u64,
+    /// Maximum age of asset prices in seconds overriding `max_price_age` for specific assets
+    pub asset_max_price_ages: Vec<(AssetInfo, u64)>,
+    /// Behaviour when some asset prices are older than their maximum age
+    pub stale_fallback: StalePriceFallback,
+}
+
+impl Default for PriceFreshnessPolicy {
+    fn default() -> Self {
+        PriceFreshnessPolicy {
+            max_price_age: 60,
+            asset_max_price_ages: vec![],
+            stale_fallback: StalePriceFallback::ProRataOnly,
+        }
+    }
+}
+
+impl PriceFreshnessPolicy {
+    /// Returns the maximum price age in seconds of the given asset.
+    pub fn max_price_age_of(&self, asset_info: &AssetInfo) -> u64 {
+        self.asset_max_price_ages
+            .iter()
+            .find(|(info, _)| info == asset_info)
+            .map(|(_, age)| *age)
+            .unwrap_or(self.max_price_age)
+    }
 }
 
 /// ## Description
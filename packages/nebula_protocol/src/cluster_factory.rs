@@ -82,6 +82,24 @@ pub enum ExecuteMsg {
         /// cluster token contract address
         cluster_token: String,
     },
+    /// RecommissionCluster reactivates a decommissioned cluster.
+    RecommissionCluster {
+        /// cluster contract address
+        cluster_contract: String,
+        /// cluster token contract address
+        cluster_token: String,
+        /// weight for the Nebula / cluster LP token staking pool (uses the default weight if not provided)
+        weight: Option<u32>,
+    },
+    /// UpdateClusterFees overrides the create / redeem fee rates of a cluster.
+    UpdateClusterFees {
+        /// cluster contract address
+        cluster_contract: String,
+        /// create fee rate (uses the factory protocol fee rate if not provided)
+        create_fee_rate: Option<String>,
+        /// redeem fee rate (uses the factory protocol fee rate if not provided)
+        redeem_fee_rate: Option<String>,
+    },
     /// PassCommand calls the provided contract to execute the given message.
     PassCommand {
         /// address of a target contract
@@ -110,8 +128,21 @@ pub enum QueryMsg {
         /// address to be queried
         contract_addr: String,
     },
+    /// ClusterRegistered returns whether the provided address is a cluster created by the factory,
+    /// including decommissioned clusters.
+    ClusterRegistered {
+        /// address to be queried
+        contract_addr: String,
+    },
     /// ClusterList returns a list of (cluster contract address, active status).
     ClusterList {},
+    /// ClusterFees returns the effective create / redeem fee rates of a cluster.
+    ClusterFees {
+        /// cluster contract address
+        cluster_contract: String,
+    },
+    /// ClusterFeeList returns the effective create / redeem fee rates of all clusters.
+    ClusterFeeList {},
     /// DistributionInfo returns last reward distributed time and reward weights of
     /// all cluster LP token staking pools
     DistributionInfo {},
@@ -142,6 +173,14 @@ pub struct ClusterExistsResponse {
     pub exists: bool,
 }
 
+/// ## Description
+/// A custom struct for each query response that returns whether a cluster is registered.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClusterRegisteredResponse {
+    /// whether is a cluster created by the factory, active or decommissioned
+    pub registered: bool,
+}
+
 /// ## Description
 /// A custom struct for each query response that returns a list of
 /// pairs of cluster contract addresses and active status.
@@ -151,6 +190,28 @@ pub struct ClusterListResponse {
     pub contract_infos: Vec<(String, bool)>,
 }
 
+/// ## Description
+/// A custom struct for each query response that returns the effective
+/// create / redeem fee rates of a cluster.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClusterFeesResponse {
+    /// cluster contract address
+    pub cluster_contract: String,
+    /// fee rate charged on cluster token creation
+    pub create_fee_rate: String,
+    /// fee rate charged on cluster token redemption
+    pub redeem_fee_rate: String,
+}
+
+/// ## Description
+/// A custom struct for each query response that returns the effective
+/// create / redeem fee rates of all clusters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClusterFeeListResponse {
+    /// vector of effective cluster fee rates
+    pub fees: Vec<ClusterFeesResponse>,
+}
+
 /// ## Description
 /// A custom struct for each query response that returns distribution information containing
 /// last reward distributed time and weights of Nebula / cluster LP token staking pools.
@@ -190,6 +251,12 @@ pub struct Params {
 
     /// Target assets and weights
     pub target: Vec<Asset>,
+
+    /// Create fee rate (default is the factory protocol fee rate)
+    pub create_fee_rate: Option<String>,
+
+    /// Redeem fee rate (default is the factory protocol fee rate)
+    pub redeem_fee_rate: Option<String>,
 }
 
 /// ## Description
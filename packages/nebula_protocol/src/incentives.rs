@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use astroport::asset::{Asset, AssetInfo};
 use astroport::pair::PoolResponse as AstroportPoolResponse;
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Decimal, Uint128};
 use cw20::Cw20ReceiveMsg;
 
 /// ## Description
@@ -97,6 +97,8 @@ pub enum ExecuteMsg {
         asset_amounts: Vec<Asset>,
         /// minimum amount of CT required from minting
         min_tokens: Option<Uint128>,
+        /// address receiving a share of the protocol fee
+        referrer: Option<String>,
     },
     /// _InternalRewardedRedeem calls the actual redeem logic in a cluster contract
     /// used in both arbitraging and rebalancing.
@@ -111,6 +113,46 @@ pub enum ExecuteMsg {
         max_tokens: Option<Uint128>,
         /// asset amounts required from burning CT if specified
         asset_amounts: Option<Vec<Asset>>,
+        /// address receiving a share of the protocol fee
+        referrer: Option<String>,
+    },
+    /// _CreateAll calls the actual create logic in a cluster contract with all balances
+    /// of the specified assets held by this contract.
+    _CreateAll {
+        /// an address performing the rebalance
+        rebalancer: Addr,
+        /// cluster contract
+        cluster_contract: Addr,
+        /// assets used to mint CT
+        asset_infos: Vec<AssetInfo>,
+        /// minimum amount of CT required from minting
+        min_tokens: Option<Uint128>,
+    },
+    /// _SwapAllTo swaps all specified assets into the ask asset.
+    _SwapAllTo {
+        /// assets to be swapped
+        asset_infos: Vec<AssetInfo>,
+        /// asset to receive
+        ask_asset_info: AssetInfo,
+        /// maximum spread allowed for each swap
+        max_spread: Option<Decimal>,
+    },
+    /// _AssertMinReturn checks this contract holds at least the minimum amount of an asset.
+    _AssertMinReturn {
+        /// asset to check
+        asset_info: AssetInfo,
+        /// minimum required amount
+        min_return: Uint128,
+    },
+    /// _SwapIntoBasket swaps all base denom held by this contract into the specified
+    /// assets of a cluster, allocated to minimize the cluster imbalance.
+    _SwapIntoBasket {
+        /// cluster contract
+        cluster_contract: Addr,
+        /// cluster assets to be bought
+        asset_infos: Vec<AssetInfo>,
+        /// maximum spread allowed for each swap
+        max_spread: Option<Decimal>,
     },
 
     /////////////////////
@@ -143,6 +185,8 @@ pub enum ExecuteMsg {
         asset_amounts: Vec<Asset>,
         /// minimum cluster tokens returned
         min_tokens: Option<Uint128>,
+        /// address receiving a share of the protocol fee
+        referrer: Option<String>,
     },
     /// IncentivesRedeem executes the redeem operation on a specific cluster.
     IncentivesRedeem {
@@ -152,6 +196,51 @@ pub enum ExecuteMsg {
         max_tokens: Uint128,
         /// specific asset amounts returned from burning cluster tokens
         asset_amounts: Option<Vec<Asset>>,
+        /// address receiving a share of the protocol fee
+        referrer: Option<String>,
+    },
+    /// ZapCreate swaps a single asset into the cluster assets on Astroport and
+    /// executes the create operation on a specific cluster.
+    ZapCreate {
+        /// cluster contract
+        cluster_contract: String,
+        /// asset offerred for minting
+        offer_asset: Asset,
+        /// minimum cluster tokens returned
+        min_tokens: Option<Uint128>,
+        /// maximum spread allowed for each swap on Astroport
+        max_spread: Option<Decimal>,
+    },
+    /// ZapRedeem executes the redeem operation on a specific cluster and swaps
+    /// all returned assets into a single asset on Astroport.
+    ZapRedeem {
+        /// cluster contract
+        cluster_contract: String,
+        /// maximum amount of cluster tokens (CT) allowed to be burned
+        max_tokens: Uint128,
+        /// redeem the basket minimizing the penalty instead of the pro-rata basket
+        penalty_optimal: bool,
+        /// asset to receive
+        ask_asset_info: AssetInfo,
+        /// minimum amount of the ask asset returned
+        min_output: Uint128,
+        /// maximum spread allowed for each swap on Astroport
+        max_spread: Option<Decimal>,
+    },
+    /// MigrateCluster redeems cluster tokens of one cluster pro-rata and creates
+    /// cluster tokens of another cluster from the redeemed assets. Assets held by
+    /// both clusters are carried over in kind, and only the rest is swapped on Astroport.
+    MigrateCluster {
+        /// cluster contract to migrate from
+        from_cluster: String,
+        /// cluster contract to migrate to
+        to_cluster: String,
+        /// maximum amount of cluster tokens (CT) of `from_cluster` allowed to be burned
+        max_tokens: Uint128,
+        /// minimum amount of CT of `to_cluster` required from minting
+        min_tokens: Option<Uint128>,
+        /// maximum spread allowed for each swap on Astroport
+        max_spread: Option<Decimal>,
     },
     /// Withdraw withdraws all rewards for the sender.
     Withdraw {},
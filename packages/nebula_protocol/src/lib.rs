@@ -7,6 +7,8 @@ pub mod community;
 pub mod gov;
 pub mod incentives;
 pub mod incentives_custody;
+pub mod market_cap_provider;
 pub mod oracle;
 pub mod penalty;
 pub mod staking;
+pub mod target_provider;
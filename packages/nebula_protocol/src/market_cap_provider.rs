@@ -0,0 +1,77 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// ## Description
+/// This structure stores the basic settings for creating a new market cap target provider contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    /// Owner of the contract
+    pub owner: String,
+    /// Nebula oracle contract used to price the assets
+    pub oracle: String,
+    /// Quote denom of the asset prices, UST (uusd)
+    pub base_denom: String,
+    /// CW20 asset tokens weighted by their market cap
+    pub assets: Vec<String>,
+    /// Maximum value weights, in basis points, of specific asset tokens
+    pub weight_caps: Vec<(String, u64)>,
+}
+
+/// ## Description
+/// This structure describes the execute messages of the contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /////////////////////
+    /// OWNER CALLABLE
+    /////////////////////
+
+    /// UpdateConfig updates contract setting.
+    UpdateConfig {
+        /// address to claim the contract ownership
+        owner: Option<String>,
+        /// new Nebula oracle contract
+        oracle: Option<String>,
+        /// new quote denom of the asset prices
+        base_denom: Option<String>,
+        /// new CW20 asset tokens weighted by their market cap
+        assets: Option<Vec<String>>,
+        /// new maximum value weights, in basis points, of specific asset tokens
+        weight_caps: Option<Vec<(String, u64)>>,
+    },
+}
+
+/// ## Description
+/// This structure describes the available query messages for the market cap
+/// target provider contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Config returns contract settings specified in the custom [`ConfigResponse`] structure.
+    Config {},
+    /// TargetWeights returns the capped market cap value weights of the assets
+    /// in basis points, following the cluster target provider interface.
+    TargetWeights {},
+}
+
+/// ## Description
+/// A custom struct for each query response that returns general contract settings/configs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    /// Owner of the contract
+    pub owner: String,
+    /// Nebula oracle contract used to price the assets
+    pub oracle: String,
+    /// Quote denom of the asset prices
+    pub base_denom: String,
+    /// CW20 asset tokens weighted by their market cap
+    pub assets: Vec<String>,
+    /// Maximum value weights, in basis points, of specific asset tokens
+    pub weight_caps: Vec<(String, u64)>,
+}
+
+/// ## Description
+/// A struct used for migrating contracts.
+/// Currently take no arguments for migrations.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
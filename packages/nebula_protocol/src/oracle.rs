@@ -14,6 +14,10 @@ pub struct InstantiateMsg {
     pub oracle_addr: String,
     /// Default denom, UST (uusd)
     pub base_denom: String,
+    /// Cluster factory contract, used to recognise cluster tokens
+    pub factory: Option<String>,
+    /// Maximum nesting depth of cluster tokens priced from their underlying assets
+    pub max_cluster_depth: Option<u8>,
 }
 
 /// ## Description
@@ -33,6 +37,10 @@ pub enum ExecuteMsg {
         oracle_addr: Option<String>,
         /// new default denom
         base_denom: Option<String>,
+        /// new cluster factory contract
+        factory: Option<String>,
+        /// new maximum nesting depth of cluster tokens
+        max_cluster_depth: Option<u8>,
     },
 }
 
@@ -74,6 +82,10 @@ pub struct ConfigResponse {
     pub oracle_addr: String,
     /// Base denom, UST
     pub base_denom: String,
+    /// Cluster factory contract
+    pub factory: Option<String>,
+    /// Maximum nesting depth of cluster tokens
+    pub max_cluster_depth: u8,
 }
 
 /// ## Description
@@ -11,6 +11,9 @@ pub struct InstantiateMsg {
     pub owner: String,
     /// penalty contract parameters
     pub penalty_params: PenaltyParams,
+    /// factory contract whose clusters share the penalty contract, a single cluster owns it if not given
+    #[serde(default)]
+    pub factory: Option<String>,
 }
 
 /// ## Description
@@ -30,6 +33,19 @@ pub enum ExecuteMsg {
         penalty_params: Option<PenaltyParams>,
     },
 
+    /// UpdateClusterParams overrides the penalty contract parameters of a cluster sharing the
+    /// penalty contract, or removes its override to fall back to the default parameters.
+    UpdateClusterParams {
+        /// address of the cluster contract
+        cluster_contract: String,
+        /// penalty contract parameters of the cluster
+        penalty_params: Option<PenaltyParams>,
+    },
+
+    /////////////////////
+    /// CLUSTER CALLABLE
+    /////////////////////
+
     /// PenaltyCreate updates the state of penalty contract after a create operation.
     PenaltyCreate {
         /// a specific height to compute mint at
@@ -63,6 +79,22 @@ pub enum ExecuteMsg {
         /// current target weights of the assets in a cluster
         target_weights: Vec<Uint128>,
     },
+
+    /// PenaltySwap updates the state of penalty contract after a swap operation.
+    PenaltySwap {
+        /// a specific height to compute swap at
+        block_height: u64,
+        /// current inventory of inventory assets in a cluster
+        inventory: Vec<Uint128>,
+        /// the offered asset amounts
+        offer_asset_amounts: Vec<Uint128>,
+        /// the asked asset amounts of the same value as the offered assets at oracle prices
+        ask_asset_amounts: Vec<Uint128>,
+        /// prices of the inventory assets in a cluster
+        asset_prices: Vec<String>,
+        /// current target weights of the assets in a cluster
+        target_weights: Vec<Uint128>,
+    },
 }
 
 /// ## Description
@@ -74,7 +106,11 @@ pub enum QueryMsg {
     Config {},
 
     /// Params returns general contract parameters using a custom [`ParamsResponse`] structure.
-    Params {},
+    Params {
+        /// cluster contract to return the parameters of, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
+    },
 
     /// PenaltyQueryCreate calculates the actual create amount after taking penalty into consideration.
     PenaltyQueryCreate {
@@ -90,6 +126,9 @@ pub enum QueryMsg {
         asset_prices: Vec<String>,
         /// current target weights of the assets in a cluster
         target_weights: Vec<Uint128>,
+        /// cluster contract to compute for, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
     },
 
     /// PenaltyQueryRedeem calculates the actual redeem amount after taking penalty into consideration.
@@ -108,6 +147,28 @@ pub enum QueryMsg {
         asset_prices: Vec<String>,
         /// current target weights of the assets in a cluster
         target_weights: Vec<Uint128>,
+        /// cluster contract to compute for, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
+    },
+
+    /// PenaltyQuerySwap calculates the actual asked asset amounts after taking penalty into consideration.
+    PenaltyQuerySwap {
+        /// a specific height to compute swap at
+        block_height: u64,
+        /// current inventory of inventory assets in a cluster
+        inventory: Vec<Uint128>,
+        /// the offered asset amounts
+        offer_asset_amounts: Vec<Uint128>,
+        /// the asked asset amounts of the same value as the offered assets at oracle prices
+        ask_asset_amounts: Vec<Uint128>,
+        /// prices of the inventory assets in a cluster
+        asset_prices: Vec<String>,
+        /// current target weights of the assets in a cluster
+        target_weights: Vec<Uint128>,
+        /// cluster contract to compute for, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
     },
 
     /// PenaltyQueryNotional Calculates the notional penalty based on the inventory change at the given height.
@@ -122,6 +183,73 @@ pub enum QueryMsg {
         asset_prices: Vec<String>,
         /// current target weights of the assets in a cluster
         target_weights: Vec<Uint128>,
+        /// cluster contract to compute for, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
+    },
+
+    /// ProjectedEma returns the EMA projected to a future height or time, assuming the net asset
+    /// value stays the same until then, using a custom [`ProjectedEmaResponse`] structure.
+    ProjectedEma {
+        /// height to project the EMA to, the current height if not given
+        block_height: Option<u64>,
+        /// block time in seconds to project the EMA to, the current time if not given
+        block_time: Option<u64>,
+        /// net asset value of the cluster until the projected height or time
+        net_asset_val: String,
+        /// cluster contract to compute for, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
+    },
+
+    /// PenaltyCurve samples the penalty curve of a cluster at the current height using a custom
+    /// [`PenaltyCurveResponse`] structure.
+    PenaltyCurve {
+        /// current inventory of inventory assets in a cluster
+        inventory: Vec<Uint128>,
+        /// prices of the inventory assets in a cluster
+        asset_prices: Vec<String>,
+        /// current target weights of the assets in a cluster
+        target_weights: Vec<Uint128>,
+        /// number of evenly spaced imbalances to sample from zero to the highest cutoff
+        points: u32,
+        /// cluster contract to compute for, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
+    },
+
+    /// OptimalBasket returns the create or redeem basket within a budget that minimizes the
+    /// cluster imbalance, and so maximizes the reward, using a custom [`OptimalBasketResponse`] structure.
+    OptimalBasket {
+        /// current total supply for a cluster token
+        cluster_token_supply: Uint128,
+        /// current inventory of inventory assets in a cluster
+        inventory: Vec<Uint128>,
+        /// prices of the inventory assets in a cluster
+        asset_prices: Vec<String>,
+        /// current target weights of the assets in a cluster
+        target_weights: Vec<Uint128>,
+        /// budget of the create or redeem operation
+        budget: RebalanceBudget,
+        /// cluster contract to compute for, required by a shared penalty contract
+        #[serde(default)]
+        cluster_contract: Option<String>,
+    },
+}
+
+/// ## Description
+/// This enum describes the budget of a create or redeem basket.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RebalanceBudget {
+    /// Create a basket with the given total value at oracle prices
+    Create { value: String },
+    /// Redeem a basket burning at most the given cluster token amount, including
+    /// the cluster redeem fee charged at the given rate
+    Redeem {
+        max_tokens: Uint128,
+        #[serde(default)]
+        redeem_fee_rate: Option<String>,
     },
 }
 
@@ -133,6 +261,9 @@ pub struct ConfigResponse {
     pub owner: String,
     /// General penalty contract parameters
     pub penalty_params: PenaltyParams,
+    /// Factory contract whose clusters share the penalty contract
+    #[serde(default)]
+    pub factory: Option<String>,
 }
 
 /// ## Description
@@ -161,6 +292,18 @@ pub struct PenaltyRedeemResponse {
     pub attributes: Vec<Attribute>,
 }
 
+/// ## Description
+/// A custom struct for each query that returns the actual asked assets and the subjected penalty.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PenaltySwapResponse {
+    /// Actual asked assets
+    pub ask_assets: Vec<Uint128>,
+    /// Incurred penalty / reward from rebalance
+    pub penalty: Uint128,
+    /// Returned attributes to the caller
+    pub attributes: Vec<Attribute>,
+}
+
 /// ## Description
 /// A custom struct for each query that returns the incurred penalty from rebalancing.
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -183,10 +326,66 @@ pub struct ParamsResponse {
     pub penalty_params: PenaltyParams,
     /// Last rebalanced block
     pub last_block: u64,
+    /// Last rebalanced block time in seconds
+    pub last_time: u64,
     /// Last rebalanced EMA
     pub ema: String,
 }
 
+/// ## Description
+/// A custom struct for each query that returns the projected EMA.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProjectedEmaResponse {
+    /// Height the EMA is projected to
+    pub block_height: u64,
+    /// Block time in seconds the EMA is projected to
+    pub block_time: u64,
+    /// Projected EMA
+    pub ema: String,
+}
+
+/// ## Description
+/// A custom struct for each query that returns a sampled penalty curve.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PenaltyCurveResponse {
+    /// Current imbalance of the cluster
+    pub imbalance: String,
+    /// Minimum of the EMA and the net asset value, which scales the cutoffs
+    pub e: String,
+    /// Imbalance where the penalty curve starts rising
+    pub penalty_cutoff_lo: String,
+    /// Highest imbalance allowed by the penalty curve
+    pub penalty_cutoff_hi: String,
+    /// Imbalance above which decreasing the imbalance is rewarded
+    pub reward_cutoff: String,
+    /// Sampled points of the penalty curve
+    pub points: Vec<PenaltyCurvePoint>,
+}
+
+/// ## Description
+/// A custom struct for each point of a sampled penalty curve.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PenaltyCurvePoint {
+    /// Imbalance of the point
+    pub imbalance: String,
+    /// Marginal penalty rate of increasing the imbalance at this point
+    pub penalty_rate: String,
+}
+
+/// ## Description
+/// A custom struct for each query that returns the optimal create or redeem basket.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OptimalBasketResponse {
+    /// Asset amounts to provide for a create, or to receive from a redeem
+    pub basket: Vec<Uint128>,
+    /// Cluster tokens minted by the create, or spent by the redeem including the redeem fee
+    pub tokens: Uint128,
+    /// Imbalance of the cluster after the rebalance
+    pub imbalance: String,
+    /// Expected reward from the rebalance
+    pub reward: Uint128,
+}
+
 /// ## Description
 /// A struct used for migrating contracts.
 /// Currently take no arguments for migrations.
@@ -194,20 +393,111 @@ pub struct ParamsResponse {
 pub struct MigrateMsg {}
 
 /// ## Description
-/// A custom struct storing general penalty contract parameters.
+/// A custom enum storing general penalty contract parameters, one variant per penalty curve family.
+///
+/// Every family charges, while the imbalance increases, the area under its penalty curve between
+/// the imbalance before and after the rebalance, so penalties are path-independent. All cutoffs
+/// are expressed as fractions of E, the minimum of the EMA and the net asset value, whose decay
+/// is set by `ema_half_life` in every family. While the
+/// imbalance decreases, every family pays the same flat reward:
+/// reward_amt -> amount of reward when imbalance >= reward_cutoff * E, no reward everywhere else.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PenaltyParams {
+    /// Flat, then linear, then flat penalty curve.
+    PiecewiseLinear {
+        // penalty_amt_lo -> amount of penalty when imbalance <= penalty_cutoff_lo * E
+        penalty_amt_lo: FPDecimal,
+        penalty_cutoff_lo: FPDecimal,
+
+        // penalty_amt_hi -> amount of penalty when imbalance >= penalty_cutoff_hi * E
+        penalty_amt_hi: FPDecimal,
+        penalty_cutoff_hi: FPDecimal,
+        // in between penalty_cutoff_hi and penalty_cutoff_lo, the amount of penalty increases linearly
+        reward_amt: FPDecimal,
+        reward_cutoff: FPDecimal,
+
+        #[serde(default)]
+        ema_half_life: Option<EmaHalfLife>,
+    },
+
+    /// Quadratic penalty curve.
+    Quadratic {
+        // penalty_amt_lo + (penalty_amt_hi - penalty_amt_lo) * (imbalance / (penalty_cutoff_hi * E))^2
+        // -- amount of penalty up to penalty_cutoff_hi * E, imbalance cannot exceed it
+        penalty_amt_lo: FPDecimal,
+        penalty_amt_hi: FPDecimal,
+        penalty_cutoff_hi: FPDecimal,
+
+        reward_amt: FPDecimal,
+        reward_cutoff: FPDecimal,
+
+        #[serde(default)]
+        ema_half_life: Option<EmaHalfLife>,
+    },
+
+    /// Sigmoid penalty curve.
+    Tanh {
+        // penalty_amt_lo + (penalty_amt_hi - penalty_amt_lo) * (1 + tanh((imbalance - penalty_cutoff_mid * E) / (penalty_width * E))) / 2
+        // -- amount of penalty up to penalty_cutoff_hi * E, imbalance cannot exceed it
+        penalty_amt_lo: FPDecimal,
+        penalty_amt_hi: FPDecimal,
+        penalty_cutoff_mid: FPDecimal,
+        penalty_width: FPDecimal,
+        penalty_cutoff_hi: FPDecimal,
+
+        reward_amt: FPDecimal,
+        reward_cutoff: FPDecimal,
+
+        #[serde(default)]
+        ema_half_life: Option<EmaHalfLife>,
+    },
+
+    /// Arbitrary piecewise-linear penalty curve.
+    Table {
+        // amount of penalty linearly interpolated between the knots, the first knot must be at
+        // an imbalance of 0 and the imbalance cannot exceed the last knot
+        knots: Vec<PenaltyKnot>,
+
+        reward_amt: FPDecimal,
+        reward_cutoff: FPDecimal,
+
+        #[serde(default)]
+        ema_half_life: Option<EmaHalfLife>,
+    },
+}
+
+/// ## Description
+/// A custom enum storing the half-life of the EMA of the net asset value, either in blocks or
+/// in seconds of block time. Without a half-life, the EMA decays with a time constant of
+/// 600 blocks.
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, JsonSchema)]
-pub struct PenaltyParams {
-    // penalty_amt_lo -> amount of penalty when imbalance <= penalty_cutoff_lo * E
-    pub penalty_amt_lo: FPDecimal,
-    pub penalty_cutoff_lo: FPDecimal,
-
-    // penalty_amt_hi -> amount of penalty when imbalance >= penalty_cutoff_hi * E
-    pub penalty_amt_hi: FPDecimal,
-    pub penalty_cutoff_hi: FPDecimal,
-    // in between penalty_cutoff_hi and penalty_cutoff_lo, the amount of penalty increases linearly
-
-    // reward_amt -> amount of reward when imbalance >= reward_cutoff * E
-    // no reward everywhere else
-    pub reward_amt: FPDecimal,
-    pub reward_cutoff: FPDecimal,
+#[serde(rename_all = "snake_case")]
+pub enum EmaHalfLife {
+    /// Half-life measured in block heights
+    Blocks(u64),
+    /// Half-life measured in seconds from the block time
+    Seconds(u64),
+}
+
+/// ## Description
+/// A custom struct storing a knot of a [`PenaltyParams::Table`] penalty curve.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PenaltyKnot {
+    /// Imbalance of the knot, as a fraction of E
+    pub imbalance: FPDecimal,
+    /// Amount of penalty at the knot
+    pub penalty_amt: FPDecimal,
+}
+
+impl PenaltyParams {
+    /// Returns the half-life of the EMA of the net asset value.
+    pub fn ema_half_life(&self) -> Option<EmaHalfLife> {
+        match self {
+            PenaltyParams::PiecewiseLinear { ema_half_life, .. }
+            | PenaltyParams::Quadratic { ema_half_life, .. }
+            | PenaltyParams::Tanh { ema_half_life, .. }
+            | PenaltyParams::Table { ema_half_life, .. } => *ema_half_life,
+        }
+    }
 }
@@ -0,0 +1,24 @@
+use astroport::asset::Asset;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// ## Description
+/// This structure describes the query interface of a cluster target provider.
+/// Any contract answering `TargetWeights {}` with a [`TargetWeightsResponse`]
+/// can be set as the target provider of a cluster.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// TargetWeights returns the asset target weights to apply to the cluster.
+    TargetWeights {},
+}
+
+/// ## Description
+/// A custom struct for each query response that returns the asset target weights
+/// of a target provider.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TargetWeightsResponse {
+    /// The vector of `Asset` in which `amount` is the target weight, interpreted
+    /// following the target mode of the cluster
+    pub target: Vec<Asset>,
+}